@@ -1,3 +1,4 @@
+#[cfg(all(feature = "vendored", not(feature = "system")))]
 use std::env;
 #[cfg(feature = "renew-bindings")]
 use std::path::{Path, PathBuf};
@@ -27,10 +28,202 @@ fn gen_bindings(include_dir: &Path) {
         .expect("Couldn't write bindings!");
 }
 
+#[cfg(feature = "system")]
+mod system {
+    use std::{
+        env, fs,
+        path::{Path, PathBuf},
+        process::Command,
+    };
+
+    // The pre-generated bindings target this version.
+    const MIN_VERSION: (u32, u32, u32) = (4, 3, 2);
+
+    // Only compiles and links if the draft API was enabled.
+    const DRAFT_CHECK_SRC: &str = r#"
+#define ZMQ_BUILD_DRAFT_API
+#include <zmq.h>
+
+int main(void) {
+    void *poller = zmq_poller_new();
+    zmq_poller_destroy(&poller);
+    return 0;
+}
+"#;
+
+    struct SystemLib {
+        include_dir: PathBuf,
+        lib_dirs: Vec<PathBuf>,
+    }
+
+    // Locate `libzmq` either via the `LIBZMQ_LIB_DIR` and `LIBZMQ_INCLUDE_DIR`
+    // env variables or via `pkg-config`.
+    fn find_system_lib() -> SystemLib {
+        let lib_dir = env::var_os("LIBZMQ_LIB_DIR").map(PathBuf::from);
+        let include_dir = env::var_os("LIBZMQ_INCLUDE_DIR").map(PathBuf::from);
+
+        match (lib_dir, include_dir) {
+            (Some(lib_dir), Some(include_dir)) => {
+                println!(
+                    "cargo:rustc-link-search=native={}",
+                    lib_dir.display()
+                );
+                println!("cargo:rustc-link-lib=zmq");
+
+                SystemLib {
+                    include_dir,
+                    lib_dirs: vec![lib_dir],
+                }
+            }
+            (None, None) => {
+                let lib = pkg_config::Config::new()
+                    .atleast_version(&format_version(MIN_VERSION))
+                    .probe("libzmq")
+                    .unwrap_or_else(|err| {
+                        panic!(
+                            "unable to find a system libzmq via pkg-config, \
+                         consider setting `LIBZMQ_LIB_DIR` and \
+                         `LIBZMQ_INCLUDE_DIR`: {}",
+                            err
+                        )
+                    });
+
+                let include_dir = lib
+                    .include_paths
+                    .iter()
+                    .find(|dir| dir.join("zmq.h").is_file())
+                    .cloned()
+                    // The header is in a default include directory.
+                    .unwrap_or_else(|| PathBuf::from("/usr/include"));
+
+                SystemLib {
+                    include_dir,
+                    lib_dirs: lib.link_paths,
+                }
+            }
+            _ => panic!(
+            "both `LIBZMQ_LIB_DIR` and `LIBZMQ_INCLUDE_DIR` must be specified"
+        ),
+        }
+    }
+
+    fn format_version(version: (u32, u32, u32)) -> String {
+        format!("{}.{}.{}", version.0, version.1, version.2)
+    }
+
+    // Parse the version from the `zmq.h` header.
+    fn header_version(include_dir: &Path) -> (u32, u32, u32) {
+        let header = include_dir.join("zmq.h");
+        let text = fs::read_to_string(&header).unwrap_or_else(|err| {
+            panic!("unable to read `{}`: {}", header.display(), err)
+        });
+
+        let parse = |name: &str| -> u32 {
+            text.lines()
+                .filter_map(|line| {
+                    let mut words = line.split_whitespace();
+                    match (words.next(), words.next(), words.next()) {
+                        (Some("#define"), Some(n), Some(value))
+                            if n == name =>
+                        {
+                            value.parse().ok()
+                        }
+                        _ => None,
+                    }
+                })
+                .next()
+                .unwrap_or_else(|| {
+                    panic!("`{}` not found in `{}`", name, header.display())
+                })
+        };
+
+        (
+            parse("ZMQ_VERSION_MAJOR"),
+            parse("ZMQ_VERSION_MINOR"),
+            parse("ZMQ_VERSION_PATCH"),
+        )
+    }
+
+    fn check_version(include_dir: &Path) {
+        let version = header_version(include_dir);
+        let (major, minor, _) = MIN_VERSION;
+
+        // The bindings are not guaranteed to be compatible with other minor
+        // versions.
+        if version < MIN_VERSION || version.0 != major || version.1 != minor {
+            panic!(
+            "system libzmq version {} is not supported, requires {}.{}.x with \
+             x >= {}",
+            format_version(version),
+            major,
+            minor,
+            MIN_VERSION.2,
+        );
+        }
+    }
+
+    // Compile and link a program that uses the draft API.
+    fn check_draft(lib: &SystemLib) {
+        let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+        let src = out_dir.join("draft_check.c");
+        fs::write(&src, DRAFT_CHECK_SRC).unwrap();
+
+        let compiler = cc::Build::new().cargo_metadata(false).get_compiler();
+        let mut cmd: Command = compiler.to_command();
+        cmd.arg("-I")
+            .arg(&lib.include_dir)
+            .arg(&src)
+            .arg("-o")
+            .arg(out_dir.join("draft_check"));
+        for dir in &lib.lib_dirs {
+            cmd.arg("-L").arg(dir);
+        }
+        cmd.arg("-lzmq");
+
+        let output = cmd.output().unwrap_or_else(|err| {
+            panic!("unable to run the C compiler: {}", err)
+        });
+
+        if !output.status.success() {
+            panic!(
+                "system libzmq was not built with the draft API \
+             (`ENABLE_DRAFTS=ON`):\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+    }
+
+    pub fn link() {
+        println!("cargo:rerun-if-env-changed=LIBZMQ_LIB_DIR");
+        println!("cargo:rerun-if-env-changed=LIBZMQ_INCLUDE_DIR");
+
+        let lib = find_system_lib();
+        check_version(&lib.include_dir);
+        check_draft(&lib);
+
+        println!("cargo:include={}", lib.include_dir.display());
+
+        #[cfg(feature = "renew-bindings")]
+        super::gen_bindings(&lib.include_dir);
+    }
+}
+
 fn main() {
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-env-changed=PROFILE");
 
+    // The system lib takes precedence since `vendored` is a default feature.
+    #[cfg(feature = "system")]
+    system::link();
+    #[cfg(all(feature = "vendored", not(feature = "system")))]
+    build_vendored();
+}
+
+#[cfg(not(any(feature = "system", feature = "vendored")))]
+compile_error!("either the `vendored` or the `system` feature must be enabled");
+
+#[cfg(all(feature = "vendored", not(feature = "system")))]
+fn build_vendored() {
     let enable_curve = cfg!(feature = "curve");
 
     let wants_debug = env::var_os("PROFILE").unwrap() == "debug";
@@ -0,0 +1,258 @@
+use anyhow::{anyhow, bail};
+use libzmq::{auth::*, config::AuthConfig};
+use libzmq_tools::keys;
+use serde::Serialize;
+use structopt::StructOpt;
+
+use std::{
+    collections::HashSet,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Encoding {
+    Z85,
+    Hex,
+    Bin,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "z85" => Ok(Encoding::Z85),
+            "hex" => Ok(Encoding::Hex),
+            "bin" => Ok(Encoding::Bin),
+            _ => Err(format!("unknown encoding: {}", s)),
+        }
+    }
+}
+
+/// Manages `CURVE` certificates.
+///
+/// A certificate is stored as a `<name>.pub` public key file and a
+/// `<name>.secret` secret key file, each holding a Z85 encoded key.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "curve-cert")]
+enum Opt {
+    /// Generates a new certificate.
+    Generate {
+        /// The name of the certificate files.
+        name: String,
+        /// The directory of the certificate files.
+        #[structopt(short, long, parse(from_os_str), default_value = ".")]
+        dir: PathBuf,
+        /// Overwrites existing files.
+        #[structopt(short, long)]
+        force: bool,
+    },
+    /// Prints the public key derived from a secret key file.
+    Public {
+        #[structopt(parse(from_os_str))]
+        secret_key: PathBuf,
+    },
+    /// Lists and validates a directory of trusted public keys.
+    List {
+        #[structopt(parse(from_os_str))]
+        dir: PathBuf,
+    },
+    /// Converts a key between encodings.
+    Convert {
+        /// The key to convert. Reads from stdin if omitted.
+        key: Option<String>,
+        /// The encoding of the input: z85, hex or bin.
+        #[structopt(long, default_value = "z85")]
+        from: Encoding,
+        /// The encoding of the output: z85, hex or bin.
+        #[structopt(long, default_value = "hex")]
+        to: Encoding,
+    },
+    /// Prints a YAML snippet to configure a socket.
+    Yaml(Role),
+}
+
+#[derive(Debug, StructOpt)]
+enum Role {
+    /// The `mechanism` of a client socket.
+    Client {
+        /// The public key file of the server.
+        #[structopt(long, parse(from_os_str))]
+        server_key: PathBuf,
+        /// The secret key file of the client.
+        #[structopt(long, parse(from_os_str))]
+        secret_key: PathBuf,
+    },
+    /// The `mechanism` of a server socket and the `auth` config
+    /// whitelisting its clients.
+    Server {
+        /// The secret key file of the server.
+        #[structopt(long, parse(from_os_str))]
+        secret_key: PathBuf,
+        /// A public key file, or directory of `.pub` files, of the clients
+        /// allowed to connect.
+        #[structopt(long, parse(from_os_str), number_of_values = 1)]
+        allow_key: Vec<PathBuf>,
+    },
+}
+
+#[derive(Serialize)]
+struct MechanismSnippet {
+    mechanism: Mechanism,
+}
+
+#[derive(Serialize)]
+struct AuthSnippet {
+    auth: AuthConfig,
+}
+
+fn generate(name: &str, dir: &Path, force: bool) -> Result<(), anyhow::Error> {
+    let cert = CurveCert::new_unique();
+    let (public, secret) = keys::write_cert(dir, name, &cert, force)?;
+    println!("public: {}", public.display());
+    println!("secret: {}", secret.display());
+    Ok(())
+}
+
+fn list(dir: &Path) -> Result<(), anyhow::Error> {
+    let mut invalid = 0;
+    let mut seen = HashSet::new();
+
+    for path in keys::public_files(dir)? {
+        let name = path.file_stem().unwrap().to_string_lossy();
+        match keys::read_public(&path) {
+            Ok(key) => {
+                if seen.insert(key.clone()) {
+                    println!("{} {}", key, name);
+                } else {
+                    println!("{} {} (duplicate)", key, name);
+                }
+            }
+            Err(err) => {
+                eprintln!("{:#}", err);
+                invalid += 1;
+            }
+        }
+    }
+
+    let secrets = std::fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension().and_then(|ext| ext.to_str())
+                == Some(keys::SECRET_EXT)
+        });
+    for path in secrets {
+        eprintln!(
+            "warning: secret key in trusted directory: {}",
+            path.display()
+        );
+    }
+
+    if invalid > 0 {
+        bail!("{} invalid key(s)", invalid);
+    }
+    Ok(())
+}
+
+fn convert(
+    key: Option<String>,
+    from: Encoding,
+    to: Encoding,
+) -> Result<(), anyhow::Error> {
+    let input = match key {
+        Some(key) => key.into_bytes(),
+        None => {
+            let mut input = vec![];
+            io::stdin().read_to_end(&mut input)?;
+            input
+        }
+    };
+
+    // The public and secret keys share the same encoding.
+    let key = match from {
+        Encoding::Z85 => {
+            let text = String::from_utf8(input)?;
+            CurvePublicKey::new(text.trim())?
+        }
+        Encoding::Hex => {
+            let text = String::from_utf8(input)?;
+            CurvePublicKey::from_bytes(&hex::decode(text.trim())?)?
+        }
+        Encoding::Bin => CurvePublicKey::from_bytes(&input)?,
+    };
+
+    match to {
+        Encoding::Z85 => println!("{}", key),
+        Encoding::Hex => println!("{}", hex::encode(key.to_bytes())),
+        Encoding::Bin => io::stdout().write_all(&key.to_bytes())?,
+    }
+    Ok(())
+}
+
+fn print_yaml<T: Serialize>(value: &T) -> Result<(), anyhow::Error> {
+    let yaml = serde_yaml::to_string(value)?;
+    println!("{}", yaml.trim_start_matches("---\n").trim_end());
+    Ok(())
+}
+
+fn yaml(role: Role) -> Result<(), anyhow::Error> {
+    match role {
+        Role::Client {
+            server_key,
+            secret_key,
+        } => {
+            let server = keys::read_public(&server_key)?;
+            let cert = CurveCert::from_secret(keys::read_secret(&secret_key)?);
+            let creds = CurveClientCreds::new(server).add_cert(cert);
+            print_yaml(&MechanismSnippet {
+                mechanism: creds.into(),
+            })
+        }
+        Role::Server {
+            secret_key,
+            allow_key,
+        } => {
+            let secret = keys::read_secret(&secret_key)?;
+            print_yaml(&MechanismSnippet {
+                mechanism: CurveServerCreds::new(secret).into(),
+            })?;
+
+            if !allow_key.is_empty() {
+                let mut curve_registry = vec![];
+                for path in &allow_key {
+                    curve_registry.extend(keys::read_publics(path)?);
+                }
+                let mut auth = AuthConfig::new();
+                auth.set_curve_registry(Some(curve_registry));
+                print_yaml(&AuthSnippet { auth })?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn run(opt: Opt) -> Result<(), anyhow::Error> {
+    match opt {
+        Opt::Generate { name, dir, force } => generate(&name, &dir, force),
+        Opt::Public { secret_key } => {
+            let secret = keys::read_secret(&secret_key)?;
+            println!("{}", CurvePublicKey::from(&secret));
+            Ok(())
+        }
+        Opt::List { dir } => list(&dir),
+        Opt::Convert { key, from, to } => convert(key, from, to)
+            .map_err(|err| anyhow!("unable to convert key: {:#}", err)),
+        Opt::Yaml(role) => yaml(role),
+    }
+}
+
+fn main() {
+    if let Err(err) = run(Opt::from_args()) {
+        eprintln!("error: {:#}", err);
+        process::exit(1);
+    }
+}
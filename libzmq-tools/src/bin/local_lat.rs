@@ -0,0 +1,27 @@
+use libzmq_tools::perf::{self, PerfOpt};
+use structopt::StructOpt;
+
+use std::process;
+
+/// Echoes the messages of `remote-lat`, binding to the endpoint.
+///
+/// With `inproc`, `remote-lat` runs in this process and its report is
+/// printed.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "local-lat")]
+struct Opt {
+    #[structopt(flatten)]
+    perf: PerfOpt,
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    match perf::local_lat(&opt.perf) {
+        Ok(Some(report)) => println!("{}\n{}", opt.perf, report),
+        Ok(None) => (),
+        Err(err) => {
+            eprintln!("error: {:#}", err);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use libzmq_tools::perf::{self, PerfOpt};
+use structopt::StructOpt;
+
+use std::process;
+
+/// Measures the throughput of the messages sent by `remote-thr`,
+/// binding to the endpoint.
+///
+/// With `inproc`, `remote-thr` runs in this process.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "local-thr")]
+struct Opt {
+    #[structopt(flatten)]
+    perf: PerfOpt,
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    match perf::local_thr(&opt.perf) {
+        Ok(report) => println!("{}\n{}", opt.perf, report),
+        Err(err) => {
+            eprintln!("error: {:#}", err);
+            process::exit(1);
+        }
+    }
+}
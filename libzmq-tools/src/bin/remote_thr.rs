@@ -0,0 +1,24 @@
+use libzmq_tools::perf::{self, PerfOpt};
+use structopt::StructOpt;
+
+use std::process;
+
+/// Sends messages as fast as possible to `local-thr`, connecting to
+/// the endpoint.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "remote-thr")]
+struct Opt {
+    #[structopt(flatten)]
+    perf: PerfOpt,
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    match perf::remote_thr(&opt.perf) {
+        Ok(report) => println!("{}\n{}", opt.perf, report),
+        Err(err) => {
+            eprintln!("error: {:#}", err);
+            process::exit(1);
+        }
+    }
+}
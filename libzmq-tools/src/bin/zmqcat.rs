@@ -0,0 +1,530 @@
+use anyhow::{bail, Context};
+use libzmq::{
+    addr::Endpoint, auth::*, config::*, monitor::*, prelude::*, Ctx, CtxHandle,
+    Group, Msg, Period, RoutingId, SocketType,
+};
+use libzmq_tools::{endpoint::parse_endpoint, keys, socket::*};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    process,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+// How long a send-only socket waits for its queued messages to be sent
+// once stdin is exhausted and a peer completed its handshake.
+const LINGER: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Kind {
+    Client,
+    Server,
+    Radio,
+    Dish,
+    Scatter,
+    Gather,
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "client" => Ok(Kind::Client),
+            "server" => Ok(Kind::Server),
+            "radio" => Ok(Kind::Radio),
+            "dish" => Ok(Kind::Dish),
+            "scatter" => Ok(Kind::Scatter),
+            "gather" => Ok(Kind::Gather),
+            _ => Err(format!("unknown socket type: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Format {
+    Text,
+    Hex,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "hex" => Ok(Format::Hex),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum MechanismKind {
+    Null,
+    PlainClient,
+    PlainServer,
+    CurveClient,
+    CurveServer,
+}
+
+impl FromStr for MechanismKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "null" => Ok(MechanismKind::Null),
+            "plain-client" => Ok(MechanismKind::PlainClient),
+            "plain-server" => Ok(MechanismKind::PlainServer),
+            "curve-client" => Ok(MechanismKind::CurveClient),
+            "curve-server" => Ok(MechanismKind::CurveServer),
+            _ => Err(format!("unknown mechanism: {}", s)),
+        }
+    }
+}
+
+/// Sends the lines read from stdin and prints the received messages
+/// to stdout.
+///
+/// With the json format, each line is an object such as
+/// `{"data": "msg", "group": "A", "routing_id": 1}`, where `hex` can be
+/// used instead of `data` for binary messages. When no routing id is
+/// specified, a server replies to the last peer it received a message from.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "zmqcat")]
+struct Opt {
+    /// The socket type: client, server, radio, dish, scatter or gather.
+    #[structopt(required_unless = "config", conflicts_with = "config")]
+    socket: Option<Kind>,
+    /// A YAML socket config, such as `client: { connect: [tcp: ...] }`.
+    #[structopt(short, long, parse(from_os_str))]
+    config: Option<PathBuf>,
+    /// Connects to the endpoint, such as `tcp://127.0.0.1:5555`.
+    #[structopt(long, number_of_values = 1)]
+    connect: Vec<String>,
+    /// Binds to the endpoint, such as `tcp://*:5555`.
+    #[structopt(long, number_of_values = 1)]
+    bind: Vec<String>,
+    /// Joins the group (dish only).
+    #[structopt(long, number_of_values = 1)]
+    join: Vec<String>,
+    /// The group of the sent messages (radio only).
+    #[structopt(long)]
+    group: Option<String>,
+    /// The format of the messages: text, hex or json.
+    #[structopt(short, long, default_value = "text")]
+    format: Format,
+    /// Exits after receiving this many messages.
+    #[structopt(short = "n", long)]
+    count: Option<u64>,
+    /// The security mechanism: null, plain-client, plain-server,
+    /// curve-client or curve-server.
+    #[structopt(short, long)]
+    mechanism: Option<MechanismKind>,
+    /// The PLAIN username.
+    #[structopt(long)]
+    username: Option<String>,
+    /// A file containing the PLAIN password.
+    #[structopt(long, parse(from_os_str))]
+    password_file: Option<PathBuf>,
+    /// The public key file of the CURVE server (curve-client only).
+    #[structopt(long, parse(from_os_str))]
+    server_key: Option<PathBuf>,
+    /// The secret key file of the socket.
+    #[structopt(long, parse(from_os_str))]
+    secret_key: Option<PathBuf>,
+    /// A public key file, or directory of `.pub` files, of the CURVE
+    /// clients allowed to connect (curve-server only). If omitted, any
+    /// client is accepted.
+    #[structopt(long, parse(from_os_str), number_of_values = 1)]
+    allow_key: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JsonMsg {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    routing_id: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hex: Option<String>,
+}
+
+fn load_config(opt: &Opt) -> Result<ConfigType, anyhow::Error> {
+    if let Some(path) = &opt.config {
+        let file = File::open(path)
+            .with_context(|| format!("unable to open {}", path.display()))?;
+        return serde_yaml::from_reader(file)
+            .with_context(|| format!("invalid config {}", path.display()));
+    }
+
+    let config = match opt.socket.unwrap() {
+        Kind::Client => ConfigType::Client(ClientConfig::new()),
+        Kind::Server => ConfigType::Server(ServerConfig::new()),
+        Kind::Radio => ConfigType::Radio(RadioConfig::new()),
+        Kind::Dish => ConfigType::Dish(DishConfig::new()),
+        Kind::Scatter => ConfigType::Scatter(ScatterConfig::new()),
+        Kind::Gather => ConfigType::Gather(GatherConfig::new()),
+    };
+    Ok(config)
+}
+
+fn mechanism(opt: &Opt) -> Result<Option<Mechanism>, anyhow::Error> {
+    let kind = match opt.mechanism {
+        Some(kind) => kind,
+        None => return Ok(None),
+    };
+
+    let mechanism = match kind {
+        MechanismKind::Null => Mechanism::Null,
+        MechanismKind::PlainClient => {
+            let (username, password) = plain_creds(opt)?;
+            PlainClientCreds::new(username, password).into()
+        }
+        MechanismKind::PlainServer => Mechanism::PlainServer,
+        MechanismKind::CurveClient => {
+            let server = opt
+                .server_key
+                .as_ref()
+                .context("curve-client requires --server-key")?;
+            let mut creds = CurveClientCreds::new(keys::read_public(server)?);
+            if let Some(path) = &opt.secret_key {
+                let secret = keys::read_secret(path)?;
+                creds = creds.add_cert(CurveCert::from_secret(secret));
+            }
+            creds.into()
+        }
+        MechanismKind::CurveServer => {
+            let path = opt
+                .secret_key
+                .as_ref()
+                .context("curve-server requires --secret-key")?;
+            CurveServerCreds::new(keys::read_secret(path)?).into()
+        }
+    };
+    Ok(Some(mechanism))
+}
+
+fn plain_creds(opt: &Opt) -> Result<(String, String), anyhow::Error> {
+    let username = opt.username.clone().context("PLAIN requires --username")?;
+    let path = opt
+        .password_file
+        .as_ref()
+        .context("PLAIN requires --password-file")?;
+    let password = fs::read_to_string(path)
+        .with_context(|| format!("unable to read {}", path.display()))?;
+    Ok((username, password.trim_end_matches('\n').to_owned()))
+}
+
+// Configures the authentication handler of the context for server
+// mechanisms.
+fn configure_auth(opt: &Opt, handle: CtxHandle) -> Result<(), anyhow::Error> {
+    let mut builder = AuthBuilder::new();
+    match opt.mechanism {
+        Some(MechanismKind::PlainServer) => {
+            let (username, password) = plain_creds(opt)?;
+            builder.plain_registry(vec![PlainClientCreds::new(
+                username, password,
+            )]);
+        }
+        Some(MechanismKind::CurveServer) => {
+            if opt.allow_key.is_empty() {
+                eprintln!("warning: accepting any CURVE client");
+                builder.no_curve_auth();
+            } else {
+                let mut allowed = vec![];
+                for path in &opt.allow_key {
+                    allowed.extend(keys::read_publics(path)?);
+                }
+                builder.curve_registry(allowed);
+            }
+        }
+        _ => return Ok(()),
+    }
+    builder.with_ctx(handle)?;
+    Ok(())
+}
+
+// The endpoints of the socket, which are bound and connected once the
+// socket is monitored.
+#[derive(Debug)]
+struct Endpoints {
+    connect: Vec<Endpoint>,
+    bind: Vec<Endpoint>,
+}
+
+impl Endpoints {
+    // Whether the peers of any endpoint perform a ZMTP handshake.
+    fn has_handshake(&self) -> bool {
+        self.connect.iter().chain(&self.bind).any(|endpoint| {
+            matches!(endpoint, Endpoint::Tcp(_) | Endpoint::Ipc(_))
+        })
+    }
+}
+
+fn configure<C>(
+    config: &mut C,
+    opt: &Opt,
+    mechanism: &Option<Mechanism>,
+) -> Result<Endpoints, anyhow::Error>
+where
+    C: ConfigureSocket,
+{
+    let mut connect = config.connect().map(<[_]>::to_vec).unwrap_or_default();
+    for s in &opt.connect {
+        connect.push(parse_endpoint(s)?);
+    }
+    config.set_connect(None::<Vec<Endpoint>>);
+
+    let mut bind = config.bind().map(<[_]>::to_vec).unwrap_or_default();
+    for s in &opt.bind {
+        bind.push(parse_endpoint(s)?);
+    }
+    config.set_bind(None::<Vec<Endpoint>>);
+
+    if mechanism.is_some() {
+        config.set_mechanism(mechanism.clone());
+    }
+    Ok(Endpoints { connect, bind })
+}
+
+fn build(
+    opt: &Opt,
+    handle: CtxHandle,
+) -> Result<(SocketType, Endpoints), anyhow::Error> {
+    let mut config = load_config(opt)?;
+    let mechanism = mechanism(opt)?;
+
+    if !opt.join.is_empty() && !matches!(config, ConfigType::Dish(_)) {
+        bail!("--join requires a dish socket");
+    }
+    if opt.group.is_some() && !matches!(config, ConfigType::Radio(_)) {
+        bail!("--group requires a radio socket");
+    }
+
+    let endpoints;
+    let socket = match &mut config {
+        ConfigType::Client(config) => {
+            endpoints = configure(config, opt, &mechanism)?;
+            SocketType::Client(config.with_ctx(handle)?)
+        }
+        ConfigType::Server(config) => {
+            endpoints = configure(config, opt, &mechanism)?;
+            SocketType::Server(config.with_ctx(handle)?)
+        }
+        ConfigType::Radio(config) => {
+            endpoints = configure(config, opt, &mechanism)?;
+            SocketType::Radio(config.with_ctx(handle)?)
+        }
+        ConfigType::Dish(config) => {
+            endpoints = configure(config, opt, &mechanism)?;
+            let mut groups =
+                config.groups().map(<[_]>::to_vec).unwrap_or_default();
+            for group in &opt.join {
+                groups.push(Group::from_str(group)?);
+            }
+            config.set_groups(Some(groups));
+            SocketType::Dish(config.with_ctx(handle)?)
+        }
+        ConfigType::Scatter(config) => {
+            endpoints = configure(config, opt, &mechanism)?;
+            SocketType::Scatter(config.with_ctx(handle)?)
+        }
+        ConfigType::Gather(config) => {
+            endpoints = configure(config, opt, &mechanism)?;
+            SocketType::Gather(config.with_ctx(handle)?)
+        }
+    };
+    Ok((socket, endpoints))
+}
+
+fn parse_line(
+    line: &str,
+    format: Format,
+) -> Result<(Vec<u8>, JsonMsg), anyhow::Error> {
+    let parsed = match format {
+        Format::Text => (line.as_bytes().to_vec(), JsonMsg::default()),
+        Format::Hex => (hex::decode(line.trim())?, JsonMsg::default()),
+        Format::Json => {
+            let mut json: JsonMsg = serde_json::from_str(line)?;
+            let data = match (json.data.take(), json.hex.take()) {
+                (Some(data), None) => data.into_bytes(),
+                (None, Some(hex)) => hex::decode(hex)?,
+                _ => bail!("expected exactly one of `data` or `hex`"),
+            };
+            (data, json)
+        }
+    };
+    Ok(parsed)
+}
+
+fn print_msg(
+    out: &mut impl Write,
+    msg: &Msg,
+    format: Format,
+) -> Result<(), anyhow::Error> {
+    match format {
+        Format::Text => {
+            out.write_all(msg.as_bytes())?;
+            out.write_all(b"\n")?;
+        }
+        Format::Hex => writeln!(out, "{}", hex::encode(msg.as_bytes()))?,
+        Format::Json => {
+            let mut json = JsonMsg {
+                routing_id: msg.routing_id().map(|id| id.0),
+                group: msg.group().map(|g| g.to_string_lossy().into_owned()),
+                ..JsonMsg::default()
+            };
+            match msg.to_str() {
+                Ok(s) => json.data = Some(s.to_owned()),
+                Err(_) => json.hex = Some(hex::encode(msg.as_bytes())),
+            }
+            serde_json::to_writer(&mut *out, &json)?;
+            out.write_all(b"\n")?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+// Sends the lines of stdin until EOF.
+fn send_stdin(
+    socket: &SocketType,
+    opt: &Opt,
+    last_peer: &AtomicU32,
+) -> Result<(), anyhow::Error> {
+    let default_group = match &opt.group {
+        Some(group) => Some(Group::from_str(group)?),
+        None => None,
+    };
+
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line?;
+        let (data, json) = match parse_line(&line, opt.format) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                eprintln!("skipping invalid line: {:#}", err);
+                continue;
+            }
+        };
+
+        let mut msg = Msg::from(data);
+        match json.group {
+            Some(group) => msg.set_group(Group::from_str(&group)?),
+            None => {
+                if let Some(group) = &default_group {
+                    msg.set_group(group);
+                }
+            }
+        }
+        if let SocketType::Server(_) = socket {
+            let id = json
+                .routing_id
+                .unwrap_or_else(|| last_peer.load(Ordering::SeqCst));
+            if id == 0 {
+                eprintln!("skipping line: no peer to reply to");
+                continue;
+            }
+            msg.set_routing_id(RoutingId(id));
+        }
+
+        send(socket, msg)?;
+    }
+    Ok(())
+}
+
+fn run(opt: Opt) -> Result<(), anyhow::Error> {
+    let ctx = Ctx::new();
+    configure_auth(&opt, ctx.handle())?;
+    let (socket, endpoints) = build(&opt, ctx.handle())?;
+
+    // A send-only socket waits for a handshake before exiting, since the
+    // messages queued for a pending connection are lost once the context
+    // terminates. The monitor is created first so that no handshake is
+    // missed.
+    let mut monitor = if !can_recv(&socket) && endpoints.has_handshake() {
+        let events = &[MonitorEvent::HandshakeSucceeded];
+        Some(Monitor::new(&socket, events)?)
+    } else {
+        None
+    };
+    for endpoint in endpoints.bind {
+        socket.bind(endpoint)?;
+    }
+    for endpoint in endpoints.connect {
+        socket.connect(endpoint)?;
+    }
+
+    let opt = Arc::new(opt);
+    let last_peer = Arc::new(AtomicU32::new(0));
+
+    let sender = if can_send(&socket) {
+        let socket = socket.clone();
+        let opt = Arc::clone(&opt);
+        let last_peer = Arc::clone(&last_peer);
+        Some(thread::spawn(move || {
+            if let Err(err) = send_stdin(&socket, &opt, &last_peer) {
+                eprintln!("error: {:#}", err);
+                process::exit(1);
+            }
+        }))
+    } else {
+        None
+    };
+
+    if !can_recv(&socket) {
+        // Send-only sockets exit once stdin is exhausted.
+        sender.unwrap().join().unwrap();
+        if let Some(monitor) = &mut monitor {
+            monitor
+                .wait_for(MonitorEvent::HandshakeSucceeded, Period::Infinite)?;
+        }
+        // Otherwise the queued messages would be discarded when the
+        // context is terminated, since sockets do not linger by default.
+        socket.set_linger(Some(LINGER))?;
+        return Ok(());
+    }
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut received = 0;
+    while Some(received) != opt.count {
+        let mut msg = Msg::new();
+        recv(&socket, &mut msg)?;
+        if let Some(id) = msg.routing_id() {
+            last_peer.store(id.0, Ordering::SeqCst);
+        }
+        print_msg(&mut out, &msg, opt.format)?;
+        received += 1;
+    }
+
+    // The sender might still be blocked on stdin while holding the socket,
+    // which would prevent the context from terminating.
+    if sender.is_some() {
+        process::exit(0);
+    }
+    Ok(())
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    if let Err(err) = run(opt) {
+        eprintln!("error: {:#}", err);
+        process::exit(1);
+    }
+}
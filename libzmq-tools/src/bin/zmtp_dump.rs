@@ -0,0 +1,83 @@
+use anyhow::{anyhow, Context};
+use libzmq::zmtp::Decoder;
+use structopt::StructOpt;
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::PathBuf,
+    process,
+};
+
+/// Decodes one direction of a captured ZMTP 3.x connection.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "zmtp-dump")]
+struct Opt {
+    /// The captured stream. Reads from stdin if omitted.
+    #[structopt(parse(from_os_str))]
+    input: Option<PathBuf>,
+    /// The input is hexadecimal, whitespace being ignored.
+    #[structopt(long)]
+    hex: bool,
+}
+
+fn read_input(opt: &Opt) -> io::Result<Box<dyn Read>> {
+    match &opt.input {
+        Some(path) => Ok(Box::new(File::open(path)?)),
+        None => Ok(Box::new(io::stdin())),
+    }
+}
+
+// Prints the packets until more bytes are required.
+fn decode(decoder: &mut Decoder) -> Result<(), anyhow::Error> {
+    loop {
+        let offset = decoder.offset();
+        match decoder.decode() {
+            Ok(Some(packet)) => println!("{:>8} {}", offset, packet),
+            Ok(None) => return Ok(()),
+            Err(err) => return Err(anyhow!("{} at offset {}", err, offset)),
+        }
+    }
+}
+
+fn run(opt: &Opt) -> Result<(), anyhow::Error> {
+    let mut input = read_input(opt).context("unable to open input")?;
+    let mut decoder = Decoder::new();
+
+    if opt.hex {
+        let mut text = String::new();
+        input.read_to_string(&mut text)?;
+        let text: String =
+            text.chars().filter(|c| !c.is_whitespace()).collect();
+        let bytes = hex::decode(text).context("invalid hex input")?;
+        decoder.feed(&bytes);
+        decode(&mut decoder)?;
+    } else {
+        let mut buf = [0; 8192];
+        loop {
+            let n = input.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            decoder.feed(&buf[..n]);
+            decode(&mut decoder)?;
+        }
+    }
+
+    if decoder.pending() > 0 {
+        eprintln!(
+            "{} trailing bytes at offset {} (truncated capture?)",
+            decoder.pending(),
+            decoder.offset()
+        );
+    }
+    Ok(())
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    if let Err(err) = run(&opt) {
+        eprintln!("error: {:#}", err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,25 @@
+//! Endpoints given on the command line.
+
+use anyhow::bail;
+use libzmq::{
+    addr::Endpoint, EpgmAddr, InprocAddr, IpcAddr, PgmAddr, TcpAddr, UdpAddr,
+};
+
+/// Parses an endpoint of the form `transport://addr`, such as
+/// `tcp://127.0.0.1:5555` or `ipc:///tmp/socket`.
+pub fn parse_endpoint(s: &str) -> Result<Endpoint, anyhow::Error> {
+    let (scheme, addr) = match s.find("://") {
+        Some(pos) => (&s[..pos], &s[pos + 3..]),
+        None => bail!("endpoint must be of the form transport://addr: {}", s),
+    };
+    let endpoint: Endpoint = match scheme {
+        "tcp" => addr.parse::<TcpAddr>()?.into(),
+        "udp" => addr.parse::<UdpAddr>()?.into(),
+        "ipc" => addr.parse::<IpcAddr>()?.into(),
+        "inproc" => addr.parse::<InprocAddr>()?.into(),
+        "pgm" => addr.parse::<PgmAddr>()?.into(),
+        "epgm" => addr.parse::<EpgmAddr>()?.into(),
+        _ => bail!("unsupported transport: {}", scheme),
+    };
+    Ok(endpoint)
+}
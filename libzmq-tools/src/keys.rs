@@ -0,0 +1,183 @@
+//! `CURVE` key files.
+//!
+//! A key file holds a single Z85 encoded key, optionally followed by
+//! whitespace. By convention, public keys use the `.pub` extension
+//! and secret keys the `.secret` extension.
+
+use anyhow::{anyhow, bail, Context};
+use libzmq::auth::{CurveCert, CurvePublicKey, CurveSecretKey};
+
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// The extension of public key files.
+pub const PUBLIC_EXT: &str = "pub";
+/// The extension of secret key files.
+pub const SECRET_EXT: &str = "secret";
+
+fn read_key(path: &Path) -> Result<String, anyhow::Error> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("unable to read {}", path.display()))?;
+    Ok(text.trim().to_owned())
+}
+
+/// Reads the public key file at `path`.
+pub fn read_public(path: &Path) -> Result<CurvePublicKey, anyhow::Error> {
+    CurvePublicKey::new(read_key(path)?).map_err(|err| {
+        anyhow!("invalid public key {}: {}", path.display(), err)
+    })
+}
+
+/// Reads the secret key file at `path`.
+pub fn read_secret(path: &Path) -> Result<CurveSecretKey, anyhow::Error> {
+    CurveSecretKey::new(read_key(path)?).map_err(|err| {
+        anyhow!("invalid secret key {}: {}", path.display(), err)
+    })
+}
+
+/// Returns the public key files of the directory, sorted by path.
+pub fn public_files(dir: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let mut paths = vec![];
+    let entries = fs::read_dir(dir)
+        .with_context(|| format!("unable to read {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        let ext = path.extension().and_then(|ext| ext.to_str());
+        if path.is_file() && ext == Some(PUBLIC_EXT) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Reads the public keys at `path`, which is either a key file or a
+/// directory of public key files.
+pub fn read_publics(path: &Path) -> Result<Vec<CurvePublicKey>, anyhow::Error> {
+    if path.is_dir() {
+        public_files(path)?
+            .iter()
+            .map(|path| read_public(path))
+            .collect()
+    } else {
+        Ok(vec![read_public(path)?])
+    }
+}
+
+fn write_key(
+    path: &Path,
+    text: &str,
+    secret: bool,
+    force: bool,
+) -> Result<(), anyhow::Error> {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    // Secret keys are only readable by their owner.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(if secret { 0o600 } else { 0o644 });
+    }
+    #[cfg(not(unix))]
+    let _ = secret;
+
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("unable to create {}", path.display()))?;
+    writeln!(file, "{}", text)?;
+    Ok(())
+}
+
+/// Writes the certificate into the `<name>.pub` and `<name>.secret` files
+/// of the directory, returning their paths.
+///
+/// Existing files are only overwritten if `force` is set, otherwise
+/// neither file is written if one of them exists.
+pub fn write_cert(
+    dir: &Path,
+    name: &str,
+    cert: &CurveCert,
+    force: bool,
+) -> Result<(PathBuf, PathBuf), anyhow::Error> {
+    let public = dir.join(format!("{}.{}", name, PUBLIC_EXT));
+    let secret = dir.join(format!("{}.{}", name, SECRET_EXT));
+
+    if !force {
+        for path in &[&secret, &public] {
+            if path.exists() {
+                bail!("{} already exists", path.display());
+            }
+        }
+    }
+    write_key(&secret, cert.secret().as_str(), true, force)?;
+    write_key(&public, cert.public().as_str(), false, force)?;
+    Ok((public, secret))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use libzmq::{Capabilities, InprocAddr};
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let name = format!("keys-{}", InprocAddr::new_unique());
+            let dir = std::env::temp_dir().join(name);
+            fs::create_dir(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_write_cert_roundtrip() {
+        if !libzmq::capabilities().contains(Capabilities::CURVE) {
+            return;
+        }
+
+        let dir = TempDir::new();
+        let cert = CurveCert::new_unique();
+        let (public, secret) = write_cert(&dir.0, "a", &cert, false).unwrap();
+
+        assert_eq!(&read_public(&public).unwrap(), cert.public());
+        assert_eq!(&read_secret(&secret).unwrap(), cert.secret());
+        assert_eq!(public_files(&dir.0).unwrap(), vec![public.clone()]);
+        assert_eq!(read_publics(&dir.0).unwrap(), vec![cert.public().clone()]);
+
+        let other = CurveCert::new_unique();
+        write_cert(&dir.0, "a", &other, false).unwrap_err();
+        write_cert(&dir.0, "a", &other, true).unwrap();
+        assert_eq!(&read_public(&public).unwrap(), other.public());
+    }
+
+    #[test]
+    fn test_write_cert_checks_both_files() {
+        if !libzmq::capabilities().contains(Capabilities::CURVE) {
+            return;
+        }
+
+        let dir = TempDir::new();
+        // Only the public key file exists.
+        let public = dir.0.join("a.pub");
+        fs::write(&public, "").unwrap();
+
+        let cert = CurveCert::new_unique();
+        write_cert(&dir.0, "a", &cert, false).unwrap_err();
+        assert!(!dir.0.join("a.secret").exists());
+    }
+}
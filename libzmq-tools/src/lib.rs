@@ -0,0 +1,6 @@
+//! Helpers shared by the *libzmq* command-line tools.
+
+pub mod endpoint;
+pub mod keys;
+pub mod perf;
+pub mod socket;
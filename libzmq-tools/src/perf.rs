@@ -0,0 +1,845 @@
+//! Latency and throughput measurements between two peers.
+//!
+//! The *local* peer binds to the endpoint and the *remote* peer connects
+//! to it, usually from another process or host. Since the `inproc`
+//! transport only works between sockets of the same `Ctx`, the local peer
+//! runs the remote peer in a thread when given an `inproc` endpoint.
+//!
+//! Latency is measured by the remote peer as the roundtrip time of a
+//! message echoed by the local peer. For the one-way patterns, the local
+//! peer replies on a second endpoint using the same pattern.
+//!
+//! Throughput is measured by the local peer from the first to the last
+//! message received from the remote peer.
+
+use crate::{
+    endpoint::parse_endpoint,
+    keys,
+    socket::{recv, send, set_recv_timeout, try_recv},
+};
+use anyhow::{bail, Context};
+use libzmq::{addr::Endpoint, auth::*, prelude::*, *};
+use structopt::StructOpt;
+
+use std::{
+    fmt,
+    path::PathBuf,
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The group of the radio messages.
+const GROUP: &str = "perf";
+/// The PLAIN credentials of the remote peer.
+const USERNAME: &str = "perf";
+const PASSWORD: &str = "perf";
+/// The delay between the probes sent to the local peer.
+const PROBE_INTERVAL: Duration = Duration::from_millis(100);
+/// How long the remote radio sends probes before a throughput run, since
+/// its messages are dropped until the dish has joined the group.
+const RADIO_WARMUP: Duration = Duration::from_secs(1);
+/// How long the local peer waits for the next message before considering
+/// the remaining messages lost.
+const LOSS_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long the sockets wait for their queued messages to be sent once
+/// dropped, since they do not linger by default.
+const LINGER: Duration = Duration::from_secs(10);
+
+/// The socket pair used for a measurement.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Pattern {
+    /// A `Client` connected to a `Server`.
+    ClientServer,
+    /// A `Radio` connected to a `Dish`.
+    RadioDish,
+    /// A `Scatter` connected to a `Gather`.
+    ScatterGather,
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "client-server" => Ok(Pattern::ClientServer),
+            "radio-dish" => Ok(Pattern::RadioDish),
+            "scatter-gather" => Ok(Pattern::ScatterGather),
+            _ => Err(format!("unknown pattern: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::ClientServer => write!(f, "client-server"),
+            Pattern::RadioDish => write!(f, "radio-dish"),
+            Pattern::ScatterGather => write!(f, "scatter-gather"),
+        }
+    }
+}
+
+/// The security mechanism used for a measurement.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Security {
+    /// The `NULL` mechanism.
+    Null,
+    /// The `PLAIN` mechanism, the local peer being the server.
+    Plain,
+    /// The `CURVE` mechanism, the local peer being the server.
+    Curve,
+}
+
+impl FromStr for Security {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "null" => Ok(Security::Null),
+            "plain" => Ok(Security::Plain),
+            "curve" => Ok(Security::Curve),
+            _ => Err(format!("unknown mechanism: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Security {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Security::Null => write!(f, "null"),
+            Security::Plain => write!(f, "plain"),
+            Security::Curve => write!(f, "curve"),
+        }
+    }
+}
+
+/// The options shared by the local and remote peers, which must match.
+#[derive(Debug, Clone, StructOpt)]
+pub struct PerfOpt {
+    /// The endpoint bound by the local peer, such as `tcp://127.0.0.1:5555`,
+    /// `ipc:///tmp/perf` or `inproc://perf`.
+    #[structopt(parse(try_from_str = parse_endpoint))]
+    pub endpoint: Endpoint,
+    /// The endpoint on which the local peer replies for the radio-dish
+    /// and scatter-gather latency. Generated for `inproc`.
+    #[structopt(long, parse(try_from_str = parse_endpoint))]
+    pub reply_endpoint: Option<Endpoint>,
+    /// The socket pair: client-server, radio-dish or scatter-gather.
+    #[structopt(short, long, default_value = "client-server")]
+    pub pattern: Pattern,
+    /// The size of the messages in bytes.
+    #[structopt(short, long, default_value = "64")]
+    pub size: usize,
+    /// The number of messages.
+    #[structopt(short = "n", long, default_value = "10000")]
+    pub count: u64,
+    /// The security mechanism: null, plain or curve.
+    #[structopt(short, long, default_value = "null")]
+    pub mechanism: Security,
+    /// The send and receive high water mark of the sockets.
+    #[structopt(long, default_value = "1000")]
+    pub hwm: i32,
+    /// The CURVE secret key file of the local peer. Generated for `inproc`.
+    #[structopt(long, parse(from_os_str))]
+    pub secret_key: Option<PathBuf>,
+    /// The CURVE public key file of the local peer.
+    #[structopt(long, parse(from_os_str))]
+    pub server_key: Option<PathBuf>,
+}
+
+impl PerfOpt {
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        // Empty messages are reserved for the probes.
+        if self.size == 0 {
+            bail!("message size must be at least 1 byte");
+        }
+        if self.count == 0 {
+            bail!("message count must be at least 1");
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PerfOpt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} over {}, {} messages of {} bytes, {} mechanism, hwm {}",
+            self.pattern,
+            transport(&self.endpoint),
+            self.count,
+            self.size,
+            self.mechanism,
+            self.hwm
+        )
+    }
+}
+
+fn transport(endpoint: &Endpoint) -> &'static str {
+    match endpoint {
+        Endpoint::Tcp(_) => "tcp",
+        Endpoint::Udp(_) => "udp",
+        Endpoint::Ipc(_) => "ipc",
+        Endpoint::Inproc(_) => "inproc",
+        Endpoint::Pgm(_) => "pgm",
+        Endpoint::Epgm(_) => "epgm",
+        _ => "unknown",
+    }
+}
+
+/// The roundtrip times measured by the remote peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyReport {
+    samples: Vec<Duration>,
+}
+
+impl LatencyReport {
+    /// Creates a report from roundtrip times.
+    ///
+    /// # Panic
+    /// Panics if `samples` is empty.
+    pub fn new(mut samples: Vec<Duration>) -> Self {
+        assert!(!samples.is_empty(), "no latency samples");
+        samples.sort();
+        Self { samples }
+    }
+
+    /// Returns the number of roundtrips.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Returns `false`, since a report has at least one roundtrip.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Returns the mean roundtrip time.
+    pub fn mean(&self) -> Duration {
+        self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+    }
+
+    /// Returns the roundtrip time of the `p`th percentile using the
+    /// nearest-rank method, where `p` is within `0.0..=100.0`.
+    pub fn percentile(&self, p: f64) -> Duration {
+        let rank = (p / 100.0 * self.samples.len() as f64).ceil() as usize;
+        self.samples[rank.max(1).min(self.samples.len()) - 1]
+    }
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1e6
+}
+
+impl fmt::Display for LatencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "roundtrips: {}", self.len())?;
+        writeln!(f, "mean: {:.1} us", micros(self.mean()))?;
+        for &(name, p) in &[
+            ("min", 0.0),
+            ("p50", 50.0),
+            ("p90", 90.0),
+            ("p99", 99.0),
+            ("p99.9", 99.9),
+            ("max", 100.0),
+        ] {
+            writeln!(f, "{}: {:.1} us", name, micros(self.percentile(p)))?;
+        }
+        write!(f, "one-way estimate: {:.1} us", micros(self.mean()) / 2.0)
+    }
+}
+
+/// The throughput measured by either peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThroughputReport {
+    /// The number of messages sent or received.
+    pub count: u64,
+    /// The number of messages that were not received.
+    pub lost: u64,
+    /// The size of the messages in bytes.
+    pub size: usize,
+    /// The time elapsed between the first and the last message.
+    pub elapsed: Duration,
+}
+
+impl ThroughputReport {
+    /// Returns the throughput in messages per second.
+    pub fn msg_per_sec(&self) -> f64 {
+        self.count as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Returns the throughput in megabits per second.
+    pub fn mbit_per_sec(&self) -> f64 {
+        self.msg_per_sec() * self.size as f64 * 8.0 / 1e6
+    }
+}
+
+impl fmt::Display for ThroughputReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "messages: {}", self.count)?;
+        if self.lost > 0 {
+            writeln!(f, "lost: {}", self.lost)?;
+        }
+        writeln!(f, "elapsed: {:.3} s", self.elapsed.as_secs_f64())?;
+        writeln!(f, "throughput: {:.0} msg/s", self.msg_per_sec())?;
+        write!(f, "throughput: {:.3} Mb/s", self.mbit_per_sec())
+    }
+}
+
+// The sockets of one of the peers.
+struct Peer {
+    pattern: Pattern,
+    bind: bool,
+    mechanism: Mechanism,
+    hwm: i32,
+    handle: CtxHandle,
+}
+
+impl Peer {
+    fn local(
+        opt: &PerfOpt,
+        cert: Option<&CurveCert>,
+        handle: CtxHandle,
+    ) -> Result<Self, anyhow::Error> {
+        let mechanism = match opt.mechanism {
+            Security::Null => Mechanism::Null,
+            Security::Plain => {
+                AuthBuilder::new()
+                    .plain_registry(vec![PlainClientCreds::new(
+                        USERNAME, PASSWORD,
+                    )])
+                    .with_ctx(handle)?;
+                Mechanism::PlainServer
+            }
+            Security::Curve => {
+                // Any client is accepted, since only the cost of the
+                // encryption matters here.
+                AuthBuilder::new().no_curve_auth().with_ctx(handle)?;
+                CurveServerCreds::new(cert.unwrap().secret()).into()
+            }
+        };
+        Ok(Self {
+            pattern: opt.pattern,
+            bind: true,
+            mechanism,
+            hwm: opt.hwm,
+            handle,
+        })
+    }
+
+    fn remote(
+        opt: &PerfOpt,
+        server: Option<CurvePublicKey>,
+        handle: CtxHandle,
+    ) -> Self {
+        let mechanism = match opt.mechanism {
+            Security::Null => Mechanism::Null,
+            Security::Plain => PlainClientCreds::new(USERNAME, PASSWORD).into(),
+            Security::Curve => CurveClientCreds::new(server.unwrap()).into(),
+        };
+        Self {
+            pattern: opt.pattern,
+            bind: false,
+            mechanism,
+            hwm: opt.hwm,
+            handle,
+        }
+    }
+
+    fn attach<B>(&self, builder: &mut B, endpoint: &Endpoint)
+    where
+        B: BuildSocket,
+    {
+        if self.bind {
+            builder.bind(endpoint);
+        } else {
+            builder.connect(endpoint);
+        }
+        builder.mechanism(self.mechanism.clone());
+    }
+
+    fn client_server(&self, endpoint: &Endpoint) -> Result<SocketType, Error> {
+        let socket = if self.bind {
+            let mut builder = ServerBuilder::new();
+            self.attach(&mut builder, endpoint);
+            builder.send_hwm(self.hwm).recv_hwm(self.hwm);
+            SocketType::Server(builder.with_ctx(self.handle)?)
+        } else {
+            let mut builder = ClientBuilder::new();
+            self.attach(&mut builder, endpoint);
+            builder.send_hwm(self.hwm).recv_hwm(self.hwm);
+            SocketType::Client(builder.with_ctx(self.handle)?)
+        };
+        Ok(socket)
+    }
+
+    fn sender(&self, endpoint: &Endpoint) -> Result<SocketType, Error> {
+        let socket = match self.pattern {
+            Pattern::ClientServer => self.client_server(endpoint)?,
+            Pattern::RadioDish => {
+                let mut builder = RadioBuilder::new();
+                self.attach(&mut builder, endpoint);
+                builder.send_hwm(self.hwm);
+                SocketType::Radio(builder.with_ctx(self.handle)?)
+            }
+            Pattern::ScatterGather => {
+                let mut builder = ScatterBuilder::new();
+                self.attach(&mut builder, endpoint);
+                builder.send_hwm(self.hwm);
+                SocketType::Scatter(builder.with_ctx(self.handle)?)
+            }
+        };
+        socket.set_linger(Some(LINGER))?;
+        Ok(socket)
+    }
+
+    fn receiver(&self, endpoint: &Endpoint) -> Result<SocketType, Error> {
+        let socket = match self.pattern {
+            Pattern::ClientServer => self.client_server(endpoint)?,
+            Pattern::RadioDish => {
+                let group: Group = GROUP.try_into().unwrap();
+                let mut builder = DishBuilder::new();
+                self.attach(&mut builder, endpoint);
+                builder.join(group).recv_hwm(self.hwm);
+                SocketType::Dish(builder.with_ctx(self.handle)?)
+            }
+            Pattern::ScatterGather => {
+                let mut builder = GatherBuilder::new();
+                self.attach(&mut builder, endpoint);
+                builder.recv_hwm(self.hwm);
+                SocketType::Gather(builder.with_ctx(self.handle)?)
+            }
+        };
+        Ok(socket)
+    }
+
+    // Returns the receiving and the sending sockets of a roundtrip, the
+    // local peer receiving on the endpoint and replying on `reply`.
+    fn roundtrip(
+        &self,
+        endpoint: &Endpoint,
+        reply: Option<&Endpoint>,
+    ) -> Result<(SocketType, SocketType), anyhow::Error> {
+        if self.pattern == Pattern::ClientServer {
+            let socket = self.client_server(endpoint)?;
+            socket.set_linger(Some(LINGER))?;
+            return Ok((socket.clone(), socket));
+        }
+
+        let reply = reply.with_context(|| {
+            format!("{} latency requires --reply-endpoint", self.pattern)
+        })?;
+        if self.bind {
+            Ok((self.receiver(endpoint)?, self.sender(reply)?))
+        } else {
+            Ok((self.receiver(reply)?, self.sender(endpoint)?))
+        }
+    }
+}
+
+fn message(pattern: Pattern, payload: &[u8]) -> Msg {
+    let mut msg = Msg::from(payload);
+    if pattern == Pattern::RadioDish {
+        let group: Group = GROUP.try_into().unwrap();
+        msg.set_group(group);
+    }
+    msg
+}
+
+fn local_cert(opt: &PerfOpt) -> Result<Option<CurveCert>, anyhow::Error> {
+    if opt.mechanism != Security::Curve {
+        return Ok(None);
+    }
+    let cert = match &opt.secret_key {
+        Some(path) => CurveCert::from_secret(keys::read_secret(path)?),
+        None if opt.endpoint.is_inproc() => CurveCert::new_unique(),
+        None => bail!("curve requires --secret-key"),
+    };
+    Ok(Some(cert))
+}
+
+fn remote_server_key(
+    opt: &PerfOpt,
+) -> Result<Option<CurvePublicKey>, anyhow::Error> {
+    if opt.mechanism != Security::Curve {
+        return Ok(None);
+    }
+    let path = opt
+        .server_key
+        .as_ref()
+        .context("curve requires --server-key")?;
+    Ok(Some(keys::read_public(path)?))
+}
+
+fn check_remote(opt: &PerfOpt) -> Result<(), anyhow::Error> {
+    opt.validate()?;
+    if opt.endpoint.is_inproc() {
+        bail!("inproc requires both peers in the same process, use the local peer alone");
+    }
+    Ok(())
+}
+
+// Spawns the remote peer in the context of the local peer.
+fn spawn_remote<T, F>(
+    opt: &PerfOpt,
+    cert: Option<&CurveCert>,
+    handle: CtxHandle,
+    f: F,
+) -> thread::JoinHandle<Result<T, anyhow::Error>>
+where
+    T: Send + 'static,
+    F: FnOnce(&PerfOpt, Peer) -> Result<T, anyhow::Error> + Send + 'static,
+{
+    let opt = opt.clone();
+    let server = cert.map(|cert| cert.public().to_owned());
+    thread::spawn(move || {
+        let peer = Peer::remote(&opt, server, handle);
+        f(&opt, peer)
+    })
+}
+
+// Echoes the messages until `count` messages of `size` bytes were echoed.
+fn echo(
+    opt: &PerfOpt,
+    rx: &SocketType,
+    tx: &SocketType,
+) -> Result<(), anyhow::Error> {
+    let mut echoed = 0;
+    while echoed < opt.count {
+        let mut msg = Msg::new();
+        recv(rx, &mut msg)?;
+        if msg.len() == opt.size {
+            echoed += 1;
+        }
+        send(tx, msg)?;
+    }
+    Ok(())
+}
+
+// Measures the roundtrips of `count` messages of `size` bytes.
+fn measure_latency(
+    opt: &PerfOpt,
+    rx: &SocketType,
+    tx: &SocketType,
+) -> Result<LatencyReport, anyhow::Error> {
+    // Probe the local peer until it replies, since radio messages are
+    // dropped until the dish subscriptions have propagated.
+    let mut msg = Msg::new();
+    'probe: loop {
+        send(tx, message(opt.pattern, &[]))?;
+        let deadline = Instant::now() + PROBE_INTERVAL;
+        while Instant::now() < deadline {
+            if try_recv(rx, &mut msg)? {
+                break 'probe;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    let payload = vec![0; opt.size];
+    let mut samples = Vec::with_capacity(opt.count as usize);
+    for _ in 0..opt.count {
+        let msg = message(opt.pattern, &payload);
+        let start = Instant::now();
+        send(tx, msg)?;
+        let mut reply = Msg::new();
+        // Skip the replies to late probes.
+        while reply.len() != opt.size {
+            recv(rx, &mut reply)?;
+        }
+        samples.push(start.elapsed());
+    }
+    Ok(LatencyReport::new(samples))
+}
+
+/// Runs the local peer of a latency measurement, which echoes the messages
+/// of the remote peer.
+///
+/// Returns the report of the remote peer if it ran in this process, which
+/// is the case with `inproc`.
+pub fn local_lat(
+    opt: &PerfOpt,
+) -> Result<Option<LatencyReport>, anyhow::Error> {
+    opt.validate()?;
+    let mut opt = opt.clone();
+    if opt.endpoint.is_inproc() && opt.reply_endpoint.is_none() {
+        opt.reply_endpoint = Some(InprocAddr::new_unique().into());
+    }
+
+    let ctx = Ctx::new();
+    let cert = local_cert(&opt)?;
+    let peer = Peer::local(&opt, cert.as_ref(), ctx.handle())?;
+    let (rx, tx) =
+        peer.roundtrip(&opt.endpoint, opt.reply_endpoint.as_ref())?;
+
+    if !opt.endpoint.is_inproc() {
+        echo(&opt, &rx, &tx)?;
+        return Ok(None);
+    }
+
+    let remote =
+        spawn_remote(&opt, cert.as_ref(), ctx.handle(), |opt, peer| {
+            let (rx, tx) =
+                peer.roundtrip(&opt.endpoint, opt.reply_endpoint.as_ref())?;
+            measure_latency(opt, &rx, &tx)
+        });
+    echo(&opt, &rx, &tx)?;
+    let report = remote.join().unwrap()?;
+    Ok(Some(report))
+}
+
+/// Runs the remote peer of a latency measurement, which connects to the
+/// local peer and measures the roundtrip times.
+pub fn remote_lat(opt: &PerfOpt) -> Result<LatencyReport, anyhow::Error> {
+    check_remote(opt)?;
+    let ctx = Ctx::new();
+    let peer = Peer::remote(opt, remote_server_key(opt)?, ctx.handle());
+    let (rx, tx) =
+        peer.roundtrip(&opt.endpoint, opt.reply_endpoint.as_ref())?;
+    measure_latency(opt, &rx, &tx)
+}
+
+// Receives up to `count` messages of `size` bytes, timing from the
+// first one. Gives up once no message was received for `LOSS_TIMEOUT`.
+fn receive_all(
+    opt: &PerfOpt,
+    rx: &SocketType,
+) -> Result<ThroughputReport, anyhow::Error> {
+    let mut msg = Msg::new();
+    // Skip the probes.
+    while msg.len() != opt.size {
+        recv(rx, &mut msg)?;
+    }
+    let start = Instant::now();
+    let mut last = start;
+
+    // Radio messages can be dropped and the remote peer can die, so we
+    // stop waiting eventually.
+    set_recv_timeout(rx, Some(LOSS_TIMEOUT))?;
+
+    let mut count = 1;
+    while count < opt.count {
+        match recv(rx, &mut msg) {
+            Ok(()) => (),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+            Err(err) => return Err(err.into()),
+        }
+        if msg.len() == opt.size {
+            count += 1;
+            last = Instant::now();
+        }
+    }
+
+    Ok(ThroughputReport {
+        count,
+        lost: opt.count - count,
+        size: opt.size,
+        elapsed: last - start,
+    })
+}
+
+// Sends `count` messages of `size` bytes.
+fn send_all(
+    opt: &PerfOpt,
+    tx: &SocketType,
+) -> Result<ThroughputReport, anyhow::Error> {
+    if opt.pattern == Pattern::RadioDish {
+        let start = Instant::now();
+        while start.elapsed() < RADIO_WARMUP {
+            send(tx, message(opt.pattern, &[]))?;
+            thread::sleep(PROBE_INTERVAL / 10);
+        }
+    }
+
+    let payload = vec![0; opt.size];
+    let start = Instant::now();
+    for _ in 0..opt.count {
+        send(tx, message(opt.pattern, &payload))?;
+    }
+    Ok(ThroughputReport {
+        count: opt.count,
+        lost: 0,
+        size: opt.size,
+        elapsed: start.elapsed(),
+    })
+}
+
+/// Runs the local peer of a throughput measurement, which receives the
+/// messages of the remote peer.
+pub fn local_thr(opt: &PerfOpt) -> Result<ThroughputReport, anyhow::Error> {
+    opt.validate()?;
+    let ctx = Ctx::new();
+    let cert = local_cert(opt)?;
+    let peer = Peer::local(opt, cert.as_ref(), ctx.handle())?;
+    let rx = peer.receiver(&opt.endpoint)?;
+
+    if !opt.endpoint.is_inproc() {
+        return receive_all(opt, &rx);
+    }
+
+    let remote = spawn_remote(opt, cert.as_ref(), ctx.handle(), |opt, peer| {
+        let tx = peer.sender(&opt.endpoint)?;
+        send_all(opt, &tx)
+    });
+    let report = receive_all(opt, &rx)?;
+    remote.join().unwrap()?;
+    Ok(report)
+}
+
+/// Runs the remote peer of a throughput measurement, which sends
+/// the messages as fast as possible.
+///
+/// Returns the rate at which the messages were queued, which can exceed
+/// the throughput measured by the local peer.
+pub fn remote_thr(opt: &PerfOpt) -> Result<ThroughputReport, anyhow::Error> {
+    check_remote(opt)?;
+    let ctx = Ctx::new();
+    let peer = Peer::remote(opt, remote_server_key(opt)?, ctx.handle());
+    let tx = peer.sender(&opt.endpoint)?;
+    send_all(opt, &tx)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let samples = (1..=100).map(Duration::from_micros).collect();
+        let report = LatencyReport::new(samples);
+
+        assert_eq!(report.percentile(0.0), Duration::from_micros(1));
+        assert_eq!(report.percentile(50.0), Duration::from_micros(50));
+        assert_eq!(report.percentile(99.0), Duration::from_micros(99));
+        assert_eq!(report.percentile(99.9), Duration::from_micros(100));
+        assert_eq!(report.percentile(100.0), Duration::from_micros(100));
+        assert_eq!(report.mean(), Duration::from_nanos(50_500));
+    }
+
+    fn opt(endpoint: &str, pattern: Pattern, mechanism: Security) -> PerfOpt {
+        PerfOpt {
+            endpoint: parse_endpoint(endpoint).unwrap(),
+            reply_endpoint: None,
+            pattern,
+            size: 16,
+            count: 100,
+            mechanism,
+            hwm: 1000,
+            secret_key: None,
+            server_key: None,
+        }
+    }
+
+    #[test]
+    fn test_inproc_lat() {
+        for &pattern in &[
+            Pattern::ClientServer,
+            Pattern::RadioDish,
+            Pattern::ScatterGather,
+        ] {
+            let addr = InprocAddr::new_unique();
+            let opt =
+                opt(&format!("inproc://{}", addr), pattern, Security::Null);
+            let report = local_lat(&opt).unwrap().unwrap();
+            assert_eq!(report.len(), 100);
+        }
+    }
+
+    #[test]
+    fn test_inproc_thr() {
+        for &pattern in &[Pattern::ClientServer, Pattern::ScatterGather] {
+            let addr = InprocAddr::new_unique();
+            let opt =
+                opt(&format!("inproc://{}", addr), pattern, Security::Plain);
+            let report = local_thr(&opt).unwrap();
+            assert_eq!(report.count, 100);
+            assert_eq!(report.lost, 0);
+        }
+    }
+
+    // The remote peer must know the port bound by the local peer.
+    fn tcp_endpoint() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("tcp://{}", listener.local_addr().unwrap())
+    }
+
+    fn lat(opt: PerfOpt) -> LatencyReport {
+        let local = {
+            let opt = opt.clone();
+            thread::spawn(move || local_lat(&opt))
+        };
+        let report = remote_lat(&opt).unwrap();
+        assert!(local.join().unwrap().unwrap().is_none());
+        report
+    }
+
+    fn thr(mut opt: PerfOpt) -> ThroughputReport {
+        // The remote peer must be connected before it exits, since the
+        // messages queued for a pending handshake are lost. This is
+        // guaranteed once it blocks on the high water mark.
+        opt.hwm = 10;
+        // Some messages are then still queued when it exits.
+        opt.size = 64 * 1024;
+        let local = {
+            let opt = opt.clone();
+            thread::spawn(move || local_thr(&opt))
+        };
+        remote_thr(&opt).unwrap();
+        local.join().unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_tcp_lat() {
+        for &pattern in &[Pattern::ClientServer, Pattern::ScatterGather] {
+            let mut opt = opt(&tcp_endpoint(), pattern, Security::Null);
+            if pattern != Pattern::ClientServer {
+                opt.reply_endpoint =
+                    Some(parse_endpoint(&tcp_endpoint()).unwrap());
+            }
+            assert_eq!(lat(opt).len(), 100);
+        }
+    }
+
+    #[test]
+    fn test_tcp_thr() {
+        for &pattern in &[Pattern::ClientServer, Pattern::ScatterGather] {
+            let report = thr(opt(&tcp_endpoint(), pattern, Security::Plain));
+            assert_eq!(report.count, 100);
+            assert_eq!(report.lost, 0);
+        }
+    }
+
+    #[test]
+    fn test_curve() {
+        if !capabilities().contains(Capabilities::CURVE) {
+            return;
+        }
+
+        let dir = std::env::temp_dir()
+            .join(format!("perf-{}", InprocAddr::new_unique()));
+        std::fs::create_dir(&dir).unwrap();
+        let cert = CurveCert::new_unique();
+        let (public, secret) =
+            keys::write_cert(&dir, "local", &cert, false).unwrap();
+
+        let mut opt =
+            opt(&tcp_endpoint(), Pattern::ClientServer, Security::Curve);
+        opt.secret_key = Some(secret);
+        opt.server_key = Some(public);
+        assert_eq!(lat(opt.clone()).len(), 100);
+
+        opt.endpoint = parse_endpoint(&tcp_endpoint()).unwrap();
+        let report = thr(opt);
+        assert_eq!(report.count, 100);
+        assert_eq!(report.lost, 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_remote_rejects_inproc() {
+        let opt = opt("inproc://perf", Pattern::ClientServer, Security::Null);
+        assert!(remote_thr(&opt).is_err());
+    }
+}
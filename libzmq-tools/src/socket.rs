@@ -0,0 +1,86 @@
+//! Sends and receives messages on any `SocketType`.
+//!
+//! The send functions panic if the socket cannot send, such as a `Dish`,
+//! while the receive functions panic if the socket cannot receive, such
+//! as a `Radio`. Use [`can_send`] and [`can_recv`] to check beforehand.
+//!
+//! [`can_send`]: fn.can_send.html
+//! [`can_recv`]: fn.can_recv.html
+
+use libzmq::{prelude::*, Error, ErrorKind, Msg, SocketType};
+
+use std::time::Duration;
+
+/// Whether the socket can send messages.
+pub fn can_send(socket: &SocketType) -> bool {
+    !matches!(socket, SocketType::Dish(_) | SocketType::Gather(_))
+}
+
+/// Whether the socket can receive messages.
+pub fn can_recv(socket: &SocketType) -> bool {
+    !matches!(socket, SocketType::Radio(_) | SocketType::Scatter(_))
+}
+
+/// Sends the message, blocking until it is queued.
+pub fn send(socket: &SocketType, msg: Msg) -> Result<(), Error> {
+    let result = match socket {
+        SocketType::Client(client) => client.send(msg),
+        SocketType::Server(server) => server.send(msg),
+        SocketType::Radio(radio) => radio.send(msg),
+        SocketType::Scatter(scatter) => scatter.send(msg),
+        SocketType::Dish(_) | SocketType::Gather(_) => {
+            panic!("socket cannot send")
+        }
+    };
+    result.map_err(Error::cast)
+}
+
+/// Receives a message into `msg`, blocking until one is available or the
+/// receive timeout expires.
+pub fn recv(socket: &SocketType, msg: &mut Msg) -> Result<(), Error> {
+    match socket {
+        SocketType::Client(client) => client.recv(msg),
+        SocketType::Server(server) => server.recv(msg),
+        SocketType::Dish(dish) => dish.recv(msg),
+        SocketType::Gather(gather) => gather.recv(msg),
+        SocketType::Radio(_) | SocketType::Scatter(_) => {
+            panic!("socket cannot receive")
+        }
+    }
+}
+
+/// Receives a message into `msg` without blocking.
+///
+/// Returns `false` if no message was available.
+pub fn try_recv(socket: &SocketType, msg: &mut Msg) -> Result<bool, Error> {
+    let result = match socket {
+        SocketType::Client(client) => client.try_recv(msg),
+        SocketType::Server(server) => server.try_recv(msg),
+        SocketType::Dish(dish) => dish.try_recv(msg),
+        SocketType::Gather(gather) => gather.try_recv(msg),
+        SocketType::Radio(_) | SocketType::Scatter(_) => {
+            panic!("socket cannot receive")
+        }
+    };
+    match result {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Sets the receive timeout of the socket.
+pub fn set_recv_timeout(
+    socket: &SocketType,
+    timeout: Option<Duration>,
+) -> Result<(), Error> {
+    match socket {
+        SocketType::Client(client) => client.set_recv_timeout(timeout),
+        SocketType::Server(server) => server.set_recv_timeout(timeout),
+        SocketType::Dish(dish) => dish.set_recv_timeout(timeout),
+        SocketType::Gather(gather) => gather.set_recv_timeout(timeout),
+        SocketType::Radio(_) | SocketType::Scatter(_) => {
+            panic!("socket cannot receive")
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use libzmq::{auth::*, config::AuthConfig, *};
+use serde::Deserialize;
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+const SECRET: &str = "sqe2ZQ%<<?*(MV2Shf%9=CtldI@T^^pgrML1S.F/";
+const PUBLIC: &str = "hb=GN9.(K*)]:{q*)XjsMgwfDTPJYh!w*n/xlIl+";
+
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> Self {
+        let name = format!("curve-cert-{}", InprocAddr::new_unique());
+        let dir = std::env::temp_dir().join(name);
+        fs::create_dir(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    fn write(&self, name: &str, key: &str) -> PathBuf {
+        let path = self.0.join(name);
+        fs::write(&path, format!("{}\n", key)).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Snippet {
+    mechanism: Mechanism,
+    auth: Option<AuthConfig>,
+}
+
+fn curve_cert(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_curve-cert"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(args: &[&str]) -> String {
+    let output = curve_cert(args, &[]);
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .trim_end()
+        .to_owned()
+}
+
+fn path(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+fn has_curve() -> bool {
+    capabilities().contains(Capabilities::CURVE)
+}
+
+#[test]
+fn test_generate_and_public() {
+    if !has_curve() {
+        return;
+    }
+
+    let dir = TempDir::new();
+    stdout(&["generate", "a", "--dir", path(&dir.0)]);
+
+    let public = fs::read_to_string(dir.0.join("a.pub")).unwrap();
+    let secret = dir.0.join("a.secret");
+    assert_eq!(stdout(&["public", path(&secret)]), public.trim());
+
+    // The existing files are kept unless forced.
+    let output = curve_cert(&["generate", "a", "--dir", path(&dir.0)], &[]);
+    assert!(!output.status.success());
+    assert_eq!(fs::read_to_string(dir.0.join("a.pub")).unwrap(), public);
+    stdout(&["generate", "a", "--dir", path(&dir.0), "--force"]);
+    assert_ne!(fs::read_to_string(dir.0.join("a.pub")).unwrap(), public);
+}
+
+#[test]
+fn test_convert_roundtrip() {
+    let hex = stdout(&["convert", PUBLIC]);
+    assert_eq!(hex.len(), 64);
+    assert_eq!(
+        stdout(&["convert", &hex, "--from", "hex", "--to", "z85"]),
+        PUBLIC
+    );
+
+    let output = curve_cert(&["convert", "--to", "bin"], PUBLIC.as_bytes());
+    assert!(output.status.success());
+    assert_eq!(output.stdout.len(), 32);
+
+    let output = curve_cert(
+        &["convert", "--from", "bin", "--to", "z85"],
+        &output.stdout,
+    );
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim_end(), PUBLIC);
+
+    let output = curve_cert(&["convert", "not a key"], &[]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_yaml_server() {
+    let dir = TempDir::new();
+    let secret = dir.write("server.secret", SECRET);
+    let allowed = TempDir::new();
+    allowed.write("client.pub", PUBLIC);
+
+    let yaml = stdout(&[
+        "yaml",
+        "server",
+        "--secret-key",
+        path(&secret),
+        "--allow-key",
+        path(&allowed.0),
+    ]);
+    let snippet: Snippet = serde_yaml::from_str(&yaml).unwrap();
+
+    let secret = CurveSecretKey::new(SECRET).unwrap();
+    assert_eq!(snippet.mechanism, CurveServerCreds::new(secret).into());
+    let mut auth = AuthConfig::new();
+    auth.set_curve_registry(Some(vec![CurvePublicKey::new(PUBLIC).unwrap()]));
+    assert_eq!(snippet.auth, Some(auth));
+}
+
+#[test]
+fn test_yaml_client() {
+    if !has_curve() {
+        return;
+    }
+
+    let dir = TempDir::new();
+    let server = dir.write("server.pub", PUBLIC);
+    let secret = dir.write("client.secret", SECRET);
+
+    let yaml = stdout(&[
+        "yaml",
+        "client",
+        "--server-key",
+        path(&server),
+        "--secret-key",
+        path(&secret),
+    ]);
+    let snippet: Snippet = serde_yaml::from_str(&yaml).unwrap();
+
+    let cert = CurveCert::from_secret(CurveSecretKey::new(SECRET).unwrap());
+    let creds = CurveClientCreds::new(CurvePublicKey::new(PUBLIC).unwrap())
+        .add_cert(cert);
+    assert_eq!(snippet.mechanism, creds.into());
+    assert!(snippet.auth.is_none());
+}
@@ -0,0 +1,54 @@
+use libzmq::{prelude::*, *};
+
+use std::{
+    io::Write,
+    net::TcpListener,
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+#[test]
+fn test_send_only_delivers_to_late_peer() {
+    // Reserve a port for the peer, which is bound once stdin is exhausted.
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let addr: TcpAddr = format!("127.0.0.1:{}", port).try_into().unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_zmqcat"))
+        .arg("scatter")
+        .arg("--connect")
+        .arg(format!("tcp://{}", addr))
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // The line payloads are large so that some of them are still queued
+    // when zmqcat exits.
+    let lines: Vec<String> =
+        (0..1000).map(|i| format!("{:01$}", i, 4096)).collect();
+    let mut stdin = child.stdin.take().unwrap();
+    for line in &lines {
+        writeln!(stdin, "{}", line).unwrap();
+    }
+    drop(stdin);
+
+    // zmqcat waits for the handshake of a peer.
+    thread::sleep(Duration::from_millis(200));
+    assert!(child.try_wait().unwrap().is_none());
+
+    let gather = GatherBuilder::new()
+        .bind(addr)
+        .recv_timeout(Duration::from_secs(5))
+        .build()
+        .unwrap();
+    assert!(child.wait().unwrap().success());
+
+    for line in &lines {
+        let msg = gather.recv_msg().unwrap();
+        assert_eq!(msg.to_str().unwrap(), line);
+    }
+}
@@ -37,6 +37,7 @@ const CURVE_CURVE_KEY_SIZE: usize = 40;
 
 /// A error when encoding or decoding a `CurveKey`.
 #[derive(Debug, Error, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum CurveError {
     #[error("input string must have len of 40 char")]
     InvalidSize,
@@ -46,6 +47,8 @@ pub enum CurveError {
         pos
     )]
     InvalidByte { pos: usize, byte: u8 },
+    #[error("input bytes must have len of 32")]
+    InvalidBinarySize,
 }
 
 fn z85_encode_chunk(input: &[u8]) -> [u8; 5] {
@@ -160,6 +163,19 @@ impl CurvePublicKey {
     pub fn as_str(&self) -> &str {
         self.inner.as_str()
     }
+
+    /// Create a new `CurvePublicKey` from its 32 bytes binary
+    /// representation.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CurveError> {
+        let inner = CurveKey::from_bytes(bytes)?;
+
+        Ok(Self { inner })
+    }
+
+    /// Returns the 32 bytes binary representation of the key.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        BinCurveKey::from(self).bytes
+    }
 }
 
 impl fmt::Display for CurvePublicKey {
@@ -286,6 +302,19 @@ impl CurveSecretKey {
     pub fn as_str(&self) -> &str {
         self.inner.as_str()
     }
+
+    /// Create a new `CurveSecretKey` from its 32 bytes binary
+    /// representation.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CurveError> {
+        let inner = CurveKey::from_bytes(bytes)?;
+
+        Ok(Self { inner })
+    }
+
+    /// Returns the 32 bytes binary representation of the key.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        BinCurveKey::from(self).bytes
+    }
 }
 
 impl fmt::Debug for CurveSecretKey {
@@ -388,6 +417,16 @@ impl CurveKey {
         Ok(Self { text })
     }
 
+    fn from_bytes(bytes: &[u8]) -> Result<Self, CurveError> {
+        if bytes.len() != CURVE_CURVE_KEY_SIZE / 5 * 4 {
+            return Err(CurveError::InvalidBinarySize);
+        }
+        let text = z85_encode(bytes)?;
+
+        // No need to validate.
+        Ok(Self { text })
+    }
+
     fn from_secret<K>(secret: K) -> Self
     where
         K: Into<CurveKey>,
@@ -554,6 +593,25 @@ impl CurveCert {
         Self { public, secret }
     }
 
+    /// Creates the certificate of a secret key, deriving its public key.
+    ///
+    /// ```
+    /// use libzmq::auth::CurveCert;
+    ///
+    /// let cert = CurveCert::new_unique();
+    /// let derived = CurveCert::from_secret(cert.secret());
+    /// assert_eq!(cert, derived);
+    /// ```
+    pub fn from_secret<S>(secret: S) -> Self
+    where
+        S: Into<CurveSecretKey>,
+    {
+        let secret = secret.into();
+        let public = CurvePublicKey::from(&secret);
+
+        Self { public, secret }
+    }
+
     /// Returns a reference to the certificate's public key.
     pub fn public(&self) -> &CurvePublicKey {
         &self.public
@@ -783,6 +841,17 @@ mod tests {
         CurveKey::new(CURVE_KEY_SECRET).unwrap();
     }
 
+    #[test]
+    fn curve_key_bytes_roundtrip() {
+        let public = CurvePublicKey::new(CURVE_KEY_PUBLIC).unwrap();
+        let bytes = public.to_bytes();
+        assert_eq!(bytes.len(), 32);
+        assert_eq!(CurvePublicKey::from_bytes(&bytes).unwrap(), public);
+
+        let err = CurveSecretKey::from_bytes(&bytes[1..]).unwrap_err();
+        assert_eq!(err, CurveError::InvalidBinarySize);
+    }
+
     #[test]
     fn curve_key_from_secret() {
         let secret = CurveKey::new(CURVE_KEY_SECRET).unwrap();
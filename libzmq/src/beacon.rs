@@ -0,0 +1,908 @@
+//! UDP beacons for service discovery on a local network.
+//!
+//! A [`Beacon`] periodically broadcasts, or multicasts, a small announcement
+//! on a UDP port while listening for the announcements of its peers on that
+//! same port. A peer appears upon its first announcement and expires once it
+//! stayed silent for longer than the [`expiry`] period.
+//!
+//! The announcement is opaque to the beacon. It usually contains a service
+//! name followed by the endpoint of a `Server`, so that `Client`s can
+//! connect to it without static configuration. Announcements are filtered
+//! by prefix, which allows many services to share the same port.
+//!
+//! [`Beacon`]: struct.Beacon.html
+//! [`expiry`]: struct.BeaconConfig.html#method.expiry
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+
+use std::{
+    collections::HashMap,
+    fmt, io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The maximum size of an announcement in bytes.
+pub const BEACON_MAX_SIZE: usize = 255;
+
+/// The default port of a [`Beacon`], which is the one used by Zyre.
+///
+/// [`Beacon`]: struct.Beacon.html
+pub const DEFAULT_BEACON_PORT: u16 = 5670;
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_EXPIRY: Duration = Duration::from_secs(5);
+// How often the beacon thread checks whether it was stopped.
+const TICK: Duration = Duration::from_millis(50);
+
+/// A config for a [`Beacon`].
+///
+/// Usefull in configuration files.
+///
+/// [`Beacon`]: struct.Beacon.html
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BeaconConfig {
+    port: u16,
+    addr: IpAddr,
+    interface: Option<IpAddr>,
+    #[serde(with = "humantime_serde")]
+    interval: Duration,
+    #[serde(with = "humantime_serde")]
+    expiry: Duration,
+    filter: Option<Vec<u8>>,
+}
+
+impl BeaconConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a `Beacon` from the configuration.
+    pub fn build(&self) -> io::Result<Beacon> {
+        Beacon::with_config(self.clone())
+    }
+
+    /// The UDP port on which announcements are sent and received.
+    ///
+    /// # Default
+    /// [`DEFAULT_BEACON_PORT`]
+    ///
+    /// [`DEFAULT_BEACON_PORT`]: constant.DEFAULT_BEACON_PORT.html
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn set_port(&mut self, port: u16) {
+        self.port = port;
+    }
+
+    /// The address to which announcements are sent.
+    ///
+    /// Either a broadcast address or a multicast group, which the beacon
+    /// joins to receive the announcements of its peers.
+    ///
+    /// # Default
+    /// `255.255.255.255`, the limited broadcast address.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn set_addr(&mut self, addr: IpAddr) {
+        self.addr = addr;
+    }
+
+    /// The address of the interface used to send announcements and join
+    /// the multicast group. Only IPv4 interfaces are supported.
+    ///
+    /// # Default
+    /// `None`, meaning that the OS picks the interface.
+    pub fn interface(&self) -> Option<IpAddr> {
+        self.interface
+    }
+
+    pub fn set_interface(&mut self, maybe: Option<IpAddr>) {
+        self.interface = maybe;
+    }
+
+    /// The period between announcements.
+    ///
+    /// # Default
+    /// 1 second.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// How long a silent peer is kept before it expires.
+    ///
+    /// This should be a few times the interval of the peers, so that a
+    /// lost announcement does not expire them.
+    ///
+    /// # Default
+    /// 5 seconds.
+    pub fn expiry(&self) -> Duration {
+        self.expiry
+    }
+
+    pub fn set_expiry(&mut self, expiry: Duration) {
+        self.expiry = expiry;
+    }
+
+    /// The prefix of the announcements to receive.
+    ///
+    /// # Default
+    /// `None`, meaning that every announcement is received.
+    pub fn filter(&self) -> Option<&[u8]> {
+        self.filter.as_deref()
+    }
+
+    pub fn set_filter<P>(&mut self, maybe: Option<P>)
+    where
+        P: Into<Vec<u8>>,
+    {
+        self.filter = maybe.map(P::into);
+    }
+}
+
+impl Default for BeaconConfig {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_BEACON_PORT,
+            addr: Ipv4Addr::BROADCAST.into(),
+            interface: None,
+            interval: DEFAULT_INTERVAL,
+            expiry: DEFAULT_EXPIRY,
+            filter: None,
+        }
+    }
+}
+
+/// A convenience builder for a [`Beacon`].
+///
+/// [`Beacon`]: struct.Beacon.html
+#[derive(
+    Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+pub struct BeaconBuilder {
+    inner: BeaconConfig,
+}
+
+impl BeaconBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a `Beacon` from a `BeaconBuilder`.
+    pub fn build(&self) -> io::Result<Beacon> {
+        self.inner.build()
+    }
+
+    /// See [`set_port`].
+    ///
+    /// [`set_port`]: struct.BeaconConfig.html#method.set_port
+    pub fn port(&mut self, port: u16) -> &mut Self {
+        self.inner.set_port(port);
+        self
+    }
+
+    /// See [`set_addr`].
+    ///
+    /// [`set_addr`]: struct.BeaconConfig.html#method.set_addr
+    pub fn addr<A>(&mut self, addr: A) -> &mut Self
+    where
+        A: Into<IpAddr>,
+    {
+        self.inner.set_addr(addr.into());
+        self
+    }
+
+    /// See [`set_interface`].
+    ///
+    /// [`set_interface`]: struct.BeaconConfig.html#method.set_interface
+    pub fn interface<A>(&mut self, addr: A) -> &mut Self
+    where
+        A: Into<IpAddr>,
+    {
+        self.inner.set_interface(Some(addr.into()));
+        self
+    }
+
+    /// See [`set_interval`].
+    ///
+    /// [`set_interval`]: struct.BeaconConfig.html#method.set_interval
+    pub fn interval(&mut self, interval: Duration) -> &mut Self {
+        self.inner.set_interval(interval);
+        self
+    }
+
+    /// See [`set_expiry`].
+    ///
+    /// [`set_expiry`]: struct.BeaconConfig.html#method.set_expiry
+    pub fn expiry(&mut self, expiry: Duration) -> &mut Self {
+        self.inner.set_expiry(expiry);
+        self
+    }
+
+    /// See [`set_filter`].
+    ///
+    /// [`set_filter`]: struct.BeaconConfig.html#method.set_filter
+    pub fn filter<P>(&mut self, prefix: P) -> &mut Self
+    where
+        P: Into<Vec<u8>>,
+    {
+        self.inner.set_filter(Some(prefix));
+        self
+    }
+}
+
+/// A peer discovered by a [`Beacon`].
+///
+/// [`Beacon`]: struct.Beacon.html
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BeaconPeer {
+    addr: SocketAddr,
+    announcement: Vec<u8>,
+}
+
+impl BeaconPeer {
+    /// The address from which the peer sends its announcements.
+    ///
+    /// The port is not the beacon port and is unique per beacon, which
+    /// distinguishes beacons running on the same host.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The announcement of the peer.
+    pub fn announcement(&self) -> &[u8] {
+        &self.announcement
+    }
+}
+
+/// A change in the peers of a [`Beacon`].
+///
+/// A peer whose announcement changes is reported as expired, then as
+/// appeared with its new announcement.
+///
+/// [`Beacon`]: struct.Beacon.html
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum BeaconEvent {
+    /// The first announcement of the peer was received.
+    Appeared(BeaconPeer),
+    /// The peer was silent for longer than the expiry period.
+    Expired(BeaconPeer),
+}
+
+impl BeaconEvent {
+    /// Returns the peer of the event.
+    pub fn peer(&self) -> &BeaconPeer {
+        match self {
+            BeaconEvent::Appeared(peer) | BeaconEvent::Expired(peer) => peer,
+        }
+    }
+}
+
+type Callback = Box<dyn FnMut(&BeaconEvent) + Send>;
+
+struct State {
+    announcement: Option<Vec<u8>>,
+    next_send: Instant,
+    filter: Option<Vec<u8>>,
+    // The announcement and the last time each peer was heard from.
+    peers: HashMap<SocketAddr, (Vec<u8>, Instant)>,
+}
+
+struct Shared {
+    config: BeaconConfig,
+    recv: UdpSocket,
+    send: UdpSocket,
+    dest: SocketAddr,
+    state: Mutex<State>,
+    callbacks: Mutex<Vec<Callback>>,
+    stopped: AtomicBool,
+}
+
+/// Announces a service and discovers peers on the local network using
+/// UDP broadcast or multicast, like CZMQ's `zbeacon`.
+///
+/// The beacon runs in a background thread from its creation until it is
+/// dropped. It only sends announcements once [`publish`] is called, but
+/// always listens for the announcements of its peers, which are reported
+/// to the callbacks registered with [`on_event`].
+///
+/// Many beacons can share the same port on the same host, since the port is
+/// bound with `SO_REUSEADDR`. However, only a multicast group guarantees that
+/// every beacon of a host receives the announcements.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), anyhow::Error> {
+/// use libzmq::beacon::*;
+/// use std::{net::Ipv4Addr, sync::mpsc, time::Duration};
+///
+/// // Multicast on the loopback interface.
+/// let mut builder = BeaconBuilder::new();
+/// builder
+///     .port(9999)
+///     .addr(Ipv4Addr::new(239, 255, 42, 99))
+///     .interface(Ipv4Addr::LOCALHOST)
+///     .interval(Duration::from_millis(100));
+///
+/// let service = builder.build()?;
+/// service.publish("echo tcp://127.0.0.1:5555")?;
+///
+/// let client = builder.filter("echo ").build()?;
+/// let (sender, receiver) = mpsc::channel();
+/// client.on_event(move |event| {
+///     let _ = sender.send(event.clone());
+/// });
+///
+/// match receiver.recv_timeout(Duration::from_secs(5))? {
+///     BeaconEvent::Appeared(peer) => {
+///         assert_eq!(peer.announcement(), b"echo tcp://127.0.0.1:5555");
+///     }
+///     _ => unreachable!(),
+/// }
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// [`publish`]: #method.publish
+/// [`on_event`]: #method.on_event
+pub struct Beacon {
+    shared: Arc<Shared>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Beacon {
+    /// Creates a `Beacon` with the default configuration.
+    pub fn new() -> io::Result<Self> {
+        Self::with_config(BeaconConfig::default())
+    }
+
+    fn with_config(config: BeaconConfig) -> io::Result<Self> {
+        if matches!(config.interface, Some(IpAddr::V6(_))) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "IPv6 interfaces are not supported",
+            ));
+        }
+
+        let recv = bind_recv(&config)?;
+        recv.set_read_timeout(Some(TICK))?;
+        let send = bind_send(&config)?;
+        let dest = SocketAddr::new(config.addr, config.port);
+
+        let state = State {
+            announcement: None,
+            next_send: Instant::now(),
+            filter: config.filter.clone(),
+            peers: HashMap::new(),
+        };
+        let shared = Arc::new(Shared {
+            config,
+            recv,
+            send,
+            dest,
+            state: Mutex::new(state),
+            callbacks: Mutex::new(vec![]),
+            stopped: AtomicBool::new(false),
+        });
+
+        let thread = {
+            let shared = Arc::clone(&shared);
+            thread::Builder::new()
+                .name("beacon".to_owned())
+                .spawn(move || run(&shared))?
+        };
+
+        Ok(Self {
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the configuration of the beacon.
+    pub fn config(&self) -> &BeaconConfig {
+        &self.shared.config
+    }
+
+    /// Starts sending the announcement at every interval, replacing the
+    /// previous announcement if any.
+    ///
+    /// The first announcement is sent right away.
+    ///
+    /// # Returned Errors
+    /// * `InvalidInput` (announcement exceeds [`BEACON_MAX_SIZE`])
+    ///
+    /// [`BEACON_MAX_SIZE`]: constant.BEACON_MAX_SIZE.html
+    pub fn publish<A>(&self, announcement: A) -> io::Result<()>
+    where
+        A: Into<Vec<u8>>,
+    {
+        let announcement = announcement.into();
+        if announcement.len() > BEACON_MAX_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "announcement cannot exceed `BEACON_MAX_SIZE` bytes",
+            ));
+        }
+
+        let mut state = self.shared.state.lock().unwrap();
+        state.announcement = Some(announcement);
+        state.next_send = Instant::now();
+        Ok(())
+    }
+
+    /// Stops sending the announcement.
+    ///
+    /// Peers will notice once the expiry period has elapsed.
+    pub fn silence(&self) {
+        self.shared.state.lock().unwrap().announcement = None;
+    }
+
+    /// Returns the announcement being sent, if any.
+    pub fn announcement(&self) -> Option<Vec<u8>> {
+        self.shared.state.lock().unwrap().announcement.clone()
+    }
+
+    /// Sets the prefix of the announcements to receive. If `None`, every
+    /// announcement is received.
+    ///
+    /// Peers whose announcement no longer match are expired.
+    pub fn set_filter<P>(&self, maybe: Option<P>)
+    where
+        P: Into<Vec<u8>>,
+    {
+        let mut callbacks = self.shared.callbacks.lock().unwrap();
+        let events = {
+            let mut state = self.shared.state.lock().unwrap();
+            state.filter = maybe.map(P::into);
+
+            let mut events = vec![];
+            let filter = state.filter.clone();
+            state.peers.retain(|addr, (announcement, _)| {
+                if accepts(&filter, announcement) {
+                    true
+                } else {
+                    events.push(BeaconEvent::Expired(BeaconPeer {
+                        addr: *addr,
+                        announcement: announcement.clone(),
+                    }));
+                    false
+                }
+            });
+            events
+        };
+        Shared::notify(&mut callbacks, &events);
+    }
+
+    /// Returns the prefix of the announcements received, if any.
+    pub fn filter(&self) -> Option<Vec<u8>> {
+        self.shared.state.lock().unwrap().filter.clone()
+    }
+
+    /// Registers a callback called with every [`BeaconEvent`].
+    ///
+    /// The callback is first called with an `Appeared` event for each
+    /// current peer, so that no peer is missed.
+    ///
+    /// Callbacks are called in order of registration from the beacon thread.
+    /// They should return quickly and must not call `on_event` or
+    /// `set_filter`.
+    ///
+    /// [`BeaconEvent`]: enum.BeaconEvent.html
+    pub fn on_event<F>(&self, mut callback: F)
+    where
+        F: FnMut(&BeaconEvent) + Send + 'static,
+    {
+        let mut callbacks = self.shared.callbacks.lock().unwrap();
+        for peer in self.peers() {
+            callback(&BeaconEvent::Appeared(peer));
+        }
+        callbacks.push(Box::new(callback));
+    }
+
+    /// Forgets the peer, which is reported as expired.
+    ///
+    /// This is useful once a peer is known to be gone, since it is otherwise
+    /// kept until the expiry period has elapsed. The peer appears again if
+    /// it is still announcing.
+    ///
+    /// Returns `false` if the peer was unknown.
+    pub fn forget(&self, addr: SocketAddr) -> bool {
+        let mut callbacks = self.shared.callbacks.lock().unwrap();
+        let removed = self.shared.state.lock().unwrap().peers.remove(&addr);
+        match removed {
+            Some((announcement, _)) => {
+                let event =
+                    BeaconEvent::Expired(BeaconPeer { addr, announcement });
+                Shared::notify(&mut callbacks, &[event]);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the current peers, in no particular order.
+    pub fn peers(&self) -> Vec<BeaconPeer> {
+        let state = self.shared.state.lock().unwrap();
+        state
+            .peers
+            .iter()
+            .map(|(addr, (announcement, _))| BeaconPeer {
+                addr: *addr,
+                announcement: announcement.clone(),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Debug for Beacon {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Beacon")
+            .field("config", &self.shared.config)
+            .finish()
+    }
+}
+
+impl Drop for Beacon {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn accepts(filter: &Option<Vec<u8>>, announcement: &[u8]) -> bool {
+    match filter {
+        Some(prefix) => announcement.starts_with(prefix),
+        None => true,
+    }
+}
+
+fn domain(addr: IpAddr) -> Domain {
+    if addr.is_ipv4() {
+        Domain::IPV4
+    } else {
+        Domain::IPV6
+    }
+}
+
+fn unspecified(addr: IpAddr) -> IpAddr {
+    if addr.is_ipv4() {
+        Ipv4Addr::UNSPECIFIED.into()
+    } else {
+        Ipv6Addr::UNSPECIFIED.into()
+    }
+}
+
+fn interface_v4(config: &BeaconConfig) -> Ipv4Addr {
+    match config.interface {
+        Some(IpAddr::V4(addr)) => addr,
+        _ => Ipv4Addr::UNSPECIFIED,
+    }
+}
+
+// Binds the socket shared by the beacons of the host.
+fn bind_recv(config: &BeaconConfig) -> io::Result<UdpSocket> {
+    let socket =
+        Socket::new(domain(config.addr), Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+    socket.set_reuse_port(true)?;
+
+    let addr = SocketAddr::new(unspecified(config.addr), config.port);
+    socket.bind(&addr.into())?;
+
+    match config.addr {
+        IpAddr::V4(group) if group.is_multicast() => {
+            socket.join_multicast_v4(&group, &interface_v4(config))?;
+        }
+        IpAddr::V6(group) if group.is_multicast() => {
+            socket.join_multicast_v6(&group, 0)?;
+        }
+        _ => (),
+    }
+    Ok(socket.into())
+}
+
+// Binds the socket used to send announcements on a system assigned port,
+// which identifies the beacon.
+fn bind_send(config: &BeaconConfig) -> io::Result<UdpSocket> {
+    let socket =
+        Socket::new(domain(config.addr), Type::DGRAM, Some(Protocol::UDP))?;
+
+    match config.addr {
+        IpAddr::V4(addr) => {
+            let interface = interface_v4(config);
+            if addr.is_multicast() {
+                socket.set_multicast_if_v4(&interface)?;
+                socket.set_multicast_loop_v4(true)?;
+            } else {
+                socket.set_broadcast(true)?;
+            }
+            socket.bind(&SocketAddr::new(interface.into(), 0).into())?;
+        }
+        IpAddr::V6(addr) => {
+            if addr.is_multicast() {
+                socket.set_multicast_loop_v6(true)?;
+            }
+            let any = SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0);
+            socket.bind(&any.into())?;
+        }
+    }
+    Ok(socket.into())
+}
+
+impl Shared {
+    // Since events are produced and notified while holding the callbacks,
+    // a callback registered concurrently cannot miss or repeat an event.
+    fn notify(callbacks: &mut [Callback], events: &[BeaconEvent]) {
+        for event in events {
+            for callback in callbacks.iter_mut() {
+                callback(event);
+            }
+        }
+    }
+
+    fn send_due(&self) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        if now < state.next_send {
+            return;
+        }
+        state.next_send = now + self.config.interval;
+
+        if let Some(announcement) = &state.announcement {
+            if let Err(err) = self.send.send_to(announcement, self.dest) {
+                warn!("unable to send beacon announcement: {}", err);
+            }
+        }
+    }
+
+    fn handle(
+        &self,
+        src: SocketAddr,
+        data: &[u8],
+        events: &mut Vec<BeaconEvent>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+
+        // Ignore our own announcements, looped back by the OS.
+        let own_port = self.send.local_addr().map(|addr| addr.port()).ok();
+        if Some(src.port()) == own_port
+            && state.announcement.as_deref() == Some(data)
+        {
+            return;
+        }
+        if !accepts(&state.filter, data) {
+            return;
+        }
+
+        let now = Instant::now();
+        match state.peers.get_mut(&src) {
+            Some((announcement, last)) if announcement.as_slice() == data => {
+                *last = now;
+            }
+            Some((announcement, last)) => {
+                events.push(BeaconEvent::Expired(BeaconPeer {
+                    addr: src,
+                    announcement: announcement.clone(),
+                }));
+                events.push(BeaconEvent::Appeared(BeaconPeer {
+                    addr: src,
+                    announcement: data.to_vec(),
+                }));
+                *announcement = data.to_vec();
+                *last = now;
+            }
+            None => {
+                events.push(BeaconEvent::Appeared(BeaconPeer {
+                    addr: src,
+                    announcement: data.to_vec(),
+                }));
+                state.peers.insert(src, (data.to_vec(), now));
+            }
+        }
+    }
+
+    fn expire(&self, events: &mut Vec<BeaconEvent>) {
+        let mut state = self.state.lock().unwrap();
+        let expiry = self.config.expiry;
+        let now = Instant::now();
+        state.peers.retain(|addr, (announcement, last)| {
+            if now.duration_since(*last) > expiry {
+                events.push(BeaconEvent::Expired(BeaconPeer {
+                    addr: *addr,
+                    announcement: announcement.clone(),
+                }));
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+fn run(shared: &Shared) {
+    // One more byte to detect oversized datagrams.
+    let mut buf = [0; BEACON_MAX_SIZE + 1];
+    let mut events = vec![];
+    let mut received = None;
+
+    while !shared.stopped.load(Ordering::SeqCst) {
+        shared.send_due();
+
+        match shared.recv.recv_from(&mut buf) {
+            Ok((len, src)) if len <= BEACON_MAX_SIZE => {
+                received = Some((src, len));
+            }
+            Ok(_) => (),
+            Err(err) => match err.kind() {
+                io::ErrorKind::WouldBlock
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::Interrupted => (),
+                _ => warn!("unable to receive beacon announcement: {}", err),
+            },
+        }
+
+        let mut callbacks = shared.callbacks.lock().unwrap();
+        if let Some((src, len)) = received.take() {
+            shared.handle(src, &buf[..len], &mut events);
+        }
+        shared.expire(&mut events);
+        Shared::notify(&mut callbacks, &events);
+        events.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::mpsc;
+
+    // Multicast on the loopback interface, on a port unused by other tests.
+    fn builder() -> BeaconBuilder {
+        let port = UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let mut builder = BeaconBuilder::new();
+        builder
+            .port(port)
+            .addr(Ipv4Addr::new(239, 255, 42, 99))
+            .interface(Ipv4Addr::LOCALHOST)
+            .interval(Duration::from_millis(50))
+            .expiry(Duration::from_millis(300));
+        builder
+    }
+
+    fn events(beacon: &Beacon) -> mpsc::Receiver<BeaconEvent> {
+        let (sender, receiver) = mpsc::channel();
+        beacon.on_event(move |event| {
+            let _ = sender.send(event.clone());
+        });
+        receiver
+    }
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn test_ser_de() {
+        let mut config = BeaconConfig::new();
+        config.set_port(9999);
+        config.set_filter(Some("service"));
+        config.set_interval(Duration::from_millis(200));
+
+        let ron = serde_yaml::to_string(&config).unwrap();
+        let de: BeaconConfig = serde_yaml::from_str(&ron).unwrap();
+        assert_eq!(config, de);
+    }
+
+    #[test]
+    fn test_publish_too_large() {
+        let beacon = builder().build().unwrap();
+        let err = beacon.publish(vec![0; BEACON_MAX_SIZE + 1]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_appear_and_expire() {
+        let builder = builder();
+        let a = builder.build().unwrap();
+        let b = builder.build().unwrap();
+        let events = events(&b);
+
+        a.publish("a").unwrap();
+        match events.recv_timeout(TIMEOUT).unwrap() {
+            BeaconEvent::Appeared(peer) => {
+                assert_eq!(peer.announcement(), b"a");
+                assert_eq!(b.peers(), vec![peer]);
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+
+        a.silence();
+        match events.recv_timeout(TIMEOUT).unwrap() {
+            BeaconEvent::Expired(peer) => assert_eq!(peer.announcement(), b"a"),
+            event => panic!("unexpected event: {:?}", event),
+        }
+        assert!(b.peers().is_empty());
+    }
+
+    #[test]
+    fn test_forget() {
+        let mut builder = builder();
+        let a = builder.build().unwrap();
+        let b = builder.expiry(TIMEOUT).build().unwrap();
+        let events = events(&b);
+
+        a.publish("a").unwrap();
+        let peer = events.recv_timeout(TIMEOUT).unwrap().peer().clone();
+        a.silence();
+        // Let the last announcement arrive.
+        thread::sleep(Duration::from_millis(100));
+
+        assert!(b.forget(peer.addr()));
+        match events.recv_timeout(TIMEOUT).unwrap() {
+            BeaconEvent::Expired(expired) => assert_eq!(expired, peer),
+            event => panic!("unexpected event: {:?}", event),
+        }
+        assert!(b.peers().is_empty());
+        assert!(!b.forget(peer.addr()));
+    }
+
+    #[test]
+    fn test_ignores_own_announcement() {
+        let beacon = builder().build().unwrap();
+        let events = events(&beacon);
+        beacon.publish("self").unwrap();
+
+        assert!(events.recv_timeout(Duration::from_millis(300)).is_err());
+        assert!(beacon.peers().is_empty());
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut builder = builder();
+        let a = builder.build().unwrap();
+        let b = builder.build().unwrap();
+        let c = builder.filter("svc").build().unwrap();
+        let events = events(&c);
+
+        a.publish("other").unwrap();
+        b.publish("svc tcp://127.0.0.1:5555").unwrap();
+
+        let event = events.recv_timeout(TIMEOUT).unwrap();
+        assert_eq!(event.peer().announcement(), b"svc tcp://127.0.0.1:5555");
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(c.peers().len(), 1);
+
+        c.set_filter(Some("none"));
+        match events.recv_timeout(TIMEOUT).unwrap() {
+            BeaconEvent::Expired(peer) => {
+                assert_eq!(peer.announcement(), b"svc tcp://127.0.0.1:5555")
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+        assert!(c.peers().is_empty());
+    }
+}
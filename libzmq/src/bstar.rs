@@ -0,0 +1,960 @@
+//! An active/passive pair of servers, using the Binary Star pattern.
+//!
+//! A [`BinaryStar`] wraps the `Server` to which clients send their requests.
+//! The two servers of a pair publish their state to each other at every
+//! heartbeat interval and decide which of them is active, which is the only
+//! one to accept requests.
+//!
+//! At startup, the primary becomes active and the backup passive. Should
+//! the active server fail, the passive server only takes over once the
+//! active server stopped publishing its state for two intervals *and* a
+//! client sends it a request. Since clients only fail over once the active
+//! server stops answering them, this prevents both servers from becoming
+//! active when the link between them is broken but the active server is still
+//! reachable by the clients.
+//!
+//! A [`BinaryStarClient`] sends its requests to one server and fails over to
+//! the other when no reply is received in time.
+//!
+//! [`BinaryStar`]: struct.BinaryStar.html
+//! [`BinaryStarClient`]: struct.BinaryStarClient.html
+
+use crate::{
+    addr::Endpoint,
+    core::{BuildRecv, BuildSocket, RecvMsg, SendMsg, Socket},
+    error::{Error, ErrorKind},
+    socket::{ClientBuilder, Dish, DishBuilder, Radio, RadioBuilder, Server},
+    Client, Ctx, CtxHandle, Group, Msg, ServerBuilder,
+};
+
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+
+use std::{
+    convert::TryInto,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const GROUP: &str = "bstar";
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+// How often the background thread checks for the state of the peer.
+const TICK: Duration = Duration::from_millis(50);
+
+/// The role of a server in a [`BinaryStar`] pair.
+///
+/// [`BinaryStar`]: struct.BinaryStar.html
+#[derive(
+    Debug, Default, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Becomes active at startup.
+    #[default]
+    Primary,
+    /// Becomes passive at startup.
+    Backup,
+}
+
+/// The state of a server in a [`BinaryStar`] pair.
+///
+/// [`BinaryStar`]: struct.BinaryStar.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BinaryStarState {
+    /// The primary, waiting for the state of its peer.
+    Primary,
+    /// The backup, waiting for the state of its peer.
+    Backup,
+    /// Accepts the requests of the clients.
+    Active,
+    /// Rejects the requests of the clients, unless the peer is silent.
+    Passive,
+}
+
+/// An event of a [`BinaryStar`].
+///
+/// [`BinaryStar`]: struct.BinaryStar.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum BinaryStarEvent {
+    /// The server became active.
+    Active,
+    /// The server became passive.
+    Passive,
+    /// Both servers of the pair are active.
+    DualActive,
+    /// Both servers of the pair are passive.
+    DualPassive,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum FsmEvent {
+    Peer(BinaryStarState),
+    ClientRequest,
+}
+
+#[derive(Debug)]
+struct Fsm {
+    state: BinaryStarState,
+    interval: Duration,
+    // Past this instant, the peer is considered dead.
+    peer_expiry: Instant,
+}
+
+impl Fsm {
+    fn new(role: Role, interval: Duration) -> Self {
+        let state = match role {
+            Role::Primary => BinaryStarState::Primary,
+            Role::Backup => BinaryStarState::Backup,
+        };
+        Self {
+            state,
+            interval,
+            peer_expiry: Instant::now(),
+        }
+    }
+
+    // Returns `false` if the event is rejected.
+    fn handle(&mut self, event: FsmEvent, now: Instant) -> bool {
+        use BinaryStarState::*;
+
+        if let FsmEvent::Peer(_) = event {
+            self.peer_expiry = now + 2 * self.interval;
+        }
+
+        let (state, accepted) = match (self.state, event) {
+            (Primary, FsmEvent::Peer(Backup)) => (Active, true),
+            (Primary, FsmEvent::Peer(Active)) => (Passive, true),
+            // The primary accepts requests until it knows about its peer.
+            (Primary, _) => (Primary, true),
+            (Backup, FsmEvent::Peer(Active)) => (Passive, true),
+            (Backup, FsmEvent::ClientRequest) => (Backup, false),
+            (Backup, _) => (Backup, true),
+            (Active, FsmEvent::Peer(Active)) => (Active, false),
+            (Active, _) => (Active, true),
+            // The peer restarted, so it becomes passive once it sees us.
+            (Passive, FsmEvent::Peer(Primary))
+            | (Passive, FsmEvent::Peer(Backup)) => (Active, true),
+            (Passive, FsmEvent::Peer(Passive)) => (Passive, false),
+            (Passive, FsmEvent::Peer(Active)) => (Passive, true),
+            // Fail over only if the clients gave up on the active peer.
+            (Passive, FsmEvent::ClientRequest) => {
+                if now >= self.peer_expiry {
+                    (Active, true)
+                } else {
+                    (Passive, false)
+                }
+            }
+        };
+
+        self.state = state;
+        accepted
+    }
+}
+
+/// A config for a [`BinaryStar`].
+///
+/// Usefull in configuration files.
+///
+/// [`BinaryStar`]: struct.BinaryStar.html
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BinaryStarConfig {
+    role: Role,
+    frontend: Option<Endpoint>,
+    local: Option<Endpoint>,
+    remote: Option<Endpoint>,
+    #[serde(with = "humantime_serde")]
+    interval: Duration,
+}
+
+impl BinaryStarConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a `BinaryStar` from the configuration, using the global
+    /// context.
+    pub fn build(&self) -> Result<BinaryStar, Error> {
+        self.with_ctx(Ctx::global())
+    }
+
+    /// Builds a `BinaryStar` from the configuration, using the given
+    /// context.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidInput`] (missing endpoint)
+    ///
+    /// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
+    pub fn with_ctx(&self, handle: CtxHandle) -> Result<BinaryStar, Error> {
+        BinaryStar::with_config(self.clone(), handle)
+    }
+
+    /// The role of the server in the pair.
+    ///
+    /// # Default
+    /// `Primary`
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    pub fn set_role(&mut self, role: Role) {
+        self.role = role;
+    }
+
+    /// The endpoint to which the `Server` that receives the requests of the
+    /// clients binds.
+    pub fn frontend(&self) -> Option<&Endpoint> {
+        self.frontend.as_ref()
+    }
+
+    pub fn set_frontend(&mut self, maybe: Option<Endpoint>) {
+        self.frontend = maybe;
+    }
+
+    /// The endpoint on which the state of the server is published to its
+    /// peer. The `Radio` connects to it for the `udp` transport and binds
+    /// to it otherwise.
+    pub fn local(&self) -> Option<&Endpoint> {
+        self.local.as_ref()
+    }
+
+    pub fn set_local(&mut self, maybe: Option<Endpoint>) {
+        self.local = maybe;
+    }
+
+    /// The endpoint on which the peer publishes its state. The `Dish` binds
+    /// to it for the `udp` transport and connects to it otherwise.
+    pub fn remote(&self) -> Option<&Endpoint> {
+        self.remote.as_ref()
+    }
+
+    pub fn set_remote(&mut self, maybe: Option<Endpoint>) {
+        self.remote = maybe;
+    }
+
+    /// The interval at which the state is published. The peer is considered
+    /// dead once silent for two intervals.
+    ///
+    /// # Default
+    /// 1 second.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+}
+
+impl Default for BinaryStarConfig {
+    fn default() -> Self {
+        Self {
+            role: Role::default(),
+            frontend: None,
+            local: None,
+            remote: None,
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+}
+
+/// A convenience builder for a [`BinaryStar`].
+///
+/// [`BinaryStar`]: struct.BinaryStar.html
+#[derive(
+    Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+pub struct BinaryStarBuilder {
+    inner: BinaryStarConfig,
+}
+
+impl BinaryStarBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a `BinaryStar` from a `BinaryStarBuilder`, using the global
+    /// context.
+    pub fn build(&self) -> Result<BinaryStar, Error> {
+        self.inner.build()
+    }
+
+    /// Builds a `BinaryStar` from a `BinaryStarBuilder`, using the given
+    /// context.
+    pub fn with_ctx(&self, handle: CtxHandle) -> Result<BinaryStar, Error> {
+        self.inner.with_ctx(handle)
+    }
+
+    /// See [`set_role`].
+    ///
+    /// [`set_role`]: struct.BinaryStarConfig.html#method.set_role
+    pub fn role(&mut self, role: Role) -> &mut Self {
+        self.inner.set_role(role);
+        self
+    }
+
+    /// See [`set_frontend`].
+    ///
+    /// [`set_frontend`]: struct.BinaryStarConfig.html#method.set_frontend
+    pub fn frontend<E>(&mut self, endpoint: E) -> &mut Self
+    where
+        E: Into<Endpoint>,
+    {
+        self.inner.set_frontend(Some(endpoint.into()));
+        self
+    }
+
+    /// See [`set_local`].
+    ///
+    /// [`set_local`]: struct.BinaryStarConfig.html#method.set_local
+    pub fn local<E>(&mut self, endpoint: E) -> &mut Self
+    where
+        E: Into<Endpoint>,
+    {
+        self.inner.set_local(Some(endpoint.into()));
+        self
+    }
+
+    /// See [`set_remote`].
+    ///
+    /// [`set_remote`]: struct.BinaryStarConfig.html#method.set_remote
+    pub fn remote<E>(&mut self, endpoint: E) -> &mut Self
+    where
+        E: Into<Endpoint>,
+    {
+        self.inner.set_remote(Some(endpoint.into()));
+        self
+    }
+
+    /// See [`set_interval`].
+    ///
+    /// [`set_interval`]: struct.BinaryStarConfig.html#method.set_interval
+    pub fn interval(&mut self, interval: Duration) -> &mut Self {
+        self.inner.set_interval(interval);
+        self
+    }
+}
+
+struct Shared {
+    fsm: Mutex<Fsm>,
+    // Dropped once the background thread stops.
+    events: Mutex<Option<mpsc::Sender<BinaryStarEvent>>>,
+    stopped: AtomicBool,
+}
+
+impl Shared {
+    // Returns `false` if the event is rejected.
+    fn handle(&self, event: FsmEvent) -> bool {
+        let mut fsm = self.fsm.lock().unwrap();
+        let before = fsm.state;
+        let accepted = fsm.handle(event, Instant::now());
+
+        let maybe = match (event, fsm.state) {
+            (FsmEvent::Peer(BinaryStarState::Active), _) if !accepted => {
+                Some(BinaryStarEvent::DualActive)
+            }
+            (FsmEvent::Peer(BinaryStarState::Passive), _) if !accepted => {
+                Some(BinaryStarEvent::DualPassive)
+            }
+            (_, state) if state == before => None,
+            (_, BinaryStarState::Active) => Some(BinaryStarEvent::Active),
+            (_, BinaryStarState::Passive) => Some(BinaryStarEvent::Passive),
+            _ => None,
+        };
+        if let Some(event) = maybe {
+            if let Some(sender) = &*self.events.lock().unwrap() {
+                let _ = sender.send(event);
+            }
+        }
+        accepted
+    }
+
+    fn run(&self, radio: &Radio, dish: &Dish, interval: Duration) {
+        let group: Group = GROUP.try_into().unwrap();
+        let mut next_send = Instant::now();
+
+        while !self.stopped.load(Ordering::SeqCst) {
+            if Instant::now() >= next_send {
+                next_send = Instant::now() + interval;
+                let state = self.fsm.lock().unwrap().state;
+                let bytes = bincode::serialize(&state).unwrap();
+                if let Err(err) = radio.transmit(bytes, &group) {
+                    match err.kind() {
+                        ErrorKind::InvalidCtx => break,
+                        _ => error!("unable to publish state: {}", err),
+                    }
+                }
+            }
+
+            match dish.recv_msg() {
+                Ok(msg) => match bincode::deserialize(msg.as_bytes()) {
+                    Ok(state) => {
+                        self.handle(FsmEvent::Peer(state));
+                    }
+                    Err(err) => warn!("invalid binary star state: {}", err),
+                },
+                Err(err) => match err.kind() {
+                    ErrorKind::WouldBlock | ErrorKind::Interrupted => (),
+                    ErrorKind::InvalidCtx => break,
+                    _ => error!("unable to receive state: {}", err),
+                },
+            }
+        }
+        self.events.lock().unwrap().take();
+    }
+}
+
+/// A server of an active/passive pair.
+///
+/// The state of the pair is maintained by a background thread, which stops
+/// when the `BinaryStar` is dropped. The requests of the clients are only
+/// returned by [`recv_msg`] while the server is active, the others are
+/// dropped so that the clients fail over to the peer.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), anyhow::Error> {
+/// use libzmq::{bstar::*, prelude::*, *};
+/// use std::time::Duration;
+///
+/// let primary_addr: TcpAddr = "127.0.0.1:*".try_into()?;
+/// let state_addr: TcpAddr = "127.0.0.1:*".try_into()?;
+/// let primary = BinaryStarBuilder::new()
+///     .frontend(primary_addr)
+///     .local(state_addr.clone())
+///     .interval(Duration::from_millis(100))
+///     .build()?;
+///
+/// let backup_addr: TcpAddr = "127.0.0.1:*".try_into()?;
+/// let backup = BinaryStarBuilder::new()
+///     .role(Role::Backup)
+///     .frontend(backup_addr)
+///     .local(state_addr)
+///     .remote(primary.local_endpoint().clone())
+///     .interval(Duration::from_millis(100))
+///     .build()?;
+/// primary.connect(backup.local_endpoint().clone())?;
+///
+/// assert_eq!(backup.recv_event()?, BinaryStarEvent::Passive);
+///
+/// let mut client = BinaryStarClient::new(
+///     primary.frontend_endpoint().clone(),
+///     backup.frontend_endpoint().clone(),
+/// )?;
+/// client.set_timeout(Duration::from_millis(500))?;
+///
+/// // The client sends its request to the primary, which is active.
+/// let handle = std::thread::spawn(move || client.request("ping"));
+/// let msg = primary.recv_msg()?;
+/// primary.server().route("pong", msg.routing_id().unwrap())?;
+/// assert_eq!(handle.join().unwrap()?.to_str()?, "pong");
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// [`recv_msg`]: #method.recv_msg
+pub struct BinaryStar {
+    shared: Arc<Shared>,
+    server: Server,
+    dish: Dish,
+    events: Mutex<mpsc::Receiver<BinaryStarEvent>>,
+    frontend: Endpoint,
+    local: Endpoint,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl BinaryStar {
+    fn with_config(
+        config: BinaryStarConfig,
+        handle: CtxHandle,
+    ) -> Result<Self, Error> {
+        let missing = |msg| Error::new(ErrorKind::InvalidInput(msg));
+        let frontend = config
+            .frontend
+            .clone()
+            .ok_or_else(|| missing("missing frontend endpoint"))?;
+        let local = config
+            .local
+            .clone()
+            .ok_or_else(|| missing("missing local endpoint"))?;
+
+        let server = ServerBuilder::new().bind(frontend).with_ctx(handle)?;
+        let frontend = server.last_endpoint()?;
+
+        let mut builder = RadioBuilder::new();
+        match local {
+            Endpoint::Udp(_) => builder.connect(local.clone()),
+            _ => builder.bind(local.clone()),
+        };
+        let radio = builder.with_ctx(handle)?;
+        let local = match local {
+            Endpoint::Udp(_) => local,
+            _ => radio.last_endpoint()?,
+        };
+
+        let group: Group = GROUP.try_into().unwrap();
+        let dish = DishBuilder::new()
+            .join(group)
+            .recv_timeout(TICK)
+            .with_ctx(handle)?;
+        if let Some(remote) = config.remote.clone() {
+            Self::link(&dish, remote)?;
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let shared = Arc::new(Shared {
+            fsm: Mutex::new(Fsm::new(config.role, config.interval)),
+            events: Mutex::new(Some(sender)),
+            stopped: AtomicBool::new(false),
+        });
+
+        let thread = {
+            let shared = Arc::clone(&shared);
+            let dish = dish.clone();
+            let interval = config.interval;
+            thread::Builder::new()
+                .name("bstar".to_owned())
+                .spawn(move || shared.run(&radio, &dish, interval))
+                .map_err(Error::from_io)?
+        };
+
+        Ok(Self {
+            shared,
+            server,
+            dish,
+            events: Mutex::new(receiver),
+            frontend,
+            local,
+            thread: Some(thread),
+        })
+    }
+
+    fn link(dish: &Dish, remote: Endpoint) -> Result<(), Error> {
+        match remote {
+            Endpoint::Udp(_) => dish.bind(remote),
+            _ => dish.connect(remote),
+        }
+    }
+
+    /// Links the server to the state endpoint of its peer, if it was not
+    /// known when the server was built. See [`set_remote`].
+    ///
+    /// [`set_remote`]: struct.BinaryStarConfig.html#method.set_remote
+    pub fn connect<E>(&self, remote: E) -> Result<(), Error>
+    where
+        E: Into<Endpoint>,
+    {
+        Self::link(&self.dish, remote.into())
+    }
+
+    /// Returns the endpoint of the `Server` that receives the requests of
+    /// the clients.
+    pub fn frontend_endpoint(&self) -> &Endpoint {
+        &self.frontend
+    }
+
+    /// Returns the endpoint on which the state of the server is published.
+    pub fn local_endpoint(&self) -> &Endpoint {
+        &self.local
+    }
+
+    /// Returns the `Server` that receives the requests of the clients, which
+    /// is used to reply to them.
+    pub fn server(&self) -> &Server {
+        &self.server
+    }
+
+    /// Returns the current state of the server.
+    pub fn state(&self) -> BinaryStarState {
+        self.shared.fsm.lock().unwrap().state
+    }
+
+    /// Returns whether the server accepts the requests of the clients.
+    pub fn is_active(&self) -> bool {
+        self.state() == BinaryStarState::Active
+    }
+
+    /// Receives the next request accepted by the server.
+    ///
+    /// This honors the receive timeout of the `Server`, and the time spent
+    /// on rejected requests counts towards it.
+    pub fn recv_msg(&self) -> Result<Msg, Error> {
+        loop {
+            let msg = self.server.recv_msg()?;
+            if self.shared.handle(FsmEvent::ClientRequest) {
+                return Ok(msg);
+            }
+        }
+    }
+
+    /// Blocks until an event occurs.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidCtx`] (the context of the server was terminated)
+    ///
+    /// [`InvalidCtx`]: ../enum.ErrorKind.html#variant.InvalidCtx
+    pub fn recv_event(&self) -> Result<BinaryStarEvent, Error> {
+        // The background thread only stops early when the context is
+        // terminated.
+        self.events
+            .lock()
+            .unwrap()
+            .recv()
+            .map_err(|_| Error::new(ErrorKind::InvalidCtx))
+    }
+
+    /// Returns an event if one occured.
+    pub fn try_recv_event(&self) -> Option<BinaryStarEvent> {
+        self.events.lock().unwrap().try_recv().ok()
+    }
+
+    /// Blocks until an event occurs or the timeout elapsed.
+    pub fn recv_event_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Option<BinaryStarEvent> {
+        self.events.lock().unwrap().recv_timeout(timeout).ok()
+    }
+}
+
+impl fmt::Debug for BinaryStar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BinaryStar")
+            .field("state", &self.state())
+            .field("frontend", &self.frontend)
+            .field("local", &self.local)
+            .finish()
+    }
+}
+
+impl Drop for BinaryStar {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A client of a [`BinaryStar`] pair that fails over between the two
+/// servers.
+///
+/// A request is sent to the current server. If no reply is received before
+/// the timeout, the client waits for the pair to settle, switches to the
+/// other server and sends the request again.
+///
+/// [`BinaryStar`]: struct.BinaryStar.html
+pub struct BinaryStarClient {
+    endpoints: [Endpoint; 2],
+    current: usize,
+    client: Client,
+    handle: CtxHandle,
+    timeout: Duration,
+    settle: Duration,
+    retries: usize,
+}
+
+impl BinaryStarClient {
+    /// Creates a `BinaryStarClient` from the global context, which first
+    /// sends its requests to the `primary`.
+    pub fn new<P, B>(primary: P, backup: B) -> Result<Self, Error>
+    where
+        P: Into<Endpoint>,
+        B: Into<Endpoint>,
+    {
+        Self::with_ctx(primary, backup, Ctx::global())
+    }
+
+    /// Creates a `BinaryStarClient` from the context aliased by the
+    /// `CtxHandle`, which first sends its requests to the `primary`.
+    pub fn with_ctx<P, B>(
+        primary: P,
+        backup: B,
+        handle: CtxHandle,
+    ) -> Result<Self, Error>
+    where
+        P: Into<Endpoint>,
+        B: Into<Endpoint>,
+    {
+        let endpoints = [primary.into(), backup.into()];
+        let client =
+            Self::connect(&endpoints[0], handle, DEFAULT_CLIENT_TIMEOUT)?;
+
+        Ok(Self {
+            endpoints,
+            current: 0,
+            client,
+            handle,
+            timeout: DEFAULT_CLIENT_TIMEOUT,
+            settle: 2 * DEFAULT_INTERVAL,
+            retries: 3,
+        })
+    }
+
+    fn connect(
+        endpoint: &Endpoint,
+        handle: CtxHandle,
+        timeout: Duration,
+    ) -> Result<Client, Error> {
+        ClientBuilder::new()
+            .connect(endpoint.clone())
+            .recv_timeout(timeout)
+            .with_ctx(handle)
+    }
+
+    /// Returns the endpoint of the server currently used.
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoints[self.current]
+    }
+
+    /// How long to wait for a reply before failing over.
+    ///
+    /// # Default
+    /// 1 second.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.client.set_recv_timeout(Some(timeout))?;
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    /// How long to wait before sending a request to the other server, which
+    /// gives it time to notice that its peer is dead. It should be at least
+    /// twice the interval of the pair.
+    ///
+    /// # Default
+    /// 2 seconds.
+    pub fn settle(&self) -> Duration {
+        self.settle
+    }
+
+    pub fn set_settle(&mut self, settle: Duration) {
+        self.settle = settle;
+    }
+
+    /// The number of times a request is sent before giving up.
+    ///
+    /// # Default
+    /// 3
+    pub fn retries(&self) -> usize {
+        self.retries
+    }
+
+    pub fn set_retries(&mut self, retries: usize) {
+        self.retries = retries;
+    }
+
+    /// Sends the request and waits for its reply, failing over to the
+    /// other server if needed.
+    ///
+    /// # Returned Error Variants
+    /// * [`WouldBlock`] (no reply after every retry)
+    ///
+    /// [`WouldBlock`]: ../enum.ErrorKind.html#variant.WouldBlock
+    pub fn request<M>(&mut self, msg: M) -> Result<Msg, Error>
+    where
+        M: Into<Msg>,
+    {
+        let msg = msg.into();
+
+        for attempt in 0..self.retries.max(1) {
+            if attempt > 0 {
+                thread::sleep(self.settle);
+                self.current = 1 - self.current;
+                self.client = Self::connect(
+                    &self.endpoints[self.current],
+                    self.handle,
+                    self.timeout,
+                )?;
+            }
+
+            self.client.send(msg.clone()).map_err(Error::cast)?;
+            match self.client.recv_msg() {
+                Ok(reply) => return Ok(reply),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                    warn!("no reply from {:?}, failing over", self.endpoint());
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(Error::new(ErrorKind::WouldBlock))
+    }
+}
+
+impl fmt::Debug for BinaryStarClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BinaryStarClient")
+            .field("endpoint", self.endpoint())
+            .field("timeout", &self.timeout)
+            .field("settle", &self.settle)
+            .field("retries", &self.retries)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Ctx, TcpAddr};
+
+    use BinaryStarState::*;
+
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    #[test]
+    fn test_startup() {
+        let now = Instant::now();
+        let mut primary = Fsm::new(Role::Primary, INTERVAL);
+        let mut backup = Fsm::new(Role::Backup, INTERVAL);
+
+        assert!(primary.handle(FsmEvent::ClientRequest, now));
+        assert!(!backup.handle(FsmEvent::ClientRequest, now));
+
+        assert!(backup.handle(FsmEvent::Peer(Primary), now));
+        assert_eq!(backup.state, Backup);
+        assert!(primary.handle(FsmEvent::Peer(Backup), now));
+        assert_eq!(primary.state, Active);
+        assert!(backup.handle(FsmEvent::Peer(Active), now));
+        assert_eq!(backup.state, Passive);
+    }
+
+    #[test]
+    fn test_failover() {
+        let now = Instant::now();
+        let mut backup = Fsm::new(Role::Backup, INTERVAL);
+        backup.handle(FsmEvent::Peer(Active), now);
+        assert_eq!(backup.state, Passive);
+
+        // The active peer is still alive.
+        assert!(!backup.handle(FsmEvent::ClientRequest, now + INTERVAL));
+        assert_eq!(backup.state, Passive);
+
+        // The active peer is dead and the clients failed over.
+        assert!(backup.handle(FsmEvent::ClientRequest, now + 2 * INTERVAL));
+        assert_eq!(backup.state, Active);
+
+        // The primary restarts and becomes passive.
+        let mut primary = Fsm::new(Role::Primary, INTERVAL);
+        assert!(primary.handle(FsmEvent::Peer(Active), now));
+        assert_eq!(primary.state, Passive);
+    }
+
+    #[test]
+    fn test_dual() {
+        let now = Instant::now();
+        let mut fsm = Fsm::new(Role::Primary, INTERVAL);
+        fsm.handle(FsmEvent::Peer(Backup), now);
+        assert!(!fsm.handle(FsmEvent::Peer(Active), now));
+        assert_eq!(fsm.state, Active);
+
+        let mut fsm = Fsm::new(Role::Backup, INTERVAL);
+        fsm.handle(FsmEvent::Peer(Active), now);
+        assert!(!fsm.handle(FsmEvent::Peer(Passive), now));
+        assert_eq!(fsm.state, Passive);
+    }
+
+    #[test]
+    fn test_ser_de() {
+        let addr: TcpAddr = "127.0.0.1:3000".try_into().unwrap();
+        let mut config = BinaryStarConfig::new();
+        config.set_role(Role::Backup);
+        config.set_frontend(Some(addr.into()));
+        config.set_interval(INTERVAL);
+
+        let ron = serde_yaml::to_string(&config).unwrap();
+        let de: BinaryStarConfig = serde_yaml::from_str(&ron).unwrap();
+        assert_eq!(config, de);
+    }
+
+    #[test]
+    fn test_pair_fails_over() {
+        let addr: TcpAddr = "127.0.0.1:*".try_into().unwrap();
+        let mut builder = BinaryStarBuilder::new();
+        builder
+            .frontend(addr.clone())
+            .local(addr.clone())
+            .interval(INTERVAL);
+
+        let primary = builder.build().unwrap();
+        let backup = builder
+            .role(Role::Backup)
+            .remote(primary.local_endpoint().clone())
+            .build()
+            .unwrap();
+        primary.connect(backup.local_endpoint().clone()).unwrap();
+        primary.server().set_recv_timeout(Some(INTERVAL)).unwrap();
+        backup.server().set_recv_timeout(Some(INTERVAL)).unwrap();
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            primary.recv_event_timeout(timeout),
+            Some(BinaryStarEvent::Active)
+        );
+        assert_eq!(
+            backup.recv_event_timeout(timeout),
+            Some(BinaryStarEvent::Passive)
+        );
+
+        let mut client = BinaryStarClient::new(
+            primary.frontend_endpoint().clone(),
+            backup.frontend_endpoint().clone(),
+        )
+        .unwrap();
+        client.set_timeout(Duration::from_millis(300)).unwrap();
+        client.set_settle(2 * INTERVAL);
+
+        // The primary dies, so the client fails over to the backup.
+        drop(primary);
+        let handle = thread::spawn(move || {
+            let reply = client.request("ping").unwrap();
+            (client, reply)
+        });
+
+        let deadline = Instant::now() + timeout;
+        let msg = loop {
+            assert!(Instant::now() < deadline, "timed out");
+            match backup.recv_msg() {
+                Ok(msg) => break msg,
+                Err(err) => assert_eq!(err.kind(), ErrorKind::WouldBlock),
+            }
+        };
+        assert!(backup.is_active());
+        assert_eq!(backup.try_recv_event(), Some(BinaryStarEvent::Active));
+        backup
+            .server()
+            .route("pong", msg.routing_id().unwrap())
+            .unwrap();
+
+        let (client, reply) = handle.join().unwrap();
+        assert_eq!(reply.to_str().unwrap(), "pong");
+        assert_eq!(client.endpoint(), backup.frontend_endpoint());
+    }
+
+    #[test]
+    fn test_recv_event_terminated_ctx() {
+        let ctx = Ctx::new();
+        let addr: TcpAddr = "127.0.0.1:*".try_into().unwrap();
+        let primary = BinaryStarBuilder::new()
+            .frontend(addr.clone())
+            .local(addr)
+            .interval(INTERVAL)
+            .with_ctx(ctx.handle())
+            .unwrap();
+
+        ctx.shutdown();
+        let err = primary.recv_event().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidCtx);
+    }
+}
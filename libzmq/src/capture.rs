@@ -0,0 +1,838 @@
+//! Record the messages of a socket into a file and replay them later.
+//!
+//! A [`Capture`] is attached to a socket via [`set_capture`]. Every message
+//! that is then sent or received by the socket is appended to the capture,
+//! along with a timestamp, the type of the socket and the routing id and group
+//! of the message. The capture can then be read back with a
+//! [`CaptureReader`] and its messages sent into another socket by a
+//! [`Replay`], at the original or at an accelerated speed.
+//!
+//! Messages are recorded after decompression on reception and before
+//! compression on emission, so a capture always contains the messages as
+//! seen by the application.
+//!
+//! # Format
+//! A capture starts with a header followed by any number of records.
+//! All integers are big-endian.
+//!
+//! The header:
+//!
+//! | Field     | Size | Description                      |
+//! |-----------|------|----------------------------------|
+//! | magic     | 4    | `b"ZCAP"`                        |
+//! | version   | 1    | The version of the format, `1`.  |
+//!
+//! Each record:
+//!
+//! | Field      | Size      | Description                                 |
+//! |------------|-----------|---------------------------------------------|
+//! | timestamp  | 8         | Microseconds since the UNIX epoch.          |
+//! | socket     | 1         | The ØMQ socket type (e.g. `ZMQ_CLIENT`).    |
+//! | direction  | 1         | `0` if received, `1` if sent.               |
+//! | flags      | 1         | Bit 0: routing id, bit 1: group.            |
+//! | routing id | 4         | Only present if flag bit 0 is set.          |
+//! | group len  | 1         | Only present if flag bit 1 is set.          |
+//! | group      | group len | Only present if flag bit 1 is set.          |
+//! | len        | 4         | The length of the payload.                  |
+//! | payload    | len       | The content of the message.                 |
+//!
+//! [`Capture`]: struct.Capture.html
+//! [`set_capture`]: ../prelude/trait.Socket.html#method.set_capture
+//! [`CaptureReader`]: struct.CaptureReader.html
+//! [`Replay`]: struct.Replay.html
+
+use crate::{
+    core::{RawSocketType, SendMsg},
+    group::{Group, GroupSlice, MAX_GROUP_SIZE},
+    msg::{Msg, RoutingId},
+};
+use libzmq_sys as sys;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use log::error;
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+const MAGIC: &[u8; 4] = b"ZCAP";
+const VERSION: u8 = 1;
+
+const ROUTING_ID_FLAG: u8 = 0b01;
+const GROUP_FLAG: u8 = 0b10;
+
+/// The type of the socket a message was captured on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SocketKind {
+    Client,
+    Server,
+    Radio,
+    Dish,
+    Scatter,
+    Gather,
+}
+
+impl SocketKind {
+    pub(crate) fn from_raw(raw: RawSocketType) -> Option<Self> {
+        match raw {
+            RawSocketType::Client => Some(SocketKind::Client),
+            RawSocketType::Server => Some(SocketKind::Server),
+            RawSocketType::Radio => Some(SocketKind::Radio),
+            RawSocketType::Dish => Some(SocketKind::Dish),
+            RawSocketType::Scatter => Some(SocketKind::Scatter),
+            RawSocketType::Gather => Some(SocketKind::Gather),
+            _ => None,
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        let raw = match self {
+            SocketKind::Client => sys::ZMQ_CLIENT,
+            SocketKind::Server => sys::ZMQ_SERVER,
+            SocketKind::Radio => sys::ZMQ_RADIO,
+            SocketKind::Dish => sys::ZMQ_DISH,
+            SocketKind::Scatter => sys::ZMQ_SCATTER,
+            SocketKind::Gather => sys::ZMQ_GATHER,
+        };
+        raw as u8
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match u32::from(byte) {
+            sys::ZMQ_CLIENT => Some(SocketKind::Client),
+            sys::ZMQ_SERVER => Some(SocketKind::Server),
+            sys::ZMQ_RADIO => Some(SocketKind::Radio),
+            sys::ZMQ_DISH => Some(SocketKind::Dish),
+            sys::ZMQ_SCATTER => Some(SocketKind::Scatter),
+            sys::ZMQ_GATHER => Some(SocketKind::Gather),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a captured message was sent or received by the socket.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Recv,
+    Send,
+}
+
+/// A message recorded in a capture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureRecord {
+    timestamp: SystemTime,
+    socket: SocketKind,
+    direction: Direction,
+    routing_id: Option<RoutingId>,
+    group: Option<Group>,
+    payload: Vec<u8>,
+}
+
+impl CaptureRecord {
+    /// Creates a record of the message timestamped with the current time.
+    ///
+    /// The content and properties of the message are copied.
+    pub fn new(msg: &Msg, socket: SocketKind, direction: Direction) -> Self {
+        Self {
+            timestamp: SystemTime::now(),
+            socket,
+            direction,
+            routing_id: msg.routing_id(),
+            // A message without a group has an empty one.
+            group: msg
+                .group()
+                .filter(|group| !group.to_bytes().is_empty())
+                .map(Group::from),
+            payload: msg.as_bytes().to_vec(),
+        }
+    }
+
+    /// The time at which the message was recorded.
+    ///
+    /// The precision of the timestamp is one microsecond.
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    /// The type of the socket the message was captured on.
+    pub fn socket(&self) -> SocketKind {
+        self.socket
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn routing_id(&self) -> Option<RoutingId> {
+        self.routing_id
+    }
+
+    pub fn group(&self) -> Option<&GroupSlice> {
+        self.group.as_ref().map(|group| group.as_ref())
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Creates a new message with the payload, routing id and group of
+    /// the record.
+    pub fn to_msg(&self) -> Msg {
+        self.build_msg(true)
+    }
+
+    fn build_msg(&self, routing_id: bool) -> Msg {
+        let mut msg = Msg::from(self.payload.as_slice());
+        match self.routing_id {
+            Some(id) if routing_id => msg.set_routing_id(id),
+            _ => (),
+        }
+        if let Some(group) = &self.group {
+            msg.set_group(group);
+        }
+        msg
+    }
+}
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Writes records in the capture format.
+#[derive(Debug)]
+pub struct CaptureWriter<W: Write> {
+    writer: W,
+}
+
+impl<W> CaptureWriter<W>
+where
+    W: Write,
+{
+    /// Creates a new `CaptureWriter`, writing the header of the capture
+    /// into `writer`.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_u8(VERSION)?;
+
+        Ok(Self { writer })
+    }
+
+    /// Appends a record to the capture.
+    pub fn write(&mut self, record: &CaptureRecord) -> io::Result<()> {
+        if record.payload.len() > u32::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "payload cannot exceed 4GB",
+            ));
+        }
+
+        let micros = record
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_micros() as u64)
+            .unwrap_or(0);
+
+        let mut flags = 0;
+        if record.routing_id.is_some() {
+            flags |= ROUTING_ID_FLAG;
+        }
+        if record.group.is_some() {
+            flags |= GROUP_FLAG;
+        }
+
+        self.writer.write_u64::<BigEndian>(micros)?;
+        self.writer.write_u8(record.socket.to_byte())?;
+        self.writer.write_u8(record.direction as u8)?;
+        self.writer.write_u8(flags)?;
+        if let Some(RoutingId(id)) = record.routing_id {
+            self.writer.write_u32::<BigEndian>(id)?;
+        }
+        if let Some(group) = &record.group {
+            let bytes = group.as_bytes();
+            self.writer.write_u8(bytes.len() as u8)?;
+            self.writer.write_all(bytes)?;
+        }
+        self.writer
+            .write_u32::<BigEndian>(record.payload.len() as u32)?;
+        self.writer.write_all(&record.payload)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads the records of a capture.
+///
+/// The reader is an iterator over the records of the capture.
+#[derive(Debug)]
+pub struct CaptureReader<R: Read> {
+    reader: R,
+}
+
+impl<R> CaptureReader<R>
+where
+    R: Read,
+{
+    /// Creates a new `CaptureReader`, reading and validating the header of
+    /// the capture from `reader`.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a capture"));
+        }
+        if reader.read_u8()? != VERSION {
+            return Err(invalid_data("unsupported capture version"));
+        }
+
+        Ok(Self { reader })
+    }
+
+    /// Reads the next record of the capture.
+    ///
+    /// Returns `None` at the end of the capture.
+    pub fn read(&mut self) -> io::Result<Option<CaptureRecord>> {
+        let micros = match self.reader.read_u64::<BigEndian>() {
+            Ok(micros) => micros,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                return Ok(None)
+            }
+            Err(err) => return Err(err),
+        };
+
+        let socket = SocketKind::from_byte(self.reader.read_u8()?)
+            .ok_or_else(|| invalid_data("invalid socket type"))?;
+        let direction = match self.reader.read_u8()? {
+            0 => Direction::Recv,
+            1 => Direction::Send,
+            _ => return Err(invalid_data("invalid direction")),
+        };
+
+        let flags = self.reader.read_u8()?;
+        let routing_id = if flags & ROUTING_ID_FLAG != 0 {
+            match self.reader.read_u32::<BigEndian>()? {
+                0 => return Err(invalid_data("invalid routing id")),
+                id => Some(RoutingId(id)),
+            }
+        } else {
+            None
+        };
+        let group = if flags & GROUP_FLAG != 0 {
+            let len = self.reader.read_u8()? as usize;
+            if len > MAX_GROUP_SIZE {
+                return Err(invalid_data("invalid group"));
+            }
+            let mut bytes = vec![0; len];
+            self.reader.read_exact(&mut bytes)?;
+            Some(Group::new(bytes).map_err(|_| invalid_data("invalid group"))?)
+        } else {
+            None
+        };
+
+        let len = self.reader.read_u32::<BigEndian>()? as usize;
+        let mut payload = Vec::new();
+        self.reader
+            .by_ref()
+            .take(len as u64)
+            .read_to_end(&mut payload)?;
+        if payload.len() != len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+
+        Ok(Some(CaptureRecord {
+            timestamp: UNIX_EPOCH + Duration::from_micros(micros),
+            socket,
+            direction,
+            routing_id,
+            group,
+            payload,
+        }))
+    }
+}
+
+impl CaptureReader<BufReader<File>> {
+    /// Opens the capture file at `path`.
+    pub fn open<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R> Iterator for CaptureReader<R>
+where
+    R: Read,
+{
+    type Item = io::Result<CaptureRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+/// A capture that can be shared between sockets.
+///
+/// Cloning a `Capture` returns a handle to the same capture.
+///
+/// Failing to record a message does not fail the socket operation,
+/// instead the error is logged.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), anyhow::Error> {
+/// use libzmq::{prelude::*, *, capture::*};
+///
+/// let addr: TcpAddr = "127.0.0.1:*".try_into()?;
+///
+/// let server = ServerBuilder::new().bind(addr).build()?;
+/// let bound = server.last_endpoint()?;
+/// let client = ClientBuilder::new().connect(bound).build()?;
+///
+/// let capture = Capture::new();
+/// server.set_capture(Some(capture.clone()));
+///
+/// client.send("ping")?;
+/// let msg = server.recv_msg()?;
+/// server.route("pong", msg.routing_id().unwrap())?;
+/// client.recv_msg()?;
+///
+/// server.set_capture(None);
+/// let records = capture.records()?;
+/// assert_eq!(records.len(), 2);
+/// assert_eq!(records[0].direction(), capture::Direction::Recv);
+/// assert_eq!(records[1].payload(), b"pong");
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Capture {
+    inner: Arc<Mutex<CaptureWriter<Sink>>>,
+}
+
+// The destination of a capture. We keep track of in-memory captures so
+// that their records can be read back.
+enum Sink {
+    Memory(Vec<u8>),
+    Writer(Box<dyn Write + Send>),
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::Memory(vec) => vec.write(buf),
+            Sink::Writer(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Memory(_) => Ok(()),
+            Sink::Writer(writer) => writer.flush(),
+        }
+    }
+}
+
+impl fmt::Debug for Sink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Sink::Memory(vec) => {
+                f.debug_tuple("Memory").field(&vec.len()).finish()
+            }
+            Sink::Writer(_) => f.debug_tuple("Writer").finish(),
+        }
+    }
+}
+
+impl Capture {
+    /// Creates an in-memory capture, whose records can be retrieved via
+    /// [`records`].
+    ///
+    /// [`records`]: #method.records
+    pub fn new() -> Self {
+        // Writing the header into memory cannot fail.
+        Self::with_sink(Sink::Memory(Vec::new())).unwrap()
+    }
+
+    /// Creates a capture that writes its records into `writer`.
+    pub fn with_writer<W>(writer: W) -> io::Result<Self>
+    where
+        W: Write + Send + 'static,
+    {
+        Self::with_sink(Sink::Writer(Box::new(writer)))
+    }
+
+    /// Creates a capture file at `path`, truncating any existing file.
+    ///
+    /// The records are buffered, call [`flush`] to make sure they are
+    /// written to disk.
+    ///
+    /// [`flush`]: #method.flush
+    pub fn create<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::create(path)?;
+        Self::with_writer(BufWriter::new(file))
+    }
+
+    fn with_sink(sink: Sink) -> io::Result<Self> {
+        let writer = CaptureWriter::new(sink)?;
+
+        Ok(Self {
+            inner: Arc::new(Mutex::new(writer)),
+        })
+    }
+
+    /// Appends a record to the capture.
+    pub fn write(&self, record: &CaptureRecord) -> io::Result<()> {
+        self.inner.lock().unwrap().write(record)
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.inner.lock().unwrap().flush()
+    }
+
+    /// Returns the records of an in-memory capture.
+    ///
+    /// Returns an empty vector if the capture is not in-memory.
+    pub fn records(&self) -> io::Result<Vec<CaptureRecord>> {
+        let guard = self.inner.lock().unwrap();
+        match &guard.writer {
+            Sink::Memory(vec) => CaptureReader::new(vec.as_slice())?.collect(),
+            Sink::Writer(_) => Ok(vec![]),
+        }
+    }
+
+    pub(crate) fn record(&self, record: &CaptureRecord) {
+        if let Err(err) = self.write(record) {
+            error!("unable to capture message: {}", err);
+        }
+    }
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Capture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let guard = self.inner.lock().unwrap();
+        f.debug_struct("Capture")
+            .field("sink", &guard.writer)
+            .finish()
+    }
+}
+
+/// Sends the messages of a capture into a socket.
+///
+/// By default, all the records are replayed at their original speed and
+/// their routing ids are stripped, since they are meaningless outside of
+/// the original connection.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), anyhow::Error> {
+/// use libzmq::{prelude::*, *, capture::*};
+///
+/// let addr = InprocAddr::new_unique();
+/// let scatter = ScatterBuilder::new().bind(&addr).build()?;
+/// let gather = GatherBuilder::new().connect(&addr).build()?;
+///
+/// # let path = std::env::temp_dir().join("libzmq-replay-doctest.zcap");
+/// let capture = Capture::create(&path)?;
+/// gather.set_capture(Some(capture.clone()));
+/// scatter.send("some msg")?;
+/// gather.recv_msg()?;
+/// capture.flush()?;
+///
+/// // Replay the received messages as fast as possible.
+/// let mut replay = Replay::new();
+/// replay.set_speed(f64::INFINITY);
+/// let reader = CaptureReader::open(&path)?;
+/// assert_eq!(replay.run(reader, &scatter)?, 1);
+///
+/// let msg = gather.recv_msg()?;
+/// assert_eq!(msg.to_str()?, "some msg");
+/// #
+/// # std::fs::remove_file(&path)?;
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    speed: f64,
+    direction: Option<Direction>,
+    routing_id: bool,
+}
+
+impl Replay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The speed factor of the replay relative to the capture.
+    ///
+    /// A speed of `2.0` replays the messages twice as fast as they were
+    /// recorded, while `f64::INFINITY` replays them without any delay.
+    ///
+    /// # Default
+    /// `1.0`
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+    }
+
+    /// If set, only the records with this direction are replayed.
+    ///
+    /// # Default
+    /// `None`
+    pub fn direction(&self) -> Option<Direction> {
+        self.direction
+    }
+
+    pub fn set_direction(&mut self, maybe: Option<Direction>) {
+        self.direction = maybe;
+    }
+
+    /// Whether the routing ids of the records are kept in the replayed
+    /// messages.
+    ///
+    /// # Default
+    /// `false`
+    pub fn routing_id(&self) -> bool {
+        self.routing_id
+    }
+
+    pub fn set_routing_id(&mut self, cond: bool) {
+        self.routing_id = cond;
+    }
+
+    /// Sends the messages of the `records` into the `socket`, respecting
+    /// the intervals between their timestamps scaled by the speed.
+    ///
+    /// The records are typically read by a [`CaptureReader`]. The replay
+    /// stops at the first record that cannot be read.
+    ///
+    /// Returns the number of messages sent.
+    ///
+    /// # Returned Error Kinds
+    /// * `InvalidInput` (if the speed is not strictly positive)
+    /// * `WouldBlock` (if `send_timeout` expires)
+    /// * `Interrupted`
+    /// * `BrokenPipe` (if the peer is unreachable, only for [`Server`]
+    ///   socket)
+    /// * `Other` (if the context was terminated)
+    /// * any error returned while reading the `records`
+    ///
+    /// [`CaptureReader`]: struct.CaptureReader.html
+    /// [`Server`]: ../struct.Server.html
+    pub fn run<I, S>(&self, records: I, socket: &S) -> io::Result<u64>
+    where
+        I: IntoIterator<Item = io::Result<CaptureRecord>>,
+        S: SendMsg,
+    {
+        if self.speed.is_nan() || self.speed <= 0.0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "speed must be strictly positive",
+            ));
+        }
+
+        let start = Instant::now();
+        let mut first = None;
+        let mut count = 0;
+
+        for record in records {
+            let record = record?;
+            if let Some(direction) = self.direction {
+                if direction != record.direction {
+                    continue;
+                }
+            }
+
+            let first = *first.get_or_insert(record.timestamp);
+            if self.speed.is_finite() {
+                let offset = record
+                    .timestamp
+                    .duration_since(first)
+                    .unwrap_or_default()
+                    .div_f64(self.speed);
+                if let Some(delay) = offset.checked_sub(start.elapsed()) {
+                    thread::sleep(delay);
+                }
+            }
+
+            let msg = record.build_msg(self.routing_id);
+            socket.send(msg)?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            direction: None,
+            routing_id: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{prelude::*, *};
+
+    use std::convert::TryInto;
+
+    fn record(payload: &str, direction: Direction) -> CaptureRecord {
+        CaptureRecord::new(&payload.into(), SocketKind::Client, direction)
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let mut msg = Msg::from("some msg");
+        msg.set_routing_id(RoutingId(42));
+        let group: Group = "group".try_into().unwrap();
+        msg.set_group(&group);
+
+        let first =
+            CaptureRecord::new(&msg, SocketKind::Server, Direction::Recv);
+        let second = record("", Direction::Send);
+
+        let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+        writer.write(&first).unwrap();
+        writer.write(&second).unwrap();
+        let bytes = writer.into_inner();
+
+        let reader = CaptureReader::new(bytes.as_slice()).unwrap();
+        let records: Vec<_> = reader.map(Result::unwrap).collect();
+        assert_eq!(records.len(), 2);
+
+        let read = &records[0];
+        assert_eq!(read.socket(), SocketKind::Server);
+        assert_eq!(read.direction(), Direction::Recv);
+        assert_eq!(read.routing_id(), Some(RoutingId(42)));
+        assert_eq!(read.group().unwrap(), "group");
+        assert_eq!(read.payload(), b"some msg");
+        // The timestamp is truncated to the microsecond.
+        let diff = first.timestamp().duration_since(read.timestamp()).unwrap();
+        assert!(diff < Duration::from_micros(1));
+
+        assert_eq!(records[1].payload(), b"");
+        assert_eq!(records[1].routing_id(), None);
+        assert_eq!(records[1].group(), None);
+    }
+
+    #[test]
+    fn test_invalid_capture() {
+        let err = CaptureReader::new(&b"ZMQ\x01"[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+        writer.write(&record("some msg", Direction::Send)).unwrap();
+        let mut bytes = writer.into_inner();
+        bytes.pop();
+
+        let mut reader = CaptureReader::new(bytes.as_slice()).unwrap();
+        let err = reader.read().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_socket_capture() {
+        let addr = InprocAddr::new_unique();
+        let scatter = ScatterBuilder::new().bind(&addr).build().unwrap();
+        let gather = GatherBuilder::new().connect(&addr).build().unwrap();
+
+        let capture = Capture::new();
+        scatter.set_capture(Some(capture.clone()));
+        gather.set_capture(Some(capture.clone()));
+
+        scatter.send("some msg").unwrap();
+        gather.recv_msg().unwrap();
+
+        let records = capture.records().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].socket(), SocketKind::Scatter);
+        assert_eq!(records[0].direction(), Direction::Send);
+        assert_eq!(records[1].socket(), SocketKind::Gather);
+        assert_eq!(records[1].direction(), Direction::Recv);
+        assert_eq!(records[1].payload(), b"some msg");
+    }
+
+    #[test]
+    fn test_replay_speed() {
+        let addr = InprocAddr::new_unique();
+        let scatter = ScatterBuilder::new().bind(&addr).build().unwrap();
+        let gather = GatherBuilder::new().connect(&addr).build().unwrap();
+
+        let first = record("0", Direction::Send);
+        let mut second = record("1", Direction::Send);
+        second.timestamp = first.timestamp + Duration::from_millis(200);
+        let ignored = record("2", Direction::Recv);
+
+        let mut replay = Replay::new();
+        replay.set_direction(Some(Direction::Send));
+        replay.set_speed(2.0);
+
+        let start = Instant::now();
+        let records = vec![Ok(first), Ok(ignored), Ok(second)];
+        assert_eq!(replay.run(records, &scatter).unwrap(), 2);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        assert_eq!(gather.recv_msg().unwrap().to_str().unwrap(), "0");
+        assert_eq!(gather.recv_msg().unwrap().to_str().unwrap(), "1");
+
+        replay.set_speed(0.0);
+        let err = replay.run(vec![], &scatter).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_replay_reader() {
+        let addr = InprocAddr::new_unique();
+        let scatter = ScatterBuilder::new().bind(&addr).build().unwrap();
+        let gather = GatherBuilder::new().connect(&addr).build().unwrap();
+
+        let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+        writer.write(&record("0", Direction::Send)).unwrap();
+        writer.write(&record("1", Direction::Send)).unwrap();
+        let mut bytes = writer.into_inner();
+        // The second record is truncated.
+        bytes.pop();
+
+        let mut replay = Replay::new();
+        replay.set_speed(f64::INFINITY);
+
+        let reader = CaptureReader::new(bytes.as_slice()).unwrap();
+        let err = replay.run(reader, &scatter).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        // The records read before the error were replayed.
+        assert_eq!(gather.recv_msg().unwrap().to_str().unwrap(), "0");
+        assert!(gather.try_recv_msg().is_err());
+    }
+}
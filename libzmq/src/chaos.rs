@@ -0,0 +1,837 @@
+//! A proxy that injects network faults for testing purposes.
+
+use crate::{
+    core::{RecvMsg, SendMsg},
+    error::{Error, ErrorKind},
+    msg::Msg,
+    poll::{Events, PollId, Pollable, Poller, Waker, READABLE, WRITABLE},
+    Period,
+};
+
+use rand_core::RngCore;
+
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{
+        atomic::{AtomicBool, Ordering as AtomicOrdering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+const DEFAULT_MAX_DELAY: Duration = Duration::from_millis(100);
+
+/// The fault probabilities of a [`ChaosProxy`].
+///
+/// Each probability is a number between `0.0` (never) and `1.0` (always)
+/// and is evaluated independently for every forwarded message.
+///
+/// [`ChaosProxy`]: struct.ChaosProxy.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChaosConfig {
+    drop: f64,
+    delay: f64,
+    max_delay: Duration,
+    duplicate: f64,
+    reorder: f64,
+    corrupt: f64,
+}
+
+impl ChaosConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The probability that a message is dropped.
+    pub fn drop(&self) -> f64 {
+        self.drop
+    }
+
+    pub fn set_drop(&mut self, probability: f64) {
+        self.drop = probability;
+    }
+
+    /// The probability that a message is delayed by a random period
+    /// of at most [`max_delay`].
+    ///
+    /// [`max_delay`]: #method.max_delay
+    pub fn delay(&self) -> f64 {
+        self.delay
+    }
+
+    pub fn set_delay(&mut self, probability: f64) {
+        self.delay = probability;
+    }
+
+    /// The maximum period a message can be delayed or held for reordering.
+    ///
+    /// # Default
+    /// 100ms
+    pub fn max_delay(&self) -> Duration {
+        self.max_delay
+    }
+
+    pub fn set_max_delay(&mut self, duration: Duration) {
+        self.max_delay = duration;
+    }
+
+    /// The probability that a message is sent twice.
+    pub fn duplicate(&self) -> f64 {
+        self.duplicate
+    }
+
+    pub fn set_duplicate(&mut self, probability: f64) {
+        self.duplicate = probability;
+    }
+
+    /// The probability that a message is held back until the next message is
+    /// forwarded, or until [`max_delay`] expires.
+    ///
+    /// [`max_delay`]: #method.max_delay
+    pub fn reorder(&self) -> f64 {
+        self.reorder
+    }
+
+    pub fn set_reorder(&mut self, probability: f64) {
+        self.reorder = probability;
+    }
+
+    /// The probability that a random bit of a message is flipped.
+    pub fn corrupt(&self) -> f64 {
+        self.corrupt
+    }
+
+    pub fn set_corrupt(&mut self, probability: f64) {
+        self.corrupt = probability;
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        let probabilities = [
+            self.drop,
+            self.delay,
+            self.duplicate,
+            self.reorder,
+            self.corrupt,
+        ];
+
+        if probabilities.iter().all(|p| (0.0..=1.0).contains(p)) {
+            Ok(())
+        } else {
+            Err(Error::new(ErrorKind::InvalidInput(
+                "probability must be between 0 and 1",
+            )))
+        }
+    }
+}
+
+impl Default for ChaosConfig {
+    fn default() -> Self {
+        Self {
+            drop: 0.0,
+            delay: 0.0,
+            max_delay: DEFAULT_MAX_DELAY,
+            duplicate: 0.0,
+            reorder: 0.0,
+            corrupt: 0.0,
+        }
+    }
+}
+
+/// The number of messages affected by a [`ChaosProxy`].
+///
+/// [`ChaosProxy`]: struct.ChaosProxy.html
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChaosStats {
+    received: u64,
+    forwarded: u64,
+    dropped: u64,
+    delayed: u64,
+    duplicated: u64,
+    reordered: u64,
+    corrupted: u64,
+}
+
+impl ChaosStats {
+    /// The number of messages received from the frontend.
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// The number of messages sent to the backend, including duplicates.
+    pub fn forwarded(&self) -> u64 {
+        self.forwarded
+    }
+
+    /// The number of messages dropped, including those dropped during a
+    /// partition.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// The number of messages delayed.
+    pub fn delayed(&self) -> u64 {
+        self.delayed
+    }
+
+    /// The number of messages duplicated.
+    pub fn duplicated(&self) -> u64 {
+        self.duplicated
+    }
+
+    /// The number of messages held back for reordering.
+    pub fn reordered(&self) -> u64 {
+        self.reordered
+    }
+
+    /// The number of messages corrupted.
+    pub fn corrupted(&self) -> u64 {
+        self.corrupted
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    partitioned: AtomicBool,
+    stopped: AtomicBool,
+    stats: Mutex<ChaosStats>,
+    waker: Waker,
+}
+
+/// A handle to control a running [`ChaosProxy`] from another thread.
+///
+/// [`ChaosProxy`]: struct.ChaosProxy.html
+#[derive(Debug, Clone)]
+pub struct ChaosHandle {
+    shared: Arc<Shared>,
+}
+
+impl ChaosHandle {
+    /// Simulates a network partition. All messages are dropped until
+    /// [`heal`] is called.
+    ///
+    /// [`heal`]: #method.heal
+    pub fn partition(&self) {
+        self.shared.partitioned.store(true, AtomicOrdering::SeqCst);
+    }
+
+    /// Ends a network partition.
+    pub fn heal(&self) {
+        self.shared.partitioned.store(false, AtomicOrdering::SeqCst);
+    }
+
+    /// Returns `true` if the proxy simulates a network partition.
+    pub fn is_partitioned(&self) -> bool {
+        self.shared.partitioned.load(AtomicOrdering::SeqCst)
+    }
+
+    /// Stops the proxy, which makes [`run`] return.
+    ///
+    /// Messages that are delayed, held back or waiting for the backend to
+    /// leave the mute state are dropped.
+    ///
+    /// [`run`]: struct.ChaosProxy.html#method.run
+    pub fn stop(&self) {
+        self.shared.stopped.store(true, AtomicOrdering::SeqCst);
+        self.shared.waker.wake()
+    }
+
+    /// Returns the statistics of the proxy.
+    pub fn stats(&self) -> ChaosStats {
+        *self.shared.stats.lock().unwrap()
+    }
+}
+
+// The faults sampled for a message that is sent to the backend.
+#[derive(Debug, Clone, Copy)]
+struct Schedule {
+    delay: bool,
+    reorder: bool,
+    period: Duration,
+}
+
+#[derive(Debug)]
+struct Delayed {
+    due: Instant,
+    seq: u64,
+    msg: Msg,
+}
+
+impl PartialEq for Delayed {
+    fn eq(&self, other: &Self) -> bool {
+        self.due == other.due && self.seq == other.seq
+    }
+}
+
+impl Eq for Delayed {}
+
+impl PartialOrd for Delayed {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Delayed {
+    // Reversed so that the `BinaryHeap` yields the earliest message first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.due, other.seq).cmp(&(self.due, self.seq))
+    }
+}
+
+/// A proxy that forwards messages from a frontend socket to a backend socket
+/// while injecting network faults.
+///
+/// Unlike [`proxy`], the `ChaosProxy` is unidirectional. To inject faults in
+/// both directions, run one proxy per direction on clones of the sockets.
+///
+/// Every fault is decided by the provided random number generator, so that
+/// a seeded generator yields the same faults for the same sequence of
+/// messages. The proxy is controlled at runtime via its [`ChaosHandle`].
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), anyhow::Error> {
+/// use libzmq::{prelude::*, *, chaos::*};
+/// use rand_core::SeedableRng;
+/// use rand_isaac::Isaac64Rng;
+/// use std::thread;
+///
+/// let (front_addr, back_addr) = (InprocAddr::new_unique(), InprocAddr::new_unique());
+///
+/// let scatter = ScatterBuilder::new().connect(&front_addr).build()?;
+/// let frontend = GatherBuilder::new().bind(&front_addr).build()?;
+/// let backend = ScatterBuilder::new().bind(&back_addr).build()?;
+/// let gather = GatherBuilder::new().connect(&back_addr).build()?;
+///
+/// let mut config = ChaosConfig::new();
+/// config.set_duplicate(1.0);
+///
+/// let rng = Isaac64Rng::seed_from_u64(42);
+/// let mut chaos = ChaosProxy::new(frontend, backend, config, rng)?;
+/// let handle = chaos.handle();
+/// let thread = thread::spawn(move || chaos.run());
+///
+/// scatter.send("msg")?;
+/// assert_eq!(gather.recv_msg()?.to_str()?, "msg");
+/// assert_eq!(gather.recv_msg()?.to_str()?, "msg");
+///
+/// handle.stop();
+/// thread.join().unwrap()?;
+/// assert_eq!(handle.stats().duplicated(), 1);
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// [`proxy`]: ../fn.proxy.html
+/// [`ChaosHandle`]: struct.ChaosHandle.html
+#[derive(Debug)]
+pub struct ChaosProxy<F, B, R> {
+    frontend: F,
+    backend: B,
+    inner: Chaos<R>,
+}
+
+impl<F, B, R> ChaosProxy<F, B, R>
+where
+    F: RecvMsg,
+    B: SendMsg,
+    R: RngCore,
+{
+    /// Creates a new `ChaosProxy` which will receive messages from the
+    /// `frontend` and send them to the `backend`.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidInput`] (if a probability is not between 0 and 1)
+    /// * [`Unexpected`] (if the waker of the proxy cannot be created)
+    ///
+    /// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
+    /// [`Unexpected`]: ../enum.ErrorKind.html#variant.Unexpected
+    pub fn new(
+        frontend: F,
+        backend: B,
+        config: ChaosConfig,
+        rng: R,
+    ) -> Result<Self, Error> {
+        config.check()?;
+
+        let shared = Shared {
+            partitioned: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            stats: Mutex::default(),
+            waker: Waker::new()?,
+        };
+
+        Ok(Self {
+            frontend,
+            backend,
+            inner: Chaos {
+                config,
+                rng,
+                shared: Arc::new(shared),
+                delayed: BinaryHeap::new(),
+                held: None,
+                seq: 0,
+            },
+        })
+    }
+
+    /// Returns a handle to control the proxy.
+    pub fn handle(&self) -> ChaosHandle {
+        ChaosHandle {
+            shared: Arc::clone(&self.inner.shared),
+        }
+    }
+
+    /// Runs the proxy until it is stopped via its [`ChaosHandle`].
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidCtx`]
+    ///
+    /// [`ChaosHandle`]: struct.ChaosHandle.html
+    /// [`InvalidCtx`]: ../enum.ErrorKind.html#variant.InvalidCtx
+    pub fn run(&mut self) -> Result<(), Error> {
+        let mut poller = Poller::new();
+        let frontend = Pollable::Socket(self.frontend.raw_socket());
+        poller.add(frontend, PollId(0), READABLE)?;
+
+        let mut events = Events::new();
+        let shared = Arc::clone(&self.inner.shared);
+
+        while !shared.stopped.load(AtomicOrdering::SeqCst) {
+            let timeout = self.inner.release_due(&self.backend)?;
+
+            if let Err(err) =
+                poller.poll_with_waker(&mut events, timeout, &shared.waker)
+            {
+                match err.kind() {
+                    ErrorKind::WouldBlock | ErrorKind::Interrupted => continue,
+                    _ => return Err(err),
+                }
+            }
+
+            loop {
+                match self.frontend.try_recv_msg() {
+                    Ok(msg) => self.inner.process(msg, &self.backend)?,
+                    Err(err) => match err.kind() {
+                        ErrorKind::WouldBlock => break,
+                        _ => return Err(err),
+                    },
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Chaos<R> {
+    config: ChaosConfig,
+    rng: R,
+    shared: Arc<Shared>,
+    delayed: BinaryHeap<Delayed>,
+    // A message held back until the next message is forwarded.
+    held: Option<Delayed>,
+    seq: u64,
+}
+
+impl<R> Chaos<R>
+where
+    R: RngCore,
+{
+    // Returns a uniformly distributed number in `[0, 1)`.
+    fn sample(&mut self) -> f64 {
+        (self.rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        self.sample() < probability
+    }
+
+    fn sample_schedule(&mut self) -> Schedule {
+        Schedule {
+            delay: self.chance(self.config.delay),
+            reorder: self.chance(self.config.reorder),
+            period: self.config.max_delay.mul_f64(self.sample()),
+        }
+    }
+
+    fn update<U>(&self, update: U)
+    where
+        U: FnOnce(&mut ChaosStats),
+    {
+        update(&mut self.shared.stats.lock().unwrap());
+    }
+
+    fn process<B>(&mut self, msg: Msg, backend: &B) -> Result<(), Error>
+    where
+        B: SendMsg,
+    {
+        self.update(|s| s.received += 1);
+
+        // Every decision is sampled upfront so that the sequence of random
+        // numbers used per message does not depend on their outcome.
+        let drop = self.chance(self.config.drop);
+        let corrupt = self.chance(self.config.corrupt);
+        // A single 64-bit draw, since mixing 32 and 64-bit draws is
+        // unsound with some `RngCore` implementations.
+        let flip = self.rng.next_u64();
+        let duplicate = self.chance(self.config.duplicate);
+        let schedule = self.sample_schedule();
+        let duplicate_schedule = self.sample_schedule();
+
+        if drop || self.shared.partitioned.load(AtomicOrdering::SeqCst) {
+            self.update(|s| s.dropped += 1);
+            return Ok(());
+        }
+
+        let msg = if corrupt && !msg.is_empty() {
+            self.update(|s| s.corrupted += 1);
+            corrupt_msg(msg, flip)
+        } else {
+            msg
+        };
+
+        if duplicate {
+            self.update(|s| s.duplicated += 1);
+            self.schedule(msg.clone(), duplicate_schedule, backend)?;
+        }
+        self.schedule(msg, schedule, backend)
+    }
+
+    fn schedule<B>(
+        &mut self,
+        msg: Msg,
+        schedule: Schedule,
+        backend: &B,
+    ) -> Result<(), Error>
+    where
+        B: SendMsg,
+    {
+        let seq = self.seq;
+        self.seq += 1;
+
+        if schedule.delay {
+            self.update(|s| s.delayed += 1);
+            let due = Instant::now() + schedule.period;
+            self.delayed.push(Delayed { due, seq, msg });
+            Ok(())
+        } else if schedule.reorder && self.held.is_none() {
+            self.update(|s| s.reordered += 1);
+            let due = Instant::now() + self.config.max_delay;
+            self.held = Some(Delayed { due, seq, msg });
+            Ok(())
+        } else {
+            self.forward(msg, backend)?;
+            match self.held.take() {
+                Some(held) => self.forward(held.msg, backend),
+                None => Ok(()),
+            }
+        }
+    }
+
+    // Sends the message to the backend, waiting for it to leave the mute
+    // state unless the proxy is stopped.
+    fn forward<B>(&mut self, mut msg: Msg, backend: &B) -> Result<(), Error>
+    where
+        B: SendMsg,
+    {
+        let mut poller = None;
+        loop {
+            match backend.try_send(msg) {
+                Ok(()) => {
+                    self.update(|s| s.forwarded += 1);
+                    return Ok(());
+                }
+                Err(mut err) => match err.kind() {
+                    ErrorKind::WouldBlock => msg = err.take().unwrap(),
+                    ErrorKind::InvalidCtx => return Err(err.cast()),
+                    // The message is lost, like it would be on a bad network.
+                    _ => {
+                        self.update(|s| s.dropped += 1);
+                        return Ok(());
+                    }
+                },
+            }
+
+            if self.shared.stopped.load(AtomicOrdering::SeqCst) {
+                self.update(|s| s.dropped += 1);
+                return Ok(());
+            }
+
+            if poller.is_none() {
+                let mut new = Poller::new();
+                let socket = Pollable::Socket(backend.raw_socket());
+                new.add(socket, PollId(0), WRITABLE)?;
+                poller = Some(new);
+            }
+
+            let mut events = Events::new();
+            let poller = poller.as_mut().unwrap();
+            if let Err(err) = poller.poll_with_waker(
+                &mut events,
+                Period::Infinite,
+                &self.shared.waker,
+            ) {
+                if err.kind() != ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    // Forwards the messages whose delay expired and returns the period
+    // until the next one is due.
+    fn release_due<B>(&mut self, backend: &B) -> Result<Period, Error>
+    where
+        B: SendMsg,
+    {
+        let now = Instant::now();
+
+        if self.held.as_ref().map(|h| h.due <= now).unwrap_or(false) {
+            let held = self.held.take().unwrap();
+            self.forward(held.msg, backend)?;
+        }
+
+        while self.delayed.peek().map(|d| d.due <= now).unwrap_or(false) {
+            let delayed = self.delayed.pop().unwrap();
+            self.forward(delayed.msg, backend)?;
+        }
+
+        let next = self
+            .delayed
+            .peek()
+            .map(|d| d.due)
+            .into_iter()
+            .chain(self.held.as_ref().map(|h| h.due))
+            .min();
+
+        Ok(match next {
+            Some(due) => Period::Finite(due.duration_since(now)),
+            None => Period::Infinite,
+        })
+    }
+}
+
+// Flips a single bit of the message, which must not be empty.
+// The 3 lowest bits of `flip` select the bit and the rest the byte.
+fn corrupt_msg(msg: Msg, flip: u64) -> Msg {
+    let mut bytes = msg.as_bytes().to_vec();
+    let index = ((flip >> 3) % bytes.len() as u64) as usize;
+    bytes[index] ^= 1 << (flip & 7);
+
+    // We copy the bytes since the content of the message might be
+    // shared with another message.
+    let mut corrupted = Msg::from(bytes);
+    msg.copy_properties(&mut corrupted);
+    corrupted
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{prelude::*, *};
+
+    use rand_core::SeedableRng;
+    use rand_isaac::Isaac64Rng;
+
+    use std::thread;
+
+    const SEED: u64 = 123_490_814_327;
+
+    struct Setup {
+        scatter: Scatter,
+        gather: Gather,
+        handle: ChaosHandle,
+        thread: thread::JoinHandle<Result<(), Error>>,
+    }
+
+    impl Setup {
+        fn new(config: ChaosConfig) -> Self {
+            let front_addr = InprocAddr::new_unique();
+            let back_addr = InprocAddr::new_unique();
+
+            let scatter =
+                ScatterBuilder::new().connect(&front_addr).build().unwrap();
+            let frontend =
+                GatherBuilder::new().bind(&front_addr).build().unwrap();
+            let backend =
+                ScatterBuilder::new().bind(&back_addr).build().unwrap();
+            let gather = GatherBuilder::new()
+                .connect(&back_addr)
+                .recv_timeout(Duration::from_millis(200))
+                .build()
+                .unwrap();
+
+            let rng = Isaac64Rng::seed_from_u64(SEED);
+            let mut chaos =
+                ChaosProxy::new(frontend, backend, config, rng).unwrap();
+            let handle = chaos.handle();
+            let thread = thread::spawn(move || chaos.run());
+
+            Self {
+                scatter,
+                gather,
+                handle,
+                thread,
+            }
+        }
+
+        fn send(&self, count: u32) {
+            for i in 0..count {
+                self.scatter.send(i.to_string()).unwrap();
+            }
+        }
+
+        // Receives until the timeout expires.
+        fn recv_all(&self) -> Vec<String> {
+            let mut received = Vec::new();
+            while let Ok(msg) = self.gather.recv_msg() {
+                received.push(msg.to_str().unwrap().to_owned());
+            }
+            received
+        }
+
+        fn stop(self) -> ChaosStats {
+            self.handle.stop();
+            self.thread.join().unwrap().unwrap();
+            self.handle.stats()
+        }
+    }
+
+    #[test]
+    fn test_invalid_probability() {
+        let mut config = ChaosConfig::new();
+        config.set_drop(1.5);
+
+        let err = ChaosProxy::new(
+            Gather::new().unwrap(),
+            Scatter::new().unwrap(),
+            config,
+            Isaac64Rng::seed_from_u64(SEED),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.kind(),
+            ErrorKind::InvalidInput("probability must be between 0 and 1")
+        );
+    }
+
+    #[test]
+    fn test_drop_is_deterministic() {
+        let mut config = ChaosConfig::new();
+        config.set_drop(0.5);
+
+        let run = || {
+            let setup = Setup::new(config.clone());
+            setup.send(100);
+            let received = setup.recv_all();
+            let stats = setup.stop();
+            assert_eq!(stats.received(), 100);
+            assert_eq!(stats.forwarded(), received.len() as u64);
+            assert_eq!(stats.dropped(), 100 - received.len() as u64);
+            received
+        };
+
+        let received = run();
+        assert!(!received.is_empty() && received.len() < 100);
+        assert_eq!(received, run());
+    }
+
+    #[test]
+    fn test_duplicate_and_reorder() {
+        let mut config = ChaosConfig::new();
+        config.set_reorder(1.0);
+        config.set_max_delay(Duration::from_secs(60));
+
+        let setup = Setup::new(config);
+        setup.send(4);
+        assert_eq!(setup.recv_all(), vec!["1", "0", "3", "2"]);
+        assert_eq!(setup.stop().reordered(), 2);
+
+        let mut config = ChaosConfig::new();
+        config.set_duplicate(1.0);
+
+        let setup = Setup::new(config);
+        setup.send(2);
+        assert_eq!(setup.recv_all(), vec!["0", "0", "1", "1"]);
+        assert_eq!(setup.stop().duplicated(), 2);
+    }
+
+    #[test]
+    fn test_delay() {
+        let mut config = ChaosConfig::new();
+        config.set_delay(1.0);
+        config.set_max_delay(Duration::from_millis(50));
+
+        let setup = Setup::new(config);
+        setup.send(10);
+        let mut received = setup.recv_all();
+        received.sort_by_key(|s| s.parse::<u32>().unwrap());
+        let expected: Vec<_> = (0..10).map(|i| i.to_string()).collect();
+        assert_eq!(received, expected);
+        assert_eq!(setup.stop().delayed(), 10);
+    }
+
+    #[test]
+    fn test_corrupt() {
+        let mut config = ChaosConfig::new();
+        config.set_corrupt(1.0);
+
+        let setup = Setup::new(config);
+        setup.scatter.send(vec![0_u8; 16]).unwrap();
+        let msg = setup.gather.recv_msg().unwrap();
+
+        let flipped: u32 = msg.as_bytes().iter().map(|b| b.count_ones()).sum();
+        assert_eq!(flipped, 1);
+        assert_eq!(setup.stop().corrupted(), 1);
+    }
+
+    #[test]
+    fn test_partition() {
+        let setup = Setup::new(ChaosConfig::new());
+
+        setup.handle.partition();
+        assert!(setup.handle.is_partitioned());
+        setup.send(3);
+        assert!(setup.recv_all().is_empty());
+
+        setup.handle.heal();
+        setup.send(3);
+        assert_eq!(setup.recv_all(), vec!["0", "1", "2"]);
+
+        let stats = setup.stop();
+        assert_eq!(stats.dropped(), 3);
+        assert_eq!(stats.forwarded(), 3);
+    }
+
+    #[test]
+    fn test_stop_while_muted() {
+        let front_addr = InprocAddr::new_unique();
+        let scatter =
+            ScatterBuilder::new().connect(&front_addr).build().unwrap();
+        let frontend = GatherBuilder::new().bind(&front_addr).build().unwrap();
+        // The backend is in mute state since it has no peer.
+        let backend = Scatter::new().unwrap();
+
+        let rng = Isaac64Rng::seed_from_u64(SEED);
+        let mut chaos =
+            ChaosProxy::new(frontend, backend, ChaosConfig::new(), rng)
+                .unwrap();
+        let handle = chaos.handle();
+        let thread = thread::spawn(move || chaos.run());
+
+        scatter.send("msg").unwrap();
+        while handle.stats().received() == 0 {
+            thread::yield_now();
+        }
+
+        handle.stop();
+        thread.join().unwrap().unwrap();
+
+        let stats = handle.stats();
+        assert_eq!(stats.dropped(), 1);
+        assert_eq!(stats.forwarded(), 0);
+    }
+}
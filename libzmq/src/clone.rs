@@ -0,0 +1,1200 @@
+//! Reliable replication of a key-value store, using the Clone pattern.
+//!
+//! A [`CloneServer`] owns the store. Every change is assigned a sequence
+//! number and published to a group over a `Radio` socket, which gives a
+//! cheap fan-out but loses the changes made before a [`CloneClient`] joined,
+//! as well as those dropped along the way.
+//!
+//! A `CloneClient` therefore starts by requesting a snapshot of the store
+//! over a `Client` socket and then applies the published changes that follow
+//! the snapshot. Should a change be missing, the client notices the gap in
+//! the sequence numbers and requests a new snapshot. The server also
+//! publishes its sequence number periodically, so that the loss of the last
+//! change is detected as well.
+//!
+//! A client can restrict itself to some subtrees of the store, which are the
+//! keys that start with a given prefix. Keys set with a time-to-live are
+//! deleted by the server once expired.
+//!
+//! [`CloneServer`]: struct.CloneServer.html
+//! [`CloneClient`]: struct.CloneClient.html
+
+use crate::{
+    addr::Endpoint,
+    core::{BuildRecv, BuildSocket, RecvMsg, SendMsg, Socket},
+    error::{Error, ErrorKind},
+    socket::{
+        Client, ClientBuilder, Dish, DishBuilder, Radio, RadioBuilder, Server,
+        ServerBuilder,
+    },
+    Ctx, CtxHandle, Group,
+};
+
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::BTreeMap,
+    convert::TryInto,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const DEFAULT_GROUP: &str = "clone";
+const DEFAULT_HEARTBEAT: Duration = Duration::from_secs(1);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+// How often the background threads check for work.
+const TICK: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum Request {
+    Snapshot {
+        id: u64,
+        subtrees: Vec<String>,
+    },
+    Set {
+        key: String,
+        value: Option<Vec<u8>>,
+        ttl: Option<Duration>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum Reply {
+    Entry {
+        id: u64,
+        key: String,
+        value: Vec<u8>,
+    },
+    End {
+        id: u64,
+        seq: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum Update {
+    // A `None` value means that the key was deleted.
+    Entry {
+        seq: u64,
+        key: String,
+        value: Option<Vec<u8>>,
+    },
+    // The current sequence number of the server.
+    Hugz(u64),
+}
+
+fn in_subtrees(subtrees: &[String], key: &str) -> bool {
+    subtrees.is_empty() || subtrees.iter().any(|s| key.starts_with(s.as_str()))
+}
+
+fn default_group() -> Group {
+    DEFAULT_GROUP.try_into().unwrap()
+}
+
+fn missing(endpoint: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidInput(endpoint))
+}
+
+/// A config for a [`CloneServer`].
+///
+/// Usefull in configuration files.
+///
+/// [`CloneServer`]: struct.CloneServer.html
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CloneServerConfig {
+    snapshot: Option<Endpoint>,
+    updates: Option<Endpoint>,
+    group: Group,
+    #[serde(with = "humantime_serde")]
+    heartbeat: Duration,
+}
+
+impl CloneServerConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a `CloneServer` from the configuration, using the global
+    /// context.
+    pub fn build(&self) -> Result<CloneServer, Error> {
+        self.with_ctx(Ctx::global())
+    }
+
+    /// Builds a `CloneServer` from the configuration, using the given
+    /// context.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidInput`] (missing endpoint)
+    ///
+    /// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
+    pub fn with_ctx(&self, handle: CtxHandle) -> Result<CloneServer, Error> {
+        CloneServer::with_config(self.clone(), handle)
+    }
+
+    /// The endpoint on which snapshots are requested. The `Server` binds to
+    /// it.
+    pub fn snapshot(&self) -> Option<&Endpoint> {
+        self.snapshot.as_ref()
+    }
+
+    pub fn set_snapshot(&mut self, maybe: Option<Endpoint>) {
+        self.snapshot = maybe;
+    }
+
+    /// The endpoint on which updates are published. The `Radio` connects to
+    /// it for the `udp` transport and binds to it otherwise.
+    pub fn updates(&self) -> Option<&Endpoint> {
+        self.updates.as_ref()
+    }
+
+    pub fn set_updates(&mut self, maybe: Option<Endpoint>) {
+        self.updates = maybe;
+    }
+
+    /// The group of the updates.
+    ///
+    /// # Default
+    /// `"clone"`
+    pub fn group(&self) -> &Group {
+        &self.group
+    }
+
+    pub fn set_group(&mut self, group: Group) {
+        self.group = group;
+    }
+
+    /// The interval at which the sequence number is published, which bounds
+    /// the time needed by clients to notice a lost update.
+    ///
+    /// # Default
+    /// 1 second.
+    pub fn heartbeat(&self) -> Duration {
+        self.heartbeat
+    }
+
+    pub fn set_heartbeat(&mut self, heartbeat: Duration) {
+        self.heartbeat = heartbeat;
+    }
+}
+
+impl Default for CloneServerConfig {
+    fn default() -> Self {
+        Self {
+            snapshot: None,
+            updates: None,
+            group: default_group(),
+            heartbeat: DEFAULT_HEARTBEAT,
+        }
+    }
+}
+
+/// A convenience builder for a [`CloneServer`].
+///
+/// [`CloneServer`]: struct.CloneServer.html
+#[derive(
+    Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+pub struct CloneServerBuilder {
+    inner: CloneServerConfig,
+}
+
+impl CloneServerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a `CloneServer` from a `CloneServerBuilder`, using the global
+    /// context.
+    pub fn build(&self) -> Result<CloneServer, Error> {
+        self.inner.build()
+    }
+
+    /// Builds a `CloneServer` from a `CloneServerBuilder`, using the given
+    /// context.
+    pub fn with_ctx(&self, handle: CtxHandle) -> Result<CloneServer, Error> {
+        self.inner.with_ctx(handle)
+    }
+
+    /// See [`set_snapshot`].
+    ///
+    /// [`set_snapshot`]: struct.CloneServerConfig.html#method.set_snapshot
+    pub fn snapshot<E>(&mut self, endpoint: E) -> &mut Self
+    where
+        E: Into<Endpoint>,
+    {
+        self.inner.set_snapshot(Some(endpoint.into()));
+        self
+    }
+
+    /// See [`set_updates`].
+    ///
+    /// [`set_updates`]: struct.CloneServerConfig.html#method.set_updates
+    pub fn updates<E>(&mut self, endpoint: E) -> &mut Self
+    where
+        E: Into<Endpoint>,
+    {
+        self.inner.set_updates(Some(endpoint.into()));
+        self
+    }
+
+    /// See [`set_group`].
+    ///
+    /// [`set_group`]: struct.CloneServerConfig.html#method.set_group
+    pub fn group(&mut self, group: Group) -> &mut Self {
+        self.inner.set_group(group);
+        self
+    }
+
+    /// See [`set_heartbeat`].
+    ///
+    /// [`set_heartbeat`]: struct.CloneServerConfig.html#method.set_heartbeat
+    pub fn heartbeat(&mut self, heartbeat: Duration) -> &mut Self {
+        self.inner.set_heartbeat(heartbeat);
+        self
+    }
+}
+
+struct ServerEntry {
+    value: Vec<u8>,
+    expires: Option<Instant>,
+}
+
+struct ServerState {
+    seq: u64,
+    entries: BTreeMap<String, ServerEntry>,
+}
+
+struct ServerShared {
+    config: CloneServerConfig,
+    radio: Radio,
+    state: Mutex<ServerState>,
+    stopped: AtomicBool,
+}
+
+impl ServerShared {
+    // Applies and publishes a change while holding the lock, so that
+    // updates are published in sequence.
+    fn apply(
+        &self,
+        state: &mut ServerState,
+        key: String,
+        value: Option<Vec<u8>>,
+        ttl: Option<Duration>,
+    ) -> Result<u64, Error> {
+        match &value {
+            Some(value) => {
+                let entry = ServerEntry {
+                    value: value.clone(),
+                    expires: ttl.map(|ttl| Instant::now() + ttl),
+                };
+                state.entries.insert(key.clone(), entry);
+            }
+            None => {
+                state.entries.remove(&key);
+            }
+        }
+        state.seq += 1;
+
+        self.publish(&Update::Entry {
+            seq: state.seq,
+            key,
+            value,
+        })?;
+        Ok(state.seq)
+    }
+
+    fn publish(&self, update: &Update) -> Result<(), Error> {
+        let bytes = bincode::serialize(update).unwrap();
+        self.radio
+            .transmit(bytes, &self.config.group)
+            .map_err(Error::cast)
+    }
+
+    fn snapshot(
+        &self,
+        server: &Server,
+        msg: &crate::Msg,
+        id: u64,
+        subtrees: &[String],
+    ) -> Result<(), Error> {
+        let routing_id = msg.routing_id().unwrap();
+        let state = self.state.lock().unwrap();
+
+        let entries = state
+            .entries
+            .iter()
+            .filter(|(key, _)| in_subtrees(subtrees, key));
+        for (key, entry) in entries {
+            let reply = Reply::Entry {
+                id,
+                key: key.clone(),
+                value: entry.value.clone(),
+            };
+            let bytes = bincode::serialize(&reply).unwrap();
+            server.route(bytes, routing_id).map_err(Error::cast)?;
+        }
+
+        let reply = Reply::End { id, seq: state.seq };
+        let bytes = bincode::serialize(&reply).unwrap();
+        server.route(bytes, routing_id).map_err(Error::cast)
+    }
+
+    fn expire(&self) -> Result<(), Error> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let expired: Vec<String> = state
+            .entries
+            .iter()
+            .filter(|(_, entry)| matches!(entry.expires, Some(at) if at <= now))
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        for key in expired {
+            self.apply(&mut state, key, None, None)?;
+        }
+        Ok(())
+    }
+
+    fn run(&self, server: &Server) {
+        let mut next_hugz = Instant::now();
+
+        while !self.stopped.load(Ordering::SeqCst) {
+            let result = server.recv_msg().and_then(|msg| {
+                match bincode::deserialize(msg.as_bytes()) {
+                    Ok(Request::Snapshot { id, subtrees }) => {
+                        self.snapshot(server, &msg, id, &subtrees)
+                    }
+                    Ok(Request::Set { key, value, ttl }) => {
+                        let mut state = self.state.lock().unwrap();
+                        self.apply(&mut state, key, value, ttl).map(drop)
+                    }
+                    Err(err) => {
+                        warn!("invalid clone request: {}", err);
+                        Ok(())
+                    }
+                }
+            });
+
+            let result = result
+                .or_else(|err| match err.kind() {
+                    ErrorKind::WouldBlock => Ok(()),
+                    _ => Err(err),
+                })
+                .and_then(|_| self.expire())
+                .and_then(|_| {
+                    if Instant::now() < next_hugz {
+                        return Ok(());
+                    }
+                    next_hugz = Instant::now() + self.config.heartbeat;
+                    let seq = self.state.lock().unwrap().seq;
+                    self.publish(&Update::Hugz(seq))
+                });
+
+            if let Err(err) = result {
+                match err.kind() {
+                    ErrorKind::InvalidCtx => break,
+                    ErrorKind::Interrupted | ErrorKind::HostUnreachable => (),
+                    _ => error!("clone server error: {}", err),
+                }
+            }
+        }
+    }
+}
+
+/// The server of a replicated key-value store.
+///
+/// The store is modified either with the methods of the server or by a
+/// [`CloneClient`]. The server answers snapshot requests and expires keys
+/// in a background thread, which stops when the server is dropped.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), anyhow::Error> {
+/// use libzmq::{clone::*, *};
+/// use std::{convert::TryInto, thread, time::Duration};
+///
+/// let addr: TcpAddr = "127.0.0.1:*".try_into()?;
+/// let server = CloneServerBuilder::new()
+///     .snapshot(addr.clone())
+///     .updates(addr)
+///     .build()?;
+///
+/// // Set before the client exists, so it is only part of the snapshot.
+/// server.set("config/color", "blue")?;
+///
+/// let client = CloneClientBuilder::new()
+///     .snapshot(server.snapshot_endpoint().clone())
+///     .updates(server.updates_endpoint().clone())
+///     .subtree("config/")
+///     .build()?;
+///
+/// server.set("config/size", "42")?;
+/// server.set("pricing/eur", "1.08")?;
+///
+/// while client.seq() < server.seq() {
+///     thread::sleep(Duration::from_millis(10));
+/// }
+/// assert_eq!(client.get("config/color").unwrap(), b"blue");
+/// assert_eq!(client.get("config/size").unwrap(), b"42");
+/// // Outside of the subtree of the client.
+/// assert!(client.get("pricing/eur").is_none());
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// [`CloneClient`]: struct.CloneClient.html
+pub struct CloneServer {
+    shared: Arc<ServerShared>,
+    snapshot: Endpoint,
+    updates: Endpoint,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl CloneServer {
+    fn with_config(
+        config: CloneServerConfig,
+        handle: CtxHandle,
+    ) -> Result<Self, Error> {
+        let snapshot = config
+            .snapshot
+            .clone()
+            .ok_or_else(|| missing("missing snapshot endpoint"))?;
+        let updates = config
+            .updates
+            .clone()
+            .ok_or_else(|| missing("missing updates endpoint"))?;
+
+        let server = ServerBuilder::new()
+            .bind(snapshot)
+            .recv_timeout(TICK)
+            .with_ctx(handle)?;
+        let snapshot = server.last_endpoint()?;
+
+        let mut builder = RadioBuilder::new();
+        match updates {
+            Endpoint::Udp(_) => builder.connect(updates.clone()),
+            _ => builder.bind(updates.clone()),
+        };
+        let radio = builder.with_ctx(handle)?;
+        let updates = match updates {
+            Endpoint::Udp(_) => updates,
+            _ => radio.last_endpoint()?,
+        };
+
+        let shared = Arc::new(ServerShared {
+            config,
+            radio,
+            state: Mutex::new(ServerState {
+                seq: 0,
+                entries: BTreeMap::new(),
+            }),
+            stopped: AtomicBool::new(false),
+        });
+
+        let thread = {
+            let shared = Arc::clone(&shared);
+            thread::Builder::new()
+                .name("clone-server".to_owned())
+                .spawn(move || shared.run(&server))
+                .unwrap()
+        };
+
+        Ok(Self {
+            shared,
+            snapshot,
+            updates,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the endpoint on which snapshots are requested.
+    pub fn snapshot_endpoint(&self) -> &Endpoint {
+        &self.snapshot
+    }
+
+    /// Returns the endpoint on which updates are published.
+    pub fn updates_endpoint(&self) -> &Endpoint {
+        &self.updates
+    }
+
+    /// Returns the sequence number of the last change.
+    pub fn seq(&self) -> u64 {
+        self.shared.state.lock().unwrap().seq
+    }
+
+    /// Returns the value of the key.
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let state = self.shared.state.lock().unwrap();
+        state.entries.get(key).map(|entry| entry.value.clone())
+    }
+
+    /// Sets the value of the key, returning the sequence number of the
+    /// change.
+    pub fn set<K, V>(&self, key: K, value: V) -> Result<u64, Error>
+    where
+        K: Into<String>,
+        V: Into<Vec<u8>>,
+    {
+        let mut state = self.shared.state.lock().unwrap();
+        self.shared
+            .apply(&mut state, key.into(), Some(value.into()), None)
+    }
+
+    /// Sets the value of the key, which is deleted once the `ttl` elapsed.
+    pub fn set_with_ttl<K, V>(
+        &self,
+        key: K,
+        value: V,
+        ttl: Duration,
+    ) -> Result<u64, Error>
+    where
+        K: Into<String>,
+        V: Into<Vec<u8>>,
+    {
+        let mut state = self.shared.state.lock().unwrap();
+        self.shared
+            .apply(&mut state, key.into(), Some(value.into()), Some(ttl))
+    }
+
+    /// Deletes the key, returning the sequence number of the change.
+    pub fn delete<K>(&self, key: K) -> Result<u64, Error>
+    where
+        K: Into<String>,
+    {
+        let mut state = self.shared.state.lock().unwrap();
+        self.shared.apply(&mut state, key.into(), None, None)
+    }
+}
+
+impl fmt::Debug for CloneServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CloneServer")
+            .field("snapshot", &self.snapshot)
+            .field("updates", &self.updates)
+            .field("seq", &self.seq())
+            .finish()
+    }
+}
+
+impl Drop for CloneServer {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A config for a [`CloneClient`].
+///
+/// Usefull in configuration files.
+///
+/// [`CloneClient`]: struct.CloneClient.html
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CloneClientConfig {
+    snapshot: Option<Endpoint>,
+    updates: Option<Endpoint>,
+    group: Group,
+    subtrees: Vec<String>,
+    #[serde(with = "humantime_serde")]
+    timeout: Duration,
+}
+
+impl CloneClientConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a `CloneClient` from the configuration, using the global
+    /// context.
+    pub fn build(&self) -> Result<CloneClient, Error> {
+        self.with_ctx(Ctx::global())
+    }
+
+    /// Builds a `CloneClient` from the configuration, using the given
+    /// context.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidInput`] (missing endpoint)
+    ///
+    /// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
+    pub fn with_ctx(&self, handle: CtxHandle) -> Result<CloneClient, Error> {
+        CloneClient::with_config(self.clone(), handle)
+    }
+
+    /// The endpoint on which snapshots are requested. The `Client`
+    /// connects to it.
+    pub fn snapshot(&self) -> Option<&Endpoint> {
+        self.snapshot.as_ref()
+    }
+
+    pub fn set_snapshot(&mut self, maybe: Option<Endpoint>) {
+        self.snapshot = maybe;
+    }
+
+    /// The endpoint on which updates are published. The `Dish` binds to it
+    /// for the `udp` transport and connects to it otherwise.
+    pub fn updates(&self) -> Option<&Endpoint> {
+        self.updates.as_ref()
+    }
+
+    pub fn set_updates(&mut self, maybe: Option<Endpoint>) {
+        self.updates = maybe;
+    }
+
+    /// The group of the updates.
+    ///
+    /// # Default
+    /// `"clone"`
+    pub fn group(&self) -> &Group {
+        &self.group
+    }
+
+    pub fn set_group(&mut self, group: Group) {
+        self.group = group;
+    }
+
+    /// The prefixes of the keys replicated by the client.
+    ///
+    /// # Default
+    /// Empty, meaning every key.
+    pub fn subtrees(&self) -> &[String] {
+        &self.subtrees
+    }
+
+    pub fn set_subtrees(&mut self, subtrees: Vec<String>) {
+        self.subtrees = subtrees;
+    }
+
+    /// How long the client waits for a snapshot before requesting it again.
+    ///
+    /// # Default
+    /// 2 seconds.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}
+
+impl Default for CloneClientConfig {
+    fn default() -> Self {
+        Self {
+            snapshot: None,
+            updates: None,
+            group: default_group(),
+            subtrees: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+/// A convenience builder for a [`CloneClient`].
+///
+/// [`CloneClient`]: struct.CloneClient.html
+#[derive(
+    Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+pub struct CloneClientBuilder {
+    inner: CloneClientConfig,
+}
+
+impl CloneClientBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a `CloneClient` from a `CloneClientBuilder`, using the global
+    /// context.
+    pub fn build(&self) -> Result<CloneClient, Error> {
+        self.inner.build()
+    }
+
+    /// Builds a `CloneClient` from a `CloneClientBuilder`, using the given
+    /// context.
+    pub fn with_ctx(&self, handle: CtxHandle) -> Result<CloneClient, Error> {
+        self.inner.with_ctx(handle)
+    }
+
+    /// See [`set_snapshot`].
+    ///
+    /// [`set_snapshot`]: struct.CloneClientConfig.html#method.set_snapshot
+    pub fn snapshot<E>(&mut self, endpoint: E) -> &mut Self
+    where
+        E: Into<Endpoint>,
+    {
+        self.inner.set_snapshot(Some(endpoint.into()));
+        self
+    }
+
+    /// See [`set_updates`].
+    ///
+    /// [`set_updates`]: struct.CloneClientConfig.html#method.set_updates
+    pub fn updates<E>(&mut self, endpoint: E) -> &mut Self
+    where
+        E: Into<Endpoint>,
+    {
+        self.inner.set_updates(Some(endpoint.into()));
+        self
+    }
+
+    /// See [`set_group`].
+    ///
+    /// [`set_group`]: struct.CloneClientConfig.html#method.set_group
+    pub fn group(&mut self, group: Group) -> &mut Self {
+        self.inner.set_group(group);
+        self
+    }
+
+    /// Adds a subtree to the client. See [`set_subtrees`].
+    ///
+    /// [`set_subtrees`]: struct.CloneClientConfig.html#method.set_subtrees
+    pub fn subtree<S>(&mut self, prefix: S) -> &mut Self
+    where
+        S: Into<String>,
+    {
+        self.inner.subtrees.push(prefix.into());
+        self
+    }
+
+    /// See [`set_timeout`].
+    ///
+    /// [`set_timeout`]: struct.CloneClientConfig.html#method.set_timeout
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.inner.set_timeout(timeout);
+        self
+    }
+}
+
+// The outcome of an update received by a client.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Outcome {
+    Applied,
+    // The update is already part of the state.
+    Ignored,
+    // An update was lost, so a new snapshot is needed.
+    Gap,
+}
+
+#[derive(Debug, Default)]
+struct ClientState {
+    // `None` until the first snapshot is received.
+    seq: Option<u64>,
+    entries: BTreeMap<String, Vec<u8>>,
+}
+
+impl ClientState {
+    fn apply(&mut self, subtrees: &[String], update: Update) -> Outcome {
+        let last = match self.seq {
+            Some(seq) => seq,
+            None => return Outcome::Ignored,
+        };
+
+        match update {
+            Update::Hugz(seq) if seq > last => Outcome::Gap,
+            Update::Hugz(_) => Outcome::Ignored,
+            Update::Entry { seq, .. } if seq <= last => Outcome::Ignored,
+            Update::Entry { seq, .. } if seq > last + 1 => Outcome::Gap,
+            Update::Entry { seq, key, value } => {
+                self.seq = Some(seq);
+                if in_subtrees(subtrees, &key) {
+                    match value {
+                        Some(value) => self.entries.insert(key, value),
+                        None => self.entries.remove(&key),
+                    };
+                }
+                Outcome::Applied
+            }
+        }
+    }
+}
+
+struct ClientShared {
+    config: CloneClientConfig,
+    client: Client,
+    state: Mutex<ClientState>,
+    stopped: AtomicBool,
+}
+
+impl ClientShared {
+    fn send(&self, request: &Request) -> Result<(), Error> {
+        let bytes = bincode::serialize(request).unwrap();
+        self.client.send(bytes).map_err(Error::cast)
+    }
+
+    // Requests a snapshot, returning `false` if it timed out.
+    fn sync(&self, id: u64) -> Result<bool, Error> {
+        self.send(&Request::Snapshot {
+            id,
+            subtrees: self.config.subtrees.clone(),
+        })?;
+
+        let deadline = Instant::now() + self.config.timeout;
+        let mut entries = BTreeMap::new();
+        while Instant::now() < deadline {
+            let msg = match self.client.recv_msg() {
+                Ok(msg) => msg,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => continue,
+                Err(err) => return Err(err),
+            };
+            match bincode::deserialize(msg.as_bytes()) {
+                Ok(Reply::Entry {
+                    id: reply,
+                    key,
+                    value,
+                }) if reply == id => {
+                    entries.insert(key, value);
+                }
+                Ok(Reply::End { id: reply, seq }) if reply == id => {
+                    let mut state = self.state.lock().unwrap();
+                    state.seq = Some(seq);
+                    state.entries = entries;
+                    return Ok(true);
+                }
+                // A reply to a previous request that timed out.
+                Ok(_) => (),
+                Err(err) => warn!("invalid clone reply: {}", err),
+            }
+        }
+        Ok(false)
+    }
+
+    fn run(&self, dish: &Dish) {
+        let mut id = 0;
+        let mut synced = false;
+
+        while !self.stopped.load(Ordering::SeqCst) {
+            if !synced {
+                id += 1;
+                match self.sync(id) {
+                    Ok(true) => synced = true,
+                    Ok(false) => warn!("clone snapshot timed out"),
+                    Err(err) if err.kind() == ErrorKind::InvalidCtx => break,
+                    Err(err) => error!("unable to request snapshot: {}", err),
+                }
+                continue;
+            }
+
+            let msg = match dish.recv_msg() {
+                Ok(msg) => msg,
+                Err(err) => match err.kind() {
+                    ErrorKind::WouldBlock | ErrorKind::Interrupted => continue,
+                    ErrorKind::InvalidCtx => break,
+                    _ => {
+                        error!("unable to receive update: {}", err);
+                        continue;
+                    }
+                },
+            };
+            let update = match bincode::deserialize(msg.as_bytes()) {
+                Ok(update) => update,
+                Err(err) => {
+                    warn!("invalid clone update: {}", err);
+                    continue;
+                }
+            };
+
+            let mut state = self.state.lock().unwrap();
+            if state.apply(&self.config.subtrees, update) == Outcome::Gap {
+                synced = false;
+            }
+        }
+    }
+}
+
+/// A client that replicates a [`CloneServer`] store, or some subtrees of it.
+///
+/// The replica is kept up to date by a background thread, which stops when
+/// the client is dropped. Changes made using the client are sent to the
+/// server, and are only visible in the replica once published back by the
+/// server.
+///
+/// See [`CloneServer`] for an example.
+///
+/// [`CloneServer`]: struct.CloneServer.html
+pub struct CloneClient {
+    shared: Arc<ClientShared>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl CloneClient {
+    fn with_config(
+        config: CloneClientConfig,
+        handle: CtxHandle,
+    ) -> Result<Self, Error> {
+        let snapshot = config
+            .snapshot
+            .clone()
+            .ok_or_else(|| missing("missing snapshot endpoint"))?;
+        let updates = config
+            .updates
+            .clone()
+            .ok_or_else(|| missing("missing updates endpoint"))?;
+
+        // The dish joins before the snapshot is requested, so that no update
+        // that follows the snapshot is missed.
+        let mut builder = DishBuilder::new();
+        builder.join(&config.group).recv_timeout(TICK);
+        match updates {
+            Endpoint::Udp(_) => builder.bind(updates),
+            _ => builder.connect(updates),
+        };
+        let dish = builder.with_ctx(handle)?;
+
+        let client = ClientBuilder::new()
+            .connect(snapshot)
+            .recv_timeout(TICK)
+            .with_ctx(handle)?;
+
+        let shared = Arc::new(ClientShared {
+            config,
+            client,
+            state: Mutex::new(ClientState::default()),
+            stopped: AtomicBool::new(false),
+        });
+
+        let thread = {
+            let shared = Arc::clone(&shared);
+            thread::Builder::new()
+                .name("clone-client".to_owned())
+                .spawn(move || shared.run(&dish))
+                .unwrap()
+        };
+
+        Ok(Self {
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the sequence number of the last change applied to the
+    /// replica, or `0` until the first snapshot is received.
+    pub fn seq(&self) -> u64 {
+        self.shared.state.lock().unwrap().seq.unwrap_or(0)
+    }
+
+    /// Returns whether the first snapshot was received.
+    pub fn is_synced(&self) -> bool {
+        self.shared.state.lock().unwrap().seq.is_some()
+    }
+
+    /// Returns the value of the key in the replica.
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let state = self.shared.state.lock().unwrap();
+        state.entries.get(key).cloned()
+    }
+
+    /// Returns a copy of the replica.
+    pub fn entries(&self) -> BTreeMap<String, Vec<u8>> {
+        self.shared.state.lock().unwrap().entries.clone()
+    }
+
+    /// Sets the value of the key on the server.
+    pub fn set<K, V>(&self, key: K, value: V) -> Result<(), Error>
+    where
+        K: Into<String>,
+        V: Into<Vec<u8>>,
+    {
+        self.shared.send(&Request::Set {
+            key: key.into(),
+            value: Some(value.into()),
+            ttl: None,
+        })
+    }
+
+    /// Sets the value of the key on the server, which deletes it once the
+    /// `ttl` elapsed.
+    pub fn set_with_ttl<K, V>(
+        &self,
+        key: K,
+        value: V,
+        ttl: Duration,
+    ) -> Result<(), Error>
+    where
+        K: Into<String>,
+        V: Into<Vec<u8>>,
+    {
+        self.shared.send(&Request::Set {
+            key: key.into(),
+            value: Some(value.into()),
+            ttl: Some(ttl),
+        })
+    }
+
+    /// Deletes the key on the server.
+    pub fn delete<K>(&self, key: K) -> Result<(), Error>
+    where
+        K: Into<String>,
+    {
+        self.shared.send(&Request::Set {
+            key: key.into(),
+            value: None,
+            ttl: None,
+        })
+    }
+}
+
+impl fmt::Debug for CloneClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CloneClient")
+            .field("config", &self.shared.config)
+            .field("seq", &self.seq())
+            .finish()
+    }
+}
+
+impl Drop for CloneClient {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::TcpAddr;
+
+    fn entry(seq: u64, key: &str, value: Option<&str>) -> Update {
+        Update::Entry {
+            seq,
+            key: key.to_owned(),
+            value: value.map(|v| v.as_bytes().to_vec()),
+        }
+    }
+
+    fn server() -> CloneServer {
+        let addr: TcpAddr = "127.0.0.1:*".try_into().unwrap();
+        CloneServerBuilder::new()
+            .snapshot(addr.clone())
+            .updates(addr)
+            .heartbeat(Duration::from_millis(100))
+            .build()
+            .unwrap()
+    }
+
+    fn client(server: &CloneServer, subtree: Option<&str>) -> CloneClient {
+        let mut builder = CloneClientBuilder::new();
+        builder
+            .snapshot(server.snapshot_endpoint().clone())
+            .updates(server.updates_endpoint().clone());
+        if let Some(subtree) = subtree {
+            builder.subtree(subtree);
+        }
+        builder.build().unwrap()
+    }
+
+    fn wait_for<F>(f: F)
+    where
+        F: Fn() -> bool,
+    {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !f() {
+            assert!(Instant::now() < deadline, "timed out");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_ser_de() {
+        let mut config = CloneClientConfig::new();
+        let addr: TcpAddr = "127.0.0.1:3000".try_into().unwrap();
+        config.set_updates(Some(addr.into()));
+        config.set_subtrees(vec!["config/".to_owned()]);
+
+        let ron = serde_yaml::to_string(&config).unwrap();
+        let de: CloneClientConfig = serde_yaml::from_str(&ron).unwrap();
+        assert_eq!(config, de);
+    }
+
+    #[test]
+    fn test_missing_endpoint() {
+        let err = CloneServerBuilder::new().build().unwrap_err();
+        assert_eq!(
+            err.kind(),
+            ErrorKind::InvalidInput("missing snapshot endpoint")
+        );
+    }
+
+    #[test]
+    fn test_apply_detects_gap() {
+        let subtrees = vec!["a/".to_owned()];
+        let mut state = ClientState::default();
+        assert_eq!(
+            state.apply(&subtrees, entry(1, "a/x", Some("1"))),
+            Outcome::Ignored
+        );
+
+        state.seq = Some(1);
+        assert_eq!(
+            state.apply(&subtrees, entry(1, "a/x", Some("1"))),
+            Outcome::Ignored
+        );
+        assert_eq!(
+            state.apply(&subtrees, entry(2, "a/x", Some("2"))),
+            Outcome::Applied
+        );
+        // Outside of the subtrees, but still in sequence.
+        assert_eq!(
+            state.apply(&subtrees, entry(3, "b/x", Some("3"))),
+            Outcome::Applied
+        );
+        assert_eq!(state.entries.len(), 1);
+        assert_eq!(state.entries["a/x"], b"2");
+
+        assert_eq!(state.apply(&subtrees, Update::Hugz(3)), Outcome::Ignored);
+        assert_eq!(state.apply(&subtrees, Update::Hugz(4)), Outcome::Gap);
+        assert_eq!(state.apply(&subtrees, entry(5, "a/x", None)), Outcome::Gap);
+        assert_eq!(state.seq, Some(3));
+    }
+
+    #[test]
+    fn test_snapshot_and_updates() {
+        let server = server();
+        server.set("a/1", "1").unwrap();
+        server.set("b/1", "1").unwrap();
+
+        let client = client(&server, Some("a/"));
+        wait_for(|| client.is_synced());
+        assert_eq!(client.entries().len(), 1);
+
+        server.set("a/2", "2").unwrap();
+        server.delete("a/1").unwrap();
+        wait_for(|| client.seq() == server.seq());
+        assert!(client.get("a/1").is_none());
+        assert_eq!(client.get("a/2").unwrap(), b"2");
+    }
+
+    #[test]
+    fn test_client_set_and_ttl() {
+        let server = server();
+        let client = client(&server, None);
+        wait_for(|| client.is_synced());
+
+        client.set("key", "value").unwrap();
+        client
+            .set_with_ttl("ttl", "value", Duration::from_millis(100))
+            .unwrap();
+        wait_for(|| client.get("key").is_some() && client.get("ttl").is_some());
+        assert_eq!(server.get("key").unwrap(), b"value");
+
+        wait_for(|| client.get("ttl").is_none());
+        assert!(server.get("ttl").is_none());
+
+        client.delete("key").unwrap();
+        wait_for(|| client.entries().is_empty());
+    }
+}
@@ -0,0 +1,779 @@
+//! Transparent message compression.
+
+use crate::{
+    error::{Error, ErrorKind},
+    msg::Msg,
+};
+
+use byteorder::{BigEndian, ByteOrder};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "zstd")]
+use std::sync::Mutex;
+use std::{
+    ffi::CStr,
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+// The handshake property advertised by a socket with compression enabled,
+// whose value is the version of the frame format.
+pub(crate) const METADATA: &str = "X-Compression:1";
+const PROPERTY: &[u8] = b"X-Compression\0";
+const VERSION: &str = "1";
+// Sent in the handshake of every ZMTP connection.
+const SOCKET_TYPE_PROPERTY: &[u8] = b"Socket-Type\0";
+// Set in the flags when the frame was compressed with a dictionary.
+const DICTIONARY_FLAG: u8 = 0x80;
+const RAW: u8 = 0;
+const LZ4: u8 = 1;
+const ZSTD: u8 = 2;
+// The flags.
+const RAW_PREFIX_LEN: usize = 1;
+// The flags and the uncompressed size.
+const PREFIX_LEN: usize = RAW_PREFIX_LEN + 4;
+
+/// The default size in bytes under which messages are not compressed.
+pub const DEFAULT_THRESHOLD: usize = 256;
+/// The default maximum size in bytes of a decompressed message.
+pub const DEFAULT_MAX_SIZE: usize = 16 * 1024 * 1024;
+
+/// A compression algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Algorithm {
+    /// Fast compression using LZ4.
+    ///
+    /// Requires enabling the feature flag `lz4`.
+    Lz4,
+    /// Slower but denser compression using Zstandard.
+    ///
+    /// Requires enabling the feature flag `zstd`.
+    Zstd,
+}
+
+impl Algorithm {
+    fn id(self) -> u8 {
+        match self {
+            Algorithm::Lz4 => LZ4,
+            Algorithm::Zstd => ZSTD,
+        }
+    }
+}
+
+/// The compression configuration of a socket.
+///
+/// Once a socket has compression enabled, each message it sends is prefixed
+/// by a flag byte which indicates whether the message was compressed.
+/// Messages smaller than the [`threshold`] are sent uncompressed.
+///
+/// Both peers must enable compression with the same [`Algorithm`] and
+/// dictionary. A socket with compression enabled advertises it with the
+/// `X-Compression` property in the handshake of its connections. When a
+/// socket with compression enabled receives a message from a peer that did
+/// not advertise it, it returns an [`InvalidInput`] error.
+///
+/// The `inproc` and `udp` transports have no handshake, in which case the
+/// peers are trusted to be configured with the same compression.
+///
+/// # Frame Format
+/// All integers are big-endian.
+/// ```text
+/// +-------+-------------------+---------+
+/// | flags | uncompressed size | payload |
+/// |  u8   |   u32 (if any)    |         |
+/// +-------+-------------------+---------+
+/// ```
+/// where the lower bits of the flags are the algorithm (`0` for
+/// uncompressed, `1` for LZ4 and `2` for Zstandard). The highest bit of the
+/// flags is set if a dictionary was used. The uncompressed size is only
+/// present if the payload is compressed.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), anyhow::Error> {
+/// # #[cfg(feature = "lz4")] {
+/// use libzmq::{prelude::*, *, compress::*};
+///
+/// let addr = InprocAddr::new_unique();
+/// let compression = Compression::new(Algorithm::Lz4);
+///
+/// let gather = GatherBuilder::new()
+///     .bind(&addr)
+///     .compression(compression.clone())
+///     .build()?;
+///
+/// let scatter = ScatterBuilder::new()
+///     .connect(&addr)
+///     .compression(compression)
+///     .build()?;
+///
+/// let telemetry = "cpu=0.42;".repeat(100);
+/// scatter.send(telemetry.as_str())?;
+///
+/// let msg = gather.recv_msg()?;
+/// assert_eq!(msg.to_str()?, telemetry);
+/// assert!(gather.compression_stats().recv_ratio() > 10.0);
+/// # }
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// [`threshold`]: #method.threshold
+/// [`Algorithm`]: enum.Algorithm.html
+/// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Compression {
+    algorithm: Algorithm,
+    level: Option<i32>,
+    threshold: Option<usize>,
+    max_size: Option<usize>,
+    dictionary: Option<Vec<u8>>,
+}
+
+impl Compression {
+    pub fn new(algorithm: Algorithm) -> Self {
+        Self {
+            algorithm,
+            level: None,
+            threshold: None,
+            max_size: None,
+            dictionary: None,
+        }
+    }
+
+    /// Returns the compression algorithm.
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Returns the compression level, if any.
+    ///
+    /// Only used by `Zstd`, which uses its default level if `None`.
+    pub fn level(&self) -> Option<i32> {
+        self.level
+    }
+
+    /// Sets the compression level.
+    pub fn set_level(&mut self, maybe: Option<i32>) {
+        self.level = maybe;
+    }
+
+    /// Returns the size in bytes under which messages are not compressed.
+    ///
+    /// # Default
+    /// `DEFAULT_THRESHOLD`
+    pub fn threshold(&self) -> usize {
+        self.threshold.unwrap_or(DEFAULT_THRESHOLD)
+    }
+
+    /// Sets the size in bytes under which messages are not compressed.
+    pub fn set_threshold(&mut self, maybe: Option<usize>) {
+        self.threshold = maybe;
+    }
+
+    /// Returns the maximum size in bytes of a decompressed message.
+    ///
+    /// Received messages that would exceed this size are rejected.
+    ///
+    /// # Default
+    /// `DEFAULT_MAX_SIZE`
+    pub fn max_size(&self) -> usize {
+        self.max_size.unwrap_or(DEFAULT_MAX_SIZE)
+    }
+
+    /// Sets the maximum size in bytes of a decompressed message.
+    pub fn set_max_size(&mut self, maybe: Option<usize>) {
+        self.max_size = maybe;
+    }
+
+    /// Returns the shared dictionary, if any.
+    pub fn dictionary(&self) -> Option<&[u8]> {
+        self.dictionary.as_deref()
+    }
+
+    /// Sets the shared dictionary.
+    ///
+    /// A dictionary improves the compression of small messages with
+    /// similar content. The peer must use the same dictionary.
+    pub fn set_dictionary<D>(&mut self, maybe: Option<D>)
+    where
+        D: Into<Vec<u8>>,
+    {
+        self.dictionary = maybe.map(D::into);
+    }
+
+    /// Returns an `Unsupported` error if the algorithm was not enabled
+    /// via its feature flag.
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        match self.algorithm {
+            Algorithm::Lz4 if cfg!(not(feature = "lz4")) => {
+                Err(unsupported(Algorithm::Lz4))
+            }
+            Algorithm::Zstd if cfg!(not(feature = "zstd")) => {
+                Err(unsupported(Algorithm::Zstd))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn unsupported(algorithm: Algorithm) -> Error {
+    let msg = match algorithm {
+        Algorithm::Lz4 => {
+            "LZ4 compression requires enabling feature flag 'lz4'"
+        }
+        Algorithm::Zstd => {
+            "Zstd compression requires enabling feature flag 'zstd'"
+        }
+    };
+    Error::new(ErrorKind::Unsupported(msg))
+}
+
+/// The compression statistics of a socket.
+///
+/// The statistics are reset whenever the compression of the socket
+/// is configured.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CompressionStats {
+    sent: u64,
+    sent_wire: u64,
+    recv: u64,
+    recv_wire: u64,
+}
+
+impl CompressionStats {
+    /// Returns the number of bytes sent before compression.
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    /// Returns the number of bytes sent after compression.
+    pub fn sent_wire(&self) -> u64 {
+        self.sent_wire
+    }
+
+    /// Returns the number of bytes received after decompression.
+    pub fn recv(&self) -> u64 {
+        self.recv
+    }
+
+    /// Returns the number of bytes received before decompression.
+    pub fn recv_wire(&self) -> u64 {
+        self.recv_wire
+    }
+
+    /// Returns the ratio of the uncompressed size over the compressed size
+    /// of sent messages.
+    ///
+    /// Returns `1.0` if no messages were sent.
+    pub fn send_ratio(&self) -> f64 {
+        ratio(self.sent, self.sent_wire)
+    }
+
+    /// Returns the ratio of the uncompressed size over the compressed size
+    /// of received messages.
+    ///
+    /// Returns `1.0` if no messages were received.
+    pub fn recv_ratio(&self) -> f64 {
+        ratio(self.recv, self.recv_wire)
+    }
+}
+
+fn ratio(raw: u64, wire: u64) -> f64 {
+    if wire == 0 {
+        1.0
+    } else {
+        raw as f64 / wire as f64
+    }
+}
+
+#[cfg(feature = "zstd")]
+struct ZstdCodec {
+    compressor: Mutex<zstd::bulk::Compressor<'static>>,
+    decompressor: Mutex<zstd::bulk::Decompressor<'static>>,
+}
+
+#[cfg(feature = "zstd")]
+impl ZstdCodec {
+    fn new(config: &Compression) -> Result<Self, Error> {
+        let level = config.level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL);
+        let dictionary = config.dictionary().unwrap_or(&[]);
+
+        let compressor =
+            zstd::bulk::Compressor::with_dictionary(level, dictionary);
+        let decompressor =
+            zstd::bulk::Decompressor::with_dictionary(dictionary);
+
+        match (compressor, decompressor) {
+            (Ok(compressor), Ok(decompressor)) => Ok(Self {
+                compressor: Mutex::new(compressor),
+                decompressor: Mutex::new(decompressor),
+            }),
+            _ => Err(Error::new(ErrorKind::InvalidInput(
+                "invalid zstd level or dictionary",
+            ))),
+        }
+    }
+}
+
+// The implementation of the algorithms whose feature flag is enabled.
+enum Backend {
+    #[cfg(feature = "lz4")]
+    Lz4,
+    #[cfg(feature = "zstd")]
+    Zstd(ZstdCodec),
+}
+
+impl Backend {
+    fn new(config: &Compression) -> Result<Self, Error> {
+        match config.algorithm {
+            #[cfg(feature = "lz4")]
+            Algorithm::Lz4 => Ok(Backend::Lz4),
+            #[cfg(feature = "zstd")]
+            Algorithm::Zstd => Ok(Backend::Zstd(ZstdCodec::new(config)?)),
+            #[cfg(not(feature = "lz4"))]
+            Algorithm::Lz4 => Err(unsupported(Algorithm::Lz4)),
+            #[cfg(not(feature = "zstd"))]
+            Algorithm::Zstd => Err(unsupported(Algorithm::Zstd)),
+        }
+    }
+
+    // Without any algorithm enabled, the backend cannot be constructed.
+    #[cfg_attr(
+        not(any(feature = "lz4", feature = "zstd")),
+        allow(unused_variables)
+    )]
+    fn compress(&self, config: &Compression, data: &[u8]) -> Option<Vec<u8>> {
+        match *self {
+            #[cfg(feature = "lz4")]
+            Backend::Lz4 => Some(match config.dictionary() {
+                Some(dict) => lz4_flex::block::compress_with_dict(data, dict),
+                None => lz4_flex::block::compress(data),
+            }),
+            #[cfg(feature = "zstd")]
+            Backend::Zstd(ref zstd) => {
+                zstd.compressor.lock().unwrap().compress(data).ok()
+            }
+        }
+    }
+
+    #[cfg_attr(
+        not(any(feature = "lz4", feature = "zstd")),
+        allow(unused_variables)
+    )]
+    fn decompress(
+        &self,
+        config: &Compression,
+        data: &[u8],
+        size: usize,
+    ) -> Option<Vec<u8>> {
+        match *self {
+            #[cfg(feature = "lz4")]
+            Backend::Lz4 => match config.dictionary() {
+                Some(dict) => {
+                    lz4_flex::block::decompress_with_dict(data, size, dict)
+                }
+                None => lz4_flex::block::decompress(data, size),
+            }
+            .ok(),
+            #[cfg(feature = "zstd")]
+            Backend::Zstd(ref zstd) => zstd
+                .decompressor
+                .lock()
+                .unwrap()
+                .decompress(data, size)
+                .ok(),
+        }
+    }
+}
+
+/// Compresses and decompresses the messages of a socket.
+pub(crate) struct Codec {
+    config: Compression,
+    backend: Backend,
+    sent: AtomicU64,
+    sent_wire: AtomicU64,
+    recv: AtomicU64,
+    recv_wire: AtomicU64,
+}
+
+impl fmt::Debug for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Codec")
+            .field("config", &self.config)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl Codec {
+    pub(crate) fn new(config: Compression) -> Result<Self, Error> {
+        let backend = Backend::new(&config)?;
+
+        Ok(Self {
+            config,
+            backend,
+            sent: AtomicU64::default(),
+            sent_wire: AtomicU64::default(),
+            recv: AtomicU64::default(),
+            recv_wire: AtomicU64::default(),
+        })
+    }
+
+    pub(crate) fn config(&self) -> &Compression {
+        &self.config
+    }
+
+    pub(crate) fn stats(&self) -> CompressionStats {
+        CompressionStats {
+            sent: self.sent.load(Ordering::Relaxed),
+            sent_wire: self.sent_wire.load(Ordering::Relaxed),
+            recv: self.recv.load(Ordering::Relaxed),
+            recv_wire: self.recv_wire.load(Ordering::Relaxed),
+        }
+    }
+
+    fn flags(&self) -> u8 {
+        let mut flags = self.config.algorithm.id();
+        if self.config.dictionary.is_some() {
+            flags |= DICTIONARY_FLAG;
+        }
+        flags
+    }
+
+    /// Encodes the message into a frame.
+    ///
+    /// The frame must be passed to [`sent`] once it was sent.
+    ///
+    /// [`sent`]: #method.sent
+    pub(crate) fn encode(&self, msg: &Msg) -> Msg {
+        let data = msg.as_bytes();
+
+        let compressed = if data.len() < self.config.threshold()
+            || data.len() > u32::MAX as usize
+        {
+            None
+        } else {
+            self.backend
+                .compress(&self.config, data)
+                .filter(|c| c.len() + PREFIX_LEN < data.len() + RAW_PREFIX_LEN)
+        };
+
+        let mut frame = match compressed {
+            Some(compressed) => {
+                let mut frame = Msg::with_size(PREFIX_LEN + compressed.len());
+                let bytes = frame.as_bytes_mut();
+                bytes[0] = self.flags();
+                BigEndian::write_u32(&mut bytes[1..5], data.len() as u32);
+                bytes[PREFIX_LEN..].copy_from_slice(&compressed);
+                frame
+            }
+            None => {
+                let mut frame = Msg::with_size(RAW_PREFIX_LEN + data.len());
+                let bytes = frame.as_bytes_mut();
+                bytes[0] = RAW;
+                bytes[RAW_PREFIX_LEN..].copy_from_slice(data);
+                frame
+            }
+        };
+        msg.copy_properties(&mut frame);
+
+        frame
+    }
+
+    /// Counts a message of `len` bytes that was sent as a frame of
+    /// `frame_len` bytes.
+    pub(crate) fn sent(&self, len: usize, frame_len: usize) {
+        self.sent.fetch_add(len as u64, Ordering::Relaxed);
+        self.sent_wire
+            .fetch_add(frame_len as u64, Ordering::Relaxed);
+    }
+
+    /// Decodes the frame into the original message.
+    pub(crate) fn decode(&self, frame: &Msg) -> Result<Msg, Error> {
+        check_peer(frame)?;
+
+        let bytes = frame.as_bytes();
+        if bytes.len() < RAW_PREFIX_LEN {
+            return Err(Error::new(ErrorKind::InvalidInput(
+                "truncated compressed frame",
+            )));
+        }
+
+        let flags = bytes[0];
+        let mut msg = if flags == RAW {
+            Msg::from(&bytes[RAW_PREFIX_LEN..])
+        } else {
+            if flags != self.flags() {
+                return Err(Error::new(ErrorKind::InvalidInput(
+                    "peer compression does not match",
+                )));
+            }
+            if bytes.len() < PREFIX_LEN {
+                return Err(Error::new(ErrorKind::InvalidInput(
+                    "truncated compressed frame",
+                )));
+            }
+
+            let size = BigEndian::read_u32(&bytes[1..5]) as usize;
+            if size > self.config.max_size() {
+                return Err(Error::new(ErrorKind::InvalidInput(
+                    "decompressed size exceeds limit",
+                )));
+            }
+
+            let payload = &bytes[PREFIX_LEN..];
+            match self.backend.decompress(&self.config, payload, size) {
+                Some(data) if data.len() == size => Msg::from(data),
+                _ => {
+                    return Err(Error::new(ErrorKind::InvalidInput(
+                        "corrupted compressed frame",
+                    )))
+                }
+            }
+        };
+        frame.copy_properties(&mut msg);
+
+        self.recv.fetch_add(msg.len() as u64, Ordering::Relaxed);
+        self.recv_wire
+            .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+
+        Ok(msg)
+    }
+}
+
+// Checks that the peer advertised compression in the handshake of the
+// connection the frame was received from, if any.
+fn check_peer(frame: &Msg) -> Result<(), Error> {
+    let property = CStr::from_bytes_with_nul(PROPERTY).unwrap();
+    let socket_type = CStr::from_bytes_with_nul(SOCKET_TYPE_PROPERTY).unwrap();
+
+    match frame.property(property) {
+        Some(VERSION) => Ok(()),
+        Some(_) => Err(Error::new(ErrorKind::InvalidInput(
+            "peer compression does not match",
+        ))),
+        None if frame.property(socket_type).is_some() => Err(Error::new(
+            ErrorKind::InvalidInput("peer does not use compression"),
+        )),
+        // The connection has no handshake.
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{config::*, prelude::*, *};
+
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    fn telemetry() -> String {
+        "temperature=21.5;humidity=0.43;".repeat(64)
+    }
+
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    fn test_scatter_gather(compression: Compression) {
+        let addr = InprocAddr::new_unique();
+        let gather = GatherBuilder::new()
+            .bind(&addr)
+            .compression(compression.clone())
+            .build()
+            .unwrap();
+        let scatter = ScatterBuilder::new()
+            .connect(&addr)
+            .compression(compression)
+            .build()
+            .unwrap();
+
+        // Under the threshold.
+        scatter.send("small").unwrap();
+        let msg = gather.recv_msg().unwrap();
+        assert_eq!(msg.to_str().unwrap(), "small");
+
+        let telemetry = telemetry();
+        scatter.send(telemetry.as_str()).unwrap();
+        let msg = gather.recv_msg().unwrap();
+        assert_eq!(msg.to_str().unwrap(), telemetry);
+
+        let sent = scatter.compression_stats();
+        assert_eq!(sent.sent(), (5 + telemetry.len()) as u64);
+        assert!(sent.send_ratio() > 5.0);
+        assert_eq!(gather.compression_stats().recv_ratio(), sent.send_ratio());
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn test_lz4() {
+        let mut compression = Compression::new(Algorithm::Lz4);
+        test_scatter_gather(compression.clone());
+
+        compression.set_dictionary(Some(telemetry()));
+        test_scatter_gather(compression);
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_zstd() {
+        let mut compression = Compression::new(Algorithm::Zstd);
+        compression.set_level(Some(9));
+        test_scatter_gather(compression.clone());
+
+        compression.set_dictionary(Some(telemetry()));
+        test_scatter_gather(compression);
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn test_radio_dish_group() {
+        let addr = InprocAddr::new_unique();
+        let group: Group = "telemetry".try_into().unwrap();
+        let compression = Compression::new(Algorithm::Lz4);
+
+        let radio = RadioBuilder::new()
+            .bind(&addr)
+            .compression(compression.clone())
+            .build()
+            .unwrap();
+        let dish = DishBuilder::new()
+            .connect(&addr)
+            .join(&group)
+            .compression(compression)
+            .build()
+            .unwrap();
+
+        let telemetry = telemetry();
+        let msg = loop {
+            radio.transmit(telemetry.as_str(), &group).unwrap();
+            if let Ok(msg) = dish.try_recv_msg() {
+                break msg;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        };
+        assert_eq!(msg.to_str().unwrap(), telemetry);
+        assert_eq!(msg.group().unwrap(), &group);
+    }
+
+    #[cfg(feature = "lz4")]
+    fn tcp_gather(compression: Compression) -> Gather {
+        let addr: TcpAddr = "127.0.0.1:*".try_into().unwrap();
+        GatherBuilder::new()
+            .bind(addr)
+            .compression(compression)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn test_tcp_negotiated() {
+        let compression = Compression::new(Algorithm::Lz4);
+        let gather = tcp_gather(compression.clone());
+        let scatter = ScatterBuilder::new()
+            .connect(gather.last_endpoint().unwrap())
+            .compression(compression)
+            .build()
+            .unwrap();
+
+        let telemetry = telemetry();
+        scatter.send(telemetry.as_str()).unwrap();
+        let msg = gather.recv_msg().unwrap();
+        assert_eq!(msg.to_str().unwrap(), telemetry);
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn test_uncompressed_peer() {
+        let gather = tcp_gather(Compression::new(Algorithm::Lz4));
+        let scatter = ScatterBuilder::new()
+            .connect(gather.last_endpoint().unwrap())
+            .build()
+            .unwrap();
+
+        // Looks like an uncompressed frame.
+        scatter.send(&b"\x00raw"[..]).unwrap();
+        let mut msg = Msg::new();
+        let err = gather.recv(&mut msg).unwrap_err();
+        assert_eq!(
+            err.kind(),
+            ErrorKind::InvalidInput("peer does not use compression")
+        );
+        // The frame is left untouched.
+        assert_eq!(msg.as_bytes(), b"\x00raw");
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn test_failed_send_not_counted() {
+        // Without peers, the message cannot be queued.
+        let scatter = ScatterBuilder::new()
+            .compression(Compression::new(Algorithm::Lz4))
+            .build()
+            .unwrap();
+
+        let err = scatter.try_send(telemetry().as_str()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WouldBlock);
+        assert_eq!(err.get().unwrap().to_str().unwrap(), telemetry());
+        assert_eq!(scatter.compression_stats(), CompressionStats::default());
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn test_decode_errors() {
+        let mut compression = Compression::new(Algorithm::Lz4);
+        compression.set_max_size(Some(1024));
+        let codec = Codec::new(compression).unwrap();
+
+        let cases: &[(&[u8], &str)] = &[
+            (b"", "truncated compressed frame"),
+            (
+                b"\x02\x00\x00\x00\x01\x00",
+                "peer compression does not match",
+            ),
+            (
+                b"\x81\x00\x00\x00\x01\x00",
+                "peer compression does not match",
+            ),
+            (b"\x01\x00\x00", "truncated compressed frame"),
+            (
+                b"\x01\xFF\xFF\xFF\xFF\x00",
+                "decompressed size exceeds limit",
+            ),
+            (b"\x01\x00\x00\x00\x10\xFF", "corrupted compressed frame"),
+        ];
+
+        for (bytes, msg) in cases {
+            let err = codec.decode(&Msg::from(*bytes)).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput(msg));
+        }
+    }
+
+    #[test]
+    fn test_ser_de() {
+        let mut compression = Compression::new(Algorithm::Zstd);
+        compression.set_threshold(Some(64));
+        compression.set_dictionary(Some(&b"dict"[..]));
+
+        let mut config = GatherConfig::new();
+        config.set_compression(Some(compression));
+
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        let de: GatherConfig = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(config, de);
+    }
+
+    #[test]
+    #[cfg(not(feature = "zstd"))]
+    fn test_algorithm_unsupported() {
+        let err = ScatterBuilder::new()
+            .compression(Compression::new(Algorithm::Zstd))
+            .build()
+            .unwrap_err();
+
+        match err.kind() {
+            ErrorKind::Unsupported(_) => (),
+            _ => panic!("unexpected error kind: {}", err),
+        }
+    }
+}
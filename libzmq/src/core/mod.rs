@@ -41,18 +41,29 @@ mod private {
     impl Sealed for GatherConfig {}
     impl Sealed for GatherBuilder {}
     impl Sealed for SocketType {}
+    impl<T: Sealed> Sealed for &T {}
 
     // Pub crate
     use crate::old::OldSocket;
     impl Sealed for OldSocket {}
 }
 
-use crate::{addr::Endpoint, auth::*, Error, ErrorKind};
+use crate::{
+    addr::Endpoint,
+    auth::*,
+    capture::Capture,
+    compress::{self, Codec, Compression, CompressionStats},
+    utils::capabilities,
+    Error, ErrorKind,
+};
 
 use humantime_serde::Serde;
 use serde::{Deserialize, Serialize};
 
-use std::{sync::MutexGuard, time::Duration};
+use std::{
+    sync::{Arc, MutexGuard},
+    time::Duration,
+};
 
 const DEFAULT_HWM: i32 = 1000;
 const DEFAULT_BATCH_SIZE: i32 = 8192;
@@ -197,7 +208,8 @@ impl From<Period> for Serde<Option<Duration>> {
 /// Methods shared by all thread-safe sockets.
 ///
 /// Here is the list of socket option that differs from the ØMQ defaults:
-/// * All sockets have their linger period set to zero (`ZMQ_BLOCKY`).
+/// * All sockets have their linger period set to zero by default
+/// (`ZMQ_BLOCKY`).
 /// * All sockets have IPV6 enabled (`ZMQ_IPV6`).
 /// * All sockets have `ZMQ_ZAP_ENFORCE_DOMAIN` set to true.
 /// * All sockets have `ZMQ_ZAP_DOMAIN` hardcoded to "global".
@@ -391,6 +403,45 @@ pub trait Socket: GetRawSocket {
         }
     }
 
+    /// Returns the linger period of the socket.
+    ///
+    /// The linger period determines how long pending outgoing messages
+    /// are kept in memory after the socket is dropped.
+    ///
+    /// # Default
+    /// `Finite(Duration::from_secs(0))`
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> Result<(), anyhow::Error> {
+    /// use libzmq::{prelude::*, Client, Period};
+    /// use std::time::Duration;
+    ///
+    /// let client = Client::new()?;
+    /// assert_eq!(client.linger()?, Period::Finite(Duration::from_secs(0)));
+    ///
+    /// client.set_linger(Some(Duration::from_millis(100)))?;
+    /// assert_eq!(client.linger()?, Period::Finite(Duration::from_millis(100)));
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    fn linger(&self) -> Result<Period, Error> {
+        self.raw_socket().linger()
+    }
+
+    /// Sets the linger period of the socket.
+    ///
+    /// If the period is `Infinite`, the pending outgoing messages will be
+    /// kept until they are sent, which will block the termination of the
+    /// context.
+    fn set_linger<P>(&self, period: P) -> Result<(), Error>
+    where
+        P: Into<Period>,
+    {
+        self.raw_socket().set_linger(period.into())
+    }
+
     /// Returns the socket's [`Mechanism`].
     ///
     /// # Example
@@ -415,7 +466,8 @@ pub trait Socket: GetRawSocket {
     /// # Feature Flags
     ///
     /// Note that `Mechanism::CurveClient` and `Mechanism::CurveServer` require
-    /// the feature flag "curve" to be enabled, and will panic if used otherwise.
+    /// the feature flag "curve" to be enabled, as well as `CURVE` support in the
+    /// linked ØMQ library, and will return [`Unsupported`] otherwise.
     ///
     /// # Example
     /// ```
@@ -446,6 +498,7 @@ pub trait Socket: GetRawSocket {
     /// ```
     ///
     /// [`Mechanism`]: ../auth/enum.Mechanism.html
+    /// [`Unsupported`]: ../enum.ErrorKind.html#variant.Unsupported
     fn set_mechanism<M>(&self, mechanism: M) -> Result<(), Error>
     where
         M: Into<Mechanism>,
@@ -456,6 +509,77 @@ pub trait Socket: GetRawSocket {
 
         set_mechanism(raw_socket, mechanism, mutex)
     }
+
+    /// Returns the socket's [`Compression`], if any.
+    ///
+    /// [`Compression`]: ../compress/struct.Compression.html
+    fn compression(&self) -> Option<Compression> {
+        self.raw_socket()
+            .codec()
+            .map(|codec| codec.config().clone())
+    }
+
+    /// Sets the socket's [`Compression`].
+    ///
+    /// The compression is applied to subsequently sent and received
+    /// messages. The peers of the socket must use the same compression.
+    /// Setting the compression resets the [`compression_stats`].
+    ///
+    /// Once enabled, the compression is advertised in the handshake of the
+    /// subsequent connections and cannot be retracted. Therefore it should
+    /// be set before connecting or binding the socket.
+    ///
+    /// # Returned Error Variants
+    /// * [`Unsupported`] (if the algorithm's feature flag is not enabled)
+    /// * [`InvalidInput`] (if the level or dictionary is invalid)
+    ///
+    /// [`Compression`]: ../compress/struct.Compression.html
+    /// [`compression_stats`]: #method.compression_stats
+    /// [`Unsupported`]: ../enum.ErrorKind.html#variant.Unsupported
+    /// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
+    fn set_compression(&self, maybe: Option<Compression>) -> Result<(), Error> {
+        let raw_socket = self.raw_socket();
+        let codec = match maybe {
+            Some(compression) => {
+                let codec = Codec::new(compression)?;
+                // Adding the same property twice is a no-op.
+                raw_socket.add_metadata(compress::METADATA)?;
+                Some(Arc::new(codec))
+            }
+            None => None,
+        };
+        raw_socket.set_codec(codec);
+        Ok(())
+    }
+
+    /// Returns the [`CompressionStats`] of the socket.
+    ///
+    /// If the socket has no compression, the statistics are empty.
+    ///
+    /// [`CompressionStats`]: ../compress/struct.CompressionStats.html
+    fn compression_stats(&self) -> CompressionStats {
+        self.raw_socket()
+            .codec()
+            .map(|codec| codec.stats())
+            .unwrap_or_default()
+    }
+
+    /// Returns the socket's [`Capture`], if any.
+    ///
+    /// [`Capture`]: ../capture/struct.Capture.html
+    fn capture(&self) -> Option<Capture> {
+        self.raw_socket().capture()
+    }
+
+    /// Sets the socket's [`Capture`].
+    ///
+    /// Every message subsequently sent or received by the socket is
+    /// recorded into the capture.
+    ///
+    /// [`Capture`]: ../capture/struct.Capture.html
+    fn set_capture(&self, maybe: Option<Capture>) {
+        self.raw_socket().set_capture(maybe);
+    }
 }
 
 fn set_mechanism(
@@ -467,6 +591,8 @@ fn set_mechanism(
         return Ok(());
     }
 
+    capabilities().check_mechanism(&mechanism)?;
+
     // Undo the previous mechanism.
     match &*mutex {
         Mechanism::Null => (),
@@ -551,6 +677,7 @@ pub struct SocketConfig {
     pub(crate) connect: Option<Vec<Endpoint>>,
     pub(crate) bind: Option<Vec<Endpoint>>,
     pub(crate) mechanism: Option<Mechanism>,
+    pub(crate) compression: Option<Compression>,
 }
 
 impl SocketConfig {
@@ -558,6 +685,9 @@ impl SocketConfig {
         if let Some(ref mechanism) = self.mechanism {
             socket.set_mechanism(mechanism)?;
         }
+        if let Some(ref compression) = self.compression {
+            socket.set_compression(Some(compression.clone()))?;
+        }
         // We connect as the last step because some socket options
         // only affect subsequent connections.
         if let Some(ref endpoints) = self.connect {
@@ -628,6 +758,14 @@ pub trait ConfigureSocket: GetSocketConfig {
     fn set_mechanism(&mut self, maybe: Option<Mechanism>) {
         self.socket_config_mut().mechanism = maybe;
     }
+
+    fn compression(&self) -> Option<&Compression> {
+        self.socket_config().compression.as_ref()
+    }
+
+    fn set_compression(&mut self, maybe: Option<Compression>) {
+        self.socket_config_mut().compression = maybe;
+    }
 }
 
 impl ConfigureSocket for SocketConfig {}
@@ -660,6 +798,11 @@ pub trait BuildSocket: GetSocketConfig + Sized {
             .set_mechanism(Some(mechanism.into()));
         self
     }
+
+    fn compression(&mut self, compression: Compression) -> &mut Self {
+        self.socket_config_mut().set_compression(Some(compression));
+        self
+    }
 }
 
 #[cfg(test)]
@@ -751,4 +894,24 @@ mod test {
             _ => panic!(),
         }
     }
+
+    #[test]
+    #[cfg(not(feature = "curve"))]
+    fn test_curve_unsupported() {
+        use crate::{auth::*, prelude::*, *};
+
+        let server = Server::new().unwrap();
+        // We can't generate a certificate without CURVE support.
+        let secret =
+            CurveSecretKey::new("JTKVSB%%)wK0E.X)V>+}o?pNmC{O&4W4b!Ni{Lh6")
+                .unwrap();
+        let creds = CurveServerCreds::new(secret);
+
+        let err = server.set_mechanism(creds).unwrap_err();
+        match err.kind() {
+            ErrorKind::Unsupported(_) => (),
+            _ => panic!("unexpected error: {}", err),
+        }
+        assert_eq!(server.mechanism(), Mechanism::Null);
+    }
 }
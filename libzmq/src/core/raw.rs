@@ -1,9 +1,14 @@
 use crate::{
     addr::Endpoint,
     auth::*,
+    capture::{Capture, SocketKind},
+    compress::Codec,
     core::sockopt::*,
     core::{Heartbeat, Period},
+    ctx::CtxState,
     error::*,
+    socket::PeerTracker,
+    utils::capabilities,
     Ctx, CtxHandle,
 };
 
@@ -14,8 +19,13 @@ use log::error;
 
 use std::{
     ffi::CString,
+    ops::Deref,
     os::raw::{c_int, c_void},
-    sync::Mutex,
+    ptr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
     time::Duration,
 };
 
@@ -26,6 +36,13 @@ pub trait GetRawSocket: super::private::Sealed {
     fn raw_socket(&self) -> &RawSocket;
 }
 
+impl<T: GetRawSocket> GetRawSocket for &T {
+    fn raw_socket(&self) -> &RawSocket {
+        (**self).raw_socket()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub(crate) enum RawSocketType {
     Client = sys::ZMQ_CLIENT as isize,
     Server = sys::ZMQ_SERVER as isize,
@@ -56,6 +73,20 @@ impl From<RawSocketType> for c_int {
     }
 }
 
+impl RawSocketType {
+    fn is_thread_safe(&self) -> bool {
+        matches!(
+            self,
+            RawSocketType::Client
+                | RawSocketType::Server
+                | RawSocketType::Radio
+                | RawSocketType::Dish
+                | RawSocketType::Scatter
+                | RawSocketType::Gather
+        )
+    }
+}
+
 fn connect(socket_ptr: *mut c_void, c_string: CString) -> Result<(), Error> {
     let rc = unsafe { sys::zmq_connect(socket_ptr, c_string.as_ptr()) };
 
@@ -74,7 +105,7 @@ fn connect(socket_ptr: *mut c_void, c_string: CString) -> Result<(), Error> {
             errno::ETERM => Error::new(ErrorKind::InvalidCtx),
             errno::ENOTSOCK => panic!("invalid socket"),
             errno::EMTHREAD => panic!("no i/o thread available"),
-            _ => panic!(msg_from_errno(errno)),
+            _ => Error::from_errno(errno),
         };
 
         Err(err)
@@ -104,7 +135,7 @@ fn bind(socket_ptr: *mut c_void, c_string: CString) -> Result<(), Error> {
             errno::ETERM => Error::new(ErrorKind::InvalidCtx),
             errno::ENOTSOCK => panic!("invalid socket"),
             errno::EMTHREAD => panic!("no i/o thread available"),
-            _ => panic!(msg_from_errno(errno)),
+            _ => Error::from_errno(errno),
         };
 
         Err(err)
@@ -127,7 +158,32 @@ fn disconnect(socket_ptr: *mut c_void, c_string: CString) -> Result<(), Error> {
             errno::ENOENT => {
                 Error::new(ErrorKind::NotFound("endpoint was not in use"))
             }
-            _ => panic!(msg_from_errno(errno)),
+            _ => Error::from_errno(errno),
+        };
+
+        Err(err)
+    } else {
+        Ok(())
+    }
+}
+
+fn monitor(
+    socket_ptr: *mut c_void,
+    c_string: Option<CString>,
+    events: c_int,
+) -> Result<(), Error> {
+    let addr_ptr = c_string.as_ref().map_or(ptr::null(), |s| s.as_ptr());
+    let rc = unsafe { sys::zmq_socket_monitor(socket_ptr, addr_ptr, events) };
+
+    if rc == -1 {
+        let errno = unsafe { sys::zmq_errno() };
+        let err = match errno {
+            errno::EPROTONOSUPPORT => {
+                Error::new(ErrorKind::InvalidInput("transport not supported"))
+            }
+            errno::ETERM => Error::new(ErrorKind::InvalidCtx),
+            errno::ENOTSOCK => panic!("invalid socket"),
+            _ => Error::from_errno(errno),
         };
 
         Err(err)
@@ -150,7 +206,7 @@ fn unbind(socket_ptr: *mut c_void, c_string: CString) -> Result<(), Error> {
             errno::ENOENT => {
                 Error::new(ErrorKind::NotFound("endpoint was not bound to"))
             }
-            _ => panic!(msg_from_errno(errno)),
+            _ => Error::from_errno(errno),
         };
 
         Err(err)
@@ -159,9 +215,38 @@ fn unbind(socket_ptr: *mut c_void, c_string: CString) -> Result<(), Error> {
     }
 }
 
-fn assert_curve_enabled() {
-    if cfg!(not(feature = "curve")) {
-        panic!("CURVE support requires enabling feature flag 'curve'");
+/// An optional extension of a socket that is consulted for every message.
+///
+/// The flag spares the message path the lock while the extension is unset,
+/// which is the common case.
+#[derive(Debug)]
+pub(crate) struct Hook<T> {
+    is_set: AtomicBool,
+    inner: Mutex<Option<T>>,
+}
+
+impl<T: Clone> Hook<T> {
+    pub(crate) fn get(&self) -> Option<T> {
+        if self.is_set.load(Ordering::Acquire) {
+            self.inner.lock().unwrap().clone()
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn set(&self, maybe: Option<T>) {
+        let mut inner = self.inner.lock().unwrap();
+        self.is_set.store(maybe.is_some(), Ordering::Release);
+        *inner = maybe;
+    }
+}
+
+impl<T> Default for Hook<T> {
+    fn default() -> Self {
+        Self {
+            is_set: AtomicBool::new(false),
+            inner: Mutex::new(None),
+        }
     }
 }
 
@@ -174,18 +259,20 @@ pub struct RawSocket {
     ctx: CtxHandle,
     mechanism: Mutex<Mechanism>,
     heartbeat: Mutex<Option<Heartbeat>>,
+    compression: Hook<Arc<Codec>>,
+    capture: Hook<Capture>,
+    peers: Hook<Arc<PeerTracker>>,
+    sock_type: RawSocketType,
+    // Only thread-safe sockets are tracked by their context.
+    ctx_state: Option<Arc<CtxState>>,
 }
 
 impl RawSocket {
-    pub(crate) fn new(sock_type: RawSocketType) -> Result<Self, Error> {
-        let handle = Ctx::global();
-        Self::with_ctx(sock_type, handle)
-    }
-
     pub(crate) fn with_ctx(
         sock_type: RawSocketType,
         ctx: CtxHandle,
     ) -> Result<Self, Error> {
+        let is_thread_safe = sock_type.is_thread_safe();
         let socket_mut_ptr =
             unsafe { sys::zmq_socket(ctx.as_ptr(), sock_type.into()) };
 
@@ -198,7 +285,7 @@ impl RawSocket {
                 errno::EMFILE => Error::new(ErrorKind::SocketLimit),
                 // The context associated with the handle is being terminated.
                 errno::ETERM => Error::new(ErrorKind::InvalidCtx),
-                _ => panic!(msg_from_errno(errno)),
+                _ => Error::from_errno(errno),
             };
 
             Err(err)
@@ -214,25 +301,58 @@ impl RawSocket {
                 Some("global"),
             )?;
 
+            let ctx_state = if is_thread_safe {
+                // This is safe since the socket is open within the context.
+                unsafe { ctx.state() }
+            } else {
+                None
+            };
+
+            if let Some(state) = &ctx_state {
+                state.register(socket_mut_ptr);
+            }
+
             Ok(Self {
                 ctx,
                 socket_mut_ptr,
                 mechanism: Mutex::default(),
                 heartbeat: Mutex::default(),
+                compression: Hook::default(),
+                capture: Hook::default(),
+                peers: Hook::default(),
+                sock_type,
+                ctx_state,
             })
         }
     }
 
     pub(crate) fn connect(&self, endpoint: &Endpoint) -> Result<(), Error> {
+        capabilities().check_endpoint(endpoint)?;
         let c_string = CString::new(endpoint.to_zmq()).unwrap();
         connect(self.as_mut_ptr(), c_string)
     }
 
     pub(crate) fn bind(&self, endpoint: &Endpoint) -> Result<(), Error> {
+        capabilities().check_endpoint(endpoint)?;
         let c_string = CString::new(endpoint.to_zmq()).unwrap();
         bind(self.as_mut_ptr(), c_string)
     }
 
+    /// Publishes the given `events` of the socket to the `Pair` socket
+    /// connected to the `inproc` endpoint, or stops publishing if `None`.
+    ///
+    /// See [`zmq_socket_monitor`].
+    ///
+    /// [`zmq_socket_monitor`]: http://api.zeromq.org/master:zmq-socket-monitor
+    pub(crate) fn monitor(
+        &self,
+        endpoint: Option<&Endpoint>,
+        events: i32,
+    ) -> Result<(), Error> {
+        let c_string = endpoint.map(|e| CString::new(e.to_zmq()).unwrap());
+        monitor(self.as_mut_ptr(), c_string, events)
+    }
+
     pub(crate) fn disconnect(&self, endpoint: &Endpoint) -> Result<(), Error> {
         let c_string = CString::new(endpoint.to_zmq()).unwrap();
         disconnect(self.as_mut_ptr(), c_string)
@@ -260,6 +380,71 @@ impl RawSocket {
         &self.heartbeat
     }
 
+    /// Returns the codec used to compress messages, if any.
+    pub(crate) fn codec(&self) -> Option<Arc<Codec>> {
+        self.compression.get()
+    }
+
+    pub(crate) fn set_codec(&self, maybe: Option<Arc<Codec>>) {
+        self.compression.set(maybe);
+    }
+
+    pub(crate) fn capture(&self) -> Option<Capture> {
+        self.capture.get()
+    }
+
+    pub(crate) fn set_capture(&self, maybe: Option<Capture>) {
+        self.capture.set(maybe);
+    }
+
+    /// Returns the capture of the socket along with the socket type to
+    /// record, if any.
+    pub(crate) fn capturing(&self) -> Option<(Capture, SocketKind)> {
+        let capture = self.capture.get()?;
+        SocketKind::from_raw(self.sock_type).map(|kind| (capture, kind))
+    }
+
+    pub(crate) fn sock_type(&self) -> RawSocketType {
+        self.sock_type
+    }
+
+    /// Returns the tracker of the connected peers, if any.
+    pub(crate) fn peer_tracker(&self) -> Option<Arc<PeerTracker>> {
+        self.peers.get()
+    }
+
+    pub(crate) fn set_peer_tracker(&self, maybe: Option<Arc<PeerTracker>>) {
+        self.peers.set(maybe);
+    }
+
+    /// Returns `true` if the context of the socket is being gracefully
+    /// shutdown.
+    pub(crate) fn is_draining(&self) -> bool {
+        self.ctx_state
+            .as_ref()
+            .map(|state| state.is_draining())
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn linger(&self) -> Result<Period, Error> {
+        let maybe = getsockopt_option_duration(
+            self.as_mut_ptr(),
+            SocketOption::Linger,
+            -1,
+        )?;
+
+        Ok(maybe.into())
+    }
+
+    pub(crate) fn set_linger(&self, period: Period) -> Result<(), Error> {
+        setsockopt_option_duration(
+            self.as_mut_ptr(),
+            SocketOption::Linger,
+            period.into(),
+            -1,
+        )
+    }
+
     pub(crate) fn last_endpoint(&self) -> Result<Option<Endpoint>, Error> {
         let maybe =
             getsockopt_string(self.as_mut_ptr(), SocketOption::LastEndpoint)?;
@@ -324,6 +509,16 @@ impl RawSocket {
         setsockopt_bool(self.as_mut_ptr(), SocketOption::PlainServer, cond)
     }
 
+    /// Adds a `X-<name>:<value>` property to the metadata sent in the
+    /// handshake of subsequent connections.
+    pub(crate) fn add_metadata(&self, property: &str) -> Result<(), Error> {
+        setsockopt_str(
+            self.as_mut_ptr(),
+            SocketOption::Metadata,
+            Some(property),
+        )
+    }
+
     pub(crate) fn recv_hwm(&self) -> Result<i32, Error> {
         getsockopt_scalar(self.as_mut_ptr(), SocketOption::RecvHighWaterMark)
     }
@@ -406,7 +601,7 @@ impl RawSocket {
         &self,
         key: Option<&BinCurveKey>,
     ) -> Result<(), Error> {
-        assert_curve_enabled();
+        capabilities().check_curve()?;
         let key = key.map(BinCurveKey::as_bytes);
         setsockopt_bytes(self.as_mut_ptr(), SocketOption::CurvePublicKey, key)
     }
@@ -415,13 +610,13 @@ impl RawSocket {
         &self,
         key: Option<&BinCurveKey>,
     ) -> Result<(), Error> {
-        assert_curve_enabled();
+        capabilities().check_curve()?;
         let key = key.map(BinCurveKey::as_bytes);
         setsockopt_bytes(self.as_mut_ptr(), SocketOption::CurveSecretKey, key)
     }
 
     pub(crate) fn set_curve_server(&self, enabled: bool) -> Result<(), Error> {
-        assert_curve_enabled();
+        capabilities().check_curve()?;
         setsockopt_bool(self.as_mut_ptr(), SocketOption::CurveServer, enabled)
     }
 
@@ -429,7 +624,7 @@ impl RawSocket {
         &self,
         key: Option<&BinCurveKey>,
     ) -> Result<(), Error> {
-        assert_curve_enabled();
+        capabilities().check_curve()?;
         let key = key.map(BinCurveKey::as_bytes);
         setsockopt_bytes(self.as_mut_ptr(), SocketOption::CurveServerKey, key)
     }
@@ -443,6 +638,37 @@ impl PartialEq for RawSocket {
 
 impl Eq for RawSocket {}
 
+/// A `RawSocket` of a thread-safe type, which can thus be shared between
+/// threads.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ThreadSafeSocket(RawSocket);
+
+impl ThreadSafeSocket {
+    pub(crate) fn new(sock_type: RawSocketType) -> Result<Self, Error> {
+        let handle = Ctx::global();
+        Self::with_ctx(sock_type, handle)
+    }
+
+    pub(crate) fn with_ctx(
+        sock_type: RawSocketType,
+        ctx: CtxHandle,
+    ) -> Result<Self, Error> {
+        assert!(sock_type.is_thread_safe(), "socket type is not thread-safe");
+        RawSocket::with_ctx(sock_type, ctx).map(Self)
+    }
+}
+
+impl Deref for ThreadSafeSocket {
+    type Target = RawSocket;
+
+    fn deref(&self) -> &RawSocket {
+        &self.0
+    }
+}
+
+unsafe impl Send for ThreadSafeSocket {}
+unsafe impl Sync for ThreadSafeSocket {}
+
 impl Drop for RawSocket {
     /// Close the ØMQ socket.
     ///
@@ -450,11 +676,18 @@ impl Drop for RawSocket {
     ///
     /// [`zmq_close`]: http://api.zeromq.org/master:zmq-close
     fn drop(&mut self) {
+        if let Some(state) = &self.ctx_state {
+            state.unregister(self.socket_mut_ptr);
+        }
+
         let rc = unsafe { sys::zmq_close(self.socket_mut_ptr) };
 
         if rc == -1 {
             let errno = unsafe { sys::zmq_errno() };
-            error!("error while dropping socket: {}", msg_from_errno(errno));
+            error!(
+                "error while dropping socket: {}",
+                ErrorKind::unexpected(errno)
+            );
         }
     }
 }
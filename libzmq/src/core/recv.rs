@@ -1,6 +1,8 @@
 use crate::{
+    capture::{CaptureRecord, Direction},
     core::{raw::GetRawSocket, *},
-    error::{msg_from_errno, Error, ErrorKind},
+    envelope::Envelope,
+    error::{Error, ErrorKind},
     msg::Msg,
 };
 use libzmq_sys as sys;
@@ -12,6 +14,29 @@ use std::{
 };
 
 fn recv(
+    raw_socket: &RawSocket,
+    msg: &mut Msg,
+    no_block: bool,
+) -> Result<(), Error> {
+    recv_raw(raw_socket.as_mut_ptr(), msg, no_block)?;
+
+    // The connection of the message is only known before decoding.
+    if let Some(tracker) = raw_socket.peer_tracker() {
+        tracker.observe(msg);
+    }
+
+    if let Some(codec) = raw_socket.codec() {
+        // On failure, the frame is left in the message.
+        *msg = codec.decode(msg)?;
+    }
+
+    if let Some((capture, kind)) = raw_socket.capturing() {
+        capture.record(&CaptureRecord::new(msg, kind, Direction::Recv));
+    }
+    Ok(())
+}
+
+fn recv_raw(
     socket_ptr: *mut c_void,
     msg: &mut Msg,
     no_block: bool,
@@ -22,25 +47,27 @@ fn recv(
 
     if rc == -1 {
         let errno = unsafe { sys::zmq_errno() };
-        let err = match errno {
-            errno::EAGAIN => Error::new(ErrorKind::WouldBlock),
-            errno::ENOTSUP => panic!("recv not supported by socket type"),
-            errno::EFSM => {
-                panic!("operation cannot be completed in current socket state")
-            }
-            errno::ETERM => Error::new(ErrorKind::InvalidCtx),
-            errno::ENOTSOCK => panic!("invalid socket"),
-            errno::EINTR => Error::new(ErrorKind::Interrupted),
-            errno::EFAULT => panic!("invalid message"),
-            _ => panic!(msg_from_errno(errno)),
-        };
-
-        Err(err)
+        Err(recv_error(errno))
     } else {
         Ok(())
     }
 }
 
+fn recv_error(errno: c_int) -> Error {
+    match errno {
+        errno::EAGAIN => Error::new(ErrorKind::WouldBlock),
+        errno::ENOTSUP => panic!("recv not supported by socket type"),
+        errno::EFSM => {
+            panic!("operation cannot be completed in current socket state")
+        }
+        errno::ETERM => Error::new(ErrorKind::InvalidCtx),
+        errno::ENOTSOCK => panic!("invalid socket"),
+        errno::EINTR => Error::new(ErrorKind::Interrupted),
+        errno::EFAULT => panic!("invalid message"),
+        _ => Error::from_errno(errno),
+    }
+}
+
 /// Receive atomic messages in an immutable, thread-safe fashion.
 ///
 /// Does not support multipart messages.
@@ -57,12 +84,15 @@ pub trait RecvMsg: GetRawSocket {
     /// * [`WouldBlock`] (if `recv_timeout` expires)
     /// * [`InvalidCtx`]
     /// * [`Interrupted`]
+    /// * [`InvalidInput`] (if the socket has compression enabled and the
+    ///   received frame is invalid, in which case it is left in `msg`)
     ///
     /// [`WouldBlock`]: ../enum.ErrorKind.html#variant.WouldBlock
     /// [`InvalidCtx`]: ../enum.ErrorKind.html#variant.InvalidCtx
     /// [`Interrupted`]: ../enum.ErrorKind.html#variant.Interrupted
+    /// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
     fn recv(&self, msg: &mut Msg) -> Result<(), Error> {
-        recv(self.raw_socket().as_mut_ptr(), msg, false)
+        recv(self.raw_socket(), msg, false)
     }
 
     /// Try to retrieve a message from the inbound socket queue without blocking.
@@ -83,7 +113,7 @@ pub trait RecvMsg: GetRawSocket {
     /// [`InvalidCtx`]: ../enum.ErrorKind.html#variant.InvalidCtx
     /// [`Interrupted`]: ../enum.ErrorKind.html#variant.Interrupted
     fn try_recv(&self, msg: &mut Msg) -> Result<(), Error> {
-        recv(self.raw_socket().as_mut_ptr(), msg, true)
+        recv(self.raw_socket(), msg, true)
     }
 
     /// A convenience function that allocates a [`Msg`] with the same properties
@@ -110,6 +140,47 @@ pub trait RecvMsg: GetRawSocket {
         Ok(msg)
     }
 
+    /// A convenience function that receives a [`Msg`] with the same
+    /// properties as [`recv`] and decodes it as an [`Envelope`].
+    ///
+    /// # Error
+    /// If the message is not a valid [`Envelope`], it is returned as the
+    /// content of the `Error`.
+    ///
+    /// ## Possible Error Variants
+    /// * [`WouldBlock`] (if `recv_timeout` expires)
+    /// * [`InvalidCtx`]
+    /// * [`Interrupted`]
+    /// * [`InvalidInput`] (if the message is not a valid envelope)
+    /// * [`Unsupported`] (if the envelope has an unknown version)
+    ///
+    /// [`recv`]: #method.recv
+    /// [`Msg`]: ../msg/struct.Msg.html
+    /// [`Envelope`]: ../envelope/struct.Envelope.html
+    /// [`WouldBlock`]: ../enum.ErrorKind.html#variant.WouldBlock
+    /// [`InvalidCtx`]: ../enum.ErrorKind.html#variant.InvalidCtx
+    /// [`Interrupted`]: ../enum.ErrorKind.html#variant.Interrupted
+    /// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
+    /// [`Unsupported`]: ../enum.ErrorKind.html#variant.Unsupported
+    fn recv_envelope(&self) -> Result<Envelope, Error<Msg>> {
+        let msg = self.recv_msg().map_err(Error::cast)?;
+        Envelope::decode(msg)
+    }
+
+    /// A convenience function that receives a [`Msg`] with the same
+    /// properties as [`try_recv`] and decodes it as an [`Envelope`].
+    ///
+    /// See [`recv_envelope`].
+    ///
+    /// [`try_recv`]: #method.try_recv
+    /// [`recv_envelope`]: #method.recv_envelope
+    /// [`Msg`]: ../msg/struct.Msg.html
+    /// [`Envelope`]: ../envelope/struct.Envelope.html
+    fn try_recv_envelope(&self) -> Result<Envelope, Error<Msg>> {
+        let msg = self.try_recv_msg().map_err(Error::cast)?;
+        Envelope::decode(msg)
+    }
+
     /// The high water mark for incoming messages on the specified socket.
     ///
     /// The high water mark is a hard limit on the maximum number of
@@ -182,6 +253,8 @@ pub trait RecvMsg: GetRawSocket {
     }
 }
 
+impl<T: RecvMsg> RecvMsg for &T {}
+
 #[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 #[doc(hidden)]
 pub struct RecvConfig {
@@ -236,3 +309,14 @@ pub trait BuildRecv: GetRecvConfig {
         self
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_recv_unexpected_error() {
+        let err = recv_error(libc::EIO);
+        assert_eq!(err.kind(), ErrorKind::unexpected(libc::EIO));
+    }
+}
@@ -1,52 +1,94 @@
 use crate::{
+    capture::{CaptureRecord, Direction},
     core::*,
-    error::{msg_from_errno, Error, ErrorKind},
+    error::{Error, ErrorKind},
     msg::Msg,
 };
 use libzmq_sys as sys;
 use sys::errno;
 
-use std::{
-    os::raw::{c_int, c_void},
-    time::Duration,
-};
+use std::{os::raw::c_int, time::Duration};
 
 fn send(
-    socket_ptr: *mut c_void,
+    raw_socket: &RawSocket,
+    msg: Msg,
+    no_block: bool,
+) -> Result<(), Error<Msg>> {
+    // The context no longer accepts new messages.
+    if raw_socket.is_draining() {
+        return Err(Error::with_content(ErrorKind::InvalidCtx, msg));
+    }
+
+    let capturing = raw_socket.capturing();
+    let record = capturing
+        .as_ref()
+        .map(|(_, kind)| CaptureRecord::new(&msg, *kind, Direction::Send));
+
+    let result = match raw_socket.codec() {
+        Some(codec) => {
+            let frame = codec.encode(&msg);
+            let frame_len = frame.len();
+            match send_raw(raw_socket, frame, no_block) {
+                Ok(()) => {
+                    codec.sent(msg.len(), frame_len);
+                    Ok(())
+                }
+                // On failure, we return the original message instead of the
+                // frame.
+                Err(err) => Err(Error::with_content(err.kind(), msg)),
+            }
+        }
+        None => send_raw(raw_socket, msg, no_block),
+    };
+
+    // Only messages that were actually queued are recorded.
+    if let (Ok(()), Some((capture, _)), Some(record)) =
+        (&result, capturing, record)
+    {
+        capture.record(&record);
+    }
+    result
+}
+
+fn send_raw(
+    raw_socket: &RawSocket,
     mut msg: Msg,
     no_block: bool,
 ) -> Result<(), Error<Msg>> {
+    let socket_ptr = raw_socket.as_mut_ptr();
     let rc = unsafe {
         sys::zmq_msg_send(msg.as_mut_ptr(), socket_ptr, no_block as c_int)
     };
 
     if rc == -1 {
         let errno = unsafe { sys::zmq_errno() };
-        let err = match errno {
-            errno::EAGAIN => Error::with_content(ErrorKind::WouldBlock, msg),
-            errno::ENOTSUP => panic!("send is not supported by socket type"),
-            errno::EINVAL => {
-                panic!("multipart messages are not supported by socket type")
-            }
-            errno::EFSM => {
-                panic!("operation cannot be completed in current socket state")
-            }
-            errno::ETERM => Error::with_content(ErrorKind::InvalidCtx, msg),
-            errno::ENOTSOCK => panic!("invalid socket"),
-            errno::EINTR => Error::with_content(ErrorKind::Interrupted, msg),
-            errno::EFAULT => panic!("invalid message"),
-            errno::EHOSTUNREACH => {
-                Error::with_content(ErrorKind::HostUnreachable, msg)
-            }
-            _ => panic!(msg_from_errno(errno)),
-        };
-
-        Err(err)
+        Err(send_error(errno, msg))
     } else {
         Ok(())
     }
 }
 
+fn send_error(errno: c_int, msg: Msg) -> Error<Msg> {
+    match errno {
+        errno::EAGAIN => Error::with_content(ErrorKind::WouldBlock, msg),
+        errno::ENOTSUP => panic!("send is not supported by socket type"),
+        errno::EINVAL => {
+            panic!("multipart messages are not supported by socket type")
+        }
+        errno::EFSM => {
+            panic!("operation cannot be completed in current socket state")
+        }
+        errno::ETERM => Error::with_content(ErrorKind::InvalidCtx, msg),
+        errno::ENOTSOCK => panic!("invalid socket"),
+        errno::EINTR => Error::with_content(ErrorKind::Interrupted, msg),
+        errno::EFAULT => panic!("invalid message"),
+        errno::EHOSTUNREACH => {
+            Error::with_content(ErrorKind::HostUnreachable, msg)
+        }
+        _ => Error::with_content(ErrorKind::unexpected(errno), msg),
+    }
+}
+
 /// Send messages in a thread-safe fashion.
 ///
 /// Does not support multipart messages.
@@ -81,7 +123,7 @@ pub trait SendMsg: GetRawSocket {
     where
         M: Into<Msg>,
     {
-        send(self.raw_socket().as_mut_ptr(), msg.into(), false)
+        send(self.raw_socket(), msg.into(), false)
     }
 
     /// Try to push a message into the outgoing socket queue without blocking.
@@ -114,7 +156,7 @@ pub trait SendMsg: GetRawSocket {
     where
         M: Into<Msg>,
     {
-        send(self.raw_socket().as_mut_ptr(), msg.into(), true)
+        send(self.raw_socket(), msg.into(), true)
     }
 
     /// The high water mark for outbound messages on the specified socket.
@@ -207,6 +249,8 @@ pub trait SendMsg: GetRawSocket {
     }
 }
 
+impl<T: SendMsg> SendMsg for &T {}
+
 #[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 #[doc(hidden)]
 pub struct SendConfig {
@@ -261,3 +305,16 @@ pub trait BuildSend: GetSendConfig {
         self
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_send_unexpected_error() {
+        let mut err = send_error(libc::EIO, "msg".into());
+        assert_eq!(err.kind(), ErrorKind::unexpected(libc::EIO));
+        // The message is not lost.
+        assert_eq!(err.take().unwrap().to_str().unwrap(), "msg");
+    }
+}
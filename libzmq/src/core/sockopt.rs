@@ -1,4 +1,4 @@
-use crate::error::{msg_from_errno, Error, ErrorKind};
+use crate::error::{Error, ErrorKind};
 use libzmq_sys as sys;
 use sys::errno;
 
@@ -44,6 +44,7 @@ pub(crate) enum SocketOption {
     CurveServerKey = sys::ZMQ_CURVE_SERVERKEY as isize,
     InBatchSize = sys::ZMQ_IN_BATCH_SIZE as isize,
     OutBatchSize = sys::ZMQ_OUT_BATCH_SIZE as isize,
+    Metadata = sys::ZMQ_METADATA as isize,
 }
 
 impl From<SocketOption> for c_int {
@@ -93,6 +94,7 @@ impl From<SocketOption> for c_int {
             }
             SocketOption::InBatchSize => SocketOption::InBatchSize as c_int,
             SocketOption::OutBatchSize => SocketOption::OutBatchSize as c_int,
+            SocketOption::Metadata => SocketOption::Metadata as c_int,
         }
     }
 }
@@ -114,7 +116,7 @@ fn getsockopt(
             errno::ETERM => Error::new(ErrorKind::InvalidCtx),
             errno::ENOTSOCK => panic!("invalid socket"),
             errno::EINTR => Error::new(ErrorKind::Interrupted),
-            _ => panic!(msg_from_errno(errno)),
+            _ => Error::from_errno(errno),
         };
 
         Err(err)
@@ -220,7 +222,7 @@ fn setsockopt(
             errno::ETERM => Error::new(ErrorKind::InvalidCtx),
             errno::ENOTSOCK => panic!("invalid socket"),
             errno::EINTR => Error::new(ErrorKind::Interrupted),
-            _ => panic!(msg_from_errno(errno)),
+            _ => Error::from_errno(errno),
         };
 
         Err(err)
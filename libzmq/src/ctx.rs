@@ -1,17 +1,35 @@
 //! The ØMQ context type.
 
-use crate::{auth::server::AuthServer, error::*};
+use crate::{
+    addr::Endpoint,
+    auth::server::AuthServer,
+    core::{sockopt::*, Period},
+    error::*,
+};
 use libzmq_sys as sys;
 use sys::errno;
 
 use lazy_static::lazy_static;
+use log::error;
 use serde::{Deserialize, Serialize};
 
 use std::{
+    collections::HashSet,
+    mem::ManuallyDrop,
     os::raw::{c_int, c_void},
-    str, thread,
+    ptr, str,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
+// The interval at which a graceful shutdown checks whether the sockets
+// of the context were closed.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
 lazy_static! {
     static ref GLOBAL_CONTEXT: Ctx = Ctx::new();
 }
@@ -25,6 +43,12 @@ enum CtxOption {
     SocketLimit,
     IPV6,
     Blocky,
+    ThreadPriority,
+    ThreadSchedPolicy,
+    ThreadAffinityCpuAdd,
+    ThreadAffinityCpuRemove,
+    ThreadNamePrefix,
+    ZeroCopyRecv,
 }
 
 impl From<CtxOption> for c_int {
@@ -36,6 +60,18 @@ impl From<CtxOption> for c_int {
             CtxOption::SocketLimit => sys::ZMQ_SOCKET_LIMIT as c_int,
             CtxOption::IPV6 => sys::ZMQ_IPV6 as c_int,
             CtxOption::Blocky => sys::ZMQ_BLOCKY as c_int,
+            CtxOption::ThreadPriority => sys::ZMQ_THREAD_PRIORITY as c_int,
+            CtxOption::ThreadSchedPolicy => {
+                sys::ZMQ_THREAD_SCHED_POLICY as c_int
+            }
+            CtxOption::ThreadAffinityCpuAdd => {
+                sys::ZMQ_THREAD_AFFINITY_CPU_ADD as c_int
+            }
+            CtxOption::ThreadAffinityCpuRemove => {
+                sys::ZMQ_THREAD_AFFINITY_CPU_REMOVE as c_int
+            }
+            CtxOption::ThreadNamePrefix => sys::ZMQ_THREAD_NAME_PREFIX as c_int,
+            CtxOption::ZeroCopyRecv => sys::ZMQ_ZERO_COPY_RECV as c_int,
         }
     }
 }
@@ -43,17 +79,24 @@ impl From<CtxOption> for c_int {
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 struct RawCtx {
     ctx: *mut c_void,
+    // The state owned by the `Ctx`, which is null for the handles used
+    // while the context is created.
+    state: *const CtxState,
 }
 
 impl RawCtx {
-    fn new() -> Self {
+    fn new() -> Result<Self, Error> {
         let ctx = unsafe { sys::zmq_ctx_new() };
 
         if ctx.is_null() {
-            panic!(msg_from_errno(unsafe { sys::zmq_errno() }));
+            let errno = unsafe { sys::zmq_errno() };
+            return Err(Error::from_errno(errno));
         }
 
-        Self { ctx }
+        Ok(Self {
+            ctx,
+            state: ptr::null(),
+        })
     }
 
     fn get(self, option: CtxOption) -> i32 {
@@ -69,13 +112,17 @@ impl RawCtx {
                 errno::EINVAL => {
                     Err(Error::new(ErrorKind::InvalidInput("invalid value")))
                 }
-                _ => panic!(msg_from_errno(errno)),
+                _ => Err(Error::from_errno(errno)),
             }
         } else {
             Ok(())
         }
     }
 
+    fn get_bool(self, opt: CtxOption) -> bool {
+        self.get(opt) != 0
+    }
+
     fn set_bool(self, opt: CtxOption, flag: bool) -> Result<(), Error> {
         self.set(opt, flag as i32)
     }
@@ -107,15 +154,121 @@ impl RawCtx {
 unsafe impl Send for RawCtx {}
 unsafe impl Sync for RawCtx {}
 
+/// The state shared between a `Ctx` and the sockets created within it.
+///
+/// Only the thread-safe sockets are tracked since the state might be
+/// accessed from any thread.
+#[derive(Debug, Default)]
+pub(crate) struct CtxState {
+    draining: AtomicBool,
+    // The addresses of the open sockets.
+    sockets: Mutex<HashSet<usize>>,
+}
+
+impl CtxState {
+    pub(crate) fn register(&self, socket_mut_ptr: *mut c_void) {
+        let mut guard = self.sockets.lock().unwrap();
+        guard.insert(socket_mut_ptr as usize);
+    }
+
+    pub(crate) fn unregister(&self, socket_mut_ptr: *mut c_void) {
+        let mut guard = self.sockets.lock().unwrap();
+        guard.remove(&(socket_mut_ptr as usize));
+    }
+
+    /// Returns `true` if the context no longer accepts new messages.
+    pub(crate) fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Acquire)
+    }
+
+    fn start_draining(&self) {
+        self.draining.store(true, Ordering::Release);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.sockets.lock().unwrap().is_empty()
+    }
+
+    fn set_linger(&self, period: Period) {
+        // We hold the lock so that no socket is closed concurrently.
+        let guard = self.sockets.lock().unwrap();
+        for &addr in guard.iter() {
+            let socket_mut_ptr = addr as *mut c_void;
+            if let Err(err) = setsockopt_option_duration(
+                socket_mut_ptr,
+                SocketOption::Linger,
+                period.into(),
+                -1,
+            ) {
+                error!("unable to set socket linger: {}", err);
+            }
+        }
+    }
+
+    fn last_endpoints(&self) -> Vec<Option<Endpoint>> {
+        let guard = self.sockets.lock().unwrap();
+        guard
+            .iter()
+            .map(|&addr| {
+                let socket_mut_ptr = addr as *mut c_void;
+                getsockopt_string(socket_mut_ptr, SocketOption::LastEndpoint)
+                    .ok()
+                    .and_then(|maybe| maybe)
+                    .map(|s| Endpoint::from_zmq(s.as_str()))
+            })
+            .collect()
+    }
+}
+
+/// The sockets that were not dropped by their owner before the deadline
+/// of a [`shutdown_graceful`].
+///
+/// Only the thread-safe sockets are tracked. Since ØMQ does not expose the
+/// outgoing queue of a socket, this does not tell whether the messages of
+/// the dropped sockets were delivered.
+///
+/// [`shutdown_graceful`]: struct.Ctx.html#method.shutdown_graceful
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UnclosedSockets {
+    endpoints: Vec<Option<Endpoint>>,
+}
+
+impl UnclosedSockets {
+    /// Returns `true` if all the sockets were dropped before the deadline.
+    pub fn is_empty(&self) -> bool {
+        self.endpoints.is_empty()
+    }
+
+    /// Returns the number of sockets that were not dropped.
+    pub fn len(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    /// Returns the last endpoint of each socket that was not dropped.
+    ///
+    /// The endpoint is `None` if the socket was never bound nor connected.
+    pub fn endpoints(&self) -> &[Option<Endpoint>] {
+        &self.endpoints
+    }
+}
+
 /// A config for a [`Ctx`].
 ///
 /// Usefull in configuration files.
 ///
 /// [`Ctx`]: struct.Ctx.html
-#[derive(Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(
+    Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
 pub struct CtxConfig {
     io_threads: Option<i32>,
     max_sockets: Option<i32>,
+    max_msg_size: Option<i32>,
+    thread_priority: Option<i32>,
+    thread_sched_policy: Option<i32>,
+    thread_affinity: Option<Vec<i32>>,
+    thread_name_prefix: Option<i32>,
+    zero_copy_recv: Option<bool>,
 }
 
 impl CtxConfig {
@@ -123,11 +276,15 @@ impl CtxConfig {
         Self::default()
     }
 
+    /// Builds a `Ctx` from the configuration.
+    ///
+    /// As opposed to [`apply`], the configuration is applied before the
+    /// context's I/O threads are started, which is required for the
+    /// thread related options to take effect.
+    ///
+    /// [`apply`]: #method.apply
     pub fn build(&self) -> Result<Ctx, Error> {
-        let ctx = Ctx::new();
-        self.apply(ctx.handle())?;
-
-        Ok(ctx)
+        Ctx::with_config(self)
     }
 
     pub fn apply(&self, handle: CtxHandle) -> Result<(), Error> {
@@ -137,6 +294,26 @@ impl CtxConfig {
         if let Some(value) = self.max_sockets {
             handle.set_max_sockets(value)?;
         }
+        if let Some(value) = self.max_msg_size {
+            handle.set_max_msg_size(value)?;
+        }
+        if let Some(value) = self.thread_priority {
+            handle.set_thread_priority(value)?;
+        }
+        if let Some(value) = self.thread_sched_policy {
+            handle.set_thread_sched_policy(value)?;
+        }
+        if let Some(ref cpus) = self.thread_affinity {
+            for &cpu in cpus {
+                handle.add_thread_affinity_cpu(cpu)?;
+            }
+        }
+        if let Some(value) = self.thread_name_prefix {
+            handle.set_thread_name_prefix(value)?;
+        }
+        if let Some(enabled) = self.zero_copy_recv {
+            handle.set_zero_copy_recv(enabled)?;
+        }
 
         Ok(())
     }
@@ -156,6 +333,57 @@ impl CtxConfig {
     pub fn set_max_sockets(&mut self, value: Option<i32>) {
         self.max_sockets = value;
     }
+
+    pub fn max_msg_size(&self) -> Option<i32> {
+        self.max_msg_size
+    }
+
+    pub fn set_max_msg_size(&mut self, value: Option<i32>) {
+        self.max_msg_size = value;
+    }
+
+    pub fn thread_priority(&self) -> Option<i32> {
+        self.thread_priority
+    }
+
+    pub fn set_thread_priority(&mut self, value: Option<i32>) {
+        self.thread_priority = value;
+    }
+
+    pub fn thread_sched_policy(&self) -> Option<i32> {
+        self.thread_sched_policy
+    }
+
+    pub fn set_thread_sched_policy(&mut self, value: Option<i32>) {
+        self.thread_sched_policy = value;
+    }
+
+    pub fn thread_affinity(&self) -> Option<&[i32]> {
+        self.thread_affinity.as_deref()
+    }
+
+    pub fn set_thread_affinity<I>(&mut self, maybe: Option<I>)
+    where
+        I: IntoIterator<Item = i32>,
+    {
+        self.thread_affinity = maybe.map(|cpus| cpus.into_iter().collect());
+    }
+
+    pub fn thread_name_prefix(&self) -> Option<i32> {
+        self.thread_name_prefix
+    }
+
+    pub fn set_thread_name_prefix(&mut self, value: Option<i32>) {
+        self.thread_name_prefix = value;
+    }
+
+    pub fn zero_copy_recv(&self) -> Option<bool> {
+        self.zero_copy_recv
+    }
+
+    pub fn set_zero_copy_recv(&mut self, value: Option<bool>) {
+        self.zero_copy_recv = value;
+    }
 }
 
 /// A convenience builder for a [`Ctx`].
@@ -163,7 +391,9 @@ impl CtxConfig {
 /// Makes complex context configuration more convenient.
 ///
 /// [`Ctx`]: struct.Ctx.html
-#[derive(Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(
+    Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
 pub struct CtxBuilder {
     inner: CtxConfig,
 }
@@ -175,6 +405,10 @@ impl CtxBuilder {
 
     /// Builds a `Ctx` from a `CtxBuilder`.
     ///
+    /// # Returned Error Variants
+    /// * [`InvalidInput`] (if the configuration is invalid)
+    /// * [`Unexpected`] (if ØMQ fails to create the context)
+    ///
     /// # Usage Example
     /// ```
     /// # fn main() -> Result<(), anyhow::Error> {
@@ -189,11 +423,11 @@ impl CtxBuilder {
     /// #     Ok(())
     /// # }
     /// ```
+    ///
+    /// [`InvalidInput`]: enum.ErrorKind.html#variant.InvalidInput
+    /// [`Unexpected`]: enum.ErrorKind.html#variant.Unexpected
     pub fn build(&self) -> Result<Ctx, Error> {
-        let ctx = Ctx::new();
-        self.apply(ctx.handle())?;
-
-        Ok(ctx)
+        self.inner.build()
     }
 
     /// Applies the configuration of `CtxBuilder` to an existing context via
@@ -236,6 +470,57 @@ impl CtxBuilder {
         self.inner.set_max_sockets(Some(value));
         self
     }
+
+    /// See [`set_max_msg_size`].
+    ///
+    /// [`set_max_msg_size`]: struct.Ctx.html#method.set_max_msg_size
+    pub fn max_msg_size(&mut self, value: i32) -> &mut Self {
+        self.inner.set_max_msg_size(Some(value));
+        self
+    }
+
+    /// See [`set_thread_priority`].
+    ///
+    /// [`set_thread_priority`]: struct.Ctx.html#method.set_thread_priority
+    pub fn thread_priority(&mut self, value: i32) -> &mut Self {
+        self.inner.set_thread_priority(Some(value));
+        self
+    }
+
+    /// See [`set_thread_sched_policy`].
+    ///
+    /// [`set_thread_sched_policy`]: struct.Ctx.html#method.set_thread_sched_policy
+    pub fn thread_sched_policy(&mut self, value: i32) -> &mut Self {
+        self.inner.set_thread_sched_policy(Some(value));
+        self
+    }
+
+    /// See [`add_thread_affinity_cpu`].
+    ///
+    /// [`add_thread_affinity_cpu`]: struct.Ctx.html#method.add_thread_affinity_cpu
+    pub fn thread_affinity<I>(&mut self, cpus: I) -> &mut Self
+    where
+        I: IntoIterator<Item = i32>,
+    {
+        self.inner.set_thread_affinity(Some(cpus));
+        self
+    }
+
+    /// See [`set_thread_name_prefix`].
+    ///
+    /// [`set_thread_name_prefix`]: struct.Ctx.html#method.set_thread_name_prefix
+    pub fn thread_name_prefix(&mut self, value: i32) -> &mut Self {
+        self.inner.set_thread_name_prefix(Some(value));
+        self
+    }
+
+    /// See [`set_zero_copy_recv`].
+    ///
+    /// [`set_zero_copy_recv`]: struct.Ctx.html#method.set_zero_copy_recv
+    pub fn zero_copy_recv(&mut self, enabled: bool) -> &mut Self {
+        self.inner.set_zero_copy_recv(Some(enabled));
+        self
+    }
 }
 
 /// A non-owning pointer to a `Ctx`.
@@ -295,6 +580,51 @@ impl CtxHandle {
         self.inner.set(CtxOption::MaxSockets, max)
     }
 
+    /// [`Read more`](struct.Ctx.html#method.max_msg_size)
+    pub fn max_msg_size(self) -> i32 {
+        self.inner.get(CtxOption::MaxMsgSize)
+    }
+
+    /// [`Read more`](struct.Ctx.html#method.set_max_msg_size)
+    pub fn set_max_msg_size(self, size: i32) -> Result<(), Error> {
+        self.inner.set(CtxOption::MaxMsgSize, size)
+    }
+
+    /// [`Read more`](struct.Ctx.html#method.set_thread_priority)
+    pub fn set_thread_priority(self, priority: i32) -> Result<(), Error> {
+        self.inner.set(CtxOption::ThreadPriority, priority)
+    }
+
+    /// [`Read more`](struct.Ctx.html#method.set_thread_sched_policy)
+    pub fn set_thread_sched_policy(self, policy: i32) -> Result<(), Error> {
+        self.inner.set(CtxOption::ThreadSchedPolicy, policy)
+    }
+
+    /// [`Read more`](struct.Ctx.html#method.add_thread_affinity_cpu)
+    pub fn add_thread_affinity_cpu(self, cpu: i32) -> Result<(), Error> {
+        self.inner.set(CtxOption::ThreadAffinityCpuAdd, cpu)
+    }
+
+    /// [`Read more`](struct.Ctx.html#method.remove_thread_affinity_cpu)
+    pub fn remove_thread_affinity_cpu(self, cpu: i32) -> Result<(), Error> {
+        self.inner.set(CtxOption::ThreadAffinityCpuRemove, cpu)
+    }
+
+    /// [`Read more`](struct.Ctx.html#method.set_thread_name_prefix)
+    pub fn set_thread_name_prefix(self, prefix: i32) -> Result<(), Error> {
+        self.inner.set(CtxOption::ThreadNamePrefix, prefix)
+    }
+
+    /// [`Read more`](struct.Ctx.html#method.zero_copy_recv)
+    pub fn zero_copy_recv(self) -> bool {
+        self.inner.get_bool(CtxOption::ZeroCopyRecv)
+    }
+
+    /// [`Read more`](struct.Ctx.html#method.set_zero_copy_recv)
+    pub fn set_zero_copy_recv(self, enabled: bool) -> Result<(), Error> {
+        self.inner.set_bool(CtxOption::ZeroCopyRecv, enabled)
+    }
+
     /// [`Read more`](struct.Ctx.html#method.shutdown)
     pub fn shutdown(self) {
         self.inner.shutdown()
@@ -303,6 +633,21 @@ impl CtxHandle {
     pub(crate) fn as_ptr(self) -> *mut c_void {
         self.inner.ctx
     }
+
+    /// Returns the state of the context, or `None` for the sockets created
+    /// along with the context.
+    ///
+    /// # Safety
+    /// A socket must be open within the context, since the state is
+    /// released once the context is terminated.
+    pub(crate) unsafe fn state(self) -> Option<Arc<CtxState>> {
+        if self.inner.state.is_null() {
+            return None;
+        }
+        // The reference owned by the `Ctx` must not be released.
+        let state = ManuallyDrop::new(Arc::from_raw(self.inner.state));
+        Some(Arc::clone(&state))
+    }
 }
 
 /// A owning pointer to a ØMQ context.
@@ -351,14 +696,35 @@ impl Ctx {
     /// let handle = ctx.handle();
     /// ```
     ///
+    /// # Panic
+    /// Panics if ØMQ fails to create the context, for instance if the
+    /// process ran out of file descriptors. Use [`CtxBuilder::build`] to
+    /// handle this error instead.
+    ///
     /// [`global`]: #method.global
+    /// [`CtxBuilder::build`]: struct.CtxBuilder.html#method.build
     pub fn new() -> Self {
-        let inner = RawCtx::new();
+        match Self::with_config(&CtxConfig::default()) {
+            Ok(ctx) => ctx,
+            // The default config is always valid.
+            Err(err) => panic!("unable to create context: {}", err),
+        }
+    }
+
+    fn with_config(config: &CtxConfig) -> Result<Self, Error> {
+        let mut inner = RawCtx::new()?;
         // Enable ipv6 by default.
         inner.set_bool(CtxOption::IPV6, true).unwrap();
         // Set linger period for all sockets to zero.
         inner.set_bool(CtxOption::Blocky, false).unwrap();
 
+        // The config must be applied before the first socket is created
+        // since this is when the I/O threads are started.
+        if let Err(err) = config.apply(CtxHandle { inner }) {
+            inner.terminate();
+            return Err(err);
+        }
+
         //// Start a `ZAP` handler for the context.
         let mut auth = AuthServer::with_ctx(CtxHandle { inner }).unwrap();
 
@@ -366,7 +732,12 @@ impl Ctx {
         // it terminates on `InvalidCtx` errors.
         thread::spawn(move || auth.run());
 
-        Self { inner }
+        // We create the state after the `ZAP` handler so that its sockets
+        // are not tracked.
+        let state: Arc<CtxState> = Arc::default();
+        inner.state = Arc::into_raw(state);
+
+        Ok(Self { inner })
     }
 
     /// Returns a handle to the `Ctx`.
@@ -481,6 +852,189 @@ impl Ctx {
         self.inner.get(CtxOption::SocketLimit)
     }
 
+    /// Returns the maximum size of a message allowed for this context.
+    pub fn max_msg_size(&self) -> i32 {
+        self.inner.get(CtxOption::MaxMsgSize)
+    }
+
+    /// Sets the maximum size in bytes of a message allowed on the context.
+    ///
+    /// # Default
+    /// The default value is `i32::max_value()`.
+    ///
+    /// # Usage Example
+    /// ```
+    /// # fn main() -> Result<(), anyhow::Error> {
+    /// use libzmq::Ctx;
+    ///
+    /// let ctx = Ctx::new();
+    /// assert_eq!(ctx.max_msg_size(), i32::max_value());
+    ///
+    /// ctx.set_max_msg_size(4096)?;
+    /// assert_eq!(ctx.max_msg_size(), 4096);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn set_max_msg_size(&self, size: i32) -> Result<(), Error> {
+        self.inner.set(CtxOption::MaxMsgSize, size)
+    }
+
+    /// Sets the scheduling priority of the context's I/O threads.
+    ///
+    /// The value is passed to `pthread_setschedparam` and its meaning
+    /// depends on the scheduling policy of the threads. This option is
+    /// only supported on POSIX systems.
+    ///
+    /// This only affects I/O threads that are started after the option is
+    /// set. Since the threads are started alongside the context, this option
+    /// should be specified via a [`CtxBuilder`] or [`CtxConfig`].
+    ///
+    /// # Usage Contract
+    /// * The priority must be positive.
+    ///
+    /// # Returned Error
+    /// * [`InvalidInput`] (on contract violation)
+    ///
+    /// # Usage Example
+    /// ```
+    /// # fn main() -> Result<(), anyhow::Error> {
+    /// use libzmq::CtxBuilder;
+    ///
+    /// let ctx = CtxBuilder::new()
+    ///   .thread_priority(0)
+    ///   .build()?;
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`CtxBuilder`]: struct.CtxBuilder.html
+    /// [`CtxConfig`]: config/struct.CtxConfig.html
+    /// [`InvalidInput`]: enum.ErrorKind.html#variant.InvalidInput
+    pub fn set_thread_priority(&self, priority: i32) -> Result<(), Error> {
+        self.inner.set(CtxOption::ThreadPriority, priority)
+    }
+
+    /// Sets the scheduling policy of the context's I/O threads.
+    ///
+    /// The value is one of the `SCHED_*` constants from `sched.h` and is
+    /// passed to `pthread_setschedparam`. This option is only supported on
+    /// POSIX systems.
+    ///
+    /// This only affects I/O threads that are started after the option is
+    /// set. See [`set_thread_priority`] for more information.
+    ///
+    /// # Usage Contract
+    /// * The policy must be positive.
+    ///
+    /// # Returned Error
+    /// * [`InvalidInput`] (on contract violation)
+    ///
+    /// [`set_thread_priority`]: #method.set_thread_priority
+    /// [`InvalidInput`]: enum.ErrorKind.html#variant.InvalidInput
+    pub fn set_thread_sched_policy(&self, policy: i32) -> Result<(), Error> {
+        self.inner.set(CtxOption::ThreadSchedPolicy, policy)
+    }
+
+    /// Adds a CPU to the list of CPUs the context's I/O threads are
+    /// pinned to.
+    ///
+    /// By default the I/O threads are not pinned. This option is only
+    /// supported on POSIX systems.
+    ///
+    /// This only affects I/O threads that are started after the option is
+    /// set. See [`set_thread_priority`] for more information.
+    ///
+    /// # Usage Contract
+    /// * The cpu index must be positive.
+    ///
+    /// # Returned Error
+    /// * [`InvalidInput`] (on contract violation)
+    ///
+    /// # Usage Example
+    /// ```
+    /// # fn main() -> Result<(), anyhow::Error> {
+    /// use libzmq::CtxBuilder;
+    ///
+    /// // Pin the I/O threads on the first two cpus.
+    /// let ctx = CtxBuilder::new()
+    ///   .thread_affinity(vec![0, 1])
+    ///   .build()?;
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`set_thread_priority`]: #method.set_thread_priority
+    /// [`InvalidInput`]: enum.ErrorKind.html#variant.InvalidInput
+    pub fn add_thread_affinity_cpu(&self, cpu: i32) -> Result<(), Error> {
+        self.inner.set(CtxOption::ThreadAffinityCpuAdd, cpu)
+    }
+
+    /// Removes a CPU from the list of CPUs the context's I/O threads are
+    /// pinned to.
+    ///
+    /// See [`add_thread_affinity_cpu`] for more information.
+    ///
+    /// [`add_thread_affinity_cpu`]: #method.add_thread_affinity_cpu
+    pub fn remove_thread_affinity_cpu(&self, cpu: i32) -> Result<(), Error> {
+        self.inner.set(CtxOption::ThreadAffinityCpuRemove, cpu)
+    }
+
+    /// Sets a numeric prefix to the name of the context's I/O threads.
+    ///
+    /// The threads are named `ZMQbg/<prefix>/IO/<n>`, which makes them
+    /// easier to identify in tools such as `top`. This option is only
+    /// supported on POSIX systems.
+    ///
+    /// This only affects I/O threads that are started after the option is
+    /// set. See [`set_thread_priority`] for more information.
+    ///
+    /// # Usage Contract
+    /// * The prefix must be positive.
+    ///
+    /// # Returned Error
+    /// * [`InvalidInput`] (on contract violation)
+    ///
+    /// [`set_thread_priority`]: #method.set_thread_priority
+    /// [`InvalidInput`]: enum.ErrorKind.html#variant.InvalidInput
+    pub fn set_thread_name_prefix(&self, prefix: i32) -> Result<(), Error> {
+        self.inner.set(CtxOption::ThreadNamePrefix, prefix)
+    }
+
+    /// Returns `true` if the zero-copy receive optimization is enabled.
+    pub fn zero_copy_recv(&self) -> bool {
+        self.inner.get_bool(CtxOption::ZeroCopyRecv)
+    }
+
+    /// Enables or disables the zero-copy receive optimization.
+    ///
+    /// When enabled, large received messages reference the receive buffer
+    /// of the connection instead of being copied. Disabling it reduces
+    /// memory usage when received messages are kept around for a long time.
+    ///
+    /// # Default
+    /// The default value is `true`.
+    ///
+    /// # Usage Example
+    /// ```
+    /// # fn main() -> Result<(), anyhow::Error> {
+    /// use libzmq::Ctx;
+    ///
+    /// let ctx = Ctx::new();
+    /// assert!(ctx.zero_copy_recv());
+    ///
+    /// ctx.set_zero_copy_recv(false)?;
+    /// assert!(!ctx.zero_copy_recv());
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn set_zero_copy_recv(&self, enabled: bool) -> Result<(), Error> {
+        self.inner.set_bool(CtxOption::ZeroCopyRecv, enabled)
+    }
+
     /// Invalidates all the handles to the ØMQ context.
     ///
     /// Context shutdown will cause any blocking operations currently in
@@ -496,6 +1050,88 @@ impl Ctx {
     pub fn shutdown(&self) {
         self.inner.shutdown()
     }
+
+    /// Gracefully shutdowns then terminates the context.
+    ///
+    /// This consists of the following steps:
+    /// 1. New messages are no longer accepted by the sockets of the
+    ///    context. Any send call will fail with [`InvalidCtx`].
+    /// 2. The linger period of all open sockets is bounded by the
+    ///    `deadline`, so that their outgoing messages are given up to the
+    ///    `deadline` to be sent once they are dropped.
+    /// 3. The context is [`shutdown`], so that any blocking call fails with
+    ///    [`InvalidCtx`] and the sockets can be dropped by their owner.
+    /// 4. Wait until all sockets are dropped or the `deadline` expires.
+    /// 5. The context is terminated, which waits for the outgoing messages
+    ///    of the dropped sockets to be sent within their linger period.
+    ///
+    /// The [`UnclosedSockets`] that were not dropped before the `deadline`
+    /// are returned. Whether the outgoing messages were delivered is not
+    /// known, since ØMQ does not expose the outgoing queue of a socket.
+    ///
+    /// Sockets dropped before this call keep the linger period they had
+    /// when dropped, which is zero by default, so their outgoing messages
+    /// might already be lost.
+    ///
+    /// Note that, just like when the `Ctx` is dropped, the termination
+    /// will block until all the unclosed sockets are dropped by their owner.
+    ///
+    /// # Usage Example
+    /// ```
+    /// # fn main() -> Result<(), anyhow::Error> {
+    /// use libzmq::{prelude::*, *};
+    /// use std::{thread, time::Duration};
+    ///
+    /// let ctx = Ctx::new();
+    /// let addr = InprocAddr::new_unique();
+    ///
+    /// let server = ServerBuilder::new().bind(&addr).with_ctx(ctx.handle())?;
+    /// let client = ClientBuilder::new().connect(&addr).with_ctx(ctx.handle())?;
+    ///
+    /// client.send("last words")?;
+    /// // The client is dropped once the context is shutdown.
+    /// let handle = thread::spawn(move || client.recv_msg().unwrap_err());
+    ///
+    /// let msg = server.recv_msg()?;
+    /// assert_eq!("last words", msg.to_str()?);
+    /// drop(server);
+    ///
+    /// let unclosed = ctx.shutdown_graceful(Duration::from_secs(1));
+    /// assert!(unclosed.is_empty());
+    /// assert_eq!(handle.join().unwrap().kind(), ErrorKind::InvalidCtx);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`InvalidCtx`]: enum.ErrorKind.html#variant.InvalidCtx
+    /// [`shutdown`]: #method.shutdown
+    /// [`UnclosedSockets`]: struct.UnclosedSockets.html
+    pub fn shutdown_graceful(self, deadline: Duration) -> UnclosedSockets {
+        let start = Instant::now();
+        let state = self.state();
+
+        state.start_draining();
+        // The linger period can no longer be changed once the context is
+        // shutdown.
+        state.set_linger(Period::Finite(deadline));
+        self.inner.shutdown();
+
+        while !state.is_empty() && start.elapsed() < deadline {
+            thread::sleep(DRAIN_POLL_INTERVAL);
+        }
+
+        let endpoints = state.last_endpoints();
+        // The context is terminated when dropped.
+        drop(self);
+
+        UnclosedSockets { endpoints }
+    }
+
+    fn state(&self) -> Arc<CtxState> {
+        // The state of a context lives as long as the `Ctx`.
+        unsafe { self.handle().state() }.unwrap()
+    }
 }
 
 impl Default for Ctx {
@@ -506,6 +1142,156 @@ impl Default for Ctx {
 
 impl Drop for Ctx {
     fn drop(&mut self) {
-        self.inner.terminate()
+        self.inner.terminate();
+        // No socket is left to use the state.
+        if !self.inner.state.is_null() {
+            unsafe { drop(Arc::from_raw(self.inner.state)) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ser_de() {
+        let mut config = CtxConfig::new();
+        config.set_max_msg_size(Some(1024));
+        config.set_thread_affinity(Some(vec![0, 1]));
+        config.set_zero_copy_recv(Some(false));
+
+        let ron = serde_yaml::to_string(&config).unwrap();
+        let de: CtxConfig = serde_yaml::from_str(&ron).unwrap();
+        assert_eq!(config, de);
+    }
+
+    #[test]
+    fn test_build_config() {
+        let ctx = CtxBuilder::new()
+            .max_msg_size(1024)
+            .thread_name_prefix(7)
+            .zero_copy_recv(false)
+            .build()
+            .unwrap();
+
+        assert_eq!(ctx.max_msg_size(), 1024);
+        assert!(!ctx.zero_copy_recv());
+    }
+
+    #[test]
+    fn test_shutdown_graceful_rejects_send() {
+        use crate::{prelude::*, *};
+
+        let ctx = Ctx::new();
+        let client = Client::with_ctx(ctx.handle()).unwrap();
+
+        let handle = thread::spawn(move || loop {
+            // The client is in mute state since it has no peer.
+            if let Err(err) = client.try_send("msg") {
+                match err.kind() {
+                    ErrorKind::WouldBlock => thread::yield_now(),
+                    kind => return kind,
+                }
+            }
+        });
+
+        let unclosed = ctx.shutdown_graceful(Duration::from_secs(5));
+        assert!(unclosed.is_empty());
+        assert_eq!(handle.join().unwrap(), ErrorKind::InvalidCtx);
+    }
+
+    #[test]
+    fn test_shutdown_graceful_unblocks() {
+        use crate::{prelude::*, *};
+
+        let ctx = Ctx::new();
+        let addr = InprocAddr::new_unique();
+        let server = ServerBuilder::new()
+            .bind(&addr)
+            .with_ctx(ctx.handle())
+            .unwrap();
+
+        // The server is blocked until the context is shutdown.
+        let handle = thread::spawn(move || server.recv_msg().unwrap_err());
+
+        let unclosed = ctx.shutdown_graceful(Duration::from_secs(5));
+        assert!(unclosed.is_empty());
+        assert_eq!(handle.join().unwrap().kind(), ErrorKind::InvalidCtx);
+    }
+
+    #[test]
+    fn test_shutdown_graceful_unclosed() {
+        use crate::{prelude::*, *};
+
+        let ctx = Ctx::new();
+        let addr = InprocAddr::new_unique();
+        let server = ServerBuilder::new()
+            .bind(&addr)
+            .with_ctx(ctx.handle())
+            .unwrap();
+
+        // The server is dropped after the deadline.
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            drop(server);
+        });
+
+        let unclosed = ctx.shutdown_graceful(Duration::from_millis(50));
+        assert_eq!(unclosed.endpoints(), &[Some(addr.into())]);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_shutdown_graceful_drains() {
+        use crate::{prelude::*, *};
+        use std::convert::TryInto;
+
+        const COUNT: usize = 1_000;
+        // Large enough for the messages to outgrow the kernel buffers.
+        let payload = vec![0; 64 * 1024];
+
+        let addr: TcpAddr = "127.0.0.1:*".try_into().unwrap();
+        let server = ServerBuilder::new()
+            .bind(&addr)
+            .recv_timeout(Duration::from_secs(5))
+            .build()
+            .unwrap();
+        let bound = server.last_endpoint().unwrap();
+
+        let ctx = Ctx::new();
+        let client = ClientBuilder::new()
+            .connect(&bound)
+            .with_ctx(ctx.handle())
+            .unwrap();
+        for _ in 0..COUNT {
+            client.send(payload.as_slice()).unwrap();
+        }
+
+        // The client is dropped once the context is shutdown.
+        let client_handle =
+            thread::spawn(move || client.recv_msg().unwrap_err().kind());
+
+        // The server is slow to start reading.
+        let server_handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            for _ in 0..COUNT {
+                server.recv_msg().unwrap();
+            }
+        });
+
+        let unclosed = ctx.shutdown_graceful(Duration::from_secs(5));
+        assert!(unclosed.is_empty());
+        assert_eq!(client_handle.join().unwrap(), ErrorKind::InvalidCtx);
+        server_handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_build_invalid_config() {
+        let err = CtxBuilder::new().thread_priority(-2).build().unwrap_err();
+        match err.kind() {
+            ErrorKind::InvalidInput(_) => (),
+            _ => panic!("unexpected error"),
+        }
     }
 }
@@ -781,6 +781,22 @@ impl<'a> From<&'a UdpAddr> for Endpoint {
 
 /// A socket address with the `PGM` transport.
 ///
+/// # Supported Sockets
+/// None. ØMQ only supports the `PGM` transport for the `PUB` and `SUB`
+/// socket types, which are not part of this crate. Any socket will
+/// return [`InvalidInput`] when binding or connecting to this address,
+/// or [`Unsupported`] if the linked ØMQ library was built without `PGM`
+/// support.
+///
+/// Multicast between [`Dish`] and [`Radio`] sockets is supported by the
+/// [`UdpAddr`] transport instead.
+///
+/// [`InvalidInput`]: enum.ErrorKind.html#variant.InvalidInput
+/// [`Unsupported`]: enum.ErrorKind.html#variant.Unsupported
+/// [`Dish`]: struct.Dish.html
+/// [`Radio`]: struct.Radio.html
+/// [`UdpAddr`]: struct.UdpAddr.html
+///
 /// # Example
 /// ```
 /// # fn main() -> Result<(), anyhow::Error> {
@@ -899,6 +915,11 @@ impl<'a> From<&'a PgmAddr> for Endpoint {
 
 /// A socket address with the Encapsulated `PGM` transport.
 ///
+/// # Supported Sockets
+/// None. See [`PgmAddr`] for more information.
+///
+/// [`PgmAddr`]: struct.PgmAddr.html
+///
 /// # Example
 /// ```
 /// # fn main() -> Result<(), anyhow::Error> {
@@ -1161,6 +1182,103 @@ impl<'a> From<&'a InprocAddr> for Endpoint {
     }
 }
 
+/// A socket address with inter-process transport.
+///
+/// The `ipc` address is a non-empty filesystem path, usually of a socket
+/// file in a temporary directory. It can also be `*` when binding, in which
+/// case a unique temporary path is assigned, retrievable using
+/// [`last_endpoint`].
+///
+/// The `ipc` transport is only available on POSIX systems.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), anyhow::Error> {
+/// use libzmq::{prelude::TryInto, IpcAddr};
+///
+/// let addr: IpcAddr = "/tmp/libzmq.sock".try_into()?;
+/// // A system assigned path.
+/// let addr: IpcAddr = "*".try_into()?;
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// [`last_endpoint`]: prelude/trait.Socket.html#method.last_endpoint
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct IpcAddr {
+    path: String,
+}
+
+impl IpcAddr {
+    /// Create a new `IpcAddr` addr from a path.
+    ///
+    /// The path cannot be empty.
+    pub fn new<S>(path: S) -> Result<Self, AddrParseError>
+    where
+        S: Into<String>,
+    {
+        let path = path.into();
+
+        if path.is_empty() {
+            Err(AddrParseError::new("empty path"))
+        } else {
+            Ok(Self { path })
+        }
+    }
+
+    /// Returns the underlying path of the `IpcAddr`.
+    pub fn as_str(&self) -> &str {
+        self.path.as_str()
+    }
+}
+
+impl FromStr for IpcAddr {
+    type Err = AddrParseError;
+    fn from_str(s: &str) -> Result<Self, AddrParseError> {
+        Self::new(s)
+    }
+}
+
+impl fmt::Display for IpcAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.path.fmt(f)
+    }
+}
+
+serde_display_tryfrom!(IpcAddr);
+tryfrom_fromstr!(IpcAddr);
+
+impl IntoIterator for IpcAddr {
+    type Item = Self;
+    type IntoIter = option::IntoIter<Self>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Some(self).into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a IpcAddr {
+    type Item = Self;
+    type IntoIter = option::IntoIter<Self>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Some(self).into_iter()
+    }
+}
+
+impl From<IpcAddr> for Endpoint {
+    fn from(addr: IpcAddr) -> Endpoint {
+        Endpoint::Ipc(addr)
+    }
+}
+
+impl<'a> From<&'a IpcAddr> for Endpoint {
+    fn from(addr: &'a IpcAddr) -> Endpoint {
+        Endpoint::Ipc(addr.to_owned())
+    }
+}
+
 /// A transport and a transport-specific address supported by ØMQ.
 ///
 /// The transport specifies the underlying protocol to use. The address
@@ -1211,6 +1329,7 @@ impl<'a> From<&'a InprocAddr> for Endpoint {
 /// [`zmq_vmci`]: http://api.zeromq.org/master:zmq_vmci
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
+#[non_exhaustive]
 pub enum Endpoint {
     /// Unicast transport using TCP, see [`zmq_tcp`].
     ///
@@ -1224,6 +1343,10 @@ pub enum Endpoint {
     ///
     /// [`zmq_inproc`]: http://api.zeromq.org/master:zmq-inproc
     Inproc(InprocAddr),
+    /// Local inter-process communication transport, see [`zmq_ipc`].
+    ///
+    /// [`zmq_ipc`]: http://api.zeromq.org/master:zmq-ipc
+    Ipc(IpcAddr),
     /// Reliable multicast transport using PGM, see [`zmq_pgm`].
     ///
     /// [`zmq_pgm`]: http://api.zeromq.org/master:zmq-pgm
@@ -1261,6 +1384,14 @@ impl Endpoint {
             false
         }
     }
+    /// Returns `true` if the endpoint uses the `Ipc` transport.
+    pub fn is_ipc(&self) -> bool {
+        if let Endpoint::Ipc(_) = self {
+            true
+        } else {
+            false
+        }
+    }
     /// Returns `true` if the endpoint uses the `Pgm` transport.
     pub fn is_pgm(&self) -> bool {
         if let Endpoint::Pgm(_) = self {
@@ -1290,6 +1421,10 @@ impl Endpoint {
                 let addr = InprocAddr::from_str(&s[index + 3..]).unwrap();
                 Endpoint::Inproc(addr)
             }
+            "ipc" => {
+                let addr = IpcAddr::from_str(&s[index + 3..]).unwrap();
+                Endpoint::Ipc(addr)
+            }
             "udp" => {
                 let addr = UdpAddr::from_str(&s[index + 3..]).unwrap();
                 Endpoint::Udp(addr)
@@ -1310,6 +1445,7 @@ impl Endpoint {
         match self {
             Endpoint::Tcp(addr) => format!("tcp://{}", addr),
             Endpoint::Inproc(addr) => format!("inproc://{}", addr),
+            Endpoint::Ipc(addr) => format!("ipc://{}", addr),
             Endpoint::Udp(addr) => format!("udp://{}", addr),
             Endpoint::Epgm(addr) => format!("pgm://{}", addr),
             Endpoint::Pgm(addr) => format!("epgm://{}", addr),
@@ -1372,4 +1508,33 @@ mod test {
     test_addr_ser_de!(pgm, PgmAddr, "0.0.0.0:3000");
     test_addr_ser_de!(epgm, EpgmAddr, "0.0.0.0:3000");
     test_addr_ser_de!(inproc, InprocAddr, "test");
+    test_addr_ser_de!(ipc, IpcAddr, "/tmp/test.sock");
+
+    mod pgm_unsupported {
+        use crate::{prelude::*, *};
+
+        #[test]
+        fn test_connect_pgm() {
+            let addr: PgmAddr =
+                "127.0.0.1;239.192.1.1:5555".try_into().unwrap();
+            let radio = Radio::new().unwrap();
+            let err = radio.connect(addr).unwrap_err();
+            match err.kind() {
+                ErrorKind::InvalidInput(_) | ErrorKind::Unsupported(_) => (),
+                _ => panic!("unexpected error: {}", err),
+            }
+        }
+
+        #[test]
+        fn test_connect_epgm() {
+            let addr: EpgmAddr =
+                "127.0.0.1;239.192.1.1:5555".try_into().unwrap();
+            let dish = Dish::new().unwrap();
+            let err = dish.connect(addr).unwrap_err();
+            match err.kind() {
+                ErrorKind::InvalidInput(_) | ErrorKind::Unsupported(_) => (),
+                _ => panic!("unexpected error: {}", err),
+            }
+        }
+    }
 }
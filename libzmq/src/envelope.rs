@@ -0,0 +1,560 @@
+//! A header map and a body encoded within a single `Msg`.
+//!
+//! The [`Envelope`] and [`EnvelopeBuilder`] types are re-exported at the
+//! crate root.
+//!
+//! [`Envelope`]: struct.Envelope.html
+//! [`EnvelopeBuilder`]: struct.EnvelopeBuilder.html
+
+use crate::{
+    error::{Error, ErrorKind},
+    msg::Msg,
+};
+
+use byteorder::{BigEndian, ByteOrder};
+
+use std::{collections::HashSet, ops::Range, str};
+
+// Identifies an envelope encoded message.
+const MAGIC: u8 = 0xE7;
+// The version of the wire format.
+const VERSION: u8 = 1;
+// The magic byte, the version and the header count.
+const PREFIX_LEN: usize = 4;
+// The key length and the value length of a header.
+const HEADER_LEN: usize = 3;
+// The smallest possible header is a single byte key with an empty value.
+const MIN_HEADER_LEN: usize = HEADER_LEN + 1;
+
+/// The maximum number of headers in an `Envelope`.
+pub const MAX_HEADERS: usize = u16::MAX as usize;
+/// The maximum size in bytes of an `Envelope` header key.
+pub const MAX_HEADER_KEY_SIZE: usize = u8::MAX as usize;
+/// The maximum size in bytes of an `Envelope` header value.
+pub const MAX_HEADER_VALUE_SIZE: usize = u16::MAX as usize;
+
+// The key is a range within the keys of the envelope and the value a
+// range within its message.
+#[derive(Debug, Clone)]
+struct Header {
+    key: Range<usize>,
+    value: Range<usize>,
+}
+
+/// A message made of a map of headers and a body.
+///
+/// Since the draft sockets do not support multipart messages, an `Envelope`
+/// encodes its headers along with its body in a single [`Msg`]. This allows
+/// attaching metadata such as a content type, a trace id or a reply-to
+/// address to a message.
+///
+/// Decoding an `Envelope` does not copy the message. The header keys are
+/// validated and copied once, while the header values and the body are
+/// borrowed from the underlying [`Msg`].
+///
+/// # Wire Format
+/// All integers are big-endian.
+/// ```text
+/// +-------+---------+--------------+-------------+------+
+/// | magic | version | header count | headers ... | body |
+/// |  u8   |   u8    |     u16      |             |      |
+/// +-------+---------+--------------+-------------+------+
+/// ```
+/// where the magic byte is `0xE7`, the version is `1` and each header is
+/// encoded as:
+/// ```text
+/// +---------+-------+-----------+-------+
+/// | key len |  key  | value len | value |
+/// |   u8    | UTF-8 |    u16    | bytes |
+/// +---------+-------+-----------+-------+
+/// ```
+/// Header keys are non-empty and unique. The body spans the rest of the
+/// message.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), anyhow::Error> {
+/// use libzmq::{prelude::*, *};
+///
+/// let addr = InprocAddr::new_unique();
+/// let server = ServerBuilder::new().bind(&addr).build()?;
+/// let client = ClientBuilder::new().connect(&addr).build()?;
+///
+/// let envelope = EnvelopeBuilder::new()
+///     .header("content-type", "text/plain")
+///     .header("trace-id", "b7ad6b71")
+///     .build("ping")?;
+///
+/// // An `Envelope` is sent like any other message.
+/// client.send(envelope)?;
+///
+/// let envelope = server.recv_envelope()?;
+/// assert_eq!(envelope.header_str("content-type"), Some("text/plain"));
+/// assert_eq!(envelope.body(), b"ping");
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// [`Msg`]: ../struct.Msg.html
+#[derive(Debug, Clone)]
+pub struct Envelope {
+    msg: Msg,
+    keys: String,
+    headers: Vec<Header>,
+    body: usize,
+}
+
+impl Envelope {
+    /// Decodes an `Envelope` from a `Msg` without copying.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidInput`] (if the message is not a valid envelope)
+    /// * [`Unsupported`] (if the envelope has an unknown version)
+    ///
+    /// The `Msg` is returned as the content of the `Error`.
+    ///
+    /// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
+    /// [`Unsupported`]: ../enum.ErrorKind.html#variant.Unsupported
+    pub fn decode(msg: Msg) -> Result<Self, Error<Msg>> {
+        match parse(msg.as_bytes()) {
+            Ok((keys, headers, body)) => Ok(Self {
+                msg,
+                keys,
+                headers,
+                body,
+            }),
+            Err(kind) => Err(Error::with_content(kind, msg)),
+        }
+    }
+
+    /// Returns the value of the header with the given key, if any.
+    pub fn header(&self, key: &str) -> Option<&[u8]> {
+        self.headers().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    /// Returns the value of the header with the given key if it exists
+    /// and is valid UTF-8.
+    pub fn header_str(&self, key: &str) -> Option<&str> {
+        self.header(key).and_then(|v| str::from_utf8(v).ok())
+    }
+
+    /// Returns an iterator over the headers in the order they were encoded.
+    pub fn headers(&self) -> Headers<'_> {
+        Headers {
+            keys: &self.keys,
+            bytes: self.msg.as_bytes(),
+            inner: self.headers.iter(),
+        }
+    }
+
+    /// Returns the number of headers.
+    pub fn header_count(&self) -> usize {
+        self.headers.len()
+    }
+
+    /// Returns the body of the envelope.
+    pub fn body(&self) -> &[u8] {
+        &self.msg.as_bytes()[self.body..]
+    }
+
+    /// Returns a reference to the underlying `Msg`.
+    ///
+    /// This can be used to access message properties such as the
+    /// `routing_id`.
+    pub fn msg(&self) -> &Msg {
+        &self.msg
+    }
+
+    /// Converts the envelope into its underlying `Msg`.
+    pub fn into_msg(self) -> Msg {
+        self.msg
+    }
+}
+
+impl From<Envelope> for Msg {
+    fn from(envelope: Envelope) -> Msg {
+        envelope.into_msg()
+    }
+}
+
+/// An iterator over the headers of an [`Envelope`].
+///
+/// [`Envelope`]: struct.Envelope.html
+#[derive(Debug, Clone)]
+pub struct Headers<'a> {
+    keys: &'a str,
+    bytes: &'a [u8],
+    inner: std::slice::Iter<'a, Header>,
+}
+
+impl<'a> Iterator for Headers<'a> {
+    type Item = (&'a str, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|header| {
+            (
+                &self.keys[header.key.clone()],
+                &self.bytes[header.value.clone()],
+            )
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for Headers<'a> {}
+
+/// A builder for an [`Envelope`].
+///
+/// Setting a header that already exists replaces its value.
+///
+/// [`Envelope`]: struct.Envelope.html
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EnvelopeBuilder {
+    headers: Vec<(String, Vec<u8>)>,
+}
+
+impl EnvelopeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the header with the given key to the given value.
+    pub fn header<K, V>(&mut self, key: K, value: V) -> &mut Self
+    where
+        K: Into<String>,
+        V: AsRef<[u8]>,
+    {
+        let key = key.into();
+        let value = value.as_ref().to_vec();
+
+        match self.headers.iter_mut().find(|(k, _)| *k == key) {
+            Some(header) => header.1 = value,
+            None => self.headers.push((key, value)),
+        }
+        self
+    }
+
+    /// Encode the headers with the given body into an `Envelope`.
+    ///
+    /// # Usage Contract
+    /// * No more than `MAX_HEADERS` headers.
+    /// * Header keys must be non-empty and of at most `MAX_HEADER_KEY_SIZE`
+    ///   bytes.
+    /// * Header values must be of at most `MAX_HEADER_VALUE_SIZE` bytes.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidInput`] (if contract is not followed)
+    ///
+    /// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
+    pub fn build<B>(&self, body: B) -> Result<Envelope, Error>
+    where
+        B: AsRef<[u8]>,
+    {
+        let body = body.as_ref();
+
+        if self.headers.len() > MAX_HEADERS {
+            return Err(Error::new(ErrorKind::InvalidInput(
+                "too many envelope headers",
+            )));
+        }
+
+        let mut size = PREFIX_LEN + body.len();
+        for (key, value) in &self.headers {
+            if key.is_empty() || key.len() > MAX_HEADER_KEY_SIZE {
+                return Err(Error::new(ErrorKind::InvalidInput(
+                    "invalid envelope header key size",
+                )));
+            }
+            if value.len() > MAX_HEADER_VALUE_SIZE {
+                return Err(Error::new(ErrorKind::InvalidInput(
+                    "invalid envelope header value size",
+                )));
+            }
+            size += HEADER_LEN + key.len() + value.len();
+        }
+
+        let mut msg = Msg::with_size(size);
+        let mut keys = String::new();
+        let mut headers = Vec::with_capacity(self.headers.len());
+        {
+            let bytes = msg.as_bytes_mut();
+            bytes[0] = MAGIC;
+            bytes[1] = VERSION;
+            BigEndian::write_u16(&mut bytes[2..4], self.headers.len() as u16);
+
+            let mut pos = PREFIX_LEN;
+            for (key, value) in &self.headers {
+                bytes[pos] = key.len() as u8;
+                pos += 1;
+                bytes[pos..pos + key.len()].copy_from_slice(key.as_bytes());
+                pos += key.len();
+
+                BigEndian::write_u16(
+                    &mut bytes[pos..pos + 2],
+                    value.len() as u16,
+                );
+                pos += 2;
+                let value_range = pos..pos + value.len();
+                bytes[value_range.clone()].copy_from_slice(value);
+                pos = value_range.end;
+
+                headers.push(Header {
+                    key: push_key(&mut keys, key),
+                    value: value_range,
+                });
+            }
+            bytes[pos..].copy_from_slice(body);
+        }
+
+        Ok(Envelope {
+            msg,
+            keys,
+            headers,
+            body: size - body.len(),
+        })
+    }
+}
+
+// Appends the key to the keys of an envelope and returns its range.
+fn push_key(keys: &mut String, key: &str) -> Range<usize> {
+    let start = keys.len();
+    keys.push_str(key);
+    start..keys.len()
+}
+
+fn parse(bytes: &[u8]) -> Result<(String, Vec<Header>, usize), ErrorKind> {
+    if bytes.len() < PREFIX_LEN || bytes[0] != MAGIC {
+        return Err(ErrorKind::InvalidInput("not an envelope"));
+    }
+    if bytes[1] != VERSION {
+        return Err(ErrorKind::Unsupported("unknown envelope version"));
+    }
+
+    let count = BigEndian::read_u16(&bytes[2..4]) as usize;
+    // Prevents a large allocation from a bogus header count.
+    if count > (bytes.len() - PREFIX_LEN) / MIN_HEADER_LEN {
+        return Err(ErrorKind::InvalidInput("truncated envelope"));
+    }
+
+    let mut keys = String::new();
+    let mut headers = Vec::with_capacity(count);
+    let mut unique = HashSet::with_capacity(count);
+    let mut pos = PREFIX_LEN;
+
+    for _ in 0..count {
+        let key_len = *bytes
+            .get(pos)
+            .ok_or(ErrorKind::InvalidInput("truncated envelope"))?
+            as usize;
+        if key_len == 0 {
+            return Err(ErrorKind::InvalidInput("empty envelope header key"));
+        }
+        pos += 1;
+
+        let key = bytes
+            .get(pos..pos + key_len)
+            .ok_or(ErrorKind::InvalidInput("truncated envelope"))?;
+        let key = str::from_utf8(key).map_err(|_| {
+            ErrorKind::InvalidInput("envelope header key is not UTF-8")
+        })?;
+        if !unique.insert(key) {
+            return Err(ErrorKind::InvalidInput(
+                "duplicate envelope header key",
+            ));
+        }
+        pos += key_len;
+
+        let value_len = bytes
+            .get(pos..pos + 2)
+            .map(BigEndian::read_u16)
+            .ok_or(ErrorKind::InvalidInput("truncated envelope"))?
+            as usize;
+        pos += 2;
+
+        let value = pos..pos + value_len;
+        if value.end > bytes.len() {
+            return Err(ErrorKind::InvalidInput("truncated envelope"));
+        }
+        pos = value.end;
+
+        headers.push(Header {
+            key: push_key(&mut keys, key),
+            value,
+        });
+    }
+
+    Ok((keys, headers, pos))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{prelude::*, *};
+
+    use quickcheck::quickcheck;
+
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_envelope_roundtrip() {
+        let envelope = EnvelopeBuilder::new()
+            .header("content-type", "application/octet-stream")
+            .header("reply-to", "tcp://127.0.0.1:5555")
+            .header("content-type", "text/plain")
+            .build("body")
+            .unwrap();
+
+        let envelope = Envelope::decode(envelope.into_msg()).unwrap();
+        assert_eq!(envelope.header_count(), 2);
+        assert_eq!(envelope.header_str("content-type"), Some("text/plain"));
+        assert_eq!(
+            envelope.header("reply-to"),
+            Some(&b"tcp://127.0.0.1:5555"[..])
+        );
+        assert_eq!(envelope.header("trace-id"), None);
+        assert_eq!(envelope.body(), b"body");
+    }
+
+    #[test]
+    fn test_envelope_shared_msg_modified() {
+        let envelope = EnvelopeBuilder::new()
+            .header("content-type", "text/plain")
+            // Large enough for the buffer to be shared by a clone.
+            .build(vec![0; 64])
+            .unwrap();
+
+        // Corrupt the first byte of the key through a clone.
+        let mut msg = envelope.msg().clone();
+        msg.as_bytes_mut()[PREFIX_LEN + 1] = 0xFF;
+
+        let headers: Vec<_> = envelope.headers().collect();
+        assert_eq!(headers, vec![("content-type", &b"text/plain"[..])]);
+    }
+
+    #[test]
+    fn test_envelope_invalid_key() {
+        let err = EnvelopeBuilder::new().header("", "").build("").unwrap_err();
+        assert_eq!(
+            err.kind(),
+            ErrorKind::InvalidInput("invalid envelope header key size")
+        );
+    }
+
+    #[test]
+    fn test_envelope_decode_errors() {
+        let cases: &[(&[u8], ErrorKind)] = &[
+            (b"", ErrorKind::InvalidInput("not an envelope")),
+            (b"\xE7\x01\x00", ErrorKind::InvalidInput("not an envelope")),
+            (
+                b"\xE7\x02\x00\x00",
+                ErrorKind::Unsupported("unknown envelope version"),
+            ),
+            (
+                b"\xE7\x01\xFF\xFF\x01a\x00\x00",
+                ErrorKind::InvalidInput("truncated envelope"),
+            ),
+            (
+                b"\xE7\x01\x00\x01\x01a\x00\x01",
+                ErrorKind::InvalidInput("truncated envelope"),
+            ),
+            (
+                b"\xE7\x01\x00\x01\x00\x00\x00\x00",
+                ErrorKind::InvalidInput("empty envelope header key"),
+            ),
+            (
+                b"\xE7\x01\x00\x01\x01\xFF\x00\x00",
+                ErrorKind::InvalidInput("envelope header key is not UTF-8"),
+            ),
+            (
+                b"\xE7\x01\x00\x02\x01a\x00\x00\x01a\x00\x00",
+                ErrorKind::InvalidInput("duplicate envelope header key"),
+            ),
+        ];
+
+        for (bytes, kind) in cases {
+            let mut err = Envelope::decode(Msg::from(*bytes)).unwrap_err();
+            assert_eq!(err.kind(), *kind);
+            assert_eq!(err.take().unwrap().as_bytes(), *bytes);
+        }
+    }
+
+    #[test]
+    fn test_recv_envelope() {
+        let addr = InprocAddr::new_unique();
+        let server = ServerBuilder::new().bind(&addr).build().unwrap();
+        let client = ClientBuilder::new().connect(&addr).build().unwrap();
+
+        let request = EnvelopeBuilder::new()
+            .header("trace-id", "42")
+            .build("ping")
+            .unwrap();
+        client.send(request).unwrap();
+
+        let request = server.recv_envelope().unwrap();
+        let id = request.msg().routing_id().unwrap();
+
+        let reply = EnvelopeBuilder::new()
+            .header("trace-id", request.header("trace-id").unwrap())
+            .build("pong")
+            .unwrap();
+        server.route(reply, id).unwrap();
+
+        let reply = client.recv_envelope().unwrap();
+        assert_eq!(reply.header_str("trace-id"), Some("42"));
+        assert_eq!(reply.body(), b"pong");
+
+        // A message that is not an envelope is returned as the error content.
+        client.send("raw").unwrap();
+        let mut err = server.recv_envelope().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput("not an envelope"));
+        assert_eq!(err.take().unwrap().to_str().unwrap(), "raw");
+    }
+
+    quickcheck! {
+        fn decode_quickcheck(input: Vec<u8>) -> bool {
+            // Also exercise the header parsing with a valid prefix.
+            let mut prefixed = vec![MAGIC, VERSION];
+            prefixed.extend_from_slice(&input);
+
+            for bytes in &[input, prefixed] {
+                if let Ok(envelope) = Envelope::decode(Msg::from(&bytes[..])) {
+                    let len: usize = envelope
+                        .headers()
+                        .map(|(k, v)| k.len() + v.len())
+                        .sum();
+                    if len + envelope.body().len() > bytes.len() {
+                        return false;
+                    }
+                }
+            }
+
+            true
+        }
+    }
+
+    quickcheck! {
+        fn codec_quickcheck(headers: HashMap<String, Vec<u8>>, body: Vec<u8>) -> bool {
+            let mut builder = EnvelopeBuilder::new();
+            for (key, value) in &headers {
+                builder.header(key.as_str(), value);
+            }
+
+            match builder.build(&body) {
+                Ok(envelope) => {
+                    let envelope = Envelope::decode(envelope.into_msg()).unwrap();
+                    envelope.header_count() == headers.len()
+                        && envelope.body() == &body[..]
+                        && envelope
+                            .headers()
+                            .all(|(k, v)| headers.get(k).map(|h| &h[..]) == Some(v))
+                }
+                // Only empty or oversized keys are rejected.
+                Err(_) => headers.keys().any(|k| k.is_empty() || k.len() > MAX_HEADER_KEY_SIZE),
+            }
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{addr::AddrParseError, group::GroupParseError};
 use libzmq_sys as sys;
+
 use thiserror::Error;
 
 use std::{convert::Infallible, ffi, fmt, io, str};
@@ -56,6 +57,21 @@ impl<T> Error<T> {
         }
     }
 
+    /// Creates a new `Error` of kind `Unexpected` from an `errno`.
+    ///
+    /// The `content` field will be `None`.
+    pub(crate) fn from_errno(errno: i32) -> Self {
+        Self::new(ErrorKind::unexpected(errno))
+    }
+
+    /// Creates a new `Error` of kind `Unexpected` from an OS error, such as
+    /// when a thread cannot be spawned.
+    ///
+    /// The `content` field will be `None`.
+    pub(crate) fn from_io(err: io::Error) -> Self {
+        Self::from_errno(err.raw_os_error().unwrap_or(0))
+    }
+
     /// Creates a new `Error` from an `ErrorKind` and some content.
     pub(crate) fn with_content(kind: ErrorKind, content: T) -> Self {
         Self {
@@ -161,6 +177,10 @@ impl<T> From<Error<T>> for io::Error {
             InvalidInput(msg) => {
                 io::Error::new(io::ErrorKind::InvalidInput, msg)
             }
+            Unsupported(msg) => io::Error::new(io::ErrorKind::Other, msg),
+            kind @ Unexpected { .. } => {
+                io::Error::new(io::ErrorKind::Other, kind.to_string())
+            }
         }
     }
 }
@@ -187,9 +207,11 @@ pub enum ErrorKind {
     /// This error only occurs if:
     /// * The `Ctx` is being dropped or was previously dropped.
     /// * [`shutdown`] was called.
+    /// * [`shutdown_graceful`] was called.
     ///
     /// [`Ctx`]: ../ctx/struct.Ctx.html
     /// [`shutdown`]: ../ctx/struct.Ctx.html#method.terminate
+    /// [`shutdown_graceful`]: ../ctx/struct.Ctx.html#method.shutdown_graceful
     #[error("context invalidated")]
     InvalidCtx,
     /// The operation was interrupted by a OS signal delivery.
@@ -215,15 +237,52 @@ pub enum ErrorKind {
     /// Contains information on the specific contract breach.
     #[error("invalid input: {}", _0)]
     InvalidInput(&'static str),
+    /// The operation requires a feature that is not available in this build.
+    ///
+    /// Contains information on the missing feature.
+    #[error("unsupported: {}", _0)]
+    Unsupported(&'static str),
+    /// An error that was not expected by the operation was returned by ØMQ.
+    ///
+    /// Contains the raw `errno`, whose description, as given by
+    /// `zmq_strerror`, is part of the displayed error.
+    #[error("unexpected error [{}]: {}", errno, strerror(*errno))]
+    Unexpected { errno: i32 },
 }
 
-pub(crate) fn msg_from_errno(x: i32) -> String {
+impl ErrorKind {
+    pub(crate) fn unexpected(errno: i32) -> Self {
+        ErrorKind::Unexpected { errno }
+    }
+}
+
+fn strerror(errno: i32) -> String {
     unsafe {
-        let s = sys::zmq_strerror(x);
-        format!(
-            "unknown error [{}]: {}",
-            x,
-            str::from_utf8(ffi::CStr::from_ptr(s).to_bytes()).unwrap()
-        )
+        let s = sys::zmq_strerror(errno);
+        ffi::CStr::from_ptr(s).to_string_lossy().into_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sys::errno;
+
+    #[test]
+    fn test_from_errno() {
+        let err: Error = Error::from_errno(errno::EFSM);
+        match err.kind() {
+            ErrorKind::Unexpected { errno } => {
+                assert_eq!(errno, errno::EFSM);
+            }
+            _ => panic!("unexpected error kind"),
+        }
+
+        let msg = "Operation cannot be accomplished in current state";
+        assert!(err.to_string().ends_with(msg));
+
+        let io_err: io::Error = err.into();
+        assert_eq!(io_err.kind(), io::ErrorKind::Other);
+        assert!(io_err.to_string().ends_with(msg));
     }
 }
@@ -0,0 +1,199 @@
+//! Zero-copy [FlatBuffers] support for `Msg`.
+//!
+//! [FlatBuffers]: https://google.github.io/flatbuffers/
+
+use crate::{
+    error::{Error, ErrorKind},
+    msg::Msg,
+};
+
+use flatbuffers::{FlatBufferBuilder, Follow, Verifiable};
+
+impl<'fbb> From<FlatBufferBuilder<'fbb>> for Msg {
+    /// Converts a finished `FlatBufferBuilder` into a `Msg` without copying.
+    ///
+    /// The builder's buffer is handed over to ØMQ so that the message is
+    /// sent directly from where it was built. Since this conversion is used
+    /// by [`SendMsg`], a builder can be passed as is to `send`.
+    ///
+    /// # Panics
+    /// Panics if the builder was not finished.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> Result<(), anyhow::Error> {
+    /// use libzmq::{prelude::*, *, flatbuffers::FlatBufferBuilder};
+    ///
+    /// let addr = InprocAddr::new_unique();
+    /// let server = ServerBuilder::new().bind(&addr).build()?;
+    /// let client = ClientBuilder::new().connect(&addr).build()?;
+    ///
+    /// let mut builder = FlatBufferBuilder::new();
+    /// let text = builder.create_string("ping");
+    /// builder.finish_minimal(text);
+    ///
+    /// client.send(builder)?;
+    ///
+    /// let msg = server.recv_msg()?;
+    /// assert_eq!(msg.flatbuffer_root::<&str>()?, "ping");
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`SendMsg`]: prelude/trait.SendMsg.html
+    fn from(builder: FlatBufferBuilder<'fbb>) -> Self {
+        // Asserts that the builder is finished.
+        builder.finished_data();
+
+        let (data, head) = builder.collapse();
+        Msg::from_vec_at(data, head)
+    }
+}
+
+impl Msg {
+    /// Returns the verified root table of the FlatBuffer contained
+    /// in the message.
+    ///
+    /// The returned table borrows the message's buffer so no copy occurs.
+    /// The buffer is verified beforehand, which makes it safe to call on
+    /// messages received from untrusted peers.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidInput`] (if the buffer is not a valid FlatBuffer of type `T`)
+    ///
+    /// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
+    pub fn flatbuffer_root<'a, T>(&'a self) -> Result<T::Inner, Error>
+    where
+        T: 'a + Follow<'a> + Verifiable,
+    {
+        flatbuffers::root::<T>(self.as_bytes()).map_err(|_| {
+            Error::new(ErrorKind::InvalidInput("invalid flatbuffer"))
+        })
+    }
+
+    /// Returns the root table of the FlatBuffer contained in the message
+    /// without verifying it.
+    ///
+    /// # Safety
+    /// The message must contain a valid FlatBuffer of type `T`, which
+    /// generally means it must come from a trusted source.
+    /// Otherwise use [`flatbuffer_root`].
+    ///
+    /// [`flatbuffer_root`]: #method.flatbuffer_root
+    pub unsafe fn flatbuffer_root_unchecked<'a, T>(&'a self) -> T::Inner
+    where
+        T: 'a + Follow<'a>,
+    {
+        flatbuffers::root_unchecked::<T>(self.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{prelude::*, *};
+
+    use flatbuffers::{
+        FlatBufferBuilder, Follow, ForwardsUOffset, InvalidFlatbuffer, Table,
+        VOffsetT, Verifiable, Verifier, WIPOffset,
+    };
+
+    // What `flatc` would generate for the following schema:
+    //
+    // table Ping {
+    //   id: uint32;
+    //   text: string;
+    // }
+    struct Ping<'a> {
+        tab: Table<'a>,
+    }
+
+    impl<'a> Ping<'a> {
+        const VT_ID: VOffsetT = 4;
+        const VT_TEXT: VOffsetT = 6;
+
+        fn create(
+            fbb: &mut FlatBufferBuilder<'a>,
+            id: u32,
+            text: &str,
+        ) -> WIPOffset<Ping<'a>> {
+            let text = fbb.create_string(text);
+            let start = fbb.start_table();
+            fbb.push_slot_always(Self::VT_TEXT, text);
+            fbb.push_slot::<u32>(Self::VT_ID, id, 0);
+            WIPOffset::new(fbb.end_table(start).value())
+        }
+
+        fn id(&self) -> u32 {
+            unsafe { self.tab.get::<u32>(Self::VT_ID, Some(0)).unwrap() }
+        }
+
+        fn text(&self) -> Option<&'a str> {
+            unsafe {
+                self.tab.get::<ForwardsUOffset<&str>>(Self::VT_TEXT, None)
+            }
+        }
+    }
+
+    impl<'a> Follow<'a> for Ping<'a> {
+        type Inner = Ping<'a>;
+
+        unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+            Ping {
+                tab: Table::new(buf, loc),
+            }
+        }
+    }
+
+    impl Verifiable for Ping<'_> {
+        fn run_verifier(
+            v: &mut Verifier,
+            pos: usize,
+        ) -> Result<(), InvalidFlatbuffer> {
+            v.visit_table(pos)?
+                .visit_field::<u32>("id", Self::VT_ID, false)?
+                .visit_field::<ForwardsUOffset<&str>>(
+                    "text",
+                    Self::VT_TEXT,
+                    false,
+                )?
+                .finish();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_flatbuffer_req_rep() {
+        let addr = InprocAddr::new_unique();
+        let server = ServerBuilder::new().bind(&addr).build().unwrap();
+        let client = ClientBuilder::new().connect(&addr).build().unwrap();
+
+        let mut builder = FlatBufferBuilder::new();
+        let ping = Ping::create(&mut builder, 1, "ping");
+        builder.finish(ping, None);
+        client.send(builder).unwrap();
+
+        let msg = server.recv_msg().unwrap();
+        let request = msg.flatbuffer_root::<Ping>().unwrap();
+        assert_eq!(request.id(), 1);
+        assert_eq!(request.text(), Some("ping"));
+
+        let mut builder = FlatBufferBuilder::new();
+        let pong = Ping::create(&mut builder, request.id() + 1, "pong");
+        builder.finish(pong, None);
+
+        server.route(builder, msg.routing_id().unwrap()).unwrap();
+
+        let msg = client.recv_msg().unwrap();
+        let reply = msg.flatbuffer_root::<Ping>().unwrap();
+        assert_eq!(reply.id(), 2);
+        assert_eq!(reply.text(), Some("pong"));
+    }
+
+    #[test]
+    fn test_flatbuffer_invalid() {
+        let msg = Msg::from(vec![0xFF; 3]);
+        let err = msg.flatbuffer_root::<Ping>().err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput("invalid flatbuffer"));
+    }
+}
@@ -5,27 +5,49 @@
 #[macro_use]
 mod core;
 pub mod auth;
+pub mod beacon;
+pub mod bstar;
+pub mod capture;
+pub mod chaos;
+pub mod clone;
+pub mod compress;
 mod ctx;
 mod endpoint;
+pub mod envelope;
 mod error;
+#[cfg(feature = "flatbuffers")]
+mod flatbuf;
 mod group;
+pub mod monitor;
 mod msg;
+pub mod node;
 mod old;
+pub mod pirate;
 pub mod poll;
+pub mod reliable;
 mod socket;
+pub mod spool;
 mod utils;
+pub mod zmtp;
 
 pub use crate::core::{Heartbeat, Period};
-pub use ctx::{Ctx, CtxBuilder, CtxHandle};
+pub use ctx::{Ctx, CtxBuilder, CtxHandle, UnclosedSockets};
 pub use endpoint::{
-    EpgmAddr, InprocAddr, PgmAddr, TcpAddr, UdpAddr, INPROC_MAX_SIZE,
+    EpgmAddr, InprocAddr, IpcAddr, PgmAddr, TcpAddr, UdpAddr, INPROC_MAX_SIZE,
+};
+pub use envelope::{
+    Envelope, EnvelopeBuilder, MAX_HEADERS, MAX_HEADER_KEY_SIZE,
+    MAX_HEADER_VALUE_SIZE,
 };
 pub use error::{Error, ErrorKind};
+#[cfg(feature = "flatbuffers")]
+pub use flatbuffers;
 pub use group::*;
 pub use msg::*;
 pub use socket::{
-    Client, ClientBuilder, Dish, DishBuilder, Gather, GatherBuilder, Radio,
-    RadioBuilder, Scatter, ScatterBuilder, Server, ServerBuilder, SocketType,
+    BroadcastReport, Client, ClientBuilder, Dish, DishBuilder, Gather,
+    GatherBuilder, PeerEvent, Radio, RadioBuilder, Scatter, ScatterBuilder,
+    Server, ServerBuilder, SocketType,
 };
 pub use utils::*;
 /// Configurations for *libzmq* types.
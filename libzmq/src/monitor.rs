@@ -0,0 +1,216 @@
+//! Socket events published by the `zmq_socket_monitor` API.
+
+use crate::{
+    addr::Endpoint,
+    core::{GetRawSocket, RawSocket, Socket},
+    error::{Error, ErrorKind},
+    old::{OldSocket, OldSocketType},
+    poll::{Events, PollId, Pollable, Poller, READABLE},
+    InprocAddr, Msg, Period,
+};
+use libzmq_sys as sys;
+
+use std::{os::raw::c_int, time::Instant};
+
+/// An event reported by a [`Monitor`].
+///
+/// [`Monitor`]: struct.Monitor.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MonitorEvent {
+    /// A connection to a remote peer was established.
+    Connected,
+    /// The security handshake of a connection succeeded, so that messages
+    /// can be exchanged with the peer.
+    HandshakeSucceeded,
+    /// A connection was closed.
+    Disconnected,
+}
+
+impl MonitorEvent {
+    fn mask(self) -> u32 {
+        match self {
+            MonitorEvent::Connected => sys::ZMQ_EVENT_CONNECTED,
+            MonitorEvent::HandshakeSucceeded => {
+                sys::ZMQ_EVENT_HANDSHAKE_SUCCEEDED
+            }
+            MonitorEvent::Disconnected => sys::ZMQ_EVENT_DISCONNECTED,
+        }
+    }
+
+    fn from_mask(mask: u32) -> Option<Self> {
+        match mask {
+            sys::ZMQ_EVENT_CONNECTED => Some(MonitorEvent::Connected),
+            sys::ZMQ_EVENT_HANDSHAKE_SUCCEEDED => {
+                Some(MonitorEvent::HandshakeSucceeded)
+            }
+            sys::ZMQ_EVENT_DISCONNECTED => Some(MonitorEvent::Disconnected),
+            _ => None,
+        }
+    }
+}
+
+/// Receives the events of a socket published by [`zmq_socket_monitor`].
+///
+/// A socket has at most one monitor, so a new `Monitor` replaces the
+/// previous one. Only the events that occur once the monitor is created
+/// are reported, so it should be created before the socket connects or
+/// binds.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), anyhow::Error> {
+/// use libzmq::{monitor::*, prelude::*, *};
+///
+/// let addr: TcpAddr = "127.0.0.1:*".try_into()?;
+/// let server = ServerBuilder::new().bind(addr).build()?;
+/// let bound = server.last_endpoint()?;
+///
+/// let client = ClientBuilder::new().build()?;
+/// let handshake = MonitorEvent::HandshakeSucceeded;
+/// let mut monitor = Monitor::new(&client, &[handshake])?;
+/// client.connect(bound)?;
+///
+/// monitor.wait_for(handshake, Period::Infinite)?;
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// [`zmq_socket_monitor`]: http://api.zeromq.org/master:zmq-socket-monitor
+#[derive(Debug)]
+pub struct Monitor {
+    socket: OldSocket,
+}
+
+impl Monitor {
+    /// Starts monitoring the given `events` of the socket.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidCtx`]
+    ///
+    /// [`InvalidCtx`]: ../enum.ErrorKind.html#variant.InvalidCtx
+    pub fn new<S>(socket: &S, events: &[MonitorEvent]) -> Result<Self, Error>
+    where
+        S: Socket,
+    {
+        let mask = events.iter().fold(0, |mask, event| mask | event.mask());
+        Self::with_mask(socket.raw_socket(), mask)
+    }
+
+    /// Starts monitoring the raw `events` of the socket.
+    pub(crate) fn with_mask(
+        socket: &RawSocket,
+        events: u32,
+    ) -> Result<Self, Error> {
+        let addr: Endpoint = InprocAddr::new_unique().into();
+        socket.monitor(Some(&addr), events as c_int)?;
+
+        let mut monitor =
+            OldSocket::with_ctx(OldSocketType::Pair, socket.ctx())?;
+        monitor.connect(addr)?;
+
+        Ok(Self { socket: monitor })
+    }
+
+    /// Returns the next event, if any.
+    ///
+    /// # Returned Error Variants
+    /// * [`WouldBlock`] (if no event is available)
+    /// * [`InvalidCtx`]
+    ///
+    /// [`WouldBlock`]: ../enum.ErrorKind.html#variant.WouldBlock
+    /// [`InvalidCtx`]: ../enum.ErrorKind.html#variant.InvalidCtx
+    pub fn try_recv_event(&mut self) -> Result<MonitorEvent, Error> {
+        loop {
+            let parts = self.socket.try_recv_msg_multipart()?;
+            if let Some(event) = parse(&parts)
+                .and_then(|(event, _)| MonitorEvent::from_mask(event))
+            {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Blocks until the `event` is reported, discarding the other events,
+    /// or until the `timeout` expires.
+    ///
+    /// # Returned Error Variants
+    /// * [`WouldBlock`] (if the timeout expires)
+    /// * [`InvalidCtx`]
+    ///
+    /// [`WouldBlock`]: ../enum.ErrorKind.html#variant.WouldBlock
+    /// [`InvalidCtx`]: ../enum.ErrorKind.html#variant.InvalidCtx
+    pub fn wait_for(
+        &mut self,
+        event: MonitorEvent,
+        timeout: Period,
+    ) -> Result<(), Error> {
+        let expiry = match timeout {
+            Period::Finite(duration) => Some(Instant::now() + duration),
+            Period::Infinite => None,
+        };
+
+        let mut poller = Poller::new();
+        let pollable = Pollable::Socket(self.socket.raw_socket());
+        poller.add(pollable, PollId(0), READABLE)?;
+        let mut events = Events::new();
+
+        loop {
+            loop {
+                match self.try_recv_event() {
+                    Ok(received) if received == event => return Ok(()),
+                    Ok(_) => (),
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                    Err(err) => return Err(err),
+                }
+            }
+
+            let timeout = match expiry {
+                Some(expiry) => {
+                    let now = Instant::now();
+                    if now >= expiry {
+                        return Err(Error::new(ErrorKind::WouldBlock));
+                    }
+                    Period::Finite(expiry - now)
+                }
+                None => Period::Infinite,
+            };
+            if let Err(err) = poller.poll(&mut events, timeout) {
+                match err.kind() {
+                    ErrorKind::WouldBlock | ErrorKind::Interrupted => {}
+                    _ => return Err(err),
+                }
+            }
+        }
+    }
+
+    /// Returns the next event along with its value, or `None` if there is
+    /// no event available.
+    pub(crate) fn try_next(&mut self) -> Option<(u32, u32)> {
+        loop {
+            let parts = self.socket.try_recv_msg_multipart().ok()?;
+            if let Some(event) = parse(&parts) {
+                return Some(event);
+            }
+        }
+    }
+
+    /// The socket that becomes readable when an event is available.
+    pub(crate) fn raw_socket(&self) -> &RawSocket {
+        self.socket.raw_socket()
+    }
+}
+
+// The first frame of an event holds its `u16` id followed by its `u32`
+// value, both in native byte order.
+fn parse(parts: &[Msg]) -> Option<(u32, u32)> {
+    let bytes = parts.first()?.as_bytes();
+    if bytes.len() != 6 {
+        return None;
+    }
+
+    let event = u16::from_ne_bytes([bytes[0], bytes[1]]);
+    let value = u32::from_ne_bytes([bytes[2], bytes[3], bytes[4], bytes[5]]);
+    Some((u32::from(event), value))
+}
@@ -1,4 +1,4 @@
-use crate::{error::msg_from_errno, GroupSlice};
+use crate::{error::ErrorKind, Group, GroupSlice};
 use libzmq_sys as sys;
 use sys::errno;
 
@@ -9,7 +9,7 @@ use serde::{Deserialize, Serialize};
 use std::{
     ffi::CStr,
     fmt,
-    os::raw::c_void,
+    os::raw::{c_int, c_void},
     ptr, slice,
     str::{self, Utf8Error},
 };
@@ -58,6 +58,10 @@ pub struct RoutingId(pub u32);
 /// or components of the same application. ØMQ messages have no internal
 /// structure and from the point of view of ØMQ itself they are considered
 /// to be opaque binary data.
+///
+/// # Panic
+/// Just like the collections of the standard library, creating a `Msg`
+/// panics if ØMQ fails to allocate it.
 pub struct Msg {
     msg: sys::zmq_msg_t,
 }
@@ -201,23 +205,20 @@ impl Msg {
     /// # Usage Contract
     /// * Cannot be zero
     ///
-    /// # Returned Error Variants
-    /// * [`InvalidInput`] (if contract is not followed)
+    /// # Panic
+    /// In debug builds, panics if the contract is not followed. Otherwise
+    /// the routing id is left unchanged.
     ///
     /// See [`zmq_msg_set_routing_id`].
     ///
     /// [`zmq_msg_set_routing_id`]: http://api.zeromq.org/master:zmq-msg-set-routing-id
-    /// [`InvalidInput`]: ../enum.Error.html#variant.InvalidInput
     pub fn set_routing_id(&mut self, routing_id: RoutingId) {
         let rc = unsafe {
             sys::zmq_msg_set_routing_id(self.as_mut_ptr(), routing_id.0)
         };
 
-        // Should never occur.
-        if rc != 0 {
-            let errno = unsafe { sys::zmq_errno() };
-            panic!(msg_from_errno(errno));
-        }
+        // Only fails if the routing id is zero.
+        debug_assert!(rc == 0, "routing id cannot be zero");
     }
 
     /// The group property on the message.
@@ -250,13 +251,9 @@ impl Msg {
     /// # }
     /// ```
     ///
-    /// # Usage Contract
-    /// * Cannot hold more than 15 characters.
-    ///
-    /// # Returned Error Variants
-    /// * [`InvalidInput`] (if contract is not followed)
+    /// The length of the group is already bounded by [`GroupSlice`].
     ///
-    /// [`InvalidInput`]: ../enum.Error.html#variant.InvalidInput
+    /// [`GroupSlice`]: struct.GroupSlice.html
     pub fn set_group<G>(&mut self, group: G)
     where
         G: AsRef<GroupSlice>,
@@ -266,11 +263,8 @@ impl Msg {
             sys::zmq_msg_set_group(self.as_mut_ptr(), group.as_c_str().as_ptr())
         };
 
-        // Should never occur.
-        if rc == -1 {
-            let errno = unsafe { sys::zmq_errno() };
-            panic!(msg_from_errno(errno));
-        }
+        // Should never occur since the group length was validated.
+        debug_assert!(rc != -1, "group cannot exceed 15 characters");
     }
 
     // Defers the allocation of a zmq_msg_t to the closure.
@@ -286,12 +280,63 @@ impl Msg {
 
         let rc = f(&mut msg);
         if rc == -1 {
-            panic!(msg_from_errno(sys::zmq_errno()));
+            let errno = sys::zmq_errno();
+            panic!(
+                "unable to allocate message: {}",
+                ErrorKind::unexpected(errno)
+            );
         }
 
         Msg { msg }
     }
 
+    // Converts the bytes of `data` starting at `offset` into a `Msg`
+    // without copying. The whole vector is released once ØMQ is done
+    // with the message.
+    #[cfg(feature = "flatbuffers")]
+    pub(crate) fn from_vec_at(data: Vec<u8>, offset: usize) -> Msg {
+        unsafe extern "C" fn drop_zmq_msg_t(
+            _data: *mut c_void,
+            hint: *mut c_void,
+        ) {
+            // The hint is the boxed vector that owns the data.
+            drop(Box::from_raw(hint as *mut Vec<u8>));
+        }
+
+        assert!(offset <= data.len(), "offset out of bounds");
+
+        let size = data.len() - offset;
+        if size == 0 {
+            return Msg::new();
+        }
+
+        let mut data = Box::new(data);
+        let ptr = unsafe { data.as_mut_ptr().add(offset) };
+        let hint = Box::into_raw(data);
+
+        unsafe {
+            Self::deferred_alloc(|msg| {
+                sys::zmq_msg_init_data(
+                    msg,
+                    ptr as *mut c_void,
+                    size as size_t,
+                    Some(drop_zmq_msg_t),
+                    hint as *mut c_void,
+                )
+            })
+        }
+    }
+
+    // Copies the routing id and the group of the message to another message.
+    pub(crate) fn copy_properties(&self, other: &mut Msg) {
+        if let Some(id) = self.routing_id() {
+            other.set_routing_id(id);
+        }
+        if let Some(group) = self.group() {
+            other.set_group(Group::from(group));
+        }
+    }
+
     pub(crate) fn as_mut_ptr(&mut self) -> *mut sys::zmq_msg_t {
         &mut self.msg
     }
@@ -304,6 +349,36 @@ impl Msg {
         let rc = unsafe { sys::zmq_msg_more(self.as_ptr()) };
         rc != 0
     }
+
+    /// Returns the file descriptor of the connection the message was
+    /// received from, if any.
+    ///
+    /// Only the `tcp` and `ipc` transports have one.
+    pub(crate) fn src_fd(&self) -> Option<c_int> {
+        let rc =
+            unsafe { sys::zmq_msg_get(self.as_ptr(), sys::ZMQ_SRCFD as c_int) };
+        if rc == -1 {
+            None
+        } else {
+            Some(rc)
+        }
+    }
+
+    /// Returns the metadata property of the connection the message was
+    /// received from, if any.
+    ///
+    /// Only the connections with a ZMTP handshake have properties, which
+    /// excludes the `inproc` and `udp` transports.
+    pub(crate) fn property(&self, name: &CStr) -> Option<&str> {
+        let ptr = unsafe { sys::zmq_msg_gets(self.as_ptr(), name.as_ptr()) };
+        if ptr.is_null() {
+            None
+        } else {
+            // The property lives as long as the message.
+            let value = unsafe { CStr::from_ptr(ptr) };
+            value.to_str().ok()
+        }
+    }
 }
 
 impl PartialEq for Msg {
@@ -337,6 +412,10 @@ impl Clone for Msg {
     ///
     /// See [`zmq_msg_copy`].
     ///
+    /// # Panic
+    /// Panics if ØMQ fails to copy the message, since `Clone` cannot
+    /// return an error.
+    ///
     /// [`zmq_msg_copy`]: http://api.zeromq.org/master:zmq-msg-copy
     fn clone(&self) -> Self {
         let mut msg = Msg::new();
@@ -353,7 +432,10 @@ impl Clone for Msg {
 
             match errno {
                 errno::EFAULT => panic!("invalid message"),
-                _ => panic!(msg_from_errno(errno)),
+                _ => panic!(
+                    "unable to copy message: {}",
+                    ErrorKind::unexpected(errno)
+                ),
             }
         }
 
@@ -372,7 +454,10 @@ impl Drop for Msg {
 
         if rc != 0 {
             let errno = unsafe { sys::zmq_errno() };
-            error!("error while dropping message: {}", msg_from_errno(errno));
+            error!(
+                "error while dropping message: {}",
+                ErrorKind::unexpected(errno)
+            );
         }
     }
 }
@@ -500,4 +585,19 @@ mod tests {
             assert_eq!(i, j.0);
         }
     }
+
+    #[test]
+    fn test_set_routing_id() {
+        let mut msg = Msg::new();
+        msg.set_routing_id(RoutingId(1));
+        assert_eq!(msg.routing_id(), Some(RoutingId(1)));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "routing id cannot be zero")]
+    fn test_set_routing_id_zero() {
+        let mut msg = Msg::new();
+        msg.set_routing_id(RoutingId(0));
+    }
 }
@@ -0,0 +1,1189 @@
+//! A peer-to-peer group membership layer, similar to Zyre.
+//!
+//! Each [`Node`] announces itself on the local network with a [`Beacon`] and
+//! binds a `Server` on which it receives the messages of its peers. Upon
+//! discovering a peer, a node connects a `Client` to the server of the peer
+//! and introduces itself with its name, headers and groups. The nodes then
+//! notify each other of the groups they join or leave, so that every node
+//! knows the groups of its peers.
+//!
+//! A peer that stays silent for a heartbeat interval is pinged. It becomes
+//! evasive once silent for the heartbeat timeout and exits once silent for
+//! the [`expiry`] period, unless it says goodbye beforehand. The `Heartbeat`
+//! is also set on the sockets, so that dead connections are detected by
+//! the transport.
+//!
+//! [`Node`]: struct.Node.html
+//! [`Beacon`]: ../beacon/struct.Beacon.html
+//! [`expiry`]: struct.NodeConfig.html#method.expiry
+
+use crate::{
+    addr::{Endpoint, Interface, Port, SocketAddr},
+    beacon::{Beacon, BeaconConfig, BeaconPeer},
+    core::{
+        BuildHeartbeating, BuildRecv, BuildSocket, Period, RecvMsg, SendMsg,
+        Socket,
+    },
+    error::{Error, ErrorKind},
+    socket::{Client, ClientBuilder, Server, ServerBuilder},
+    Ctx, CtxHandle, Heartbeat, TcpAddr,
+};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt, io,
+    net::{self, IpAddr, Ipv4Addr},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+// The prefix of the beacon announcements of the nodes, followed by the
+// id of the node and the port of its server.
+const ANNOUNCEMENT_PREFIX: &[u8] = b"LZN\x01";
+const ANNOUNCEMENT_SIZE: usize = ANNOUNCEMENT_PREFIX.len() + 16 + 2;
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_EXPIRY: Duration = Duration::from_secs(30);
+// How often the node thread checks its peers.
+const TICK: Duration = Duration::from_millis(50);
+// How long a goodbye is kept when the context terminates.
+const BYE_LINGER: Duration = Duration::from_millis(100);
+
+/// The unique id of a [`Node`].
+///
+/// [`Node`]: struct.Node.html
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+)]
+pub struct NodeId([u8; 16]);
+
+impl NodeId {
+    fn new_unique() -> Self {
+        NodeId(*Uuid::new_v4().as_bytes())
+    }
+
+    /// Returns the bytes of the id.
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+}
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Uuid::from_bytes(self.0).fmt(f)
+    }
+}
+
+/// An event received by a [`Node`].
+///
+/// [`Node`]: struct.Node.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NodeEvent {
+    /// A peer introduced itself.
+    Enter {
+        peer: NodeId,
+        name: String,
+        headers: BTreeMap<String, String>,
+    },
+    /// A peer left or expired.
+    Exit { peer: NodeId, name: String },
+    /// A peer was silent for longer than the heartbeat timeout.
+    Evasive { peer: NodeId, name: String },
+    /// A peer joined a group.
+    Join {
+        peer: NodeId,
+        name: String,
+        group: String,
+    },
+    /// A peer left a group.
+    Leave {
+        peer: NodeId,
+        name: String,
+        group: String,
+    },
+    /// A peer sent a message to this node.
+    Whisper {
+        peer: NodeId,
+        name: String,
+        content: Vec<u8>,
+    },
+    /// A peer sent a message to a group joined by this node.
+    Shout {
+        peer: NodeId,
+        name: String,
+        group: String,
+        content: Vec<u8>,
+    },
+}
+
+impl NodeEvent {
+    /// Returns the peer of the event.
+    pub fn peer(&self) -> NodeId {
+        match self {
+            NodeEvent::Enter { peer, .. }
+            | NodeEvent::Exit { peer, .. }
+            | NodeEvent::Evasive { peer, .. }
+            | NodeEvent::Join { peer, .. }
+            | NodeEvent::Leave { peer, .. }
+            | NodeEvent::Whisper { peer, .. }
+            | NodeEvent::Shout { peer, .. } => *peer,
+        }
+    }
+}
+
+/// A config for a [`Node`].
+///
+/// Usefull in configuration files.
+///
+/// [`Node`]: struct.Node.html
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NodeConfig {
+    name: Option<String>,
+    headers: BTreeMap<String, String>,
+    interface: Option<IpAddr>,
+    beacon: BeaconConfig,
+    heartbeat: Heartbeat,
+    #[serde(with = "humantime_serde")]
+    expiry: Duration,
+}
+
+impl NodeConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a `Node` from the configuration, using the global context.
+    pub fn build(&self) -> io::Result<Node> {
+        self.with_ctx(Ctx::global())
+    }
+
+    /// Builds a `Node` from the configuration, using the given context.
+    pub fn with_ctx(&self, handle: CtxHandle) -> io::Result<Node> {
+        Node::with_config(self.clone(), handle)
+    }
+
+    /// The name of the node, announced to its peers.
+    ///
+    /// # Default
+    /// `None`, meaning that the first 6 characters of the id are used.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn set_name<S>(&mut self, maybe: Option<S>)
+    where
+        S: Into<String>,
+    {
+        self.name = maybe.map(S::into);
+    }
+
+    /// The headers of the node, announced to its peers.
+    pub fn headers(&self) -> &BTreeMap<String, String> {
+        &self.headers
+    }
+
+    pub fn set_headers(&mut self, headers: BTreeMap<String, String>) {
+        self.headers = headers;
+    }
+
+    /// The address of the interface on which the node binds its `Server`.
+    ///
+    /// # Default
+    /// `None`, meaning every IPv4 interface.
+    pub fn interface(&self) -> Option<IpAddr> {
+        self.interface
+    }
+
+    pub fn set_interface(&mut self, maybe: Option<IpAddr>) {
+        self.interface = maybe;
+    }
+
+    /// The config of the `Beacon` used to discover peers.
+    ///
+    /// Its filter is replaced by the prefix of the node announcements.
+    pub fn beacon(&self) -> &BeaconConfig {
+        &self.beacon
+    }
+
+    pub fn set_beacon(&mut self, beacon: BeaconConfig) {
+        self.beacon = beacon;
+    }
+
+    /// The heartbeat of the sockets, which also drives the pings of silent
+    /// peers at every interval. A peer becomes evasive once silent for the
+    /// heartbeat timeout, or for 3 intervals if there is none.
+    ///
+    /// # Default
+    /// An interval of 1 second and a timeout of 5 seconds.
+    pub fn heartbeat(&self) -> &Heartbeat {
+        &self.heartbeat
+    }
+
+    pub fn set_heartbeat(&mut self, heartbeat: Heartbeat) {
+        self.heartbeat = heartbeat;
+    }
+
+    /// How long a silent peer is kept before it exits.
+    ///
+    /// # Default
+    /// 30 seconds.
+    pub fn expiry(&self) -> Duration {
+        self.expiry
+    }
+
+    pub fn set_expiry(&mut self, expiry: Duration) {
+        self.expiry = expiry;
+    }
+
+    fn evasive(&self) -> Duration {
+        match self.heartbeat.timeout() {
+            Period::Finite(timeout) => timeout,
+            Period::Infinite => 3 * self.heartbeat.interval(),
+        }
+    }
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        Self {
+            name: None,
+            headers: BTreeMap::new(),
+            interface: None,
+            beacon: BeaconConfig::default(),
+            heartbeat: Heartbeat::new(DEFAULT_INTERVAL)
+                .add_timeout(DEFAULT_TIMEOUT),
+            expiry: DEFAULT_EXPIRY,
+        }
+    }
+}
+
+/// A convenience builder for a [`Node`].
+///
+/// [`Node`]: struct.Node.html
+#[derive(
+    Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+pub struct NodeBuilder {
+    inner: NodeConfig,
+}
+
+impl NodeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a `Node` from a `NodeBuilder`, using the global context.
+    pub fn build(&self) -> io::Result<Node> {
+        self.inner.build()
+    }
+
+    /// Builds a `Node` from a `NodeBuilder`, using the given context.
+    pub fn with_ctx(&self, handle: CtxHandle) -> io::Result<Node> {
+        self.inner.with_ctx(handle)
+    }
+
+    /// See [`set_name`].
+    ///
+    /// [`set_name`]: struct.NodeConfig.html#method.set_name
+    pub fn name<S>(&mut self, name: S) -> &mut Self
+    where
+        S: Into<String>,
+    {
+        self.inner.set_name(Some(name));
+        self
+    }
+
+    /// Adds a header to the node. See [`set_headers`].
+    ///
+    /// [`set_headers`]: struct.NodeConfig.html#method.set_headers
+    pub fn header<K, V>(&mut self, key: K, value: V) -> &mut Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.inner.headers.insert(key.into(), value.into());
+        self
+    }
+
+    /// See [`set_interface`].
+    ///
+    /// [`set_interface`]: struct.NodeConfig.html#method.set_interface
+    pub fn interface<A>(&mut self, addr: A) -> &mut Self
+    where
+        A: Into<IpAddr>,
+    {
+        self.inner.set_interface(Some(addr.into()));
+        self
+    }
+
+    /// See [`set_beacon`].
+    ///
+    /// [`set_beacon`]: struct.NodeConfig.html#method.set_beacon
+    pub fn beacon(&mut self, beacon: BeaconConfig) -> &mut Self {
+        self.inner.set_beacon(beacon);
+        self
+    }
+
+    /// See [`set_heartbeat`].
+    ///
+    /// [`set_heartbeat`]: struct.NodeConfig.html#method.set_heartbeat
+    pub fn heartbeat<H>(&mut self, heartbeat: H) -> &mut Self
+    where
+        H: Into<Heartbeat>,
+    {
+        self.inner.set_heartbeat(heartbeat.into());
+        self
+    }
+
+    /// See [`set_expiry`].
+    ///
+    /// [`set_expiry`]: struct.NodeConfig.html#method.set_expiry
+    pub fn expiry(&mut self, expiry: Duration) -> &mut Self {
+        self.inner.set_expiry(expiry);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Hello {
+    name: String,
+    headers: BTreeMap<String, String>,
+    groups: BTreeSet<String>,
+    // Whether the hello answers another hello, which prevents an endless
+    // exchange of hellos.
+    reply: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Command {
+    Hello(Hello),
+    Whisper(Vec<u8>),
+    Shout { group: String, content: Vec<u8> },
+    Join(String),
+    Leave(String),
+    Ping,
+    PingOk,
+    Bye,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Packet {
+    sender: NodeId,
+    command: Command,
+}
+
+struct Peer {
+    // `None` until the beacon of the peer is received.
+    client: Option<Client>,
+    // A hello received before the beacon of the peer, which is only
+    // processed once connected, so that a peer that entered can always be
+    // replied to.
+    hello: Option<Hello>,
+    // `None` until the peer entered.
+    name: Option<String>,
+    groups: BTreeSet<String>,
+    last_seen: Instant,
+    last_ping: Instant,
+    evasive: bool,
+}
+
+impl Peer {
+    fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            client: None,
+            hello: None,
+            name: None,
+            groups: BTreeSet::new(),
+            last_seen: now,
+            last_ping: now,
+            evasive: false,
+        }
+    }
+
+    fn entered(&self) -> bool {
+        self.name.is_some()
+    }
+
+    fn enter(
+        &mut self,
+        id: NodeId,
+        hello: Hello,
+        events: &mpsc::Sender<NodeEvent>,
+    ) {
+        let _ = events.send(NodeEvent::Enter {
+            peer: id,
+            name: hello.name.clone(),
+            headers: hello.headers,
+        });
+        for group in &hello.groups {
+            let _ = events.send(NodeEvent::Join {
+                peer: id,
+                name: hello.name.clone(),
+                group: group.clone(),
+            });
+        }
+        self.name = Some(hello.name);
+        self.groups = hello.groups;
+    }
+
+    // Messages to a peer are dropped rather than blocking the node.
+    fn send(&self, packet: &Packet) -> io::Result<()> {
+        let client = self.client.as_ref().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotConnected, "peer not reachable")
+        })?;
+        let bytes = bincode::serialize(packet).unwrap();
+        client.try_send(bytes)?;
+        Ok(())
+    }
+}
+
+struct State {
+    groups: BTreeSet<String>,
+    peers: HashMap<NodeId, Peer>,
+}
+
+struct Shared {
+    id: NodeId,
+    name: String,
+    config: NodeConfig,
+    handle: CtxHandle,
+    state: Mutex<State>,
+    stopped: AtomicBool,
+}
+
+/// A node of a peer-to-peer network that discovers its peers on the local
+/// network, tracks the groups they join and sends messages to one peer or
+/// to a group.
+///
+/// The node runs in a background thread from its creation until it is
+/// dropped, at which point it says goodbye to its peers. Events are received
+/// using [`recv`].
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), anyhow::Error> {
+/// use libzmq::{beacon::*, node::*};
+/// use std::{net::Ipv4Addr, time::Duration};
+///
+/// // Discover the peers on the loopback interface.
+/// let mut beacon = BeaconConfig::new();
+/// beacon.set_port(9998);
+/// beacon.set_addr(Ipv4Addr::new(239, 255, 42, 98).into());
+/// beacon.set_interface(Some(Ipv4Addr::LOCALHOST.into()));
+/// beacon.set_interval(Duration::from_millis(100));
+///
+/// let mut builder = NodeBuilder::new();
+/// builder.interface(Ipv4Addr::LOCALHOST).beacon(beacon);
+///
+/// let alice = builder.name("alice").header("role", "sender").build()?;
+/// let bob = builder.name("bob").build()?;
+/// bob.join("chat");
+///
+/// // Wait for alice to know that bob joined the group.
+/// loop {
+///     if let NodeEvent::Join { group, .. } = alice.recv()? {
+///         assert_eq!(group, "chat");
+///         break;
+///     }
+/// }
+/// alice.shout("chat", "hello")?;
+///
+/// loop {
+///     if let NodeEvent::Shout { name, content, .. } = bob.recv()? {
+///         assert_eq!(name, "alice");
+///         assert_eq!(content, b"hello");
+///         break;
+///     }
+/// }
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// [`recv`]: #method.recv
+pub struct Node {
+    shared: Arc<Shared>,
+    events: Mutex<mpsc::Receiver<NodeEvent>>,
+    endpoint: Endpoint,
+    beacon: Arc<Beacon>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Node {
+    /// Creates a `Node` with the default configuration, using the global
+    /// context.
+    pub fn new() -> io::Result<Self> {
+        NodeConfig::default().build()
+    }
+
+    fn with_config(config: NodeConfig, handle: CtxHandle) -> io::Result<Self> {
+        let id = NodeId::new_unique();
+        let name = match &config.name {
+            Some(name) => name.clone(),
+            None => id.to_string()[..6].to_owned(),
+        };
+
+        let ip = config
+            .interface
+            .unwrap_or_else(|| Ipv4Addr::UNSPECIFIED.into());
+        let addr =
+            TcpAddr::new(SocketAddr::new(Interface::Ip(ip), Port::Unspecified));
+        let server = ServerBuilder::new()
+            .bind(addr)
+            .heartbeat(&config.heartbeat)
+            .recv_timeout(TICK)
+            .with_ctx(handle)?;
+        let endpoint = server.last_endpoint()?;
+        let port = match &endpoint {
+            Endpoint::Tcp(addr) => match addr.host().port() {
+                Port::Specified(port) => port,
+                Port::Unspecified => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+
+        let mut beacon_config = config.beacon.clone();
+        beacon_config.set_filter(Some(ANNOUNCEMENT_PREFIX));
+        let beacon = Arc::new(beacon_config.build()?);
+        let mut announcement = ANNOUNCEMENT_PREFIX.to_vec();
+        announcement.extend_from_slice(id.as_bytes());
+        announcement.extend_from_slice(&port.to_be_bytes());
+        beacon.publish(announcement)?;
+
+        let shared = Arc::new(Shared {
+            id,
+            name,
+            config,
+            handle,
+            state: Mutex::new(State {
+                groups: BTreeSet::new(),
+                peers: HashMap::new(),
+            }),
+            stopped: AtomicBool::new(false),
+        });
+
+        let (sender, receiver) = mpsc::channel();
+        let thread = {
+            let shared = Arc::clone(&shared);
+            let beacon = Arc::clone(&beacon);
+            thread::Builder::new()
+                .name("node".to_owned())
+                .spawn(move || run(&shared, &server, &beacon, &sender))?
+        };
+
+        Ok(Self {
+            shared,
+            events: Mutex::new(receiver),
+            endpoint,
+            beacon,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the unique id of the node.
+    pub fn id(&self) -> NodeId {
+        self.shared.id
+    }
+
+    /// Returns the name of the node.
+    pub fn name(&self) -> &str {
+        &self.shared.name
+    }
+
+    /// Returns the endpoint of the `Server` of the node.
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    /// Joins the group, notifying the peers.
+    ///
+    /// Returns `false` if the group was already joined.
+    pub fn join<S>(&self, group: S) -> bool
+    where
+        S: Into<String>,
+    {
+        let group = group.into();
+        let mut state = self.shared.state.lock().unwrap();
+        if !state.groups.insert(group.clone()) {
+            return false;
+        }
+        self.shared.broadcast(&state, Command::Join(group));
+        true
+    }
+
+    /// Leaves the group, notifying the peers.
+    ///
+    /// Returns `false` if the group was not joined.
+    pub fn leave(&self, group: &str) -> bool {
+        let mut state = self.shared.state.lock().unwrap();
+        if !state.groups.remove(group) {
+            return false;
+        }
+        self.shared
+            .broadcast(&state, Command::Leave(group.to_owned()));
+        true
+    }
+
+    /// Returns the groups joined by the node.
+    pub fn groups(&self) -> Vec<String> {
+        let state = self.shared.state.lock().unwrap();
+        state.groups.iter().cloned().collect()
+    }
+
+    /// Sends a message to the peer.
+    ///
+    /// # Returned Errors
+    /// * `NotFound` (unknown peer)
+    /// * `WouldBlock` (the peer is not reading its messages)
+    pub fn whisper<C>(&self, peer: NodeId, content: C) -> io::Result<()>
+    where
+        C: Into<Vec<u8>>,
+    {
+        let state = self.shared.state.lock().unwrap();
+        let peer = state
+            .peers
+            .get(&peer)
+            .filter(|peer| peer.entered())
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "unknown peer")
+            })?;
+        peer.send(&self.shared.packet(Command::Whisper(content.into())))
+    }
+
+    /// Sends a message to the peers that joined the group, returning
+    /// the number of peers the message was sent to.
+    ///
+    /// The node itself does not receive the message, even if it joined
+    /// the group. Peers that cannot receive the message are skipped.
+    pub fn shout<S, C>(&self, group: S, content: C) -> io::Result<usize>
+    where
+        S: Into<String>,
+        C: Into<Vec<u8>>,
+    {
+        let group = group.into();
+        let packet = self.shared.packet(Command::Shout {
+            group: group.clone(),
+            content: content.into(),
+        });
+
+        let state = self.shared.state.lock().unwrap();
+        let count = state
+            .peers
+            .values()
+            .filter(|peer| peer.entered() && peer.groups.contains(&group))
+            .filter(|peer| peer.send(&packet).is_ok())
+            .count();
+        Ok(count)
+    }
+
+    /// Returns the peers that introduced themselves, in no particular order.
+    pub fn peers(&self) -> Vec<NodeId> {
+        let state = self.shared.state.lock().unwrap();
+        state
+            .peers
+            .iter()
+            .filter(|(_, peer)| peer.entered())
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Returns the peers that joined the group, in no particular order.
+    pub fn peers_by_group(&self, group: &str) -> Vec<NodeId> {
+        let state = self.shared.state.lock().unwrap();
+        state
+            .peers
+            .iter()
+            .filter(|(_, peer)| peer.entered() && peer.groups.contains(group))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Returns the name of the peer, if known.
+    pub fn peer_name(&self, peer: NodeId) -> Option<String> {
+        let state = self.shared.state.lock().unwrap();
+        state.peers.get(&peer).and_then(|peer| peer.name.clone())
+    }
+
+    /// Returns the groups joined by the peer, if known.
+    pub fn peer_groups(&self, peer: NodeId) -> Option<Vec<String>> {
+        let state = self.shared.state.lock().unwrap();
+        state
+            .peers
+            .get(&peer)
+            .filter(|peer| peer.entered())
+            .map(|peer| peer.groups.iter().cloned().collect())
+    }
+
+    /// Blocks until an event is received.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidCtx`] (the context of the node was terminated)
+    ///
+    /// [`InvalidCtx`]: ../enum.ErrorKind.html#variant.InvalidCtx
+    pub fn recv(&self) -> Result<NodeEvent, Error> {
+        // The node thread only stops early when the context is terminated.
+        self.events
+            .lock()
+            .unwrap()
+            .recv()
+            .map_err(|_| Error::new(ErrorKind::InvalidCtx))
+    }
+
+    /// Returns an event if one was received.
+    pub fn try_recv(&self) -> Option<NodeEvent> {
+        self.events.lock().unwrap().try_recv().ok()
+    }
+
+    /// Blocks until an event is received or the timeout elapsed.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<NodeEvent> {
+        self.events.lock().unwrap().recv_timeout(timeout).ok()
+    }
+}
+
+impl fmt::Debug for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Node")
+            .field("id", &self.shared.id)
+            .field("name", &self.shared.name)
+            .field("endpoint", &self.endpoint)
+            .finish()
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        self.beacon.silence();
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+
+        let state = self.shared.state.lock().unwrap();
+        self.shared.broadcast(&state, Command::Bye);
+    }
+}
+
+// Parses the announcement of a node into its id and the port of its server.
+fn parse_announcement(peer: &BeaconPeer) -> Option<(NodeId, net::SocketAddr)> {
+    let announcement = peer.announcement();
+    if announcement.len() != ANNOUNCEMENT_SIZE
+        || !announcement.starts_with(ANNOUNCEMENT_PREFIX)
+    {
+        return None;
+    }
+    let bytes = &announcement[ANNOUNCEMENT_PREFIX.len()..];
+    let mut id = [0; 16];
+    id.copy_from_slice(&bytes[..16]);
+    let port = u16::from_be_bytes([bytes[16], bytes[17]]);
+    Some((NodeId(id), net::SocketAddr::new(peer.addr().ip(), port)))
+}
+
+impl Shared {
+    fn packet(&self, command: Command) -> Packet {
+        Packet {
+            sender: self.id,
+            command,
+        }
+    }
+
+    fn hello(&self, state: &State, reply: bool) -> Packet {
+        self.packet(Command::Hello(Hello {
+            name: self.name.clone(),
+            headers: self.config.headers.clone(),
+            groups: state.groups.clone(),
+            reply,
+        }))
+    }
+
+    fn broadcast(&self, state: &State, command: Command) {
+        let packet = self.packet(command);
+        for peer in state.peers.values() {
+            if peer.client.is_some() {
+                let _ = peer.send(&packet);
+            }
+        }
+    }
+
+    fn connect(&self, addr: net::SocketAddr) -> io::Result<Client> {
+        let client = ClientBuilder::new()
+            .connect(TcpAddr::new(addr))
+            .heartbeat(&self.config.heartbeat)
+            .with_ctx(self.handle)?;
+        client.set_linger(Some(BYE_LINGER))?;
+        Ok(client)
+    }
+
+    // Connects to the peers announced by the beacon.
+    fn discover(
+        &self,
+        beacon_peers: &[BeaconPeer],
+        events: &mpsc::Sender<NodeEvent>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        for beacon_peer in beacon_peers {
+            let (id, addr) = match parse_announcement(beacon_peer) {
+                Some(parsed) if parsed.0 != self.id => parsed,
+                _ => continue,
+            };
+            if let Some(Peer {
+                client: Some(_), ..
+            }) = state.peers.get(&id)
+            {
+                continue;
+            }
+
+            let client = match self.connect(addr) {
+                Ok(client) => client,
+                Err(err) => {
+                    error!("unable to connect to node {}: {}", id, err);
+                    continue;
+                }
+            };
+            let hello = self.hello(&state, false);
+            let peer = state.peers.entry(id).or_insert_with(Peer::new);
+            peer.client = Some(client);
+            let _ = peer.send(&hello);
+            if let Some(hello) = peer.hello.take() {
+                peer.enter(id, hello, events);
+            }
+        }
+    }
+
+    fn handle(
+        &self,
+        packet: Packet,
+        beacon: &Beacon,
+        events: &mpsc::Sender<NodeEvent>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let sender = packet.sender;
+        let reply = match &packet.command {
+            Command::Hello(hello) if !hello.reply => {
+                Some(self.hello(&state, true))
+            }
+            _ => None,
+        };
+
+        let peer = state.peers.entry(sender).or_insert_with(Peer::new);
+        peer.last_seen = Instant::now();
+        peer.evasive = false;
+
+        if let Some(reply) = reply {
+            let _ = peer.send(&reply);
+        }
+
+        let command = match (&peer.name, packet.command) {
+            (None, Command::Hello(hello)) => {
+                if peer.client.is_some() {
+                    peer.enter(sender, hello, events);
+                } else {
+                    peer.hello = Some(hello);
+                }
+                return;
+            }
+            // Messages sent before the hello are ignored.
+            (None, _) => return,
+            (Some(name), command) => (name.clone(), command),
+        };
+
+        match command {
+            (_, Command::Hello(_)) | (_, Command::PingOk) => (),
+            (_, Command::Ping) => {
+                let _ = peer.send(&self.packet(Command::PingOk));
+            }
+            (name, Command::Whisper(content)) => {
+                let _ = events.send(NodeEvent::Whisper {
+                    peer: sender,
+                    name,
+                    content,
+                });
+            }
+            (name, Command::Shout { group, content }) => {
+                if state.groups.contains(&group) {
+                    let _ = events.send(NodeEvent::Shout {
+                        peer: sender,
+                        name,
+                        group,
+                        content,
+                    });
+                }
+            }
+            (name, Command::Join(group)) => {
+                if peer.groups.insert(group.clone()) {
+                    let _ = events.send(NodeEvent::Join {
+                        peer: sender,
+                        name,
+                        group,
+                    });
+                }
+            }
+            (name, Command::Leave(group)) => {
+                if peer.groups.remove(&group) {
+                    let _ = events.send(NodeEvent::Leave {
+                        peer: sender,
+                        name,
+                        group,
+                    });
+                }
+            }
+            (name, Command::Bye) => {
+                state.peers.remove(&sender);
+                // Otherwise the peer would be discovered again until its
+                // announcement expires.
+                for beacon_peer in beacon.peers() {
+                    if let Some((id, _)) = parse_announcement(&beacon_peer) {
+                        if id == sender {
+                            beacon.forget(beacon_peer.addr());
+                        }
+                    }
+                }
+                let _ = events.send(NodeEvent::Exit { peer: sender, name });
+            }
+        }
+    }
+
+    // Pings the silent peers and expires the dead ones.
+    fn check_peers(&self, events: &mpsc::Sender<NodeEvent>) {
+        let interval = self.config.heartbeat.interval();
+        let evasive = self.config.evasive();
+        let expiry = self.config.expiry;
+        let ping = self.packet(Command::Ping);
+        let now = Instant::now();
+
+        let mut state = self.state.lock().unwrap();
+        state.peers.retain(|&id, peer| {
+            let silent = now.duration_since(peer.last_seen);
+            if silent >= expiry {
+                if let Some(name) = peer.name.take() {
+                    let _ = events.send(NodeEvent::Exit { peer: id, name });
+                }
+                return false;
+            }
+
+            if silent >= evasive && !peer.evasive {
+                peer.evasive = true;
+                if let Some(name) = &peer.name {
+                    let _ = events.send(NodeEvent::Evasive {
+                        peer: id,
+                        name: name.clone(),
+                    });
+                }
+            }
+            if silent >= interval
+                && now.duration_since(peer.last_ping) >= interval
+            {
+                peer.last_ping = now;
+                let _ = peer.send(&ping);
+            }
+            true
+        });
+    }
+}
+
+fn run(
+    shared: &Shared,
+    server: &Server,
+    beacon: &Beacon,
+    events: &mpsc::Sender<NodeEvent>,
+) {
+    while !shared.stopped.load(Ordering::SeqCst) {
+        shared.discover(&beacon.peers(), events);
+
+        match server.recv_msg() {
+            Ok(msg) => match bincode::deserialize(msg.as_bytes()) {
+                Ok(packet) => shared.handle(packet, beacon, events),
+                Err(err) => error!("invalid node packet: {}", err),
+            },
+            Err(err) => match err.kind() {
+                ErrorKind::WouldBlock | ErrorKind::Interrupted => (),
+                ErrorKind::InvalidCtx => break,
+                _ => error!("unable to receive node packet: {}", err),
+            },
+        }
+
+        shared.check_peers(events);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::net::UdpSocket;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    // Nodes on the loopback interface, on a beacon port unused by other tests.
+    fn builder() -> NodeBuilder {
+        let port = UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let mut beacon = BeaconConfig::new();
+        beacon.set_port(port);
+        beacon.set_addr(Ipv4Addr::new(239, 255, 42, 99).into());
+        beacon.set_interface(Some(Ipv4Addr::LOCALHOST.into()));
+        beacon.set_interval(Duration::from_millis(50));
+
+        let mut builder = NodeBuilder::new();
+        builder
+            .interface(Ipv4Addr::LOCALHOST)
+            .beacon(beacon)
+            .heartbeat(
+                Heartbeat::new(Duration::from_millis(100))
+                    .add_timeout(Duration::from_millis(300)),
+            )
+            .expiry(Duration::from_secs(1));
+        builder
+    }
+
+    fn expect<F>(node: &Node, mut f: F) -> NodeEvent
+    where
+        F: FnMut(&NodeEvent) -> bool,
+    {
+        loop {
+            let event = node.recv_timeout(TIMEOUT).expect("no event");
+            if f(&event) {
+                return event;
+            }
+        }
+    }
+
+    #[test]
+    fn test_ser_de() {
+        let mut config = NodeConfig::new();
+        config.set_name(Some("node"));
+        config.set_interface(Some(Ipv4Addr::LOCALHOST.into()));
+        config.set_expiry(Duration::from_secs(10));
+
+        let ron = serde_yaml::to_string(&config).unwrap();
+        let de: NodeConfig = serde_yaml::from_str(&ron).unwrap();
+        assert_eq!(config, de);
+    }
+
+    #[test]
+    fn test_enter_and_exit() {
+        let mut builder = builder();
+        let a = builder.name("a").header("key", "value").build().unwrap();
+        let b = builder.name("b").build().unwrap();
+
+        match expect(&b, |e| matches!(e, NodeEvent::Enter { .. })) {
+            NodeEvent::Enter {
+                peer,
+                name,
+                headers,
+            } => {
+                assert_eq!(peer, a.id());
+                assert_eq!(name, "a");
+                assert_eq!(headers.get("key").unwrap(), "value");
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(b.peers(), vec![a.id()]);
+        assert_eq!(b.peer_name(a.id()).unwrap(), "a");
+
+        let id = a.id();
+        drop(a);
+        let event = expect(&b, |e| matches!(e, NodeEvent::Exit { .. }));
+        assert_eq!(event.peer(), id);
+        assert!(b.peers().is_empty());
+
+        // The announcement of `a` must not be discovered again.
+        thread::sleep(Duration::from_millis(300));
+        assert!(b.shared.state.lock().unwrap().peers.is_empty());
+    }
+
+    #[test]
+    fn test_recv_terminated_ctx() {
+        let ctx = Ctx::new();
+        let node = builder().with_ctx(ctx.handle()).unwrap();
+
+        ctx.shutdown();
+        let err = node.recv().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidCtx);
+    }
+
+    #[test]
+    fn test_groups() {
+        let mut builder = builder();
+        let a = builder.name("a").build().unwrap();
+        let b = builder.name("b").build().unwrap();
+        expect(&a, |e| matches!(e, NodeEvent::Enter { .. }));
+        expect(&b, |e| matches!(e, NodeEvent::Enter { .. }));
+
+        assert!(b.join("group"));
+        assert!(!b.join("group"));
+        match expect(&a, |e| matches!(e, NodeEvent::Join { .. })) {
+            NodeEvent::Join { peer, group, .. } => {
+                assert_eq!(peer, b.id());
+                assert_eq!(group, "group");
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(a.peers_by_group("group"), vec![b.id()]);
+        assert_eq!(a.peer_groups(b.id()).unwrap(), vec!["group"]);
+
+        assert_eq!(a.shout("group", "shout").unwrap(), 1);
+        assert_eq!(a.shout("other", "shout").unwrap(), 0);
+        match expect(&b, |e| matches!(e, NodeEvent::Shout { .. })) {
+            NodeEvent::Shout {
+                name,
+                group,
+                content,
+                ..
+            } => {
+                assert_eq!(name, "a");
+                assert_eq!(group, "group");
+                assert_eq!(content, b"shout");
+            }
+            _ => unreachable!(),
+        }
+
+        assert!(b.leave("group"));
+        expect(&a, |e| matches!(e, NodeEvent::Leave { .. }));
+        assert!(a.peers_by_group("group").is_empty());
+    }
+
+    #[test]
+    fn test_whisper() {
+        let mut builder = builder();
+        let a = builder.name("a").build().unwrap();
+        let b = builder.name("b").build().unwrap();
+        expect(&a, |e| matches!(e, NodeEvent::Enter { .. }));
+
+        let err = a.whisper(a.id(), "self").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        a.whisper(b.id(), "whisper").unwrap();
+        match expect(&b, |e| matches!(e, NodeEvent::Whisper { .. })) {
+            NodeEvent::Whisper { peer, content, .. } => {
+                assert_eq!(peer, a.id());
+                assert_eq!(content, b"whisper");
+            }
+            _ => unreachable!(),
+        }
+    }
+}
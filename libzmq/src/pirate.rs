@@ -0,0 +1,1328 @@
+//! A reliable request-reply queue, using the Paranoid Pirate pattern.
+//!
+//! A [`PirateBroker`] sits between clients and workers. Its frontend
+//! `Server` receives the requests of the [`PirateClient`]s, which it hands
+//! out to the least recently used ready [`PirateWorker`] connected to its
+//! backend `Server`. Workers are tracked by their `RoutingId`.
+//!
+//! The broker and its workers exchange heartbeats in both directions. The
+//! broker forgets about a worker that was silent for the heartbeat timeout,
+//! while a worker reconnects to a silent broker, waiting longer after every
+//! failed attempt.
+//!
+//! A request that no ready worker accepts, since they all disconnected, is
+//! queued until a worker becomes ready.
+//!
+//! A request sent to a worker that dies is lost. The client therefore waits
+//! for the reply for a limited time, after which it re-creates its `Client`
+//! and sends the request again, backing off exponentially between retries.
+//!
+//! [`PirateBroker`]: struct.PirateBroker.html
+//! [`PirateClient`]: struct.PirateClient.html
+//! [`PirateWorker`]: struct.PirateWorker.html
+
+use crate::{
+    addr::Endpoint,
+    core::{
+        BuildHeartbeating, BuildRecv, BuildSocket, RecvMsg, SendMsg, Socket,
+    },
+    error::{Error, ErrorKind},
+    poll::{Events, PollId, Poller, EMPTY, READABLE},
+    socket::{Client, ClientBuilder, Server, ServerBuilder},
+    Ctx, CtxHandle, Heartbeat, Msg, Period, RoutingId,
+};
+
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+
+use std::{
+    cmp,
+    collections::VecDeque,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+const DEFAULT_RECONNECT: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_RECONNECT: Duration = Duration::from_secs(32);
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_millis(2500);
+const DEFAULT_RETRIES: usize = 3;
+// How often the broker thread checks whether it was dropped.
+const TICK: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum ToWorker {
+    Heartbeat,
+    Request {
+        client: RoutingId,
+        seq: u64,
+        body: Vec<u8>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum FromWorker {
+    Ready,
+    Heartbeat,
+    Reply {
+        client: RoutingId,
+        seq: u64,
+        body: Vec<u8>,
+    },
+}
+
+// The requests and replies of clients, tagged with a sequence number so that
+// the late reply to a request that was retried is discarded.
+type Tagged = (u64, Vec<u8>);
+
+fn default_heartbeat() -> Heartbeat {
+    Heartbeat::new(DEFAULT_INTERVAL).add_timeout(DEFAULT_TIMEOUT)
+}
+
+// How long a peer may be silent, which defaults to 3 intervals.
+fn liveness(heartbeat: &Heartbeat) -> Duration {
+    match heartbeat.timeout() {
+        Period::Finite(timeout) => timeout,
+        Period::Infinite => 3 * heartbeat.interval(),
+    }
+}
+
+fn missing(msg: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidInput(msg))
+}
+
+fn routing_id(msg: &Msg) -> Result<RoutingId, Error> {
+    msg.routing_id()
+        .ok_or_else(|| Error::new(ErrorKind::NotFound("missing routing id")))
+}
+
+/// A config for a [`PirateBroker`].
+///
+/// Usefull in configuration files.
+///
+/// [`PirateBroker`]: struct.PirateBroker.html
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PirateBrokerConfig {
+    frontend: Option<Endpoint>,
+    backend: Option<Endpoint>,
+    heartbeat: Heartbeat,
+}
+
+impl PirateBrokerConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a `PirateBroker` from the configuration, using the global
+    /// context.
+    pub fn build(&self) -> Result<PirateBroker, Error> {
+        self.with_ctx(Ctx::global())
+    }
+
+    /// Builds a `PirateBroker` from the configuration, using the given
+    /// context.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidInput`] (missing endpoint)
+    ///
+    /// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
+    pub fn with_ctx(&self, handle: CtxHandle) -> Result<PirateBroker, Error> {
+        PirateBroker::with_config(self.clone(), handle)
+    }
+
+    /// The endpoint to which the `Server` that receives the requests of the
+    /// clients binds.
+    pub fn frontend(&self) -> Option<&Endpoint> {
+        self.frontend.as_ref()
+    }
+
+    pub fn set_frontend(&mut self, maybe: Option<Endpoint>) {
+        self.frontend = maybe;
+    }
+
+    /// The endpoint to which the `Server` that the workers connect to binds.
+    pub fn backend(&self) -> Option<&Endpoint> {
+        self.backend.as_ref()
+    }
+
+    pub fn set_backend(&mut self, maybe: Option<Endpoint>) {
+        self.backend = maybe;
+    }
+
+    /// The heartbeat exchanged with the workers, which must match the one
+    /// of the workers. A worker is forgotten once silent for the heartbeat
+    /// timeout, or for 3 intervals if there is none.
+    ///
+    /// # Default
+    /// An interval of 1 second and a timeout of 3 seconds.
+    pub fn heartbeat(&self) -> &Heartbeat {
+        &self.heartbeat
+    }
+
+    pub fn set_heartbeat(&mut self, heartbeat: Heartbeat) {
+        self.heartbeat = heartbeat;
+    }
+}
+
+impl Default for PirateBrokerConfig {
+    fn default() -> Self {
+        Self {
+            frontend: None,
+            backend: None,
+            heartbeat: default_heartbeat(),
+        }
+    }
+}
+
+/// A convenience builder for a [`PirateBroker`].
+///
+/// [`PirateBroker`]: struct.PirateBroker.html
+#[derive(
+    Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+pub struct PirateBrokerBuilder {
+    inner: PirateBrokerConfig,
+}
+
+impl PirateBrokerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a `PirateBroker` from a `PirateBrokerBuilder`, using the
+    /// global context.
+    pub fn build(&self) -> Result<PirateBroker, Error> {
+        self.inner.build()
+    }
+
+    /// Builds a `PirateBroker` from a `PirateBrokerBuilder`, using the given
+    /// context.
+    pub fn with_ctx(&self, handle: CtxHandle) -> Result<PirateBroker, Error> {
+        self.inner.with_ctx(handle)
+    }
+
+    /// See [`set_frontend`].
+    ///
+    /// [`set_frontend`]: struct.PirateBrokerConfig.html#method.set_frontend
+    pub fn frontend<E>(&mut self, endpoint: E) -> &mut Self
+    where
+        E: Into<Endpoint>,
+    {
+        self.inner.set_frontend(Some(endpoint.into()));
+        self
+    }
+
+    /// See [`set_backend`].
+    ///
+    /// [`set_backend`]: struct.PirateBrokerConfig.html#method.set_backend
+    pub fn backend<E>(&mut self, endpoint: E) -> &mut Self
+    where
+        E: Into<Endpoint>,
+    {
+        self.inner.set_backend(Some(endpoint.into()));
+        self
+    }
+
+    /// See [`set_heartbeat`].
+    ///
+    /// [`set_heartbeat`]: struct.PirateBrokerConfig.html#method.set_heartbeat
+    pub fn heartbeat<H>(&mut self, heartbeat: H) -> &mut Self
+    where
+        H: Into<Heartbeat>,
+    {
+        self.inner.set_heartbeat(heartbeat.into());
+        self
+    }
+}
+
+struct Worker {
+    id: RoutingId,
+    expiry: Instant,
+}
+
+struct Queue {
+    frontend: Server,
+    backend: Server,
+    heartbeat: Heartbeat,
+    // The ready workers, from the least recently used.
+    workers: Vec<Worker>,
+    // The requests that no worker accepted yet, from the oldest.
+    requests: VecDeque<Vec<u8>>,
+}
+
+impl Queue {
+    fn ready(&mut self, id: RoutingId) -> Result<(), Error> {
+        self.workers.retain(|worker| worker.id != id);
+        self.workers.push(Worker {
+            id,
+            expiry: Instant::now() + liveness(&self.heartbeat),
+        });
+        self.dispatch()
+    }
+
+    // Hands out the queued requests to the ready workers, skipping the
+    // workers that disconnected since they were ready.
+    fn dispatch(&mut self) -> Result<(), Error> {
+        while let Some(bytes) = self.requests.front() {
+            if self.workers.is_empty() {
+                break;
+            }
+            let worker = self.workers.remove(0);
+            match self.backend.try_route(bytes.as_slice(), worker.id) {
+                Ok(()) => {
+                    self.requests.pop_front();
+                }
+                Err(err) => match err.kind() {
+                    ErrorKind::HostUnreachable | ErrorKind::WouldBlock => (),
+                    _ => return Err(err.cast()),
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_worker(&mut self) -> Result<(), Error> {
+        let msg = self.backend.recv_msg()?;
+        let id = routing_id(&msg)?;
+
+        match bincode::deserialize(msg.as_bytes()) {
+            Ok(FromWorker::Ready) => self.ready(id)?,
+            Ok(FromWorker::Heartbeat) => {
+                let expiry = Instant::now() + liveness(&self.heartbeat);
+                if let Some(worker) =
+                    self.workers.iter_mut().find(|worker| worker.id == id)
+                {
+                    worker.expiry = expiry;
+                }
+            }
+            Ok(FromWorker::Reply { client, seq, body }) => {
+                self.ready(id)?;
+                let bytes = bincode::serialize(&(seq, body)).unwrap();
+                // The client might have given up on the request.
+                if let Err(err) = self.frontend.try_route(bytes, client) {
+                    match err.kind() {
+                        ErrorKind::HostUnreachable | ErrorKind::WouldBlock => {}
+                        _ => return Err(err.cast()),
+                    }
+                }
+            }
+            Err(err) => warn!("invalid worker message: {}", err),
+        }
+        Ok(())
+    }
+
+    fn handle_client(&mut self) -> Result<(), Error> {
+        let msg = self.frontend.recv_msg()?;
+        let client = routing_id(&msg)?;
+        let (seq, body): Tagged = match bincode::deserialize(msg.as_bytes()) {
+            Ok(tagged) => tagged,
+            Err(err) => {
+                warn!("invalid client request: {}", err);
+                return Ok(());
+            }
+        };
+
+        let bytes =
+            bincode::serialize(&ToWorker::Request { client, seq, body })
+                .unwrap();
+        self.requests.push_back(bytes);
+        self.dispatch()
+    }
+
+    fn heartbeat(&mut self) -> Result<(), Error> {
+        let now = Instant::now();
+        self.workers.retain(|worker| worker.expiry > now);
+
+        let bytes = bincode::serialize(&ToWorker::Heartbeat).unwrap();
+        for worker in &self.workers {
+            if let Err(err) =
+                self.backend.try_route(bytes.as_slice(), worker.id)
+            {
+                match err.kind() {
+                    ErrorKind::HostUnreachable | ErrorKind::WouldBlock => (),
+                    _ => return Err(err.cast()),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn run(
+        &mut self,
+        stopped: &AtomicBool,
+        ready: &AtomicUsize,
+    ) -> Result<(), Error> {
+        let mut poller = Poller::new();
+        poller.add(&self.backend, PollId(0), READABLE)?;
+        poller.add(&self.frontend, PollId(1), EMPTY)?;
+
+        let mut events = Events::new();
+        let mut next_heartbeat = Instant::now() + self.heartbeat.interval();
+
+        while !stopped.load(Ordering::SeqCst) {
+            // Requests are only received when they can be handed out.
+            let trigger = if self.workers.is_empty() {
+                EMPTY
+            } else {
+                READABLE
+            };
+            poller.modify(&self.frontend, trigger)?;
+
+            let mut result = poller.poll(&mut events, Period::Finite(TICK));
+            match &result {
+                Ok(()) => {
+                    for event in &events {
+                        result = match event.id() {
+                            PollId(0) => self.handle_worker(),
+                            _ => self.handle_client(),
+                        };
+                        if result.is_err() {
+                            break;
+                        }
+                    }
+                }
+                // Nothing happened during the tick, but the workers must
+                // still expire.
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                    result = Ok(());
+                }
+                Err(_) => (),
+            }
+
+            if result.is_ok() && Instant::now() >= next_heartbeat {
+                next_heartbeat = Instant::now() + self.heartbeat.interval();
+                result = self.heartbeat();
+            }
+            ready.store(self.workers.len(), Ordering::SeqCst);
+
+            if let Err(err) = result {
+                match err.kind() {
+                    ErrorKind::WouldBlock | ErrorKind::Interrupted => (),
+                    ErrorKind::InvalidCtx => break,
+                    _ => error!("pirate broker error: {}", err),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A broker that queues the requests of [`PirateClient`]s to
+/// [`PirateWorker`]s.
+///
+/// The broker runs in a background thread, which stops when it is dropped.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), anyhow::Error> {
+/// use libzmq::{pirate::*, prelude::*, *};
+/// use std::thread;
+///
+/// let addr: TcpAddr = "127.0.0.1:*".try_into()?;
+/// let broker = PirateBrokerBuilder::new()
+///     .frontend(addr.clone())
+///     .backend(addr)
+///     .build()?;
+///
+/// let mut worker = PirateWorkerBuilder::new()
+///     .broker(broker.backend_endpoint().clone())
+///     .build()?;
+/// thread::spawn(move || -> Result<(), Error> {
+///     loop {
+///         let request = worker.recv()?;
+///         let reply = request.body().to_ascii_uppercase();
+///         worker.reply(&request, reply)?;
+///     }
+/// });
+///
+/// let mut client = PirateClientBuilder::new()
+///     .broker(broker.frontend_endpoint().clone())
+///     .build()?;
+/// assert_eq!(client.request("ahoy")?, b"AHOY");
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// [`PirateClient`]: struct.PirateClient.html
+/// [`PirateWorker`]: struct.PirateWorker.html
+pub struct PirateBroker {
+    frontend: Endpoint,
+    backend: Endpoint,
+    ready: Arc<AtomicUsize>,
+    stopped: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl PirateBroker {
+    fn with_config(
+        config: PirateBrokerConfig,
+        handle: CtxHandle,
+    ) -> Result<Self, Error> {
+        let frontend = config
+            .frontend
+            .clone()
+            .ok_or_else(|| missing("missing frontend endpoint"))?;
+        let backend = config
+            .backend
+            .clone()
+            .ok_or_else(|| missing("missing backend endpoint"))?;
+
+        let frontend = ServerBuilder::new().bind(frontend).with_ctx(handle)?;
+        let backend = ServerBuilder::new()
+            .bind(backend)
+            .heartbeat(&config.heartbeat)
+            .with_ctx(handle)?;
+
+        let ready = Arc::new(AtomicUsize::new(0));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let mut queue = Queue {
+            frontend: frontend.clone(),
+            backend: backend.clone(),
+            heartbeat: config.heartbeat,
+            workers: Vec::new(),
+            requests: VecDeque::new(),
+        };
+
+        let thread = {
+            let ready = Arc::clone(&ready);
+            let stopped = Arc::clone(&stopped);
+            thread::Builder::new()
+                .name("pirate-broker".to_owned())
+                .spawn(move || {
+                    if let Err(err) = queue.run(&stopped, &ready) {
+                        error!("pirate broker stopped: {}", err);
+                    }
+                })
+                .map_err(Error::from_io)?
+        };
+
+        Ok(Self {
+            frontend: frontend.last_endpoint()?,
+            backend: backend.last_endpoint()?,
+            ready,
+            stopped,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the endpoint to which the clients connect.
+    pub fn frontend_endpoint(&self) -> &Endpoint {
+        &self.frontend
+    }
+
+    /// Returns the endpoint to which the workers connect.
+    pub fn backend_endpoint(&self) -> &Endpoint {
+        &self.backend
+    }
+
+    /// Returns the number of workers ready to handle a request.
+    pub fn ready_workers(&self) -> usize {
+        self.ready.load(Ordering::SeqCst)
+    }
+}
+
+impl fmt::Debug for PirateBroker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PirateBroker")
+            .field("frontend", &self.frontend)
+            .field("backend", &self.backend)
+            .field("ready_workers", &self.ready_workers())
+            .finish()
+    }
+}
+
+impl Drop for PirateBroker {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A config for a [`PirateWorker`].
+///
+/// Usefull in configuration files.
+///
+/// [`PirateWorker`]: struct.PirateWorker.html
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PirateWorkerConfig {
+    broker: Option<Endpoint>,
+    heartbeat: Heartbeat,
+    #[serde(with = "humantime_serde")]
+    reconnect: Duration,
+    #[serde(with = "humantime_serde")]
+    max_reconnect: Duration,
+}
+
+impl PirateWorkerConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a `PirateWorker` from the configuration, using the global
+    /// context.
+    pub fn build(&self) -> Result<PirateWorker, Error> {
+        self.with_ctx(Ctx::global())
+    }
+
+    /// Builds a `PirateWorker` from the configuration, using the given
+    /// context.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidInput`] (missing endpoint)
+    ///
+    /// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
+    pub fn with_ctx(&self, handle: CtxHandle) -> Result<PirateWorker, Error> {
+        PirateWorker::with_config(self.clone(), handle)
+    }
+
+    /// The backend endpoint of the broker.
+    pub fn broker(&self) -> Option<&Endpoint> {
+        self.broker.as_ref()
+    }
+
+    pub fn set_broker(&mut self, maybe: Option<Endpoint>) {
+        self.broker = maybe;
+    }
+
+    /// The heartbeat exchanged with the broker, which must match the one of
+    /// the broker. The worker reconnects once the broker was silent for the
+    /// heartbeat timeout, or for 3 intervals if there is none.
+    ///
+    /// # Default
+    /// An interval of 1 second and a timeout of 3 seconds.
+    pub fn heartbeat(&self) -> &Heartbeat {
+        &self.heartbeat
+    }
+
+    pub fn set_heartbeat(&mut self, heartbeat: Heartbeat) {
+        self.heartbeat = heartbeat;
+    }
+
+    /// How long to wait before the first reconnection, which doubles after
+    /// every failed attempt.
+    ///
+    /// # Default
+    /// 1 second.
+    pub fn reconnect(&self) -> Duration {
+        self.reconnect
+    }
+
+    pub fn set_reconnect(&mut self, reconnect: Duration) {
+        self.reconnect = reconnect;
+    }
+
+    /// The maximum time to wait before reconnecting.
+    ///
+    /// # Default
+    /// 32 seconds.
+    pub fn max_reconnect(&self) -> Duration {
+        self.max_reconnect
+    }
+
+    pub fn set_max_reconnect(&mut self, max: Duration) {
+        self.max_reconnect = max;
+    }
+}
+
+impl Default for PirateWorkerConfig {
+    fn default() -> Self {
+        Self {
+            broker: None,
+            heartbeat: default_heartbeat(),
+            reconnect: DEFAULT_RECONNECT,
+            max_reconnect: DEFAULT_MAX_RECONNECT,
+        }
+    }
+}
+
+/// A convenience builder for a [`PirateWorker`].
+///
+/// [`PirateWorker`]: struct.PirateWorker.html
+#[derive(
+    Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+pub struct PirateWorkerBuilder {
+    inner: PirateWorkerConfig,
+}
+
+impl PirateWorkerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a `PirateWorker` from a `PirateWorkerBuilder`, using the
+    /// global context.
+    pub fn build(&self) -> Result<PirateWorker, Error> {
+        self.inner.build()
+    }
+
+    /// Builds a `PirateWorker` from a `PirateWorkerBuilder`, using the given
+    /// context.
+    pub fn with_ctx(&self, handle: CtxHandle) -> Result<PirateWorker, Error> {
+        self.inner.with_ctx(handle)
+    }
+
+    /// See [`set_broker`].
+    ///
+    /// [`set_broker`]: struct.PirateWorkerConfig.html#method.set_broker
+    pub fn broker<E>(&mut self, endpoint: E) -> &mut Self
+    where
+        E: Into<Endpoint>,
+    {
+        self.inner.set_broker(Some(endpoint.into()));
+        self
+    }
+
+    /// See [`set_heartbeat`].
+    ///
+    /// [`set_heartbeat`]: struct.PirateWorkerConfig.html#method.set_heartbeat
+    pub fn heartbeat<H>(&mut self, heartbeat: H) -> &mut Self
+    where
+        H: Into<Heartbeat>,
+    {
+        self.inner.set_heartbeat(heartbeat.into());
+        self
+    }
+
+    /// See [`set_reconnect`].
+    ///
+    /// [`set_reconnect`]: struct.PirateWorkerConfig.html#method.set_reconnect
+    pub fn reconnect(&mut self, reconnect: Duration) -> &mut Self {
+        self.inner.set_reconnect(reconnect);
+        self
+    }
+
+    /// See [`set_max_reconnect`].
+    ///
+    /// [`set_max_reconnect`]: struct.PirateWorkerConfig.html#method.set_max_reconnect
+    pub fn max_reconnect(&mut self, max: Duration) -> &mut Self {
+        self.inner.set_max_reconnect(max);
+        self
+    }
+}
+
+/// A request received by a [`PirateWorker`].
+///
+/// [`PirateWorker`]: struct.PirateWorker.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PirateRequest {
+    client: RoutingId,
+    seq: u64,
+    body: Vec<u8>,
+}
+
+impl PirateRequest {
+    /// Returns the body of the request.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+}
+
+/// A worker that handles the requests queued by a [`PirateBroker`].
+///
+/// Heartbeats are only exchanged with the broker while waiting in [`recv`].
+///
+/// See [`PirateBroker`] for an example.
+///
+/// [`PirateBroker`]: struct.PirateBroker.html
+/// [`recv`]: #method.recv
+pub struct PirateWorker {
+    config: PirateWorkerConfig,
+    handle: CtxHandle,
+    client: Client,
+    reconnect: Duration,
+    last_seen: Instant,
+    next_heartbeat: Instant,
+}
+
+impl PirateWorker {
+    fn with_config(
+        config: PirateWorkerConfig,
+        handle: CtxHandle,
+    ) -> Result<Self, Error> {
+        let client = Self::connect(&config, handle)?;
+        let now = Instant::now();
+
+        Ok(Self {
+            reconnect: config.reconnect,
+            config,
+            handle,
+            client,
+            last_seen: now,
+            next_heartbeat: now,
+        })
+    }
+
+    // Connects a new `Client` to the broker and tells it that we are ready.
+    fn connect(
+        config: &PirateWorkerConfig,
+        handle: CtxHandle,
+    ) -> Result<Client, Error> {
+        let broker = config
+            .broker
+            .clone()
+            .ok_or_else(|| missing("missing broker endpoint"))?;
+
+        let client = ClientBuilder::new()
+            .connect(broker)
+            .heartbeat(&config.heartbeat)
+            .recv_timeout(config.heartbeat.interval())
+            .with_ctx(handle)?;
+        let bytes = bincode::serialize(&FromWorker::Ready).unwrap();
+        client.send(bytes).map_err(Error::cast)?;
+        Ok(client)
+    }
+
+    fn send(&self, msg: &FromWorker) -> Result<(), Error> {
+        let bytes = bincode::serialize(msg).unwrap();
+        match self.client.try_send(bytes) {
+            Err(err) if err.kind() != ErrorKind::WouldBlock => Err(err.cast()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Blocks until a request is received, exchanging heartbeats with the
+    /// broker in the meantime and reconnecting to it if it is silent.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidCtx`]
+    /// * [`Interrupted`]
+    ///
+    /// [`InvalidCtx`]: ../enum.ErrorKind.html#variant.InvalidCtx
+    /// [`Interrupted`]: ../enum.ErrorKind.html#variant.Interrupted
+    pub fn recv(&mut self) -> Result<PirateRequest, Error> {
+        loop {
+            let now = Instant::now();
+            if now >= self.next_heartbeat {
+                self.next_heartbeat = now + self.config.heartbeat.interval();
+                self.send(&FromWorker::Heartbeat)?;
+            }
+
+            if now >= self.last_seen + liveness(&self.config.heartbeat) {
+                warn!("broker is silent, reconnecting in {:?}", self.reconnect);
+                thread::sleep(self.reconnect);
+                self.reconnect =
+                    cmp::min(2 * self.reconnect, self.config.max_reconnect);
+                self.client = Self::connect(&self.config, self.handle)?;
+                self.last_seen = Instant::now();
+                continue;
+            }
+
+            let msg = match self.client.recv_msg() {
+                Ok(msg) => msg,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => continue,
+                Err(err) => return Err(err),
+            };
+            self.last_seen = Instant::now();
+            self.reconnect = self.config.reconnect;
+
+            match bincode::deserialize(msg.as_bytes()) {
+                Ok(ToWorker::Heartbeat) => (),
+                Ok(ToWorker::Request { client, seq, body }) => {
+                    return Ok(PirateRequest { client, seq, body });
+                }
+                Err(err) => warn!("invalid broker message: {}", err),
+            }
+        }
+    }
+
+    /// Replies to the request, which makes the worker ready for the next
+    /// one.
+    pub fn reply<B>(
+        &mut self,
+        request: &PirateRequest,
+        body: B,
+    ) -> Result<(), Error>
+    where
+        B: Into<Vec<u8>>,
+    {
+        // The broker did not expect heartbeats while the request was handled.
+        self.last_seen = Instant::now();
+        self.send(&FromWorker::Reply {
+            client: request.client,
+            seq: request.seq,
+            body: body.into(),
+        })
+    }
+}
+
+impl fmt::Debug for PirateWorker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PirateWorker")
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+/// A config for a [`PirateClient`].
+///
+/// Usefull in configuration files.
+///
+/// [`PirateClient`]: struct.PirateClient.html
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PirateClientConfig {
+    broker: Option<Endpoint>,
+    #[serde(with = "humantime_serde")]
+    timeout: Duration,
+    retries: usize,
+    #[serde(with = "humantime_serde")]
+    backoff: Duration,
+    #[serde(with = "humantime_serde")]
+    max_backoff: Duration,
+}
+
+impl PirateClientConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a `PirateClient` from the configuration, using the global
+    /// context.
+    pub fn build(&self) -> Result<PirateClient, Error> {
+        self.with_ctx(Ctx::global())
+    }
+
+    /// Builds a `PirateClient` from the configuration, using the given
+    /// context.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidInput`] (missing endpoint)
+    ///
+    /// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
+    pub fn with_ctx(&self, handle: CtxHandle) -> Result<PirateClient, Error> {
+        PirateClient::with_config(self.clone(), handle)
+    }
+
+    /// The frontend endpoint of the broker.
+    pub fn broker(&self) -> Option<&Endpoint> {
+        self.broker.as_ref()
+    }
+
+    pub fn set_broker(&mut self, maybe: Option<Endpoint>) {
+        self.broker = maybe;
+    }
+
+    /// How long to wait for a reply before retrying.
+    ///
+    /// # Default
+    /// 2.5 seconds.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// The number of times a request is sent before giving up.
+    ///
+    /// # Default
+    /// 3
+    pub fn retries(&self) -> usize {
+        self.retries
+    }
+
+    pub fn set_retries(&mut self, retries: usize) {
+        self.retries = retries;
+    }
+
+    /// How long to wait before the first retry, which doubles after every
+    /// retry.
+    ///
+    /// # Default
+    /// 1 second.
+    pub fn backoff(&self) -> Duration {
+        self.backoff
+    }
+
+    pub fn set_backoff(&mut self, backoff: Duration) {
+        self.backoff = backoff;
+    }
+
+    /// The maximum time to wait before a retry.
+    ///
+    /// # Default
+    /// 32 seconds.
+    pub fn max_backoff(&self) -> Duration {
+        self.max_backoff
+    }
+
+    pub fn set_max_backoff(&mut self, max: Duration) {
+        self.max_backoff = max;
+    }
+}
+
+impl Default for PirateClientConfig {
+    fn default() -> Self {
+        Self {
+            broker: None,
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+            retries: DEFAULT_RETRIES,
+            backoff: DEFAULT_RECONNECT,
+            max_backoff: DEFAULT_MAX_RECONNECT,
+        }
+    }
+}
+
+/// A convenience builder for a [`PirateClient`].
+///
+/// [`PirateClient`]: struct.PirateClient.html
+#[derive(
+    Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+pub struct PirateClientBuilder {
+    inner: PirateClientConfig,
+}
+
+impl PirateClientBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a `PirateClient` from a `PirateClientBuilder`, using the
+    /// global context.
+    pub fn build(&self) -> Result<PirateClient, Error> {
+        self.inner.build()
+    }
+
+    /// Builds a `PirateClient` from a `PirateClientBuilder`, using the given
+    /// context.
+    pub fn with_ctx(&self, handle: CtxHandle) -> Result<PirateClient, Error> {
+        self.inner.with_ctx(handle)
+    }
+
+    /// See [`set_broker`].
+    ///
+    /// [`set_broker`]: struct.PirateClientConfig.html#method.set_broker
+    pub fn broker<E>(&mut self, endpoint: E) -> &mut Self
+    where
+        E: Into<Endpoint>,
+    {
+        self.inner.set_broker(Some(endpoint.into()));
+        self
+    }
+
+    /// See [`set_timeout`].
+    ///
+    /// [`set_timeout`]: struct.PirateClientConfig.html#method.set_timeout
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.inner.set_timeout(timeout);
+        self
+    }
+
+    /// See [`set_retries`].
+    ///
+    /// [`set_retries`]: struct.PirateClientConfig.html#method.set_retries
+    pub fn retries(&mut self, retries: usize) -> &mut Self {
+        self.inner.set_retries(retries);
+        self
+    }
+
+    /// See [`set_backoff`].
+    ///
+    /// [`set_backoff`]: struct.PirateClientConfig.html#method.set_backoff
+    pub fn backoff(&mut self, backoff: Duration) -> &mut Self {
+        self.inner.set_backoff(backoff);
+        self
+    }
+
+    /// See [`set_max_backoff`].
+    ///
+    /// [`set_max_backoff`]: struct.PirateClientConfig.html#method.set_max_backoff
+    pub fn max_backoff(&mut self, max: Duration) -> &mut Self {
+        self.inner.set_max_backoff(max);
+        self
+    }
+}
+
+/// A client that sends requests to a [`PirateBroker`], retrying them
+/// until a reply is received.
+///
+/// See [`PirateBroker`] for an example.
+///
+/// [`PirateBroker`]: struct.PirateBroker.html
+pub struct PirateClient {
+    config: PirateClientConfig,
+    handle: CtxHandle,
+    client: Client,
+    seq: u64,
+}
+
+impl PirateClient {
+    fn with_config(
+        config: PirateClientConfig,
+        handle: CtxHandle,
+    ) -> Result<Self, Error> {
+        let client = Self::connect(&config, handle)?;
+        Ok(Self {
+            config,
+            handle,
+            client,
+            seq: 0,
+        })
+    }
+
+    fn connect(
+        config: &PirateClientConfig,
+        handle: CtxHandle,
+    ) -> Result<Client, Error> {
+        let broker = config
+            .broker
+            .clone()
+            .ok_or_else(|| missing("missing broker endpoint"))?;
+
+        ClientBuilder::new()
+            .connect(broker)
+            .recv_timeout(TICK)
+            .with_ctx(handle)
+    }
+
+    /// Sends the request and waits for its reply.
+    ///
+    /// After every timeout, the client waits for the backoff, re-creates
+    /// its `Client` so that queued requests are discarded, and sends the
+    /// request again.
+    ///
+    /// # Returned Error Variants
+    /// * [`WouldBlock`] (no reply after every retry)
+    /// * [`InvalidCtx`]
+    ///
+    /// [`WouldBlock`]: ../enum.ErrorKind.html#variant.WouldBlock
+    /// [`InvalidCtx`]: ../enum.ErrorKind.html#variant.InvalidCtx
+    pub fn request<B>(&mut self, body: B) -> Result<Vec<u8>, Error>
+    where
+        B: Into<Vec<u8>>,
+    {
+        self.seq += 1;
+        let bytes = bincode::serialize(&(self.seq, body.into())).unwrap();
+        let mut backoff = self.config.backoff;
+
+        for attempt in 0..self.config.retries.max(1) {
+            if attempt > 0 {
+                warn!("no reply, retrying in {:?}", backoff);
+                thread::sleep(backoff);
+                backoff = cmp::min(2 * backoff, self.config.max_backoff);
+                self.client = Self::connect(&self.config, self.handle)?;
+            }
+
+            self.client.send(bytes.as_slice()).map_err(Error::cast)?;
+
+            let deadline = Instant::now() + self.config.timeout;
+            while Instant::now() < deadline {
+                let msg = match self.client.recv_msg() {
+                    Ok(msg) => msg,
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => continue,
+                    Err(err) => return Err(err),
+                };
+                match bincode::deserialize::<Tagged>(msg.as_bytes()) {
+                    Ok((seq, body)) if seq == self.seq => return Ok(body),
+                    // The late reply to a previous request.
+                    Ok(_) => (),
+                    Err(err) => warn!("invalid broker reply: {}", err),
+                }
+            }
+        }
+
+        Err(Error::new(ErrorKind::WouldBlock))
+    }
+}
+
+impl fmt::Debug for PirateClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PirateClient")
+            .field("config", &self.config)
+            .field("seq", &self.seq)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::TcpAddr;
+
+    use std::{convert::TryInto, sync::mpsc};
+
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    fn heartbeat() -> Heartbeat {
+        Heartbeat::new(INTERVAL).add_timeout(3 * INTERVAL)
+    }
+
+    fn broker(handle: CtxHandle) -> PirateBroker {
+        let addr: TcpAddr = "127.0.0.1:*".try_into().unwrap();
+        PirateBrokerBuilder::new()
+            .frontend(addr.clone())
+            .backend(addr)
+            .heartbeat(heartbeat())
+            .with_ctx(handle)
+            .unwrap()
+    }
+
+    fn worker(broker: &PirateBroker, handle: CtxHandle) -> PirateWorker {
+        PirateWorkerBuilder::new()
+            .broker(broker.backend_endpoint().clone())
+            .heartbeat(heartbeat())
+            .reconnect(INTERVAL)
+            .with_ctx(handle)
+            .unwrap()
+    }
+
+    fn client(broker: &PirateBroker, handle: CtxHandle) -> PirateClient {
+        PirateClientBuilder::new()
+            .broker(broker.frontend_endpoint().clone())
+            .timeout(5 * INTERVAL)
+            .retries(5)
+            .backoff(INTERVAL)
+            .with_ctx(handle)
+            .unwrap()
+    }
+
+    // Echoes requests until the context is shutdown.
+    fn spawn_echo(
+        mut worker: PirateWorker,
+    ) -> thread::JoinHandle<Result<(), Error>> {
+        thread::spawn(move || loop {
+            let request = worker.recv()?;
+            let body = request.body().to_vec();
+            worker.reply(&request, body)?;
+        })
+    }
+
+    fn wait_for_workers(broker: &PirateBroker, count: usize) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while broker.ready_workers() != count {
+            assert!(Instant::now() < deadline, "timed out");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_ser_de() {
+        let addr: TcpAddr = "127.0.0.1:3000".try_into().unwrap();
+        let mut config = PirateClientConfig::new();
+        config.set_broker(Some(addr.into()));
+        config.set_retries(10);
+        config.set_backoff(INTERVAL);
+
+        let ron = serde_yaml::to_string(&config).unwrap();
+        let de: PirateClientConfig = serde_yaml::from_str(&ron).unwrap();
+        assert_eq!(config, de);
+    }
+
+    #[test]
+    fn test_request_reply() {
+        let ctx = Ctx::new();
+        let broker = broker(ctx.handle());
+        let handle = spawn_echo(worker(&broker, ctx.handle()));
+        let mut client = client(&broker, ctx.handle());
+
+        for i in 0..10 {
+            let body = format!("request {}", i);
+            assert_eq!(client.request(body.as_str()).unwrap(), body.as_bytes());
+        }
+
+        ctx.shutdown();
+        let err = handle.join().unwrap().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidCtx);
+    }
+
+    #[test]
+    fn test_worker_killed_mid_request() {
+        let ctx = Ctx::new();
+        let broker = broker(ctx.handle());
+
+        // This worker dies as soon as it receives a request.
+        let mut doomed = worker(&broker, ctx.handle());
+        wait_for_workers(&broker, 1);
+        let (sender, receiver) = mpsc::channel();
+        let killed = thread::spawn(move || {
+            let request = doomed.recv().unwrap();
+            sender.send(request.body().to_vec()).unwrap();
+        });
+
+        let mut client = client(&broker, ctx.handle());
+        let handle = thread::spawn(move || client.request("survive"));
+
+        assert_eq!(receiver.recv().unwrap(), b"survive");
+        killed.join().unwrap();
+
+        // The retried request is handled by a healthy worker.
+        let echo = spawn_echo(worker(&broker, ctx.handle()));
+        assert_eq!(handle.join().unwrap().unwrap(), b"survive");
+
+        ctx.shutdown();
+        echo.join().unwrap().unwrap_err();
+    }
+
+    #[test]
+    fn test_request_queued_without_worker() {
+        let ctx = Ctx::new();
+        let broker = broker(ctx.handle());
+
+        // The worker disconnects before the request is received.
+        let gone = worker(&broker, ctx.handle());
+        wait_for_workers(&broker, 1);
+        drop(gone);
+        thread::sleep(INTERVAL);
+
+        let mut client = PirateClientBuilder::new()
+            .broker(broker.frontend_endpoint().clone())
+            .timeout(50 * INTERVAL)
+            .retries(1)
+            .with_ctx(ctx.handle())
+            .unwrap();
+        let start = Instant::now();
+        let handle = thread::spawn(move || client.request("queued"));
+
+        // The request is handed out once a worker is ready, without a retry.
+        thread::sleep(5 * INTERVAL);
+        let echo = spawn_echo(worker(&broker, ctx.handle()));
+        assert_eq!(handle.join().unwrap().unwrap(), b"queued");
+        assert!(start.elapsed() < 50 * INTERVAL);
+
+        ctx.shutdown();
+        echo.join().unwrap().unwrap_err();
+    }
+
+    #[test]
+    fn test_silent_worker_expires() {
+        let ctx = Ctx::new();
+        let broker = broker(ctx.handle());
+
+        let worker = worker(&broker, ctx.handle());
+        wait_for_workers(&broker, 1);
+
+        // A worker that stops heartbeating is forgotten.
+        drop(worker);
+        wait_for_workers(&broker, 0);
+    }
+
+    #[test]
+    fn test_client_gives_up() {
+        let ctx = Ctx::new();
+        let broker = broker(ctx.handle());
+        let mut client = PirateClientBuilder::new()
+            .broker(broker.frontend_endpoint().clone())
+            .timeout(INTERVAL)
+            .retries(2)
+            .backoff(INTERVAL)
+            .with_ctx(ctx.handle())
+            .unwrap();
+
+        let err = client.request("nobody").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WouldBlock);
+    }
+}
@@ -4,9 +4,13 @@
 //!
 //! [`Poller`]: struct.Poller.html
 
+mod token;
+
+pub use token::*;
+
 use crate::{
     core::{GetRawSocket, Period, RawSocket},
-    error::{msg_from_errno, Error, ErrorKind},
+    error::{Error, ErrorKind},
     old::OldSocket,
     socket::*,
 };
@@ -14,10 +18,15 @@ use libzmq_sys as sys;
 use sys::errno;
 
 use bitflags::bitflags;
-
-use std::os::{
-    raw::{c_long, c_short, c_void},
-    unix::io::{AsRawFd, RawFd},
+use log::error;
+
+use std::{
+    io,
+    os::{
+        raw::{c_int, c_long, c_short, c_void},
+        unix::io::{AsRawFd, RawFd},
+    },
+    sync::Arc,
 };
 
 bitflags! {
@@ -122,6 +131,7 @@ impl From<PollId> for usize {
 /// ```
 ///
 /// [`Poller`]: struct.Poller.html#method.add
+#[derive(Copy, Clone, Debug)]
 pub enum Pollable<'a> {
     /// A `Socket` type.
     Socket(&'a RawSocket),
@@ -181,6 +191,224 @@ impl<'a> From<&'a OldSocket> for Pollable<'a> {
     }
 }
 
+// The file descriptors backing a `Waker`.
+//
+// On linux, a single `eventfd` is used for both reading and writing. On
+// other platforms, a non-blocking pipe is used instead.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct WakerFd {
+    read: RawFd,
+    write: RawFd,
+}
+
+impl WakerFd {
+    #[cfg(target_os = "linux")]
+    fn new() -> Result<Self, Error> {
+        let fd =
+            unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+
+        if fd == -1 {
+            Err(last_os_error())
+        } else {
+            Ok(Self {
+                read: fd,
+                write: fd,
+            })
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn new() -> Result<Self, Error> {
+        let mut fds: [RawFd; 2] = [-1; 2];
+        let rc = unsafe { libc::pipe(fds.as_mut_ptr()) };
+        if rc == -1 {
+            return Err(last_os_error());
+        }
+
+        let this = Self {
+            read: fds[0],
+            write: fds[1],
+        };
+
+        for &fd in &fds {
+            let rc = unsafe {
+                let flags = libc::fcntl(fd, libc::F_GETFL);
+                libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK)
+            };
+            if rc == -1 {
+                return Err(last_os_error());
+            }
+
+            let rc =
+                unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+            if rc == -1 {
+                return Err(last_os_error());
+            }
+        }
+
+        Ok(this)
+    }
+
+    fn wake(&self) {
+        // An `eventfd` requires a 8 byte write.
+        let buf = 1u64.to_ne_bytes();
+        let rc = unsafe {
+            libc::write(self.write, buf.as_ptr() as *const c_void, buf.len())
+        };
+
+        if rc == -1 {
+            let err = io::Error::last_os_error();
+            // If the counter or the pipe is full, the waker is already
+            // readable. Any other error means that the fd is invalid.
+            debug_assert_eq!(
+                err.kind(),
+                io::ErrorKind::WouldBlock,
+                "unable to wake: {}",
+                err
+            );
+        }
+    }
+
+    fn reset(&self) {
+        let mut buf = [0u8; 64];
+        loop {
+            let rc = unsafe {
+                libc::read(
+                    self.read,
+                    buf.as_mut_ptr() as *mut c_void,
+                    buf.len(),
+                )
+            };
+
+            if rc == -1 {
+                let err = io::Error::last_os_error();
+                match err.kind() {
+                    io::ErrorKind::WouldBlock => return,
+                    io::ErrorKind::Interrupted => (),
+                    // The fd is invalid.
+                    _ => {
+                        debug_assert!(false, "unable to reset: {}", err);
+                        return;
+                    }
+                }
+            } else if rc == 0 {
+                return;
+            }
+        }
+    }
+}
+
+fn last_os_error() -> Error {
+    let errno = io::Error::last_os_error().raw_os_error().unwrap_or(0);
+    Error::from_errno(errno)
+}
+
+impl Drop for WakerFd {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.read);
+            if self.write != self.read {
+                libc::close(self.write);
+            }
+        }
+    }
+}
+
+/// A handle used to wake up a thread blocked on a [`Poller`].
+///
+/// A `Waker` can be cloned and triggered from any thread. When triggered,
+/// it becomes readable until it is [`reset`]. Multiple wake ups between two
+/// resets are coalesced.
+///
+/// The easiest way to use a `Waker` is via [`poll_with_waker`], which
+/// takes care of registering and resetting it. Since a `Waker` implements
+/// `AsRawFd`, it can also be manually [`added`] to a `Poller`.
+///
+/// On linux, this is backed by an `eventfd`. On other platforms, a pipe
+/// is used instead.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), anyhow::Error> {
+/// use libzmq::{prelude::*, *, poll::*};
+/// use std::thread;
+///
+/// let server = Server::new()?;
+/// let waker = Waker::new()?;
+///
+/// let handle = {
+///     let waker = waker.clone();
+///     thread::spawn(move || {
+///         let mut poller = Poller::new();
+///         poller.add(&server, PollId(0), READABLE).unwrap();
+///
+///         let mut events = Events::new();
+///         // Block until the waker is triggered.
+///         let woken = poller
+///             .poll_with_waker(&mut events, Period::Infinite, &waker)
+///             .unwrap();
+///
+///         assert!(woken);
+///         assert!(events.is_empty());
+///     })
+/// };
+///
+/// waker.wake();
+/// handle.join().unwrap();
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// [`Poller`]: struct.Poller.html
+/// [`reset`]: #method.reset
+/// [`poll_with_waker`]: struct.Poller.html#method.poll_with_waker
+/// [`added`]: struct.Poller.html#method.add
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Waker {
+    inner: Arc<WakerFd>,
+}
+
+impl Waker {
+    /// Creates a new `Waker`.
+    ///
+    /// # Returned Error Variants
+    /// * [`Unexpected`] (if the file descriptors cannot be created)
+    ///
+    /// [`Unexpected`]: ../enum.ErrorKind.html#variant.Unexpected
+    pub fn new() -> Result<Self, Error> {
+        let inner = WakerFd::new()?;
+        Ok(Self {
+            inner: Arc::new(inner),
+        })
+    }
+
+    /// Wakes up the poller polling the `Waker`.
+    ///
+    /// The `Waker` will remain readable until it is [`reset`].
+    ///
+    /// [`reset`]: #method.reset
+    pub fn wake(&self) {
+        self.inner.wake()
+    }
+
+    /// Resets the `Waker` so that it is no longer readable.
+    ///
+    /// This must be called after the `Waker` was detected as readable
+    /// unless it is used via [`poll_with_waker`].
+    ///
+    /// [`poll_with_waker`]: struct.Poller.html#method.poll_with_waker
+    pub fn reset(&self) {
+        self.inner.reset()
+    }
+}
+
+impl AsRawFd for Waker {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.read
+    }
+}
+
 /// An `Iterator` over references to [`Event`].
 ///
 /// Note that every event is guaranteed to be non-[`EMPTY`].
@@ -438,6 +666,10 @@ pub struct Poller {
 
 impl Poller {
     /// Create a new empty poller.
+    ///
+    /// # Panic
+    /// Just like the collections of the standard library, panics if ØMQ
+    /// fails to allocate the poller.
     pub fn new() -> Self {
         Self::default()
     }
@@ -511,7 +743,7 @@ impl Poller {
                 errno::EBADF => Error::new(ErrorKind::InvalidInput(
                     "specified fd was the retired fd",
                 )),
-                _ => panic!(msg_from_errno(errno)),
+                _ => Error::from_errno(errno),
             };
 
             Err(err)
@@ -548,7 +780,7 @@ impl Poller {
                     "cannot add socket twice",
                 )),
                 errno::ENOTSOCK => panic!("invalid socket"),
-                _ => panic!(msg_from_errno(errno)),
+                _ => Error::from_errno(errno),
             };
 
             Err(err)
@@ -609,7 +841,7 @@ impl Poller {
                 errno::EBADF => Error::new(ErrorKind::InvalidInput(
                     "specified fd was the retired fd",
                 )),
-                _ => panic!(msg_from_errno(errno)),
+                _ => Error::from_errno(errno),
             };
 
             Err(err)
@@ -635,7 +867,7 @@ impl Poller {
                 errno::EINVAL => Error::new(ErrorKind::InvalidInput(
                     "cannot remove absent socket",
                 )),
-                _ => panic!(msg_from_errno(errno)),
+                _ => Error::from_errno(errno),
             };
 
             Err(err)
@@ -685,7 +917,7 @@ impl Poller {
                 errno::EBADF => Error::new(ErrorKind::InvalidInput(
                     "specified fd is the retired fd",
                 )),
-                _ => panic!(msg_from_errno(errno)),
+                _ => Error::from_errno(errno),
             };
 
             Err(err)
@@ -713,7 +945,7 @@ impl Poller {
                     "cannot modify absent socket",
                 )),
                 errno::ENOTSOCK => panic!("invalid socket"),
-                _ => panic!(msg_from_errno(errno)),
+                _ => Error::from_errno(errno),
             };
 
             Err(err)
@@ -737,17 +969,12 @@ impl Poller {
         };
 
         if rc == -1 {
+            events.clear();
             let errno = unsafe { sys::zmq_errno() };
-            let err = match errno {
-                errno::EINVAL => panic!("invalid poller"),
-                errno::ETERM => Error::new(ErrorKind::InvalidCtx),
-                errno::EINTR => Error::new(ErrorKind::Interrupted),
-                errno::EAGAIN => Error::new(ErrorKind::WouldBlock),
-                _ => panic!(msg_from_errno(errno)),
-            };
-
-            Err(err)
+            Err(wait_error(errno))
         } else {
+            // Only the first `rc` events are filled.
+            events.inner.truncate(rc as usize);
             Ok(())
         }
     }
@@ -802,20 +1029,82 @@ impl Poller {
             Period::Infinite => self.wait(events, -1),
         }
     }
+
+    /// Wait for events in the monitored elements, similarly to [`poll`],
+    /// but also returns when the [`Waker`] is triggered.
+    ///
+    /// Returns `true` if the `Waker` was triggered, in which case it is
+    /// reset. The `Waker` is never part of the returned `Events`, which
+    /// means that they might be empty if the `Waker` was the only
+    /// element triggered.
+    ///
+    /// # Returned Errors
+    /// * [`WouldBlock`] (timeout expired)
+    /// * [`InvalidCtx`] (`Ctx` of a polled socket was terminated)
+    /// * [`Interrupted`]
+    /// * [`InvalidInput`] (the `Waker` was already added to the poller)
+    ///
+    /// [`poll`]: #method.poll
+    /// [`Waker`]: struct.Waker.html
+    /// [`Interrupted`]: ../enum.ErrorKind.html#variant.Interrupted
+    /// [`InvalidCtx`]: ../enum.ErrorKind.html#variant.InvalidCtx
+    /// [`WouldBlock`]: ../enum.ErrorKind.html#variant.WouldBlock
+    /// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
+    /// [`Unexpected`]: ../enum.ErrorKind.html#variant.Unexpected
+    pub fn poll_with_waker(
+        &mut self,
+        events: &mut Events,
+        timeout: Period,
+        waker: &Waker,
+    ) -> Result<bool, Error> {
+        let fd = waker.as_raw_fd();
+        // The id is irrelevant since we identify the waker by its fd.
+        self.add_fd(fd, PollId(0), READABLE)?;
+        let result = self.poll(events, timeout);
+        self.remove_fd(fd)?;
+        result?;
+
+        let len = events.inner.len();
+        events
+            .inner
+            .retain(|event| !(event.socket.is_null() && event.fd == fd));
+
+        let woken = events.inner.len() != len;
+        if woken {
+            waker.reset();
+        }
+
+        Ok(woken)
+    }
 }
 
 impl Default for Poller {
     fn default() -> Self {
         let poller = unsafe { sys::zmq_poller_new() };
 
+        // Only fails if the allocation fails.
         if poller.is_null() {
-            panic!(msg_from_errno(unsafe { sys::zmq_errno() }));
+            let errno = unsafe { sys::zmq_errno() };
+            panic!(
+                "unable to allocate poller: {}",
+                ErrorKind::unexpected(errno)
+            );
         }
 
         Self { poller, count: 0 }
     }
 }
 
+fn wait_error(errno: c_int) -> Error {
+    match errno {
+        errno::EINVAL => panic!("invalid poller"),
+        errno::ETERM => Error::new(ErrorKind::InvalidCtx),
+        errno::EINTR => Error::new(ErrorKind::Interrupted),
+        errno::EAGAIN => Error::new(ErrorKind::WouldBlock),
+        _ => Error::from_errno(errno),
+    }
+}
+
 impl Drop for Poller {
     fn drop(&mut self) {
         let rc = unsafe { sys::zmq_poller_destroy(&mut self.poller) };
@@ -825,7 +1114,10 @@ impl Drop for Poller {
 
             match errno {
                 errno::EFAULT => panic!("invalid poller"),
-                _ => panic!(msg_from_errno(errno)),
+                _ => error!(
+                    "error while dropping poller: {}",
+                    ErrorKind::unexpected(errno)
+                ),
             }
         }
     }
@@ -840,4 +1132,52 @@ mod test {
         assert_eq!(READABLE.bits(), sys::ZMQ_POLLIN as c_short);
         assert_eq!(WRITABLE.bits(), sys::ZMQ_POLLOUT as c_short);
     }
+
+    #[test]
+    fn test_wait_unexpected_error() {
+        let err = wait_error(libc::EIO);
+        assert_eq!(err.kind(), ErrorKind::unexpected(libc::EIO));
+    }
+
+    #[test]
+    fn test_poll_with_waker() {
+        use crate::{prelude::*, *};
+        use std::time::Duration;
+
+        let addr = InprocAddr::new_unique();
+        let server = ServerBuilder::new().bind(&addr).build().unwrap();
+        let client = ClientBuilder::new().connect(&addr).build().unwrap();
+
+        let mut poller = Poller::new();
+        poller.add(&server, PollId(0), READABLE).unwrap();
+
+        let waker = Waker::new().unwrap();
+        let mut events = Events::new();
+        let timeout = Period::Finite(Duration::from_millis(10));
+
+        let err = poller
+            .poll_with_waker(&mut events, timeout, &waker)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WouldBlock);
+
+        // Multiple wake ups are coalesced.
+        waker.wake();
+        waker.wake();
+        client.send("").unwrap();
+
+        let woken = poller
+            .poll_with_waker(&mut events, Period::Infinite, &waker)
+            .unwrap();
+        assert!(woken);
+        assert_eq!(events.iter().count(), 1);
+        assert!(!events.is_empty());
+
+        server.recv_msg().unwrap();
+
+        // The waker was reset.
+        let err = poller
+            .poll_with_waker(&mut events, timeout, &waker)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WouldBlock);
+    }
 }
@@ -0,0 +1,385 @@
+use super::*;
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc, slice, vec};
+
+#[derive(Debug)]
+struct Shared<T> {
+    poller: Poller,
+    tokens: HashMap<usize, T>,
+    next_id: usize,
+}
+
+/// A [`Poller`] that associates arbitrary tokens to the monitored elements.
+///
+/// Instead of manually managing [`PollId`], each element is registered
+/// with a user defined token, typically an `enum`. The [`TokenEvent`] detected
+/// while polling then yield a reference to this token.
+///
+/// Adding an element returns a [`Registration`] guard which removes the
+/// element from the poller when dropped. Since the guard borrows the element,
+/// a socket cannot be dropped while it is being monitored.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), anyhow::Error> {
+/// use libzmq::{prelude::*, *, poll::*};
+///
+/// #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// enum Token {
+///     Server,
+///     Client,
+/// }
+///
+/// let addr = InprocAddr::new_unique();
+/// let server = ServerBuilder::new().bind(&addr).build()?;
+/// let client = ClientBuilder::new().connect(&addr).build()?;
+///
+/// let poller = TokenPoller::new();
+/// let _server_reg = poller.add(&server, Token::Server, READABLE)?;
+/// let client_reg = poller.add(&client, Token::Client, READABLE)?;
+///
+/// client.send("ping")?;
+///
+/// let mut events = TokenEvents::new();
+/// poller.poll(&mut events, Period::Infinite)?;
+///
+/// for event in &events {
+///     match event.token() {
+///         Token::Server => {
+///             let msg = server.recv_msg()?;
+///             assert_eq!("ping", msg.to_str()?);
+///         }
+///         Token::Client => unreachable!(),
+///     }
+/// }
+///
+/// // The client is no longer monitored.
+/// drop(client_reg);
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// [`Poller`]: struct.Poller.html
+/// [`PollId`]: struct.PollId.html
+/// [`TokenEvent`]: struct.TokenEvent.html
+/// [`Registration`]: struct.Registration.html
+#[derive(Debug)]
+pub struct TokenPoller<T> {
+    shared: Rc<RefCell<Shared<T>>>,
+}
+
+impl<T> TokenPoller<T>
+where
+    T: Clone,
+{
+    /// Create a new empty poller.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a [`Pollable`] element for monitoring by the poller with the
+    /// specified token and [`Trigger`] condition.
+    ///
+    /// The element will be monitored until the returned [`Registration`]
+    /// is dropped.
+    ///
+    /// # Returned Errors
+    /// * [`InvalidInput`] (added element twice or invalid fd)
+    ///
+    /// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
+    /// [`Pollable`]: enum.Pollable.html
+    /// [`Trigger`]: struct.Trigger.html
+    /// [`Registration`]: struct.Registration.html
+    pub fn add<'a, P>(
+        &self,
+        pollable: P,
+        token: T,
+        trigger: Trigger,
+    ) -> Result<Registration<'a, T>, Error>
+    where
+        P: Into<Pollable<'a>>,
+    {
+        let pollable = pollable.into();
+        let mut shared = self.shared.borrow_mut();
+
+        let id = shared.next_id;
+        shared.poller.add(pollable, PollId(id), trigger)?;
+        shared.next_id = id.wrapping_add(1);
+        shared.tokens.insert(id, token);
+
+        Ok(Registration {
+            shared: Rc::clone(&self.shared),
+            pollable,
+            id,
+        })
+    }
+
+    /// Check for events in the monitored elements, returning instantly.
+    ///
+    /// See [`Poller::try_poll`].
+    ///
+    /// [`Poller::try_poll`]: struct.Poller.html#method.try_poll
+    pub fn try_poll(&self, events: &mut TokenEvents<T>) -> Result<(), Error> {
+        let mut shared = self.shared.borrow_mut();
+        let result = shared.poller.try_poll(&mut events.raw);
+        events.fill(&shared.tokens);
+        result
+    }
+
+    /// The poller will wait for events in the monitored elements,
+    /// blocking until at least one event occurs, or the specified
+    /// timeout `Period` expires.
+    ///
+    /// See [`Poller::poll`].
+    ///
+    /// [`Poller::poll`]: struct.Poller.html#method.poll
+    pub fn poll(
+        &self,
+        events: &mut TokenEvents<T>,
+        timeout: Period,
+    ) -> Result<(), Error> {
+        let mut shared = self.shared.borrow_mut();
+        let result = shared.poller.poll(&mut events.raw, timeout);
+        events.fill(&shared.tokens);
+        result
+    }
+
+    /// Wait for events in the monitored elements, but also returns when the
+    /// [`Waker`] is triggered.
+    ///
+    /// See [`Poller::poll_with_waker`].
+    ///
+    /// [`Waker`]: struct.Waker.html
+    /// [`Poller::poll_with_waker`]: struct.Poller.html#method.poll_with_waker
+    pub fn poll_with_waker(
+        &self,
+        events: &mut TokenEvents<T>,
+        timeout: Period,
+        waker: &Waker,
+    ) -> Result<bool, Error> {
+        let mut shared = self.shared.borrow_mut();
+        let result =
+            shared
+                .poller
+                .poll_with_waker(&mut events.raw, timeout, waker);
+        events.fill(&shared.tokens);
+        result
+    }
+}
+
+impl<T> Default for TokenPoller<T> {
+    fn default() -> Self {
+        let shared = Shared {
+            poller: Poller::new(),
+            tokens: HashMap::new(),
+            next_id: 0,
+        };
+
+        Self {
+            shared: Rc::new(RefCell::new(shared)),
+        }
+    }
+}
+
+/// A guard that keeps an element monitored by a [`TokenPoller`].
+///
+/// When dropped, the element is removed from the poller.
+///
+/// [`TokenPoller`]: struct.TokenPoller.html
+#[derive(Debug)]
+pub struct Registration<'a, T> {
+    shared: Rc<RefCell<Shared<T>>>,
+    pollable: Pollable<'a>,
+    id: usize,
+}
+
+impl<'a, T> Registration<'a, T> {
+    /// Modify the [`Trigger`] condition of the registered element.
+    ///
+    /// [`Trigger`]: struct.Trigger.html
+    pub fn modify(&self, trigger: Trigger) -> Result<(), Error> {
+        let mut shared = self.shared.borrow_mut();
+        shared.poller.modify(self.pollable, trigger)
+    }
+}
+
+impl<'a, T> Drop for Registration<'a, T> {
+    fn drop(&mut self) {
+        let mut shared = self.shared.borrow_mut();
+        shared.tokens.remove(&self.id);
+
+        if let Err(err) = shared.poller.remove(self.pollable) {
+            error!("error while removing registration: {}", err);
+        }
+    }
+}
+
+/// An event detected by a [`TokenPoller`].
+///
+/// [`TokenPoller`]: struct.TokenPoller.html
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TokenEvent<T> {
+    token: T,
+    event: Event,
+}
+
+impl<T> TokenEvent<T> {
+    /// Returns a reference to the token associated with the element.
+    pub fn token(&self) -> &T {
+        &self.token
+    }
+
+    /// Returns the token associated with the element.
+    pub fn into_token(self) -> T {
+        self.token
+    }
+
+    /// Returns the underlying [`Event`].
+    ///
+    /// [`Event`]: struct.Event.html
+    pub fn event(&self) -> Event {
+        self.event
+    }
+
+    /// Indicates read readiness.
+    pub fn is_readable(&self) -> bool {
+        self.event.is_readable()
+    }
+
+    /// Indicates write readiness.
+    pub fn is_writable(&self) -> bool {
+        self.event.is_writable()
+    }
+}
+
+/// A vector of [`TokenEvent`] detected while polling.
+///
+/// Note that every event is guaranteed to be non-[`EMPTY`].
+///
+/// [`TokenEvent`]: struct.TokenEvent.html
+/// [`EMPTY`]: constant.EMPTY.html
+#[derive(Clone, Debug)]
+pub struct TokenEvents<T> {
+    raw: Events,
+    inner: Vec<TokenEvent<T>>,
+}
+
+impl<T> TokenEvents<T>
+where
+    T: Clone,
+{
+    /// Creates a new empty event vector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `true` is the event vector contains no events.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Returns the number of events in the event vector.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns an iterator over the `TokenEvent` in the event vector.
+    pub fn iter(&self) -> slice::Iter<'_, TokenEvent<T>> {
+        self.inner.iter()
+    }
+
+    /// Empties the event vector.
+    pub fn clear(&mut self) {
+        self.raw.clear();
+        self.inner.clear();
+    }
+
+    fn fill(&mut self, tokens: &HashMap<usize, T>) {
+        self.inner.clear();
+        for event in &self.raw {
+            let PollId(id) = event.id();
+            // The registration of a polled element cannot be dropped while
+            // polling so its token is guaranteed to be present.
+            let token = tokens[&id].clone();
+            self.inner.push(TokenEvent { token, event });
+        }
+    }
+}
+
+impl<T> Default for TokenEvents<T> {
+    fn default() -> Self {
+        Self {
+            raw: Events::new(),
+            inner: Vec::new(),
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a TokenEvents<T> {
+    type Item = &'a TokenEvent<T>;
+    type IntoIter = slice::Iter<'a, TokenEvent<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.inner.iter()
+    }
+}
+
+impl<T> IntoIterator for TokenEvents<T> {
+    type Item = TokenEvent<T>;
+    type IntoIter = vec::IntoIter<TokenEvent<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.inner.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{prelude::*, *};
+
+    use std::time::Duration;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Token {
+        Server,
+        Waker,
+    }
+
+    #[test]
+    fn test_registration_drop() {
+        let addr = InprocAddr::new_unique();
+        let server = ServerBuilder::new().bind(&addr).build().unwrap();
+        let client = ClientBuilder::new().connect(&addr).build().unwrap();
+        let waker = Waker::new().unwrap();
+
+        let poller = TokenPoller::new();
+        let server_reg = poller.add(&server, Token::Server, READABLE).unwrap();
+        let _waker_reg = poller.add(&waker, Token::Waker, READABLE).unwrap();
+
+        client.send("").unwrap();
+        waker.wake();
+
+        let mut events = TokenEvents::new();
+        poller.poll(&mut events, Period::Infinite).unwrap();
+
+        let mut tokens: Vec<_> = events.iter().map(|e| *e.token()).collect();
+        tokens.sort_by_key(|t| *t as usize);
+        assert_eq!(tokens, vec![Token::Server, Token::Waker]);
+
+        // Once the registration is dropped, the server is no longer polled.
+        drop(server_reg);
+        waker.reset();
+
+        let timeout = Period::Finite(Duration::from_millis(10));
+        let err = poller.poll(&mut events, timeout).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WouldBlock);
+        assert!(events.is_empty());
+
+        // Since the registration was dropped, it can be added again.
+        let _server_reg = poller.add(&server, Token::Server, READABLE).unwrap();
+        poller.poll(&mut events, Period::Infinite).unwrap();
+        assert_eq!(events.len(), 1);
+    }
+}
@@ -0,0 +1,961 @@
+//! Acknowledged at-least-once delivery on top of the existing sockets.
+//!
+//! The sockets drop messages when a peer disconnects or when a high water
+//! mark is reached. A [`ReliableSender`] tags each message with a sequence
+//! id and keeps it until a [`ReliableReceiver`] acknowledges it, which it
+//! does as soon as the message is received. Unacknowledged messages are
+//! retransmitted until they are either acknowledged or they exhaust their
+//! retries, in which case they are reported as a [`DeliveryFailure`]. They
+//! are also retransmitted as soon as the sending socket (re)connects, since
+//! the messages in flight on a lost connection are dropped.
+//!
+//! Since a message can be retransmitted after it was received, the
+//! receiver discards the duplicates within a bounded window of sequence ids.
+//!
+//! Acknowledgements travel on a separate socket so that unidirectional
+//! sockets such as [`Scatter`] and [`Gather`] can be used. Bidirectional
+//! sockets such as [`Client`] can be passed by reference for both roles.
+//!
+//! [`ReliableSender`]: struct.ReliableSender.html
+//! [`ReliableReceiver`]: struct.ReliableReceiver.html
+//! [`DeliveryFailure`]: struct.DeliveryFailure.html
+//! [`Scatter`]: ../struct.Scatter.html
+//! [`Gather`]: ../struct.Gather.html
+//! [`Client`]: ../struct.Client.html
+
+use crate::{
+    core::{RawSocketType, RecvMsg, SendMsg},
+    error::{Error, ErrorKind},
+    monitor::Monitor,
+    msg::{Msg, RoutingId},
+    poll::{Events, PollId, Pollable, Poller, READABLE},
+    Period,
+};
+use libzmq_sys as sys;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+const DEFAULT_WINDOW: usize = 64;
+const DEFAULT_DEDUP_WINDOW: usize = 1024;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+const DEFAULT_RETRIES: u32 = 5;
+// The maximum number of senders a receiver deduplicates at once.
+const MAX_SESSIONS: usize = 1024;
+
+/// A configuration for a [`ReliableSender`] or a [`ReliableReceiver`].
+///
+/// Usefull in configuration files.
+///
+/// [`ReliableSender`]: struct.ReliableSender.html
+/// [`ReliableReceiver`]: struct.ReliableReceiver.html
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReliableConfig {
+    window: usize,
+    dedup_window: usize,
+    #[serde(with = "humantime_serde")]
+    timeout: Duration,
+    retries: u32,
+}
+
+impl ReliableConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The maximum number of messages a sender keeps unacknowledged.
+    ///
+    /// # Default
+    /// 64
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    pub fn set_window(&mut self, size: usize) {
+        self.window = size;
+    }
+
+    /// The number of sequence ids of a sender a receiver remembers to
+    /// discard duplicates.
+    ///
+    /// It must not be smaller than the `window` of the senders, otherwise a
+    /// retransmission could be mistaken for a duplicate.
+    ///
+    /// # Default
+    /// 1024
+    pub fn dedup_window(&self) -> usize {
+        self.dedup_window
+    }
+
+    pub fn set_dedup_window(&mut self, size: usize) {
+        self.dedup_window = size;
+    }
+
+    /// The period after which an unacknowledged message is retransmitted.
+    ///
+    /// # Default
+    /// 1s
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// The number of retransmissions of a message before it is reported as
+    /// a [`DeliveryFailure`].
+    ///
+    /// # Default
+    /// 5
+    ///
+    /// [`DeliveryFailure`]: struct.DeliveryFailure.html
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    pub fn set_retries(&mut self, retries: u32) {
+        self.retries = retries;
+    }
+
+    fn check_window(size: usize) -> Result<(), Error> {
+        if size == 0 {
+            Err(Error::new(ErrorKind::InvalidInput(
+                "window size must be greater than zero",
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Default for ReliableConfig {
+    fn default() -> Self {
+        Self {
+            window: DEFAULT_WINDOW,
+            dedup_window: DEFAULT_DEDUP_WINDOW,
+            timeout: DEFAULT_TIMEOUT,
+            retries: DEFAULT_RETRIES,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Frame {
+    Data {
+        session: u64,
+        seq: u64,
+        payload: Vec<u8>,
+    },
+    Ack {
+        session: u64,
+        seq: u64,
+    },
+}
+
+fn encode(frame: &Frame, id: Option<RoutingId>) -> Msg {
+    let mut msg: Msg = bincode::serialize(frame).unwrap().into();
+    if let Some(id) = id {
+        msg.set_routing_id(id);
+    }
+    msg
+}
+
+/// A message that was not acknowledged after exhausting its retries.
+#[derive(Debug)]
+pub struct DeliveryFailure {
+    seq: u64,
+    msg: Msg,
+}
+
+impl DeliveryFailure {
+    /// Returns the sequence id that was assigned to the message by
+    /// [`send`].
+    ///
+    /// [`send`]: struct.ReliableSender.html#method.send
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Returns the message that could not be delivered.
+    pub fn msg(&self) -> &Msg {
+        &self.msg
+    }
+
+    /// Returns the message that could not be delivered.
+    pub fn into_msg(self) -> Msg {
+        self.msg
+    }
+}
+
+#[derive(Debug)]
+struct Pending {
+    msg: Msg,
+    deadline: Instant,
+    retries: u32,
+}
+
+/// Sends messages that are retransmitted until a [`ReliableReceiver`]
+/// acknowledges them.
+///
+/// Messages are sent on the `socket` while the acknowledgements are
+/// received on the `acks` socket, which must not be used to receive
+/// anything else. Messages routed by a [`Server`] keep their `RoutingId`
+/// for every retransmission.
+///
+/// The sender does not use a background thread. The acknowledgements are
+/// processed and the due retransmissions performed whenever [`send`],
+/// [`process`] or [`flush`] is called.
+///
+/// Every unacknowledged message is also retransmitted when a connection of
+/// the `socket` is established, which is detected by monitoring the socket.
+/// This is not done for a [`Server`], since a client that reconnects is
+/// assigned a new `RoutingId`.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), anyhow::Error> {
+/// use libzmq::{prelude::*, reliable::*, *};
+/// use std::time::Duration;
+///
+/// let addr: TcpAddr = "127.0.0.1:*".try_into()?;
+///
+/// let server = ServerBuilder::new().bind(addr).build()?;
+/// let bound = server.last_endpoint()?;
+/// let client = ClientBuilder::new().connect(bound).build()?;
+///
+/// let config = ReliableConfig::new();
+/// let mut sender = ReliableSender::new(&client, &client, config.clone())?;
+/// let mut receiver = ReliableReceiver::new(&server, &server, config)?;
+///
+/// sender.send("msg")?;
+/// assert_eq!(receiver.recv()?.to_str()?, "msg");
+///
+/// // Wait until the message is acknowledged.
+/// sender.flush(Duration::from_secs(1))?;
+/// assert_eq!(sender.outstanding(), 0);
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// [`ReliableReceiver`]: struct.ReliableReceiver.html
+/// [`Server`]: ../struct.Server.html
+/// [`send`]: #method.send
+/// [`process`]: #method.process
+/// [`flush`]: #method.flush
+#[derive(Debug)]
+pub struct ReliableSender<S, A> {
+    socket: S,
+    acks: A,
+    config: ReliableConfig,
+    session: u64,
+    next_seq: u64,
+    pending: BTreeMap<u64, Pending>,
+    failures: VecDeque<DeliveryFailure>,
+    // Reports the connections established by the socket.
+    monitor: Option<Monitor>,
+}
+
+impl<S, A> ReliableSender<S, A>
+where
+    S: SendMsg,
+    A: RecvMsg,
+{
+    /// Creates a new `ReliableSender` which sends its messages on `socket`
+    /// and receives their acknowledgements on `acks`.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidInput`] (if the `window` is zero)
+    /// * [`InvalidCtx`]
+    ///
+    /// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
+    /// [`InvalidCtx`]: ../enum.ErrorKind.html#variant.InvalidCtx
+    pub fn new(
+        socket: S,
+        acks: A,
+        config: ReliableConfig,
+    ) -> Result<Self, Error> {
+        ReliableConfig::check_window(config.window)?;
+
+        let raw_socket = socket.raw_socket();
+        let monitor = if raw_socket.sock_type() == RawSocketType::Server {
+            None
+        } else {
+            let events = sys::ZMQ_EVENT_HANDSHAKE_SUCCEEDED;
+            Some(Monitor::with_mask(raw_socket, events)?)
+        };
+
+        Ok(Self {
+            socket,
+            acks,
+            config,
+            session: Uuid::new_v4().as_u128() as u64,
+            next_seq: 0,
+            pending: BTreeMap::new(),
+            failures: VecDeque::new(),
+            monitor,
+        })
+    }
+
+    /// Returns a reference to the socket the messages are sent on.
+    pub fn socket(&self) -> &S {
+        &self.socket
+    }
+
+    /// Returns the number of messages that are not yet acknowledged.
+    pub fn outstanding(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Sends a message and returns its sequence id.
+    ///
+    /// The message is kept until it is acknowledged, so it is not lost if it
+    /// cannot be sent right away because of the mute state or a missing peer.
+    ///
+    /// # Error
+    /// The `Msg` is returned as the content of the `Error`.
+    ///
+    /// ## Possible Error Variants
+    /// * [`WouldBlock`] (if the window is full)
+    /// * [`InvalidCtx`]
+    ///
+    /// [`WouldBlock`]: ../enum.ErrorKind.html#variant.WouldBlock
+    /// [`InvalidCtx`]: ../enum.ErrorKind.html#variant.InvalidCtx
+    pub fn send<M>(&mut self, msg: M) -> Result<u64, Error<Msg>>
+    where
+        M: Into<Msg>,
+    {
+        let msg = msg.into();
+        if let Err(err) = self.process() {
+            return Err(Error::with_content(err.kind(), msg));
+        }
+        if self.pending.len() >= self.config.window {
+            return Err(Error::with_content(ErrorKind::WouldBlock, msg));
+        }
+
+        let seq = self.next_seq;
+        if let Err(err) = self.transmit(seq, &msg) {
+            return Err(Error::with_content(err.kind(), msg));
+        }
+        self.next_seq += 1;
+
+        let pending = Pending {
+            msg,
+            deadline: Instant::now() + self.config.timeout,
+            retries: 0,
+        };
+        self.pending.insert(seq, pending);
+
+        Ok(seq)
+    }
+
+    /// Processes the received acknowledgements and retransmits the messages
+    /// whose `timeout` expired or that were sent before a connection was
+    /// established, without blocking.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidCtx`]
+    ///
+    /// [`InvalidCtx`]: ../enum.ErrorKind.html#variant.InvalidCtx
+    pub fn process(&mut self) -> Result<(), Error> {
+        loop {
+            match self.acks.try_recv_msg() {
+                Ok(msg) => self.acknowledge(&msg),
+                Err(err) => match err.kind() {
+                    ErrorKind::WouldBlock => break,
+                    ErrorKind::Interrupted => continue,
+                    _ => return Err(err),
+                },
+            }
+        }
+
+        if self.is_connected_since() {
+            self.retransmit()?;
+        }
+
+        let now = Instant::now();
+        let due: Vec<u64> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| pending.deadline <= now)
+            .map(|(seq, _)| *seq)
+            .collect();
+
+        for seq in due {
+            if self.pending[&seq].retries >= self.config.retries {
+                let pending = self.pending.remove(&seq).unwrap();
+                self.failures.push_back(DeliveryFailure {
+                    seq,
+                    msg: pending.msg,
+                });
+            } else {
+                self.transmit(seq, &self.pending[&seq].msg)?;
+                let pending = self.pending.get_mut(&seq).unwrap();
+                pending.retries += 1;
+                pending.deadline = now + self.config.timeout;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Retransmits every unacknowledged message right away.
+    ///
+    /// This does not count as a retry. It is done automatically when a
+    /// connection of the socket is established.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidCtx`]
+    ///
+    /// [`InvalidCtx`]: ../enum.ErrorKind.html#variant.InvalidCtx
+    pub fn retransmit(&mut self) -> Result<(), Error> {
+        let deadline = Instant::now() + self.config.timeout;
+        for (seq, pending) in &self.pending {
+            self.transmit(*seq, &pending.msg)?;
+        }
+        for pending in self.pending.values_mut() {
+            pending.deadline = deadline;
+        }
+
+        Ok(())
+    }
+
+    /// Blocks until every message is either acknowledged or failed, or until
+    /// the `timeout` expires.
+    ///
+    /// # Returned Error Variants
+    /// * [`WouldBlock`] (if the timeout expires)
+    /// * [`InvalidCtx`]
+    ///
+    /// [`WouldBlock`]: ../enum.ErrorKind.html#variant.WouldBlock
+    /// [`InvalidCtx`]: ../enum.ErrorKind.html#variant.InvalidCtx
+    pub fn flush(&mut self, timeout: Duration) -> Result<(), Error> {
+        let expiry = Instant::now() + timeout;
+
+        let mut poller = Poller::new();
+        let acks = Pollable::Socket(self.acks.raw_socket());
+        poller.add(acks, PollId(0), READABLE)?;
+        if let Some(monitor) = &self.monitor {
+            let monitor = Pollable::Socket(monitor.raw_socket());
+            poller.add(monitor, PollId(1), READABLE)?;
+        }
+        let mut events = Events::new();
+
+        loop {
+            self.process()?;
+            if self.pending.is_empty() {
+                return Ok(());
+            }
+
+            let now = Instant::now();
+            if now >= expiry {
+                return Err(Error::new(ErrorKind::WouldBlock));
+            }
+
+            // Wake up in time for the next retransmission.
+            let next = self.pending.values().map(|p| p.deadline).min().unwrap();
+            let wait = next.min(expiry).saturating_duration_since(now);
+            if let Err(err) = poller.poll(&mut events, Period::Finite(wait)) {
+                match err.kind() {
+                    ErrorKind::WouldBlock | ErrorKind::Interrupted => {}
+                    _ => return Err(err),
+                }
+            }
+        }
+    }
+
+    /// Returns the next message that could not be delivered, if any.
+    pub fn next_failure(&mut self) -> Option<DeliveryFailure> {
+        self.failures.pop_front()
+    }
+
+    // Whether a connection was established since the last call.
+    fn is_connected_since(&mut self) -> bool {
+        let mut connected = false;
+        if let Some(monitor) = &mut self.monitor {
+            while monitor.try_next().is_some() {
+                connected = true;
+            }
+        }
+        connected
+    }
+
+    fn acknowledge(&mut self, msg: &Msg) {
+        // Anything but an acknowledgement of our session is ignored.
+        if let Ok(Frame::Ack { session, seq }) =
+            bincode::deserialize(msg.as_bytes())
+        {
+            if session == self.session {
+                self.pending.remove(&seq);
+            }
+        }
+    }
+
+    fn transmit(&self, seq: u64, msg: &Msg) -> Result<(), Error> {
+        let frame = Frame::Data {
+            session: self.session,
+            seq,
+            payload: msg.as_bytes().to_vec(),
+        };
+
+        match self.socket.try_send(encode(&frame, msg.routing_id())) {
+            Ok(()) => Ok(()),
+            Err(err) => match err.kind() {
+                // The message will be retransmitted.
+                ErrorKind::WouldBlock
+                | ErrorKind::HostUnreachable
+                | ErrorKind::Interrupted => Ok(()),
+                _ => Err(err.cast()),
+            },
+        }
+    }
+}
+
+// The sequence ids received from a sender.
+#[derive(Debug)]
+struct Session {
+    seen: BTreeSet<u64>,
+    highest: u64,
+    last_used: u64,
+}
+
+/// Receives the messages of [`ReliableSender`]s, acknowledges them and
+/// discards the duplicates.
+///
+/// Messages are received on the `socket` while the acknowledgements are
+/// sent on the `acks` socket. The acknowledgement of a message received by
+/// a [`Server`] is routed back to its sender.
+///
+/// Each sender is identified by a random session id, so a receiver can
+/// deduplicate many senders at once.
+///
+/// [`ReliableSender`]: struct.ReliableSender.html
+/// [`Server`]: ../struct.Server.html
+#[derive(Debug)]
+pub struct ReliableReceiver<R, A> {
+    socket: R,
+    acks: A,
+    config: ReliableConfig,
+    sessions: HashMap<u64, Session>,
+    count: u64,
+}
+
+impl<R, A> ReliableReceiver<R, A>
+where
+    R: RecvMsg,
+    A: SendMsg,
+{
+    /// Creates a new `ReliableReceiver` which receives its messages on
+    /// `socket` and sends their acknowledgements on `acks`.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidInput`] (if the `dedup_window` is zero)
+    ///
+    /// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
+    pub fn new(
+        socket: R,
+        acks: A,
+        config: ReliableConfig,
+    ) -> Result<Self, Error> {
+        ReliableConfig::check_window(config.dedup_window)?;
+
+        Ok(Self {
+            socket,
+            acks,
+            config,
+            sessions: HashMap::new(),
+            count: 0,
+        })
+    }
+
+    /// Returns a reference to the socket the messages are received on.
+    pub fn socket(&self) -> &R {
+        &self.socket
+    }
+
+    /// Retreive the next message that was not already received.
+    ///
+    /// This operation might block until the socket receives a message or,
+    /// if it is set, until the `recv_timeout` of the socket expires.
+    ///
+    /// ## Possible Error Variants
+    /// * [`WouldBlock`] (if `recv_timeout` expires)
+    /// * [`InvalidCtx`]
+    /// * [`Interrupted`]
+    /// * [`InvalidInput`] (if the message was not sent by a
+    ///   [`ReliableSender`])
+    ///
+    /// [`WouldBlock`]: ../enum.ErrorKind.html#variant.WouldBlock
+    /// [`InvalidCtx`]: ../enum.ErrorKind.html#variant.InvalidCtx
+    /// [`Interrupted`]: ../enum.ErrorKind.html#variant.Interrupted
+    /// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
+    /// [`ReliableSender`]: struct.ReliableSender.html
+    pub fn recv(&mut self) -> Result<Msg, Error> {
+        self.recv_inner(false)
+    }
+
+    /// Try to retrieve the next message that was not already received,
+    /// without blocking.
+    ///
+    /// See [`recv`].
+    ///
+    /// [`recv`]: #method.recv
+    pub fn try_recv(&mut self) -> Result<Msg, Error> {
+        self.recv_inner(true)
+    }
+
+    fn recv_inner(&mut self, no_block: bool) -> Result<Msg, Error> {
+        loop {
+            let msg = if no_block {
+                self.socket.try_recv_msg()?
+            } else {
+                self.socket.recv_msg()?
+            };
+
+            let (session, seq, payload) =
+                match bincode::deserialize(msg.as_bytes()) {
+                    Ok(Frame::Data {
+                        session,
+                        seq,
+                        payload,
+                    }) => (session, seq, payload),
+                    _ => {
+                        return Err(Error::new(ErrorKind::InvalidInput(
+                            "message is not a reliable data frame",
+                        )))
+                    }
+                };
+
+            // Duplicates are acknowledged again since the previous
+            // acknowledgement might have been lost.
+            let id = msg.routing_id();
+            self.ack(session, seq, id)?;
+
+            if self.is_new(session, seq) {
+                let mut msg: Msg = payload.into();
+                if let Some(id) = id {
+                    msg.set_routing_id(id);
+                }
+                return Ok(msg);
+            }
+        }
+    }
+
+    fn ack(
+        &self,
+        session: u64,
+        seq: u64,
+        id: Option<RoutingId>,
+    ) -> Result<(), Error> {
+        let frame = Frame::Ack { session, seq };
+        match self.acks.try_send(encode(&frame, id)) {
+            Ok(()) => Ok(()),
+            Err(err) => match err.kind() {
+                // The sender will retransmit the message.
+                ErrorKind::WouldBlock
+                | ErrorKind::HostUnreachable
+                | ErrorKind::Interrupted => Ok(()),
+                _ => Err(err.cast()),
+            },
+        }
+    }
+
+    fn is_new(&mut self, session: u64, seq: u64) -> bool {
+        self.count += 1;
+        let window = self.config.dedup_window as u64;
+
+        if !self.sessions.contains_key(&session)
+            && self.sessions.len() >= MAX_SESSIONS
+        {
+            let oldest = self
+                .sessions
+                .iter()
+                .min_by_key(|(_, s)| s.last_used)
+                .map(|(session, _)| *session)
+                .unwrap();
+            self.sessions.remove(&oldest);
+        }
+
+        let entry = self.sessions.entry(session).or_insert_with(|| Session {
+            seen: BTreeSet::new(),
+            highest: seq,
+            last_used: 0,
+        });
+        entry.last_used = self.count;
+
+        // Sequence ids older than the window were necessarily received,
+        // since the sender window is not larger.
+        if seq + window <= entry.highest || !entry.seen.insert(seq) {
+            return false;
+        }
+
+        if seq > entry.highest {
+            entry.highest = seq;
+            let lowest = entry.highest.saturating_sub(window - 1);
+            entry.seen = entry.seen.split_off(&lowest);
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{prelude::*, *};
+
+    use std::{convert::TryInto, thread};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn config() -> ReliableConfig {
+        let mut config = ReliableConfig::new();
+        config.set_timeout(Duration::from_millis(50));
+        config
+    }
+
+    fn pair(handle: CtxHandle) -> (Server, Client) {
+        let addr: TcpAddr = "127.0.0.1:*".try_into().unwrap();
+        let server = ServerBuilder::new()
+            .bind(addr)
+            .recv_timeout(TIMEOUT)
+            .with_ctx(handle)
+            .unwrap();
+        let bound = server.last_endpoint().unwrap();
+        let client = ClientBuilder::new()
+            .connect(bound)
+            .recv_timeout(TIMEOUT)
+            .with_ctx(handle)
+            .unwrap();
+
+        (server, client)
+    }
+
+    #[test]
+    fn test_ser_de() {
+        let config = ReliableConfig::new();
+
+        let ron = serde_yaml::to_string(&config).unwrap();
+        let de: ReliableConfig = serde_yaml::from_str(&ron).unwrap();
+        assert_eq!(config, de);
+    }
+
+    #[test]
+    fn test_invalid_window() {
+        let ctx = Ctx::new();
+        let (server, client) = pair(ctx.handle());
+
+        let mut config = ReliableConfig::new();
+        config.set_window(0);
+
+        let err =
+            ReliableSender::new(&client, &client, config.clone()).unwrap_err();
+        assert_eq!(
+            err.kind(),
+            ErrorKind::InvalidInput("window size must be greater than zero")
+        );
+        assert!(ReliableReceiver::new(&server, &server, config).is_ok());
+
+        let mut config = ReliableConfig::new();
+        config.set_dedup_window(0);
+        assert!(ReliableReceiver::new(&server, &server, config).is_err());
+    }
+
+    #[test]
+    fn test_deliver_and_ack() {
+        let ctx = Ctx::new();
+        let (server, client) = pair(ctx.handle());
+
+        let mut sender =
+            ReliableSender::new(&client, &client, config()).unwrap();
+        let mut receiver =
+            ReliableReceiver::new(&server, &server, config()).unwrap();
+
+        for i in 0..10 {
+            assert_eq!(sender.send(i.to_string()).unwrap(), i);
+        }
+        for i in 0..10 {
+            let msg = receiver.recv().unwrap();
+            assert_eq!(msg.to_str().unwrap(), i.to_string());
+        }
+
+        sender.flush(TIMEOUT).unwrap();
+        assert_eq!(sender.outstanding(), 0);
+        assert!(sender.next_failure().is_none());
+    }
+
+    #[test]
+    fn test_retransmit_and_dedup() {
+        let ctx = Ctx::new();
+        let (server, client) = pair(ctx.handle());
+
+        let mut sender =
+            ReliableSender::new(&client, &client, config()).unwrap();
+        // The acknowledgements are sent to a socket nobody listens to, so the
+        // messages are retransmitted.
+        let blackhole = Server::with_ctx(ctx.handle()).unwrap();
+        let mut receiver =
+            ReliableReceiver::new(&server, &blackhole, config()).unwrap();
+
+        sender.send("msg").unwrap();
+        assert_eq!(receiver.recv().unwrap().to_str().unwrap(), "msg");
+
+        thread::sleep(Duration::from_millis(100));
+        sender.process().unwrap();
+        sender.retransmit().unwrap();
+
+        // Both retransmissions are discarded as duplicates.
+        server
+            .set_recv_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        assert_eq!(receiver.recv().unwrap_err().kind(), ErrorKind::WouldBlock);
+        assert_eq!(sender.outstanding(), 1);
+    }
+
+    #[test]
+    fn test_retransmit_on_reconnect() {
+        let ctx = Ctx::new();
+        let (server, client) = pair(ctx.handle());
+        let bound = server.last_endpoint().unwrap();
+
+        // The timeout never expires, so only a reconnection can trigger a
+        // retransmission.
+        let mut config = config();
+        config.set_timeout(Duration::from_secs(3600));
+        let mut sender =
+            ReliableSender::new(&client, &client, config.clone()).unwrap();
+
+        // The message is received but never acknowledged.
+        let blackhole = Server::with_ctx(ctx.handle()).unwrap();
+        let mut receiver =
+            ReliableReceiver::new(&server, &blackhole, config.clone()).unwrap();
+        sender.send("msg").unwrap();
+        assert_eq!(receiver.recv().unwrap().to_str().unwrap(), "msg");
+
+        // Restart the peer.
+        drop(receiver);
+        drop(server);
+        let start = Instant::now();
+        let server = loop {
+            match ServerBuilder::new()
+                .bind(&bound)
+                .recv_timeout(TIMEOUT)
+                .with_ctx(ctx.handle())
+            {
+                Ok(server) => break server,
+                Err(err) => {
+                    assert_eq!(err.kind(), ErrorKind::AddrInUse);
+                    assert!(start.elapsed() < TIMEOUT);
+                    thread::sleep(Duration::from_millis(10));
+                }
+            }
+        };
+
+        let handle = thread::spawn(move || {
+            let mut receiver =
+                ReliableReceiver::new(server.clone(), server, config).unwrap();
+            receiver.recv().unwrap().to_str().unwrap().to_owned()
+        });
+
+        sender.flush(TIMEOUT).unwrap();
+        assert_eq!(handle.join().unwrap(), "msg");
+        assert!(sender.next_failure().is_none());
+    }
+
+    #[test]
+    fn test_window_full() {
+        let ctx = Ctx::new();
+        let (_server, client) = pair(ctx.handle());
+
+        let mut config = config();
+        config.set_window(2);
+        let mut sender = ReliableSender::new(&client, &client, config).unwrap();
+
+        sender.send("0").unwrap();
+        sender.send("1").unwrap();
+        let mut err = sender.send("2").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WouldBlock);
+        assert_eq!(err.take().unwrap().to_str().unwrap(), "2");
+    }
+
+    #[test]
+    fn test_delivery_failure() {
+        let ctx = Ctx::new();
+        let (_server, client) = pair(ctx.handle());
+
+        let mut config = config();
+        config.set_retries(2);
+        let mut sender = ReliableSender::new(&client, &client, config).unwrap();
+
+        let seq = sender.send("lost").unwrap();
+        sender.flush(TIMEOUT).unwrap();
+
+        let failure = sender.next_failure().unwrap();
+        assert_eq!(failure.seq(), seq);
+        assert_eq!(failure.msg().to_str().unwrap(), "lost");
+        assert!(sender.next_failure().is_none());
+    }
+
+    #[test]
+    fn test_scatter_gather() {
+        let ctx = Ctx::new();
+        let (data, acks) = (InprocAddr::new_unique(), InprocAddr::new_unique());
+
+        let scatter = ScatterBuilder::new()
+            .bind(&data)
+            .with_ctx(ctx.handle())
+            .unwrap();
+        let ack_gather = GatherBuilder::new()
+            .bind(&acks)
+            .with_ctx(ctx.handle())
+            .unwrap();
+        let gather = GatherBuilder::new()
+            .connect(&data)
+            .recv_timeout(TIMEOUT)
+            .with_ctx(ctx.handle())
+            .unwrap();
+        let ack_scatter = ScatterBuilder::new()
+            .connect(&acks)
+            .with_ctx(ctx.handle())
+            .unwrap();
+
+        let mut sender =
+            ReliableSender::new(scatter, ack_gather, config()).unwrap();
+        let mut receiver =
+            ReliableReceiver::new(gather, ack_scatter, config()).unwrap();
+
+        sender.send("msg").unwrap();
+        assert_eq!(receiver.recv().unwrap().to_str().unwrap(), "msg");
+        sender.flush(TIMEOUT).unwrap();
+        assert!(sender.next_failure().is_none());
+    }
+
+    #[test]
+    fn test_dedup_window() {
+        let ctx = Ctx::new();
+        let (server, _client) = pair(ctx.handle());
+
+        let mut config = config();
+        config.set_dedup_window(4);
+        let mut receiver =
+            ReliableReceiver::new(&server, &server, config).unwrap();
+
+        assert!(receiver.is_new(1, 0));
+        assert!(receiver.is_new(1, 2));
+        assert!(!receiver.is_new(1, 0));
+        assert!(receiver.is_new(1, 1));
+        assert!(receiver.is_new(1, 5));
+        // Out of the window.
+        assert!(!receiver.is_new(1, 1));
+        assert!(!receiver.is_new(1, 5));
+        // Another sender has its own window.
+        assert!(receiver.is_new(2, 0));
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{addr::Endpoint, auth::*, core::*, error::*, Ctx, CtxHandle};
+use crate::{
+    addr::Endpoint, auth::*, compress::Compression, core::*, error::*, Ctx,
+    CtxHandle,
+};
 
 use serde::{Deserialize, Serialize};
 
@@ -75,7 +78,7 @@ use std::sync::Arc;
 /// [`Server`]: struct.Server.html
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Client {
-    inner: Arc<RawSocket>,
+    inner: Arc<ThreadSafeSocket>,
 }
 
 impl Client {
@@ -89,7 +92,7 @@ impl Client {
     /// [`SocketLimit`]: enum.ErrorKind.html#variant.SocketLimit
     /// [`global context`]: struct.Ctx.html#method.global
     pub fn new() -> Result<Self, Error> {
-        let inner = Arc::new(RawSocket::new(RawSocketType::Client)?);
+        let inner = Arc::new(ThreadSafeSocket::new(RawSocketType::Client)?);
 
         Ok(Self { inner })
     }
@@ -104,8 +107,10 @@ impl Client {
     /// [`InvalidCtx`]: enum.ErrorKind.html#variant.InvalidCtx
     /// [`SocketLimit`]: enum.ErrorKind.html#variant.SocketLimit
     pub fn with_ctx(handle: CtxHandle) -> Result<Self, Error> {
-        let inner =
-            Arc::new(RawSocket::with_ctx(RawSocketType::Client, handle)?);
+        let inner = Arc::new(ThreadSafeSocket::with_ctx(
+            RawSocketType::Client,
+            handle,
+        )?);
 
         Ok(Self { inner })
     }
@@ -181,6 +186,7 @@ struct FlatClientConfig {
     recv_hwm: HighWaterMark,
     recv_timeout: Period,
     mechanism: Option<Mechanism>,
+    compression: Option<Compression>,
 }
 
 impl From<ClientConfig> for FlatClientConfig {
@@ -194,6 +200,7 @@ impl From<ClientConfig> for FlatClientConfig {
             bind: socket_config.bind,
             heartbeat: heartbeat_config.heartbeat,
             mechanism: socket_config.mechanism,
+            compression: socket_config.compression,
             send_hwm: send_config.send_hwm,
             send_timeout: send_config.send_timeout,
             recv_hwm: recv_config.recv_hwm,
@@ -208,6 +215,7 @@ impl From<FlatClientConfig> for ClientConfig {
             connect: flat.connect,
             bind: flat.bind,
             mechanism: flat.mechanism,
+            compression: flat.compression,
         };
         let send_config = SendConfig {
             send_hwm: flat.send_hwm,
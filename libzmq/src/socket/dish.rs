@@ -1,6 +1,6 @@
 use crate::{
-    addr::Endpoint, auth::*, core::*, error::*, Ctx, CtxHandle, Group,
-    GroupSlice,
+    addr::Endpoint, auth::*, compress::Compression, core::*, error::*, Ctx,
+    CtxHandle, Group, GroupSlice,
 };
 use libzmq_sys as sys;
 use sys::errno;
@@ -27,7 +27,7 @@ fn join(socket_mut_ptr: *mut c_void, group: &GroupSlice) -> Result<(), Error> {
             errno::EINTR => Error::new(ErrorKind::Interrupted),
             errno::ENOTSOCK => panic!("invalid socket"),
             errno::EMTHREAD => panic!("no i/o thread available"),
-            _ => panic!(msg_from_errno(errno)),
+            _ => Error::from_errno(errno),
         };
 
         Err(err)
@@ -50,7 +50,7 @@ fn leave(socket_mut_ptr: *mut c_void, group: &GroupSlice) -> Result<(), Error> {
             errno::EINTR => Error::new(ErrorKind::Interrupted),
             errno::ENOTSOCK => panic!("invalid socket"),
             errno::EMTHREAD => panic!("no i/o thread available"),
-            _ => panic!(msg_from_errno(errno)),
+            _ => Error::from_errno(errno),
         };
 
         Err(err)
@@ -131,7 +131,7 @@ fn leave(socket_mut_ptr: *mut c_void, group: &GroupSlice) -> Result<(), Error> {
 /// [`join`]: #method.join
 #[derive(Debug, Clone)]
 pub struct Dish {
-    inner: Arc<RawSocket>,
+    inner: Arc<ThreadSafeSocket>,
     groups: Arc<Mutex<Vec<Group>>>,
 }
 
@@ -146,7 +146,7 @@ impl Dish {
     /// [`SocketLimit`]: enum.ErrorKind.html#variant.SocketLimit
     /// [`global context`]: struct.Ctx.html#method.global
     pub fn new() -> Result<Self, Error> {
-        let inner = Arc::new(RawSocket::new(RawSocketType::Dish)?);
+        let inner = Arc::new(ThreadSafeSocket::new(RawSocketType::Dish)?);
 
         Ok(Self {
             inner,
@@ -164,7 +164,8 @@ impl Dish {
     /// [`InvalidCtx`]: enum.ErrorKind.html#variant.InvalidCtx
     /// [`SocketLimit`]: enum.ErrorKind.html#variant.SocketLimit
     pub fn with_ctx(handle: CtxHandle) -> Result<Self, Error> {
-        let inner = Arc::new(RawSocket::with_ctx(RawSocketType::Dish, handle)?);
+        let inner =
+            Arc::new(ThreadSafeSocket::with_ctx(RawSocketType::Dish, handle)?);
 
         Ok(Self {
             inner,
@@ -367,6 +368,7 @@ struct FlatDishConfig {
     recv_timeout: Period,
     groups: Option<Vec<Group>>,
     mechanism: Option<Mechanism>,
+    compression: Option<Compression>,
 }
 
 impl From<DishConfig> for FlatDishConfig {
@@ -377,6 +379,7 @@ impl From<DishConfig> for FlatDishConfig {
             connect: socket_config.connect,
             bind: socket_config.bind,
             mechanism: socket_config.mechanism,
+            compression: socket_config.compression,
             recv_hwm: recv_config.recv_hwm,
             recv_timeout: recv_config.recv_timeout,
             groups: config.groups,
@@ -390,6 +393,7 @@ impl From<FlatDishConfig> for DishConfig {
             connect: flat.connect,
             bind: flat.bind,
             mechanism: flat.mechanism,
+            compression: flat.compression,
         };
         let recv_config = RecvConfig {
             recv_hwm: flat.recv_hwm,
@@ -1,4 +1,7 @@
-use crate::{addr::Endpoint, auth::*, core::*, error::*, Ctx, CtxHandle};
+use crate::{
+    addr::Endpoint, auth::*, compress::Compression, core::*, error::*, Ctx,
+    CtxHandle,
+};
 
 use serde::{Deserialize, Serialize};
 
@@ -66,7 +69,7 @@ use std::{str, sync::Arc};
 /// [`Scatter`]: struct.Scatter.html
 #[derive(Debug, Clone)]
 pub struct Gather {
-    inner: Arc<RawSocket>,
+    inner: Arc<ThreadSafeSocket>,
 }
 
 impl Gather {
@@ -80,7 +83,7 @@ impl Gather {
     /// [`SocketLimit`]: enum.ErrorKind.html#variant.SocketLimit
     /// [`global context`]: struct.Ctx.html#method.global
     pub fn new() -> Result<Self, Error> {
-        let inner = Arc::new(RawSocket::new(RawSocketType::Gather)?);
+        let inner = Arc::new(ThreadSafeSocket::new(RawSocketType::Gather)?);
 
         Ok(Self { inner })
     }
@@ -95,8 +98,10 @@ impl Gather {
     /// [`InvalidCtx`]: enum.ErrorKind.html#variant.InvalidCtx
     /// [`SocketLimit`]: enum.ErrorKind.html#variant.SocketLimit
     pub fn with_ctx(handle: CtxHandle) -> Result<Self, Error> {
-        let inner =
-            Arc::new(RawSocket::with_ctx(RawSocketType::Gather, handle)?);
+        let inner = Arc::new(ThreadSafeSocket::with_ctx(
+            RawSocketType::Gather,
+            handle,
+        )?);
 
         Ok(Self { inner })
     }
@@ -174,6 +179,7 @@ struct FlatGatherConfig {
     recv_hwm: HighWaterMark,
     recv_timeout: Period,
     mechanism: Option<Mechanism>,
+    compression: Option<Compression>,
 }
 
 impl From<GatherConfig> for FlatGatherConfig {
@@ -186,6 +192,7 @@ impl From<GatherConfig> for FlatGatherConfig {
             bind: socket_config.bind,
             heartbeat: heartbeat_config.heartbeat,
             mechanism: socket_config.mechanism,
+            compression: socket_config.compression,
             recv_hwm: recv_config.recv_hwm,
             recv_timeout: recv_config.recv_timeout,
         }
@@ -198,6 +205,7 @@ impl From<FlatGatherConfig> for GatherConfig {
             connect: flat.connect,
             bind: flat.bind,
             mechanism: flat.mechanism,
+            compression: flat.compression,
         };
         let recv_config = RecvConfig {
             recv_hwm: flat.recv_hwm,
@@ -15,7 +15,8 @@ pub use scatter::*;
 pub use server::*;
 
 use crate::{
-    core::{GetRawSocket, RawSocket},
+    core::{GetRawSocket, GetSocketConfig, RawSocket, Socket},
+    utils::capabilities,
     Error,
 };
 
@@ -51,6 +52,8 @@ impl GetRawSocket for SocketType {
     }
 }
 
+impl Socket for SocketType {}
+
 /// An enum containing all the socket config types.
 ///
 /// # Note
@@ -70,7 +73,16 @@ pub enum ConfigType {
 }
 
 impl ConfigType {
+    /// Builds the socket from the config.
+    ///
+    /// Returns [`Unsupported`] if the config requires a transport or a
+    /// mechanism that is not available in the linked ØMQ library. This is
+    /// checked before creating the socket.
+    ///
+    /// [`Unsupported`]: ../enum.ErrorKind.html#variant.Unsupported
     pub fn build(&self) -> Result<SocketType, Error> {
+        self.check_capabilities()?;
+
         match self {
             ConfigType::Client(config) => {
                 let client = config.build()?;
@@ -98,4 +110,61 @@ impl ConfigType {
             }
         }
     }
+
+    fn check_capabilities(&self) -> Result<(), Error> {
+        let capabilities = capabilities();
+        capabilities.check_draft()?;
+
+        let socket_config = match self {
+            ConfigType::Client(config) => config.socket_config(),
+            ConfigType::Server(config) => config.socket_config(),
+            ConfigType::Radio(config) => config.socket_config(),
+            ConfigType::Dish(config) => config.socket_config(),
+            ConfigType::Gather(config) => config.socket_config(),
+            ConfigType::Scatter(config) => config.socket_config(),
+        };
+
+        let connect = socket_config.connect.iter().flatten();
+        let bind = socket_config.bind.iter().flatten();
+        for endpoint in connect.chain(bind) {
+            capabilities.check_endpoint(endpoint)?;
+        }
+
+        if let Some(mechanism) = &socket_config.mechanism {
+            capabilities.check_mechanism(mechanism)?;
+        }
+
+        if let Some(compression) = &socket_config.compression {
+            compression.check()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{prelude::*, *};
+
+    #[test]
+    fn test_build_unavailable_transport() {
+        let addr: PgmAddr = "127.0.0.1;239.192.1.1:5555".try_into().unwrap();
+        let mut config = RadioConfig::new();
+        config.set_connect(Some(addr));
+
+        let result = ConfigType::Radio(config).build();
+        if capabilities().contains(Capabilities::PGM) {
+            // The socket type does not support the transport.
+            match result.unwrap_err().kind() {
+                ErrorKind::InvalidInput(_) => (),
+                kind => panic!("unexpected error: {}", kind),
+            }
+        } else {
+            match result.unwrap_err().kind() {
+                ErrorKind::Unsupported(_) => (),
+                kind => panic!("unexpected error: {}", kind),
+            }
+        }
+    }
 }
@@ -1,4 +1,6 @@
-use crate::{addr::Endpoint, auth::*, core::*, error::*, *};
+use crate::{
+    addr::Endpoint, auth::*, compress::Compression, core::*, error::*, *,
+};
 
 use serde::{Deserialize, Serialize};
 
@@ -90,7 +92,7 @@ use std::sync::Arc;
 /// [`transmit`]: #method.transmit
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Radio {
-    inner: Arc<RawSocket>,
+    inner: Arc<ThreadSafeSocket>,
 }
 
 impl Radio {
@@ -104,7 +106,7 @@ impl Radio {
     /// [`SocketLimit`]: enum.ErrorKind.html#variant.SocketLimit
     /// [`global context`]: struct.Ctx.html#method.global
     pub fn new() -> Result<Self, Error> {
-        let inner = Arc::new(RawSocket::new(RawSocketType::Radio)?);
+        let inner = Arc::new(ThreadSafeSocket::new(RawSocketType::Radio)?);
 
         Ok(Self { inner })
     }
@@ -120,7 +122,7 @@ impl Radio {
     /// [`SocketLimit`]: enum.ErrorKind.html#variant.SocketLimit
     pub fn with_ctx(handle: CtxHandle) -> Result<Self, Error> {
         let inner =
-            Arc::new(RawSocket::with_ctx(RawSocketType::Radio, handle)?);
+            Arc::new(ThreadSafeSocket::with_ctx(RawSocketType::Radio, handle)?);
 
         Ok(Self { inner })
     }
@@ -257,6 +259,7 @@ struct FlatRadioConfig {
     send_timeout: Period,
     no_drop: Option<bool>,
     mechanism: Option<Mechanism>,
+    compression: Option<Compression>,
 }
 
 impl From<RadioConfig> for FlatRadioConfig {
@@ -270,6 +273,7 @@ impl From<RadioConfig> for FlatRadioConfig {
             send_timeout: send_config.send_timeout,
             no_drop: config.no_drop,
             mechanism: socket_config.mechanism,
+            compression: socket_config.compression,
         }
     }
 }
@@ -280,6 +284,7 @@ impl From<FlatRadioConfig> for RadioConfig {
             connect: flat.connect,
             bind: flat.bind,
             mechanism: flat.mechanism,
+            compression: flat.compression,
         };
         let send_config = SendConfig {
             send_hwm: flat.send_hwm,
@@ -1,4 +1,7 @@
-use crate::{addr::Endpoint, auth::*, core::*, error::*, Ctx, CtxHandle};
+use crate::{
+    addr::Endpoint, auth::*, compress::Compression, core::*, error::*, Ctx,
+    CtxHandle,
+};
 
 use serde::{Deserialize, Serialize};
 
@@ -57,7 +60,7 @@ use std::{str, sync::Arc};
 /// [`Gather`]: struct.Gather.html
 #[derive(Debug, Clone)]
 pub struct Scatter {
-    inner: Arc<RawSocket>,
+    inner: Arc<ThreadSafeSocket>,
 }
 
 impl Scatter {
@@ -71,7 +74,7 @@ impl Scatter {
     /// [`SocketLimit`]: enum.ErrorKind.html#variant.SocketLimit
     /// [`global context`]: struct.Ctx.html#method.global
     pub fn new() -> Result<Self, Error> {
-        let inner = Arc::new(RawSocket::new(RawSocketType::Scatter)?);
+        let inner = Arc::new(ThreadSafeSocket::new(RawSocketType::Scatter)?);
 
         Ok(Self { inner })
     }
@@ -86,8 +89,10 @@ impl Scatter {
     /// [`InvalidCtx`]: enum.ErrorKind.html#variant.InvalidCtx
     /// [`SocketLimit`]: enum.ErrorKind.html#variant.SocketLimit
     pub fn with_ctx(handle: CtxHandle) -> Result<Self, Error> {
-        let inner =
-            Arc::new(RawSocket::with_ctx(RawSocketType::Scatter, handle)?);
+        let inner = Arc::new(ThreadSafeSocket::with_ctx(
+            RawSocketType::Scatter,
+            handle,
+        )?);
 
         Ok(Self { inner })
     }
@@ -165,6 +170,7 @@ struct FlatScatterConfig {
     send_hwm: HighWaterMark,
     send_timeout: Period,
     mechanism: Option<Mechanism>,
+    compression: Option<Compression>,
 }
 
 impl From<ScatterConfig> for FlatScatterConfig {
@@ -177,6 +183,7 @@ impl From<ScatterConfig> for FlatScatterConfig {
             bind: socket_config.bind,
             heartbeat: heartbeat_config.heartbeat,
             mechanism: socket_config.mechanism,
+            compression: socket_config.compression,
             send_hwm: send_config.send_hwm,
             send_timeout: send_config.send_timeout,
         }
@@ -189,6 +196,7 @@ impl From<FlatScatterConfig> for ScatterConfig {
             connect: flat.connect,
             bind: flat.bind,
             mechanism: flat.mechanism,
+            compression: flat.compression,
         };
         let send_config = SendConfig {
             send_hwm: flat.send_hwm,
@@ -1,8 +1,17 @@
-use crate::{addr::Endpoint, auth::*, core::*, error::*, *};
+use crate::{
+    addr::Endpoint, auth::*, compress::Compression, core::*, error::*,
+    monitor::Monitor, *,
+};
 
+use libzmq_sys as sys;
 use serde::{Deserialize, Serialize};
 
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    fmt,
+    os::raw::c_int,
+    sync::{Arc, Mutex},
+};
 
 /// A `Server` socket is a socket used for advanced request-reply messaging.
 ///
@@ -77,9 +86,25 @@ use std::sync::Arc;
 /// [`routing_id`]: struct.Msg.html#method.routing_id
 /// [`set_routing_id`]: struct.Msg.html#method.set_routing_id
 /// [`HostUnreachable`]: enum.ErrorKind.html#variant.host-unreachable
+///
+/// # Peer Tracking
+/// Once [`set_track_peers`] is enabled, the `Server` keeps track of its
+/// connected clients, which are returned by [`peers`] and notified to the
+/// callbacks registered with [`on_peer_event`]. This allows to [`broadcast`]
+/// a message to every client.
+///
+/// Since ØMQ only assigns a [`routing_id`] to a client once it sends a
+/// message, a client is considered connected from its first received message.
+/// Its disconnection is detected by monitoring the socket. Only the clients
+/// connected via the `tcp` and `ipc` transports are tracked.
+///
+/// [`set_track_peers`]: #method.set_track_peers
+/// [`peers`]: #method.peers
+/// [`on_peer_event`]: #method.on_peer_event
+/// [`broadcast`]: #method.broadcast
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Server {
-    inner: Arc<RawSocket>,
+    inner: Arc<ThreadSafeSocket>,
 }
 
 impl Server {
@@ -93,7 +118,7 @@ impl Server {
     /// [`SocketLimit`]: enum.ErrorKind.html#variant.SocketLimit
     /// [`global context`]: ctx/struct.Ctx.html#method.global
     pub fn new() -> Result<Self, Error> {
-        let inner = Arc::new(RawSocket::new(RawSocketType::Server)?);
+        let inner = Arc::new(ThreadSafeSocket::new(RawSocketType::Server)?);
 
         Ok(Self { inner })
     }
@@ -108,8 +133,10 @@ impl Server {
     /// [`InvalidCtx`]: enum.ErrorKind.html#variant.InvalidCtx
     /// [`SocketLimit`]: enum.ErrorKind.html#variant.SocketLimit
     pub fn with_ctx(handle: CtxHandle) -> Result<Server, Error> {
-        let inner =
-            Arc::new(RawSocket::with_ctx(RawSocketType::Server, handle)?);
+        let inner = Arc::new(ThreadSafeSocket::with_ctx(
+            RawSocketType::Server,
+            handle,
+        )?);
 
         Ok(Self { inner })
     }
@@ -154,6 +181,143 @@ impl Server {
         msg.set_routing_id(id);
         self.try_send(msg)
     }
+
+    /// Returns `true` if the server tracks its connected peers.
+    pub fn is_tracking_peers(&self) -> bool {
+        self.inner.peer_tracker().is_some()
+    }
+
+    /// Sets whether the server tracks its connected peers.
+    ///
+    /// Only the connections established after the option is enabled are
+    /// tracked, so it should be set before binding or connecting the socket.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidCtx`]
+    ///
+    /// # Default value
+    /// `false`
+    ///
+    /// [`InvalidCtx`]: enum.ErrorKind.html#variant.InvalidCtx
+    pub fn set_track_peers(&self, enabled: bool) -> Result<(), Error> {
+        if enabled {
+            if self.inner.peer_tracker().is_none() {
+                let tracker = PeerTracker::new(&self.inner)?;
+                self.inner.set_peer_tracker(Some(Arc::new(tracker)));
+            }
+        } else if self.inner.peer_tracker().is_some() {
+            self.inner.monitor(None, 0)?;
+            self.inner.set_peer_tracker(None);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the `RoutingId` of each connected peer, in no particular
+    /// order.
+    ///
+    /// This is always empty if the server does not [`track its peers`].
+    ///
+    /// [`track its peers`]: #method.set_track_peers
+    pub fn peers(&self) -> Vec<RoutingId> {
+        match self.inner.peer_tracker() {
+            Some(tracker) => tracker.peers(),
+            None => vec![],
+        }
+    }
+
+    /// Registers a callback called with every [`PeerEvent`].
+    ///
+    /// The callback is first called with a `Connected` event for each
+    /// current peer, so that no peer is missed.
+    ///
+    /// Callbacks are called in order of registration from the thread that
+    /// receives from the socket or queries its peers. They should return
+    /// quickly and must not call any method of the `Server`.
+    ///
+    /// Does nothing if the server does not [`track its peers`].
+    ///
+    /// [`PeerEvent`]: enum.PeerEvent.html
+    /// [`track its peers`]: #method.set_track_peers
+    pub fn on_peer_event<F>(&self, callback: F)
+    where
+        F: FnMut(&PeerEvent) + Send + 'static,
+    {
+        if let Some(tracker) = self.inner.peer_tracker() {
+            tracker.on_event(callback);
+        }
+    }
+
+    /// Try to route a copy of the message to every connected peer.
+    ///
+    /// A peer in the mute state does not block the others, it is instead
+    /// reported as failed with [`WouldBlock`]. A peer that disconnected
+    /// before it was detected is reported as failed with [`HostUnreachable`]
+    /// and is no longer tracked.
+    ///
+    /// This requires the server to [`track its peers`].
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> Result<(), anyhow::Error> {
+    /// use libzmq::{prelude::*, *};
+    /// use std::time::Duration;
+    ///
+    /// let addr: TcpAddr = "127.0.0.1:*".try_into()?;
+    ///
+    /// let server = ServerBuilder::new()
+    ///     .track_peers()
+    ///     .bind(addr)
+    ///     .recv_timeout(Duration::from_millis(100))
+    ///     .build()?;
+    ///
+    /// let bound = server.last_endpoint()?;
+    /// let client = ClientBuilder::new().connect(bound).build()?;
+    ///
+    /// // The client is tracked once its first message is received.
+    /// client.send("hello")?;
+    /// let msg = server.recv_msg()?;
+    /// assert_eq!(server.peers(), vec![msg.routing_id().unwrap()]);
+    ///
+    /// let report = server.broadcast("news");
+    /// assert!(report.is_clean());
+    /// assert_eq!(report.sent(), server.peers().as_slice());
+    ///
+    /// let msg = client.recv_msg()?;
+    /// assert_eq!("news", msg.to_str()?);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`WouldBlock`]: enum.ErrorKind.html#variant.WouldBlock
+    /// [`HostUnreachable`]: enum.ErrorKind.html#variant.HostUnreachable
+    /// [`track its peers`]: #method.set_track_peers
+    pub fn broadcast<M>(&self, msg: M) -> BroadcastReport
+    where
+        M: Into<Msg>,
+    {
+        let msg = msg.into();
+        let mut peers = self.peers();
+        peers.sort_by_key(|id| id.0);
+
+        let mut report = BroadcastReport::default();
+        for id in peers {
+            match self.try_route(msg.clone(), id) {
+                Ok(()) => report.sent.push(id),
+                Err(err) => {
+                    if let ErrorKind::HostUnreachable = err.kind() {
+                        if let Some(tracker) = self.inner.peer_tracker() {
+                            tracker.unreachable(id);
+                        }
+                    }
+                    report.failed.push((id, err.cast()));
+                }
+            }
+        }
+
+        report
+    }
 }
 
 impl GetRawSocket for Server {
@@ -170,6 +334,200 @@ impl RecvMsg for Server {}
 unsafe impl Send for Server {}
 unsafe impl Sync for Server {}
 
+/// A change in the peers connected to a [`Server`] that tracks them.
+///
+/// [`Server`]: struct.Server.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PeerEvent {
+    /// A peer connected to the server.
+    Connected(RoutingId),
+    /// A peer disconnected from the server.
+    Disconnected(RoutingId),
+}
+
+impl PeerEvent {
+    /// Returns the `RoutingId` of the peer concerned by the event.
+    pub fn routing_id(&self) -> RoutingId {
+        match self {
+            PeerEvent::Connected(id) | PeerEvent::Disconnected(id) => *id,
+        }
+    }
+}
+
+/// The outcome of a [`broadcast`].
+///
+/// [`broadcast`]: struct.Server.html#method.broadcast
+#[derive(Debug, Default)]
+pub struct BroadcastReport {
+    sent: Vec<RoutingId>,
+    failed: Vec<(RoutingId, Error)>,
+}
+
+impl BroadcastReport {
+    /// Returns `true` if the message was routed to every peer.
+    pub fn is_clean(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Returns the peers the message was routed to.
+    pub fn sent(&self) -> &[RoutingId] {
+        &self.sent
+    }
+
+    /// Returns the peers the message could not be routed to, along with
+    /// the reason.
+    pub fn failed(&self) -> &[(RoutingId, Error)] {
+        &self.failed
+    }
+}
+
+type Callback = Box<dyn FnMut(&PeerEvent) + Send>;
+
+/// Keeps track of the peers connected to a `Server` from the messages
+/// received on the socket and from the disconnections reported by its
+/// monitor.
+///
+/// The peers are identified by the file descriptor of their connection.
+pub(crate) struct PeerTracker {
+    state: Mutex<TrackerState>,
+    callbacks: Mutex<Vec<Callback>>,
+}
+
+struct TrackerState {
+    monitor: Monitor,
+    peers: HashMap<c_int, RoutingId>,
+    // The last peer disconnected from each file descriptor, so that its
+    // messages still queued are not mistaken for a new connection.
+    closed: HashMap<c_int, RoutingId>,
+}
+
+impl PeerTracker {
+    fn new(socket: &RawSocket) -> Result<Self, Error> {
+        let state = TrackerState {
+            monitor: Monitor::with_mask(socket, sys::ZMQ_EVENT_DISCONNECTED)?,
+            peers: HashMap::new(),
+            closed: HashMap::new(),
+        };
+        Ok(Self {
+            state: Mutex::new(state),
+            callbacks: Mutex::default(),
+        })
+    }
+
+    fn peers(&self) -> Vec<RoutingId> {
+        self.update(None);
+        self.state.lock().unwrap().peers.values().cloned().collect()
+    }
+
+    fn on_event<F>(&self, mut callback: F)
+    where
+        F: FnMut(&PeerEvent) + Send + 'static,
+    {
+        let mut callbacks = self.callbacks.lock().unwrap();
+        let state = self.state.lock().unwrap();
+        for id in state.peers.values() {
+            callback(&PeerEvent::Connected(*id));
+        }
+        callbacks.push(Box::new(callback));
+    }
+
+    /// Handles a message received by the socket.
+    pub(crate) fn observe(&self, msg: &Msg) {
+        self.update(Some(msg));
+    }
+
+    /// Handles a peer that was found to be disconnected.
+    fn unreachable(&self, id: RoutingId) {
+        let mut callbacks = self.callbacks.lock().unwrap();
+        let removed = {
+            let mut state = self.state.lock().unwrap();
+            let fd = state
+                .peers
+                .iter()
+                .find(|(_, peer)| **peer == id)
+                .map(|(fd, _)| *fd);
+            fd.map(|fd| state.disconnect(fd))
+        };
+
+        if removed.is_some() {
+            for callback in callbacks.iter_mut() {
+                callback(&PeerEvent::Disconnected(id));
+            }
+        }
+    }
+
+    fn update(&self, msg: Option<&Msg>) {
+        // Notifying while holding the callbacks prevents a callback
+        // registered concurrently from missing or repeating an event.
+        let mut callbacks = self.callbacks.lock().unwrap();
+        let mut events = vec![];
+        {
+            let mut state = self.state.lock().unwrap();
+            // The disconnections are handled first since the file
+            // descriptor of the message might have been reused.
+            while let Some((event, fd)) = state.monitor.try_next() {
+                if event != sys::ZMQ_EVENT_DISCONNECTED {
+                    continue;
+                }
+                if let Some(id) = state.disconnect(fd as c_int) {
+                    events.push(PeerEvent::Disconnected(id));
+                }
+            }
+
+            if let Some(msg) = msg {
+                if let (Some(fd), Some(id)) = (msg.src_fd(), msg.routing_id()) {
+                    state.observe(fd, id, &mut events);
+                }
+            }
+        }
+
+        for event in &events {
+            for callback in callbacks.iter_mut() {
+                callback(event);
+            }
+        }
+    }
+}
+
+impl TrackerState {
+    fn observe(
+        &mut self,
+        fd: c_int,
+        id: RoutingId,
+        events: &mut Vec<PeerEvent>,
+    ) {
+        if self.closed.get(&fd) == Some(&id) {
+            return;
+        }
+        self.closed.remove(&fd);
+
+        match self.peers.insert(fd, id) {
+            Some(previous) if previous == id => (),
+            Some(previous) => {
+                // We missed the disconnection of the previous peer.
+                events.push(PeerEvent::Disconnected(previous));
+                events.push(PeerEvent::Connected(id));
+            }
+            None => events.push(PeerEvent::Connected(id)),
+        }
+    }
+
+    fn disconnect(&mut self, fd: c_int) -> Option<RoutingId> {
+        let id = self.peers.remove(&fd)?;
+        self.closed.insert(fd, id);
+        Some(id)
+    }
+}
+
+impl fmt::Debug for PeerTracker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("PeerTracker")
+            .field("peers", &state.peers)
+            .finish()
+    }
+}
+
 /// A configuration for a `Server`.
 ///
 /// Especially helpfull in config files.
@@ -181,6 +539,7 @@ pub struct ServerConfig {
     send_config: SendConfig,
     recv_config: RecvConfig,
     heartbeat_config: HeartbeatingConfig,
+    track_peers: Option<bool>,
 }
 
 impl ServerConfig {
@@ -199,7 +558,20 @@ impl ServerConfig {
         Ok(server)
     }
 
+    /// Returns `true` if the `track_peers` option is set.
+    pub fn track_peers(&self) -> bool {
+        self.track_peers.unwrap_or_default()
+    }
+
+    /// Sets the `track_peers` option.
+    pub fn set_track_peers(&mut self, cond: bool) {
+        self.track_peers = Some(cond);
+    }
+
     pub fn apply(&self, server: &Server) -> Result<(), Error> {
+        if let Some(enabled) = self.track_peers {
+            server.set_track_peers(enabled)?;
+        }
         self.send_config.apply(server)?;
         self.recv_config.apply(server)?;
         self.heartbeat_config.apply(server)?;
@@ -222,6 +594,8 @@ struct FlatServerConfig {
     recv_hwm: HighWaterMark,
     recv_timeout: Period,
     mechanism: Option<Mechanism>,
+    compression: Option<Compression>,
+    track_peers: Option<bool>,
 }
 
 impl From<ServerConfig> for FlatServerConfig {
@@ -235,10 +609,12 @@ impl From<ServerConfig> for FlatServerConfig {
             bind: socket_config.bind,
             heartbeat: heartbeat_config.heartbeat,
             mechanism: socket_config.mechanism,
+            compression: socket_config.compression,
             send_hwm: send_config.send_hwm,
             send_timeout: send_config.send_timeout,
             recv_hwm: recv_config.recv_hwm,
             recv_timeout: recv_config.recv_timeout,
+            track_peers: config.track_peers,
         }
     }
 }
@@ -249,6 +625,7 @@ impl From<FlatServerConfig> for ServerConfig {
             connect: flat.connect,
             bind: flat.bind,
             mechanism: flat.mechanism,
+            compression: flat.compression,
         };
         let send_config = SendConfig {
             send_hwm: flat.send_hwm,
@@ -266,6 +643,7 @@ impl From<FlatServerConfig> for ServerConfig {
             send_config,
             recv_config,
             heartbeat_config,
+            track_peers: flat.track_peers,
         }
     }
 }
@@ -331,6 +709,11 @@ impl ServerBuilder {
         Self::default()
     }
 
+    pub fn track_peers(&mut self) -> &mut Self {
+        self.inner.set_track_peers(true);
+        self
+    }
+
     pub fn build(&self) -> Result<Server, Error> {
         self.inner.build()
     }
@@ -391,6 +774,47 @@ impl BuildHeartbeating for ServerBuilder {}
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::TcpAddr;
+
+    use std::{
+        convert::TryInto,
+        sync::mpsc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn tracking_server(handle: CtxHandle) -> Server {
+        let addr: TcpAddr = "127.0.0.1:*".try_into().unwrap();
+        ServerBuilder::new()
+            .track_peers()
+            .bind(addr)
+            .recv_timeout(TIMEOUT)
+            .with_ctx(handle)
+            .unwrap()
+    }
+
+    // Connects a client that identifies itself to the server.
+    fn tracked_client(server: &Server) -> (Client, RoutingId) {
+        let client = ClientBuilder::new()
+            .connect(server.last_endpoint().unwrap())
+            .recv_timeout(TIMEOUT)
+            .with_ctx(server.ctx())
+            .unwrap();
+
+        client.send("hello").unwrap();
+        let msg = server.recv_msg().unwrap();
+        (client, msg.routing_id().unwrap())
+    }
+
+    fn wait_for_peers(server: &Server, count: usize) {
+        let start = Instant::now();
+        while server.peers().len() != count {
+            assert!(start.elapsed() < TIMEOUT, "timed out waiting for peers");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
 
     #[test]
     fn test_ser_de() {
@@ -400,4 +824,112 @@ mod test {
         let de: ServerConfig = serde_yaml::from_str(&ron).unwrap();
         assert_eq!(config, de);
     }
+
+    #[test]
+    fn test_ser_de_track_peers() {
+        let mut config = ServerConfig::new();
+        config.set_track_peers(true);
+
+        let ron = serde_yaml::to_string(&config).unwrap();
+        let de: ServerConfig = serde_yaml::from_str(&ron).unwrap();
+        assert_eq!(config, de);
+        assert!(de.track_peers());
+    }
+
+    #[test]
+    fn test_track_peers() {
+        let ctx = Ctx::new();
+        let server = tracking_server(ctx.handle());
+        assert!(server.is_tracking_peers());
+
+        let (sender, receiver) = mpsc::channel();
+        server.on_peer_event(move |event| sender.send(*event).unwrap());
+
+        let (client, id) = tracked_client(&server);
+        assert_eq!(server.peers(), vec![id]);
+        assert_eq!(
+            receiver.recv_timeout(TIMEOUT).unwrap(),
+            PeerEvent::Connected(id)
+        );
+
+        client.send("msg").unwrap();
+        let msg = server.recv_msg().unwrap();
+        assert_eq!("msg", msg.to_str().unwrap());
+        assert_eq!(Some(id), msg.routing_id());
+
+        drop(client);
+        wait_for_peers(&server, 0);
+        assert_eq!(
+            receiver.recv_timeout(TIMEOUT).unwrap(),
+            PeerEvent::Disconnected(id)
+        );
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_track_peers_empty_msg() {
+        let ctx = Ctx::new();
+        let server = tracking_server(ctx.handle());
+
+        let (sender, receiver) = mpsc::channel();
+        server.on_peer_event(move |event| sender.send(*event).unwrap());
+
+        let client = ClientBuilder::new()
+            .connect(server.last_endpoint().unwrap())
+            .with_ctx(ctx.handle())
+            .unwrap();
+
+        // Empty messages are ordinary messages.
+        for _ in 0..3 {
+            client.send(Msg::new()).unwrap();
+            let msg = server.recv_msg().unwrap();
+            assert!(msg.is_empty());
+        }
+
+        let id = receiver.recv_timeout(TIMEOUT).unwrap().routing_id();
+        assert_eq!(server.peers(), vec![id]);
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_on_peer_event_replays_peers() {
+        let ctx = Ctx::new();
+        let server = tracking_server(ctx.handle());
+        let (_client, id) = tracked_client(&server);
+
+        let (sender, receiver) = mpsc::channel();
+        server.on_peer_event(move |event| sender.send(*event).unwrap());
+
+        let event = receiver.try_recv().unwrap();
+        assert_eq!(event, PeerEvent::Connected(id));
+    }
+
+    #[test]
+    fn test_broadcast() {
+        let ctx = Ctx::new();
+        let server = tracking_server(ctx.handle());
+
+        let clients: Vec<Client> =
+            (0..3).map(|_| tracked_client(&server).0).collect();
+
+        let report = server.broadcast("news");
+        assert!(report.is_clean());
+        assert_eq!(report.sent().len(), clients.len());
+
+        for client in &clients {
+            let msg = client.recv_msg().unwrap();
+            assert_eq!("news", msg.to_str().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_broadcast_without_tracking() {
+        let ctx = Ctx::new();
+        let server = Server::with_ctx(ctx.handle()).unwrap();
+        assert!(!server.is_tracking_peers());
+
+        let report = server.broadcast("news");
+        assert!(report.is_clean());
+        assert!(report.sent().is_empty());
+    }
 }
@@ -0,0 +1,1218 @@
+//! A disk-backed store-and-forward queue for outbound messages.
+//!
+//! When a [`Client`] or a [`Scatter`] has no peer or has reached its high
+//! water mark, sending blocks or fails with `WouldBlock`, and whatever the
+//! application keeps in memory is lost if the process restarts. A
+//! [`SpooledSender`] instead appends such messages to a [`Spool`] on disk
+//! and forwards them, in order, as the socket becomes writable again.
+//!
+//! A spool is a directory of append-only segment files. A segment is
+//! deleted once all of its messages are forwarded. The position of the next
+//! message to forward is kept in a `cursor` file, so a spool reopened after
+//! a restart resumes where it left off. Messages forwarded after the cursor
+//! was last synced to disk might be forwarded twice.
+//!
+//! # Format
+//! All integers are big-endian.
+//!
+//! Each segment is named after its id, e.g. `00000000000000000042.seg`,
+//! and starts with a header followed by any number of records.
+//!
+//! The header:
+//!
+//! | Field     | Size | Description                      |
+//! |-----------|------|----------------------------------|
+//! | magic     | 4    | `b"ZSPL"`                        |
+//! | version   | 1    | The version of the format, `1`.  |
+//!
+//! Each record:
+//!
+//! | Field     | Size | Description                           |
+//! |-----------|------|---------------------------------------|
+//! | len       | 4    | The length of the payload.            |
+//! | checksum  | 4    | The FNV-1a hash of the payload.       |
+//! | payload   | len  | The content of the message.           |
+//!
+//! The `cursor` file contains the id of the segment and the offset of the
+//! next record to forward, both as 8 bytes integers.
+//!
+//! A record whose checksum does not match is skipped when it is read and
+//! counted as [`corrupted`]. If its length is corrupted, the records that
+//! follow it in the segment cannot be located and are skipped as well.
+//!
+//! [`Client`]: ../struct.Client.html
+//! [`Scatter`]: ../struct.Scatter.html
+//! [`SpooledSender`]: struct.SpooledSender.html
+//! [`Spool`]: struct.Spool.html
+//! [`corrupted`]: struct.SpoolStats.html#method.corrupted
+
+use crate::{
+    core::SendMsg,
+    error::ErrorKind,
+    msg::Msg,
+    poll::{Events, PollId, Pollable, Poller, WRITABLE},
+    Period,
+};
+
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+const MAGIC: &[u8; 4] = b"ZSPL";
+const VERSION: u8 = 1;
+const HEADER_SIZE: u64 = 5;
+const RECORD_HEADER_SIZE: u64 = 8;
+
+const CURSOR_FILE: &str = "cursor";
+const SEGMENT_EXTENSION: &str = "seg";
+
+const DEFAULT_PATH: &str = "spool";
+const DEFAULT_SEGMENT_SIZE: u64 = 16 * 1024 * 1024;
+const DEFAULT_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// The 32 bits FNV-1a hash, used to detect torn or corrupted records.
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(*byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// When the writes to a [`Spool`] are synced to disk.
+///
+/// [`Spool`]: struct.Spool.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SyncPolicy {
+    /// Every write is synced before returning.
+    Always,
+    /// Writes are synced by the first operation after the interval elapsed
+    /// since the last sync, as well as when the spool is dropped.
+    Interval(#[serde(with = "humantime_serde")] Duration),
+    /// Syncing is left to the operating system.
+    Never,
+}
+
+impl Default for SyncPolicy {
+    fn default() -> Self {
+        SyncPolicy::Interval(DEFAULT_SYNC_INTERVAL)
+    }
+}
+
+/// A configuration for a [`Spool`].
+///
+/// Usefull in configuration files.
+///
+/// [`Spool`]: struct.Spool.html
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpoolConfig {
+    path: PathBuf,
+    segment_size: u64,
+    max_size: Option<u64>,
+    sync: SyncPolicy,
+}
+
+impl SpoolConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens the spool, creating its directory if required.
+    pub fn open(&self) -> io::Result<Spool> {
+        Spool::open(self.clone())
+    }
+
+    /// The directory of the spool.
+    ///
+    /// # Default
+    /// `spool`
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn set_path<P>(&mut self, path: P)
+    where
+        P: Into<PathBuf>,
+    {
+        self.path = path.into();
+    }
+
+    /// The size in bytes after which a new segment is started.
+    ///
+    /// A message larger than the segment size gets a segment of its own.
+    ///
+    /// # Default
+    /// 16MB
+    pub fn segment_size(&self) -> u64 {
+        self.segment_size
+    }
+
+    pub fn set_segment_size(&mut self, size: u64) {
+        self.segment_size = size;
+    }
+
+    /// The maximum size in bytes of the segments on disk, if any.
+    ///
+    /// # Default
+    /// `None`
+    pub fn max_size(&self) -> Option<u64> {
+        self.max_size
+    }
+
+    pub fn set_max_size(&mut self, maybe: Option<u64>) {
+        self.max_size = maybe;
+    }
+
+    /// When the writes are synced to disk.
+    ///
+    /// # Default
+    /// `SyncPolicy::Interval(1s)`
+    pub fn sync(&self) -> SyncPolicy {
+        self.sync
+    }
+
+    pub fn set_sync(&mut self, policy: SyncPolicy) {
+        self.sync = policy;
+    }
+}
+
+impl Default for SpoolConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from(DEFAULT_PATH),
+            segment_size: DEFAULT_SEGMENT_SIZE,
+            max_size: None,
+            sync: SyncPolicy::default(),
+        }
+    }
+}
+
+/// The metrics of a [`Spool`].
+///
+/// [`Spool`]: struct.Spool.html
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SpoolStats {
+    depth: u64,
+    bytes: u64,
+    disk_size: u64,
+    segments: usize,
+    spooled: u64,
+    forwarded: u64,
+    corrupted: u64,
+}
+
+impl SpoolStats {
+    /// The number of messages waiting to be forwarded.
+    ///
+    /// This includes the corrupted records that were not yet skipped.
+    pub fn depth(&self) -> u64 {
+        self.depth
+    }
+
+    /// The total size of the payloads waiting to be forwarded.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// The size of the segments on disk, including the messages already
+    /// forwarded from the oldest segment.
+    pub fn disk_size(&self) -> u64 {
+        self.disk_size
+    }
+
+    /// The number of segments on disk.
+    pub fn segments(&self) -> usize {
+        self.segments
+    }
+
+    /// The number of messages appended since the spool was opened.
+    pub fn spooled(&self) -> u64 {
+        self.spooled
+    }
+
+    /// The number of messages removed since the spool was opened.
+    pub fn forwarded(&self) -> u64 {
+        self.forwarded
+    }
+
+    /// The number of corrupted records skipped since the spool was opened.
+    pub fn corrupted(&self) -> u64 {
+        self.corrupted
+    }
+}
+
+#[derive(Debug)]
+struct Segment {
+    id: u64,
+    size: u64,
+    // The records left to forward and the size of their payloads.
+    depth: u64,
+    bytes: u64,
+}
+
+/// A persistent FIFO queue of messages.
+///
+/// Messages are appended with [`push`] and consumed by [`peek`] followed
+/// by [`pop`]. See the [module level documentation] for the layout on disk.
+///
+/// The spool expects to be the only one using its directory.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), anyhow::Error> {
+/// use libzmq::spool::*;
+///
+/// # let dir = std::env::temp_dir().join("libzmq-spool-doctest");
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// let mut config = SpoolConfig::new();
+/// config.set_path(&dir);
+///
+/// let mut spool = config.open()?;
+/// spool.push(b"first")?;
+/// spool.push(b"second")?;
+/// assert_eq!(spool.peek()?, Some(&b"first"[..]));
+/// spool.pop()?;
+/// drop(spool);
+///
+/// // The spool resumes where it left off.
+/// let mut spool = config.open()?;
+/// assert_eq!(spool.stats().depth(), 1);
+/// assert_eq!(spool.peek()?, Some(&b"second"[..]));
+/// #
+/// # drop(spool);
+/// # std::fs::remove_dir_all(&dir)?;
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// [`push`]: #method.push
+/// [`peek`]: #method.peek
+/// [`pop`]: #method.pop
+/// [module level documentation]: index.html
+#[derive(Debug)]
+pub struct Spool {
+    config: SpoolConfig,
+    // From the oldest to the one being written to.
+    segments: VecDeque<Segment>,
+    next_id: u64,
+    writer: Option<File>,
+    reader: Option<BufReader<File>>,
+    // The offset of the next record in the oldest segment.
+    offset: u64,
+    cursor: File,
+    head: Option<Vec<u8>>,
+    stats: SpoolStats,
+    last_sync: Instant,
+    unsynced: bool,
+}
+
+impl Spool {
+    /// Opens the spool described by the config, creating its directory if
+    /// required.
+    ///
+    /// A record left incomplete or corrupted by a crash at the end of a
+    /// segment is discarded.
+    pub fn open(config: SpoolConfig) -> io::Result<Self> {
+        if config.segment_size <= HEADER_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "segment size is too small",
+            ));
+        }
+        fs::create_dir_all(&config.path)?;
+
+        let mut ids = vec![];
+        for entry in fs::read_dir(&config.path)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str())
+                != Some(SEGMENT_EXTENSION)
+            {
+                continue;
+            }
+            let id = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<u64>().ok());
+            if let Some(id) = id {
+                ids.push(id);
+            }
+        }
+        ids.sort();
+
+        let cursor_path = config.path.join(CURSOR_FILE);
+        let (read_id, offset) = match fs::read(&cursor_path) {
+            Ok(bytes) if bytes.len() == 16 => (
+                BigEndian::read_u64(&bytes[..8]),
+                BigEndian::read_u64(&bytes[8..]),
+            ),
+            Ok(_) => return Err(invalid_data("invalid spool cursor")),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                (ids.first().cloned().unwrap_or(0), HEADER_SIZE)
+            }
+            Err(err) => return Err(err),
+        };
+
+        let mut segments = VecDeque::new();
+        let mut stats = SpoolStats::default();
+        for id in ids {
+            let path = segment_path(&config.path, id);
+            // The segment was consumed before the last restart.
+            if id < read_id {
+                fs::remove_file(&path)?;
+                continue;
+            }
+
+            let start = if id == read_id { offset } else { HEADER_SIZE };
+            match scan(&path, start)? {
+                Some((size, depth, bytes)) => {
+                    segments.push_back(Segment {
+                        id,
+                        size,
+                        depth,
+                        bytes,
+                    });
+                    stats.depth += depth;
+                    stats.bytes += bytes;
+                    stats.disk_size += size;
+                }
+                None => fs::remove_file(&path)?,
+            }
+        }
+
+        let next_id = segments.back().map_or(read_id, |s| s.id + 1);
+        let offset = match segments.front() {
+            Some(segment) if segment.id == read_id => offset,
+            _ => HEADER_SIZE,
+        };
+        let writer = match segments.back() {
+            Some(segment) => Some(
+                OpenOptions::new()
+                    .append(true)
+                    .open(segment_path(&config.path, segment.id))?,
+            ),
+            None => None,
+        };
+        let cursor = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&cursor_path)?;
+
+        let mut spool = Self {
+            config,
+            segments,
+            next_id,
+            writer,
+            reader: None,
+            offset,
+            cursor,
+            head: None,
+            stats,
+            last_sync: Instant::now(),
+            unsynced: false,
+        };
+        spool.write_cursor()?;
+
+        Ok(spool)
+    }
+
+    /// Returns the metrics of the spool.
+    pub fn stats(&self) -> SpoolStats {
+        SpoolStats {
+            segments: self.segments.len(),
+            ..self.stats
+        }
+    }
+
+    /// Returns `true` if there are no messages waiting to be forwarded.
+    pub fn is_empty(&self) -> bool {
+        self.stats.depth == 0
+    }
+
+    /// Appends a message to the spool.
+    ///
+    /// # Returned Error Kinds
+    /// * `WouldBlock` (if the `max_size` of the spool would be exceeded)
+    /// * `InvalidInput` (if the payload exceeds 4GB)
+    pub fn push(&mut self, payload: &[u8]) -> io::Result<()> {
+        if payload.len() > u32::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "payload cannot exceed 4GB",
+            ));
+        }
+        let record = RECORD_HEADER_SIZE + payload.len() as u64;
+
+        // Once every message was forwarded, a new segment is started so that
+        // the previous ones can be released.
+        let roll = match (self.segments.back(), &self.writer) {
+            (Some(segment), Some(_)) => {
+                segment.size > HEADER_SIZE
+                    && (self.is_empty()
+                        || segment.size + record > self.config.segment_size)
+            }
+            _ => true,
+        };
+
+        if let Some(max_size) = self.config.max_size {
+            let disk_size = if roll && self.is_empty() {
+                HEADER_SIZE
+            } else if roll {
+                self.stats.disk_size + HEADER_SIZE
+            } else {
+                self.stats.disk_size
+            };
+            if disk_size + record > max_size {
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    "spool is full",
+                ));
+            }
+        }
+
+        if roll {
+            self.roll()?;
+        }
+
+        let mut buf = Vec::with_capacity(record as usize);
+        buf.write_u32::<BigEndian>(payload.len() as u32)?;
+        buf.write_u32::<BigEndian>(checksum(payload))?;
+        buf.extend_from_slice(payload);
+        // The writer is always set after rolling.
+        self.writer.as_mut().unwrap().write_all(&buf)?;
+
+        let segment = self.segments.back_mut().unwrap();
+        segment.size += record;
+        segment.depth += 1;
+        segment.bytes += payload.len() as u64;
+        self.stats.disk_size += record;
+        self.stats.depth += 1;
+        self.stats.bytes += payload.len() as u64;
+        self.stats.spooled += 1;
+        self.unsynced = true;
+
+        self.maybe_sync()
+    }
+
+    /// Returns the oldest message of the spool without removing it.
+    ///
+    /// Corrupted records are skipped.
+    pub fn peek(&mut self) -> io::Result<Option<&[u8]>> {
+        if self.head.is_none() {
+            self.head = self.read_next()?;
+        }
+
+        Ok(self.head.as_deref())
+    }
+
+    /// Removes the oldest message of the spool.
+    ///
+    /// Returns `false` if the spool was empty.
+    pub fn pop(&mut self) -> io::Result<bool> {
+        if self.head.is_none() {
+            self.head = self.read_next()?;
+        }
+        let payload = match self.head.take() {
+            Some(payload) => payload,
+            None => return Ok(false),
+        };
+
+        self.stats.forwarded += 1;
+        self.consume(payload.len() as u64)?;
+
+        Ok(true)
+    }
+
+    /// Syncs the pending writes to disk, regardless of the [`SyncPolicy`].
+    ///
+    /// [`SyncPolicy`]: enum.SyncPolicy.html
+    pub fn sync(&mut self) -> io::Result<()> {
+        if self.unsynced {
+            if let Some(writer) = &self.writer {
+                writer.sync_data()?;
+            }
+            self.cursor.sync_data()?;
+            self.unsynced = false;
+        }
+        self.last_sync = Instant::now();
+
+        Ok(())
+    }
+
+    fn maybe_sync(&mut self) -> io::Result<()> {
+        match self.config.sync {
+            SyncPolicy::Always => self.sync(),
+            SyncPolicy::Interval(interval)
+                if self.last_sync.elapsed() >= interval =>
+            {
+                self.sync()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn read_next(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            if self.is_empty() {
+                return Ok(None);
+            }
+
+            // Skip the oldest segment if it was consumed before a restart.
+            while self.segments.len() > 1
+                && self.segments.front().unwrap().size <= self.offset
+            {
+                self.release_front()?;
+            }
+
+            let segment = self.segments.front().unwrap();
+            let path = segment_path(&self.config.path, segment.id);
+            let size = segment.size;
+            if self.reader.is_none() {
+                let mut file = File::open(&path)?;
+                file.seek(SeekFrom::Start(self.offset))?;
+                self.reader = Some(BufReader::new(file));
+            }
+
+            let reader = self.reader.as_mut().unwrap();
+            let len = reader.read_u32::<BigEndian>()?;
+            let sum = reader.read_u32::<BigEndian>()?;
+            if self.offset + RECORD_HEADER_SIZE + u64::from(len) > size {
+                warn!(
+                    "skipping the rest of {} after a corrupted record length",
+                    path.display()
+                );
+                self.discard_front()?;
+                continue;
+            }
+
+            let mut payload = vec![0; len as usize];
+            reader.read_exact(&mut payload)?;
+            if checksum(&payload) == sum {
+                return Ok(Some(payload));
+            }
+
+            warn!(
+                "skipping corrupted record at offset {} of {}",
+                self.offset,
+                path.display()
+            );
+            self.stats.corrupted += 1;
+            self.consume(u64::from(len))?;
+        }
+    }
+
+    // Moves past the next record of the oldest segment.
+    fn consume(&mut self, len: u64) -> io::Result<()> {
+        self.offset += RECORD_HEADER_SIZE + len;
+        let segment = self.segments.front_mut().unwrap();
+        segment.depth -= 1;
+        segment.bytes -= len;
+        self.stats.depth -= 1;
+        self.stats.bytes -= len;
+
+        // The oldest segment is released as soon as it is consumed.
+        if segment.size <= self.offset && self.segments.len() > 1 {
+            self.release_front()
+        } else {
+            self.write_cursor()
+        }
+    }
+
+    // Skips the records left in the oldest segment.
+    fn discard_front(&mut self) -> io::Result<()> {
+        let segment = self.segments.front_mut().unwrap();
+        self.stats.depth -= segment.depth;
+        self.stats.bytes -= segment.bytes;
+        self.stats.corrupted += segment.depth;
+        segment.depth = 0;
+        segment.bytes = 0;
+        self.offset = segment.size;
+        self.reader = None;
+
+        if self.segments.len() > 1 {
+            self.release_front()
+        } else {
+            self.write_cursor()
+        }
+    }
+
+    fn roll(&mut self) -> io::Result<()> {
+        let durable = self.config.sync != SyncPolicy::Never;
+        if let Some(writer) = self.writer.take() {
+            if durable {
+                writer.sync_data()?;
+            }
+        }
+
+        // Every message was forwarded, so all segments can be released.
+        if self.is_empty() {
+            while let Some(segment) = self.segments.pop_front() {
+                fs::remove_file(segment_path(&self.config.path, segment.id))?;
+            }
+            self.stats.disk_size = 0;
+            self.reader = None;
+            self.head = None;
+        }
+
+        let id = self.next_id;
+        let path = segment_path(&self.config.path, id);
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create_new(true)
+            .open(path)?;
+        file.write_all(MAGIC)?;
+        file.write_u8(VERSION)?;
+        if durable {
+            file.sync_data()?;
+            sync_dir(&self.config.path)?;
+        }
+
+        self.next_id += 1;
+        self.segments.push_back(Segment {
+            id,
+            size: HEADER_SIZE,
+            depth: 0,
+            bytes: 0,
+        });
+        self.stats.disk_size += HEADER_SIZE;
+        self.writer = Some(file);
+
+        if self.segments.len() == 1 {
+            self.offset = HEADER_SIZE;
+            self.write_cursor()?;
+        }
+
+        Ok(())
+    }
+
+    fn release_front(&mut self) -> io::Result<()> {
+        let segment = self.segments.pop_front().unwrap();
+        self.reader = None;
+        self.offset = HEADER_SIZE;
+        // The cursor is moved before the segment is removed so that a crash
+        // in between does not lose the position.
+        self.write_cursor()?;
+
+        fs::remove_file(segment_path(&self.config.path, segment.id))?;
+        self.stats.disk_size -= segment.size;
+
+        Ok(())
+    }
+
+    fn write_cursor(&mut self) -> io::Result<()> {
+        let id = self.segments.front().map_or(self.next_id, |s| s.id);
+        let mut buf = [0; 16];
+        BigEndian::write_u64(&mut buf[..8], id);
+        BigEndian::write_u64(&mut buf[8..], self.offset);
+
+        self.cursor.seek(SeekFrom::Start(0))?;
+        self.cursor.write_all(&buf)?;
+        self.unsynced = true;
+
+        self.maybe_sync()
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        if self.config.sync != SyncPolicy::Never {
+            if let Err(err) = self.sync() {
+                warn!("failed to sync spool: {}", err);
+            }
+        }
+    }
+}
+
+fn segment_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{:020}.{}", id, SEGMENT_EXTENSION))
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+// Validates the records of a segment, truncating any invalid tail left by a
+// crash. Returns the size of the segment along with the number of records
+// and payload bytes from `start`, or `None` if the header is incomplete.
+//
+// A corrupted record followed by other records is kept, it is skipped once
+// it is read.
+fn scan(path: &Path, start: u64) -> io::Result<Option<(u64, u64, u64)>> {
+    let data = fs::read(path)?;
+    if (data.len() as u64) < HEADER_SIZE {
+        return Ok(None);
+    }
+    if &data[..4] != MAGIC {
+        return Err(invalid_data("not a spool segment"));
+    }
+    if data[4] != VERSION {
+        return Err(invalid_data("unsupported spool version"));
+    }
+
+    let (mut pos, mut depth, mut bytes) = (HEADER_SIZE as usize, 0, 0);
+    while pos + RECORD_HEADER_SIZE as usize <= data.len() {
+        let len = BigEndian::read_u32(&data[pos..]) as usize;
+        let sum = BigEndian::read_u32(&data[pos + 4..]);
+        let begin = pos + RECORD_HEADER_SIZE as usize;
+        let end = begin + len;
+        if end > data.len()
+            || (end == data.len() && checksum(&data[begin..end]) != sum)
+        {
+            break;
+        }
+        if pos as u64 >= start {
+            depth += 1;
+            bytes += len as u64;
+        }
+        pos = end;
+    }
+
+    if pos < data.len() {
+        warn!(
+            "discarding {} invalid bytes at the end of {}",
+            data.len() - pos,
+            path.display()
+        );
+        OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_len(pos as u64)?;
+    }
+
+    Ok(Some((pos as u64, depth, bytes)))
+}
+
+/// Sends messages on a socket, spooling them to disk while the socket
+/// cannot accept them.
+///
+/// A message is sent directly if the spool is empty and the socket is
+/// writable, otherwise it is appended to the spool. The spooled messages are
+/// forwarded in order whenever [`send`], [`drain`] or [`flush`] is called.
+/// Once a message is accepted by the socket, it is no longer spooled.
+///
+/// Since the messages are stored without their routing id nor group, this
+/// is intended for [`Client`] and [`Scatter`] sockets.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), anyhow::Error> {
+/// use libzmq::{prelude::*, spool::*, *};
+/// use std::time::Duration;
+///
+/// # let dir = std::env::temp_dir().join("libzmq-spooled-sender-doctest");
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// let mut config = SpoolConfig::new();
+/// config.set_path(&dir);
+///
+/// // The client is not connected so its messages are spooled.
+/// let client = Client::new()?;
+/// let mut sender = SpooledSender::new(&client, config.open()?);
+/// sender.send("msg")?;
+/// assert_eq!(sender.spool().stats().depth(), 1);
+///
+/// let addr: TcpAddr = "127.0.0.1:*".try_into()?;
+/// let server = ServerBuilder::new().bind(addr).build()?;
+/// client.connect(server.last_endpoint()?)?;
+///
+/// // Forward the spooled messages as the client connects.
+/// sender.flush(Duration::from_secs(1))?;
+/// assert_eq!(server.recv_msg()?.to_str()?, "msg");
+/// #
+/// # drop(sender);
+/// # std::fs::remove_dir_all(&dir)?;
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// [`send`]: #method.send
+/// [`drain`]: #method.drain
+/// [`flush`]: #method.flush
+/// [`Client`]: ../struct.Client.html
+/// [`Scatter`]: ../struct.Scatter.html
+#[derive(Debug)]
+pub struct SpooledSender<S> {
+    socket: S,
+    spool: Spool,
+}
+
+impl<S> SpooledSender<S>
+where
+    S: SendMsg,
+{
+    /// Creates a new `SpooledSender` that sends on `socket`.
+    ///
+    /// The messages already in the `spool` are forwarded first.
+    pub fn new(socket: S, spool: Spool) -> Self {
+        Self { socket, spool }
+    }
+
+    /// Returns a reference to the socket.
+    pub fn socket(&self) -> &S {
+        &self.socket
+    }
+
+    /// Returns a reference to the spool.
+    pub fn spool(&self) -> &Spool {
+        &self.spool
+    }
+
+    /// Returns the socket and the spool.
+    pub fn into_inner(self) -> (S, Spool) {
+        (self.socket, self.spool)
+    }
+
+    /// Sends the message if possible, otherwise appends it to the spool.
+    ///
+    /// # Returned Error Kinds
+    /// * `WouldBlock` (if the spool is full)
+    pub fn send<M>(&mut self, msg: M) -> io::Result<()>
+    where
+        M: Into<Msg>,
+    {
+        let msg = msg.into();
+        // The message cannot overtake the spooled messages.
+        self.drain()?;
+        if !self.spool.is_empty() {
+            return self.spool.push(msg.as_bytes());
+        }
+
+        match self.socket.try_send(msg) {
+            Ok(()) => Ok(()),
+            Err(mut err) => match err.kind() {
+                ErrorKind::WouldBlock | ErrorKind::Interrupted => {
+                    let msg = err.take().unwrap();
+                    self.spool.push(msg.as_bytes())
+                }
+                _ => Err(err.into()),
+            },
+        }
+    }
+
+    /// Forwards the spooled messages until the socket would block, without
+    /// blocking.
+    ///
+    /// Returns the number of forwarded messages.
+    pub fn drain(&mut self) -> io::Result<u64> {
+        let mut count = 0;
+        while let Some(payload) = self.spool.peek()? {
+            let msg = Msg::from(payload);
+            match self.socket.try_send(msg) {
+                Ok(()) => {
+                    self.spool.pop()?;
+                    count += 1;
+                }
+                Err(err) => match err.kind() {
+                    ErrorKind::WouldBlock | ErrorKind::Interrupted => break,
+                    _ => return Err(err.into()),
+                },
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Blocks until every spooled message is forwarded, or until the
+    /// `timeout` expires.
+    ///
+    /// # Returned Error Kinds
+    /// * `TimedOut` (if the timeout expires)
+    pub fn flush(&mut self, timeout: Duration) -> io::Result<()> {
+        let expiry = Instant::now() + timeout;
+
+        let mut poller = Poller::new();
+        let socket = Pollable::Socket(self.socket.raw_socket());
+        poller.add(socket, PollId(0), WRITABLE)?;
+        let mut events = Events::new();
+
+        loop {
+            self.drain()?;
+            if self.spool.is_empty() {
+                return Ok(());
+            }
+
+            let now = Instant::now();
+            if now >= expiry {
+                return Err(io::Error::from(io::ErrorKind::TimedOut));
+            }
+
+            if let Err(err) =
+                poller.poll(&mut events, Period::Finite(expiry - now))
+            {
+                match err.kind() {
+                    ErrorKind::WouldBlock | ErrorKind::Interrupted => {}
+                    _ => return Err(err.into()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{prelude::*, *};
+
+    use uuid::Uuid;
+
+    use std::{convert::TryInto, env};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let name = format!("libzmq-spool-{}", Uuid::new_v4());
+            TempDir(env::temp_dir().join(name))
+        }
+
+        fn config(&self) -> SpoolConfig {
+            let mut config = SpoolConfig::new();
+            config.set_path(&self.0);
+            config
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn pop(spool: &mut Spool) -> Vec<u8> {
+        let payload = spool.peek().unwrap().unwrap().to_vec();
+        assert!(spool.pop().unwrap());
+        payload
+    }
+
+    #[test]
+    fn test_ser_de() {
+        let mut config = SpoolConfig::new();
+        config.set_max_size(Some(1024));
+        config.set_sync(SyncPolicy::Interval(Duration::from_millis(10)));
+
+        let ron = serde_yaml::to_string(&config).unwrap();
+        let de: SpoolConfig = serde_yaml::from_str(&ron).unwrap();
+        assert_eq!(config, de);
+    }
+
+    #[test]
+    fn test_push_pop_reopen() {
+        let dir = TempDir::new();
+        let config = dir.config();
+
+        let mut spool = config.open().unwrap();
+        assert!(spool.is_empty());
+        assert!(spool.peek().unwrap().is_none());
+        assert!(!spool.pop().unwrap());
+
+        for payload in &["0", "1", "2"] {
+            spool.push(payload.as_bytes()).unwrap();
+        }
+        assert_eq!(pop(&mut spool), b"0");
+        drop(spool);
+
+        let mut spool = config.open().unwrap();
+        let stats = spool.stats();
+        assert_eq!(stats.depth(), 2);
+        assert_eq!(stats.bytes(), 2);
+        assert_eq!(pop(&mut spool), b"1");
+
+        spool.push(b"3").unwrap();
+        assert_eq!(pop(&mut spool), b"2");
+        assert_eq!(pop(&mut spool), b"3");
+        assert!(spool.is_empty());
+
+        let stats = spool.stats();
+        assert_eq!(stats.spooled(), 1);
+        assert_eq!(stats.forwarded(), 3);
+    }
+
+    #[test]
+    fn test_segments() {
+        let dir = TempDir::new();
+        let mut config = dir.config();
+        // Fits two records of 8 bytes per segment.
+        config.set_segment_size(HEADER_SIZE + 2 * (RECORD_HEADER_SIZE + 8));
+        config.set_sync(SyncPolicy::Never);
+
+        let mut spool = config.open().unwrap();
+        for i in 0..10_u64 {
+            spool.push(&i.to_be_bytes()).unwrap();
+        }
+        assert_eq!(spool.stats().segments(), 5);
+
+        for i in 0..5_u64 {
+            assert_eq!(pop(&mut spool), i.to_be_bytes());
+        }
+        // The consumed segments are removed.
+        assert_eq!(spool.stats().segments(), 3);
+        drop(spool);
+
+        let mut spool = config.open().unwrap();
+        assert_eq!(spool.stats().depth(), 5);
+        for i in 5..10_u64 {
+            assert_eq!(pop(&mut spool), i.to_be_bytes());
+        }
+        assert_eq!(spool.stats().segments(), 1);
+    }
+
+    #[test]
+    fn test_max_size() {
+        let dir = TempDir::new();
+        let mut config = dir.config();
+        config.set_max_size(Some(HEADER_SIZE + 2 * (RECORD_HEADER_SIZE + 4)));
+
+        let mut spool = config.open().unwrap();
+        spool.push(b"0000").unwrap();
+        spool.push(b"1111").unwrap();
+        let err = spool.push(b"2222").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+        // Once the spool is drained, its segments are reused.
+        pop(&mut spool);
+        pop(&mut spool);
+        spool.push(b"2222").unwrap();
+        assert_eq!(pop(&mut spool), b"2222");
+    }
+
+    #[test]
+    fn test_torn_record() {
+        let dir = TempDir::new();
+        let config = dir.config();
+
+        let mut spool = config.open().unwrap();
+        spool.push(b"complete").unwrap();
+        spool.push(b"torn").unwrap();
+        drop(spool);
+
+        let path = segment_path(&dir.0, 0);
+        let len = fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 1)
+            .unwrap();
+
+        let mut spool = config.open().unwrap();
+        assert_eq!(spool.stats().depth(), 1);
+        assert_eq!(pop(&mut spool), b"complete");
+        spool.push(b"after").unwrap();
+        assert_eq!(pop(&mut spool), b"after");
+    }
+
+    #[test]
+    fn test_corrupted_record() {
+        let dir = TempDir::new();
+        let config = dir.config();
+
+        let mut spool = config.open().unwrap();
+        for payload in &["0", "1", "2", "3"] {
+            spool.push(payload.as_bytes()).unwrap();
+        }
+        drop(spool);
+
+        // Flip the payload of the second record.
+        let path = segment_path(&dir.0, 0);
+        let mut data = fs::read(&path).unwrap();
+        let offset = HEADER_SIZE + 2 * RECORD_HEADER_SIZE + 1;
+        data[offset as usize] ^= 0xff;
+        fs::write(&path, data).unwrap();
+
+        let mut spool = config.open().unwrap();
+        assert_eq!(spool.stats().depth(), 4);
+        assert_eq!(pop(&mut spool), b"0");
+        assert_eq!(pop(&mut spool), b"2");
+        assert_eq!(pop(&mut spool), b"3");
+        assert!(spool.is_empty());
+        assert_eq!(spool.stats().corrupted(), 1);
+        assert_eq!(spool.stats().forwarded(), 3);
+    }
+
+    #[test]
+    fn test_corrupted_len() {
+        let dir = TempDir::new();
+        let mut config = dir.config();
+        // Fits two records of 8 bytes per segment.
+        config.set_segment_size(HEADER_SIZE + 2 * (RECORD_HEADER_SIZE + 8));
+
+        let mut spool = config.open().unwrap();
+        for i in 0..4_u64 {
+            spool.push(&i.to_be_bytes()).unwrap();
+        }
+
+        // The records that follow a corrupted length in the oldest segment
+        // are lost, but not those of the next segments.
+        let path = segment_path(&dir.0, 0);
+        let mut data = fs::read(&path).unwrap();
+        data[HEADER_SIZE as usize] = 0xff;
+        fs::write(&path, data).unwrap();
+
+        assert_eq!(pop(&mut spool), 2_u64.to_be_bytes());
+        assert_eq!(pop(&mut spool), 3_u64.to_be_bytes());
+        assert!(spool.is_empty());
+        assert_eq!(spool.stats().corrupted(), 2);
+        assert_eq!(spool.stats().segments(), 1);
+    }
+
+    #[test]
+    fn test_spooled_sender() {
+        let dir = TempDir::new();
+        let config = dir.config();
+        let ctx = Ctx::new();
+
+        let client = Client::with_ctx(ctx.handle()).unwrap();
+        let mut sender = SpooledSender::new(&client, config.open().unwrap());
+        sender.send("0").unwrap();
+        sender.send("1").unwrap();
+        assert_eq!(sender.drain().unwrap(), 0);
+        let err = sender.flush(Duration::from_millis(10)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        // The spooled messages survive a restart.
+        let (_, spool) = sender.into_inner();
+        drop(spool);
+        let mut sender = SpooledSender::new(&client, config.open().unwrap());
+        assert_eq!(sender.spool().stats().depth(), 2);
+        sender.send("2").unwrap();
+
+        let addr: TcpAddr = "127.0.0.1:*".try_into().unwrap();
+        let server = ServerBuilder::new()
+            .bind(addr)
+            .recv_timeout(TIMEOUT)
+            .with_ctx(ctx.handle())
+            .unwrap();
+        client.connect(server.last_endpoint().unwrap()).unwrap();
+
+        sender.flush(TIMEOUT).unwrap();
+        assert!(sender.spool().is_empty());
+        for expected in &["0", "1", "2"] {
+            let msg = server.recv_msg().unwrap();
+            assert_eq!(msg.to_str().unwrap(), *expected);
+        }
+
+        // Nothing is spooled once the client is connected.
+        sender.send("3").unwrap();
+        assert_eq!(sender.spool().stats().spooled(), 1);
+        assert_eq!(server.recv_msg().unwrap().to_str().unwrap(), "3");
+    }
+}
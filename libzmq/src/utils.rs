@@ -1,8 +1,161 @@
-use crate::{core::GetRawSocket, error::*};
+use crate::{addr::Endpoint, auth::Mechanism, core::GetRawSocket, error::*};
 use libzmq_sys as sys;
 use sys::errno;
 
-use std::{os::raw::*, ptr};
+use bitflags::bitflags;
+use lazy_static::lazy_static;
+
+use std::{ffi::CString, os::raw::*, ptr};
+
+lazy_static! {
+    static ref CAPABILITIES: Capabilities = Capabilities::detect();
+}
+
+bitflags! {
+    /// The set of optional capabilities compiled into the linked ØMQ library.
+    ///
+    /// See [`capabilities`].
+    ///
+    /// [`capabilities`]: fn.capabilities.html
+    pub struct Capabilities: u32 {
+        /// The `ipc` transport.
+        const IPC = 1;
+        /// The `pgm` and `epgm` transports.
+        const PGM = 1 << 1;
+        /// The `tipc` transport.
+        const TIPC = 1 << 2;
+        /// The `norm` transport.
+        const NORM = 1 << 3;
+        /// The `CURVE` security mechanism.
+        const CURVE = 1 << 4;
+        /// The `GSSAPI` security mechanism.
+        const GSSAPI = 1 << 5;
+        /// The draft API, which is required by all the socket types of this
+        /// crate.
+        const DRAFT = 1 << 6;
+        /// The `ws` and `wss` transports.
+        const WS = 1 << 7;
+    }
+}
+
+impl Capabilities {
+    fn detect() -> Self {
+        let candidates = [
+            ("ipc", Capabilities::IPC),
+            ("pgm", Capabilities::PGM),
+            ("tipc", Capabilities::TIPC),
+            ("norm", Capabilities::NORM),
+            ("curve", Capabilities::CURVE),
+            ("gssapi", Capabilities::GSSAPI),
+            ("draft", Capabilities::DRAFT),
+            ("ws", Capabilities::WS),
+        ];
+
+        let mut capabilities = Capabilities::empty();
+        for (name, capability) in candidates.iter() {
+            let c_string = CString::new(*name).unwrap();
+            if unsafe { sys::zmq_has(c_string.as_ptr()) } == 1 {
+                capabilities |= *capability;
+            }
+        }
+
+        capabilities
+    }
+
+    /// Returns an `Unsupported` error if the draft API is not available.
+    pub(crate) fn check_draft(self) -> Result<(), Error> {
+        if self.contains(Capabilities::DRAFT) {
+            Ok(())
+        } else {
+            Err(Error::new(ErrorKind::Unsupported(
+                "draft API is not available in the linked libzmq",
+            )))
+        }
+    }
+
+    /// Returns an `Unsupported` error if the transport of the endpoint
+    /// is not available.
+    pub(crate) fn check_endpoint(
+        self,
+        endpoint: &Endpoint,
+    ) -> Result<(), Error> {
+        match endpoint {
+            Endpoint::Pgm(_) | Endpoint::Epgm(_) => {
+                if self.contains(Capabilities::PGM) {
+                    Ok(())
+                } else {
+                    Err(Error::new(ErrorKind::Unsupported(
+                        "PGM transport is not available in the linked libzmq",
+                    )))
+                }
+            }
+            Endpoint::Ipc(_) => {
+                if self.contains(Capabilities::IPC) {
+                    Ok(())
+                } else {
+                    Err(Error::new(ErrorKind::Unsupported(
+                        "IPC transport is not available in the linked libzmq",
+                    )))
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns an `Unsupported` error if the mechanism is not available.
+    pub(crate) fn check_mechanism(
+        self,
+        mechanism: &Mechanism,
+    ) -> Result<(), Error> {
+        match mechanism {
+            Mechanism::CurveClient(_) | Mechanism::CurveServer(_) => {
+                self.check_curve()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns an `Unsupported` error if `CURVE` is not available.
+    pub(crate) fn check_curve(self) -> Result<(), Error> {
+        if cfg!(not(feature = "curve")) {
+            Err(Error::new(ErrorKind::Unsupported(
+                "CURVE support requires enabling feature flag 'curve'",
+            )))
+        } else if !self.contains(Capabilities::CURVE) {
+            Err(Error::new(ErrorKind::Unsupported(
+                "CURVE is not available in the linked libzmq",
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Reports the optional capabilities of the linked ØMQ library.
+///
+/// The capabilities are detected once using [`zmq_has`], then cached.
+///
+/// This is used internally to return an [`Unsupported`] error when an
+/// unavailable transport or mechanism is requested.
+///
+/// # Example
+/// ```
+/// use libzmq::{capabilities, Capabilities};
+///
+/// let capabilities = capabilities();
+/// // The draft API is always required.
+/// assert!(capabilities.contains(Capabilities::DRAFT));
+///
+/// if capabilities.contains(Capabilities::PGM) {
+///     // Use a multicast transport.
+/// }
+/// ```
+///
+/// [`zmq_has`]: http://api.zeromq.org/master:zmq-has
+/// [`Unsupported`]: enum.ErrorKind.html#variant.Unsupported
+pub fn capabilities() -> Capabilities {
+    *CAPABILITIES
+}
 
 /// Reports the ØMQ library version.
 ///
@@ -102,10 +255,23 @@ where
     assert_eq!(rc, -1);
 
     let errno = unsafe { sys::zmq_errno() };
-    let err = match errno {
+    Err(proxy_error(errno))
+}
+
+fn proxy_error(errno: c_int) -> Error {
+    match errno {
         errno::ETERM => Error::new(ErrorKind::InvalidCtx),
-        _ => panic!(msg_from_errno(errno)),
-    };
+        _ => Error::from_errno(errno),
+    }
+}
 
-    Err(err)
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_proxy_unexpected_error() {
+        let err = proxy_error(libc::EIO);
+        assert_eq!(err.kind(), ErrorKind::unexpected(libc::EIO));
+    }
 }
@@ -0,0 +1,922 @@
+//! A decoder for the ZMTP 3.x wire protocol, used for debugging.
+//!
+//! The [`Decoder`] parses one direction of a ZMTP connection, as it
+//! would be captured from TCP traffic. It understands the greeting, the
+//! `NULL`, `PLAIN` and `CURVE` handshake commands, the `READY` metadata,
+//! the `PING` and `PONG` heartbeats as well as the message frames.
+//!
+//! Since the `CURVE` boxes are encrypted, only their unencrypted parts
+//! are decoded.
+//!
+//! See [ZMTP 3.0] and [ZMTP 3.1].
+//!
+//! [`Decoder`]: struct.Decoder.html
+//! [ZMTP 3.0]: https://rfc.zeromq.org/spec/23/
+//! [ZMTP 3.1]: https://rfc.zeromq.org/spec/37/
+
+use crate::error::{Error, ErrorKind};
+
+use byteorder::{BigEndian, ByteOrder};
+
+use std::{fmt, str, time::Duration};
+
+const GREETING_SIZE: usize = 64;
+const MECHANISM_SIZE: usize = 20;
+
+const MORE_FLAG: u8 = 0b001;
+const LONG_FLAG: u8 = 0b010;
+const COMMAND_FLAG: u8 = 0b100;
+
+// libzmq sends the `CURVE` messages as data frames, not as commands.
+const CURVE_MESSAGE: &[u8] = b"\x07MESSAGE";
+
+// The number of payload bytes that are displayed.
+const DISPLAY_SIZE: usize = 64;
+
+fn invalid(msg: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidInput(msg))
+}
+
+/// The security mechanism advertised in a [`Greeting`].
+///
+/// [`Greeting`]: struct.Greeting.html
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MechanismName {
+    Null,
+    Plain,
+    Curve,
+    /// A mechanism unknown to the decoder, such as `GSSAPI`.
+    Other(String),
+}
+
+impl MechanismName {
+    fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+        let name = str::from_utf8(&bytes[..end])
+            .map_err(|_| invalid("invalid mechanism"))?;
+
+        let mechanism = match name {
+            "NULL" => MechanismName::Null,
+            "PLAIN" => MechanismName::Plain,
+            "CURVE" => MechanismName::Curve,
+            _ => MechanismName::Other(name.to_owned()),
+        };
+        Ok(mechanism)
+    }
+}
+
+impl fmt::Display for MechanismName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MechanismName::Null => write!(f, "NULL"),
+            MechanismName::Plain => write!(f, "PLAIN"),
+            MechanismName::Curve => write!(f, "CURVE"),
+            MechanismName::Other(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// The greeting that starts a ZMTP connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Greeting {
+    major: u8,
+    minor: u8,
+    mechanism: MechanismName,
+    as_server: bool,
+}
+
+impl Greeting {
+    fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes[0] != 0xFF || bytes[9] != 0x7F {
+            return Err(invalid("invalid greeting signature"));
+        }
+        let major = bytes[10];
+        if major < 3 {
+            return Err(invalid("unsupported ZMTP version"));
+        }
+
+        Ok(Self {
+            major,
+            minor: bytes[11],
+            mechanism: MechanismName::from_bytes(
+                &bytes[12..12 + MECHANISM_SIZE],
+            )?,
+            as_server: bytes[12 + MECHANISM_SIZE] != 0,
+        })
+    }
+
+    /// The major and minor version of the protocol.
+    pub fn version(&self) -> (u8, u8) {
+        (self.major, self.minor)
+    }
+
+    pub fn mechanism(&self) -> &MechanismName {
+        &self.mechanism
+    }
+
+    /// Whether the peer acts as the server of the mechanism.
+    pub fn as_server(&self) -> bool {
+        self.as_server
+    }
+}
+
+impl fmt::Display for Greeting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "GREETING version={}.{} mechanism={} as-server={}",
+            self.major, self.minor, self.mechanism, self.as_server
+        )
+    }
+}
+
+/// The metadata properties of a `READY` or `INITIATE` command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    properties: Vec<(String, Vec<u8>)>,
+}
+
+impl Metadata {
+    fn parse(mut cursor: Cursor) -> Result<Self, Error> {
+        let mut properties = vec![];
+        while !cursor.is_empty() {
+            let len = cursor.u8()? as usize;
+            let name = str::from_utf8(cursor.take(len)?)
+                .map_err(|_| invalid("invalid property name"))?;
+            let len = cursor.u32()? as usize;
+            let value = cursor.take(len)?;
+            properties.push((name.to_owned(), value.to_vec()));
+        }
+
+        Ok(Self { properties })
+    }
+
+    /// Returns the value of the property, if any.
+    ///
+    /// As per the specification, property names are case-insensitive.
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.properties
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_slice())
+    }
+
+    /// Returns an iterator over the properties in order of appearance.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.properties
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_slice()))
+    }
+}
+
+impl fmt::Display for Metadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (name, value)) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}={}", name, Bytes(value))?;
+        }
+        Ok(())
+    }
+}
+
+/// A ZMTP command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Command {
+    /// Ends the handshake of the `NULL` and `PLAIN` mechanisms.
+    Ready(Metadata),
+    /// The handshake failed, such as when the peer was denied by ZAP.
+    Error {
+        reason: String,
+    },
+    PlainHello {
+        username: Vec<u8>,
+        password: Vec<u8>,
+    },
+    PlainWelcome,
+    PlainInitiate(Metadata),
+    CurveHello {
+        version: (u8, u8),
+        /// The transient public key of the client.
+        client_key: Vec<u8>,
+        nonce: u64,
+    },
+    CurveWelcome {
+        nonce: Vec<u8>,
+    },
+    CurveInitiate {
+        nonce: u64,
+        box_size: usize,
+    },
+    /// Ends the handshake of the `CURVE` mechanism. The metadata are
+    /// encrypted.
+    CurveReady {
+        nonce: u64,
+        box_size: usize,
+    },
+    /// An encrypted message frame. It is sent as a data frame once the
+    /// `CURVE` handshake completed.
+    CurveMessage {
+        nonce: u64,
+        box_size: usize,
+    },
+    Ping {
+        /// The period after which the peer should time out.
+        ttl: Duration,
+        context: Vec<u8>,
+    },
+    Pong {
+        context: Vec<u8>,
+    },
+    Subscribe(Vec<u8>),
+    Cancel(Vec<u8>),
+    Join(Vec<u8>),
+    Leave(Vec<u8>),
+    /// A command unknown to the decoder.
+    Unknown {
+        name: String,
+        data: Vec<u8>,
+    },
+}
+
+impl Command {
+    fn parse(body: &[u8], mechanism: &MechanismName) -> Result<Self, Error> {
+        let mut cursor = Cursor { bytes: body };
+        let len = cursor.u8()? as usize;
+        let name = str::from_utf8(cursor.take(len)?)
+            .map_err(|_| invalid("invalid command name"))?;
+
+        let command = match (name, mechanism) {
+            ("READY", MechanismName::Curve) => Command::CurveReady {
+                nonce: cursor.u64()?,
+                box_size: cursor.rest().len(),
+            },
+            ("READY", _) => Command::Ready(Metadata::parse(cursor)?),
+            ("ERROR", _) => {
+                let len = cursor.u8()? as usize;
+                let reason = cursor.take(len)?;
+                Command::Error {
+                    reason: String::from_utf8_lossy(reason).into_owned(),
+                }
+            }
+            ("HELLO", MechanismName::Plain) => {
+                let len = cursor.u8()? as usize;
+                let username = cursor.take(len)?.to_vec();
+                let len = cursor.u8()? as usize;
+                let password = cursor.take(len)?.to_vec();
+                Command::PlainHello { username, password }
+            }
+            ("WELCOME", MechanismName::Plain) => Command::PlainWelcome,
+            ("INITIATE", MechanismName::Plain) => {
+                Command::PlainInitiate(Metadata::parse(cursor)?)
+            }
+            ("HELLO", MechanismName::Curve) => {
+                let major = cursor.u8()?;
+                let minor = cursor.u8()?;
+                cursor.take(72)?;
+                let client_key = cursor.take(32)?.to_vec();
+                let nonce = cursor.u64()?;
+                cursor.take(80)?;
+                Command::CurveHello {
+                    version: (major, minor),
+                    client_key,
+                    nonce,
+                }
+            }
+            ("WELCOME", MechanismName::Curve) => {
+                let nonce = cursor.take(16)?.to_vec();
+                cursor.take(144)?;
+                Command::CurveWelcome { nonce }
+            }
+            ("INITIATE", MechanismName::Curve) => {
+                cursor.take(96)?;
+                Command::CurveInitiate {
+                    nonce: cursor.u64()?,
+                    box_size: cursor.rest().len(),
+                }
+            }
+            ("MESSAGE", MechanismName::Curve) => Command::CurveMessage {
+                nonce: cursor.u64()?,
+                box_size: cursor.rest().len(),
+            },
+            ("PING", _) => {
+                let ttl = cursor.u16()?;
+                Command::Ping {
+                    ttl: Duration::from_millis(u64::from(ttl) * 100),
+                    context: cursor.rest().to_vec(),
+                }
+            }
+            ("PONG", _) => Command::Pong {
+                context: cursor.rest().to_vec(),
+            },
+            ("SUBSCRIBE", _) => Command::Subscribe(cursor.rest().to_vec()),
+            ("CANCEL", _) => Command::Cancel(cursor.rest().to_vec()),
+            ("JOIN", _) => Command::Join(cursor.rest().to_vec()),
+            ("LEAVE", _) => Command::Leave(cursor.rest().to_vec()),
+            _ => Command::Unknown {
+                name: name.to_owned(),
+                data: cursor.rest().to_vec(),
+            },
+        };
+
+        Ok(command)
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Ready(metadata) => write!(f, "READY {}", metadata),
+            Command::Error { reason } => write!(f, "ERROR reason={:?}", reason),
+            Command::PlainHello { username, password } => write!(
+                f,
+                "HELLO username={} password-size={}",
+                Bytes(username),
+                password.len()
+            ),
+            Command::PlainWelcome => write!(f, "WELCOME"),
+            Command::PlainInitiate(metadata) => {
+                write!(f, "INITIATE {}", metadata)
+            }
+            Command::CurveHello {
+                version,
+                client_key,
+                nonce,
+            } => write!(
+                f,
+                "HELLO version={}.{} client-key={} nonce={}",
+                version.0,
+                version.1,
+                Hex(client_key),
+                nonce
+            ),
+            Command::CurveWelcome { nonce } => {
+                write!(f, "WELCOME nonce={}", Hex(nonce))
+            }
+            Command::CurveInitiate { nonce, box_size } => {
+                write!(f, "INITIATE nonce={} box-size={}", nonce, box_size)
+            }
+            Command::CurveReady { nonce, box_size } => {
+                write!(f, "READY nonce={} box-size={}", nonce, box_size)
+            }
+            Command::CurveMessage { nonce, box_size } => {
+                write!(f, "MESSAGE nonce={} box-size={}", nonce, box_size)
+            }
+            Command::Ping { ttl, context } => write!(
+                f,
+                "PING ttl={}ms context={}",
+                ttl.as_millis(),
+                Bytes(context)
+            ),
+            Command::Pong { context } => {
+                write!(f, "PONG context={}", Bytes(context))
+            }
+            Command::Subscribe(topic) => {
+                write!(f, "SUBSCRIBE {}", Bytes(topic))
+            }
+            Command::Cancel(topic) => write!(f, "CANCEL {}", Bytes(topic)),
+            Command::Join(group) => write!(f, "JOIN {}", Bytes(group)),
+            Command::Leave(group) => write!(f, "LEAVE {}", Bytes(group)),
+            Command::Unknown { name, data } => {
+                write!(f, "{} {}", name, Bytes(data))
+            }
+        }
+    }
+}
+
+/// A message frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    more: bool,
+    data: Vec<u8>,
+}
+
+impl Frame {
+    /// Whether more frames of the same message follow.
+    pub fn more(&self) -> bool {
+        self.more
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "FRAME more={} size={} {}",
+            self.more,
+            self.data.len(),
+            Bytes(&self.data)
+        )
+    }
+}
+
+/// A unit decoded from a ZMTP stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Packet {
+    Greeting(Greeting),
+    Command(Command),
+    Frame(Frame),
+}
+
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Packet::Greeting(greeting) => greeting.fmt(f),
+            Packet::Command(command) => command.fmt(f),
+            Packet::Frame(frame) => frame.fmt(f),
+        }
+    }
+}
+
+/// An incremental decoder for one direction of a ZMTP 3.x connection.
+///
+/// Bytes are fed to the decoder as they are captured and the packets are
+/// decoded as soon as they are complete. Once an error is returned, the
+/// stream cannot be decoded further.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), anyhow::Error> {
+/// use libzmq::zmtp::*;
+///
+/// let mut greeting = [0; 64];
+/// greeting[0] = 0xFF;
+/// greeting[9] = 0x7F;
+/// greeting[10] = 3;
+/// greeting[11] = 1;
+/// greeting[12..16].copy_from_slice(b"NULL");
+///
+/// let mut decoder = Decoder::new();
+/// decoder.feed(&greeting[..10]);
+/// // The greeting is incomplete.
+/// assert!(decoder.decode()?.is_none());
+///
+/// decoder.feed(&greeting[10..]);
+/// // A short command frame holding a `PING` with a TTL of 1s.
+/// decoder.feed(b"\x04\x07\x04PING\x00\x0A");
+///
+/// match decoder.decode()? {
+///     Some(Packet::Greeting(greeting)) => {
+///         assert_eq!(greeting.mechanism(), &MechanismName::Null);
+///     }
+///     _ => unreachable!(),
+/// }
+/// let ping = decoder.decode()?.unwrap();
+/// assert_eq!(ping.to_string(), "PING ttl=1000ms context=\"\"");
+/// assert_eq!(decoder.offset(), 73);
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct Decoder {
+    buf: Vec<u8>,
+    greeting: Option<Greeting>,
+    offset: u64,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends captured bytes to the decoder.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// The greeting of the stream, if it was decoded.
+    pub fn greeting(&self) -> Option<&Greeting> {
+        self.greeting.as_ref()
+    }
+
+    /// The number of bytes of the stream that were decoded.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The number of bytes that were fed but not yet decoded.
+    pub fn pending(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Decodes the next packet.
+    ///
+    /// Returns `None` if more bytes are required.
+    ///
+    /// # Returned Error Variants
+    /// * [`InvalidInput`] (if the stream is not valid ZMTP 3.x)
+    ///
+    /// [`InvalidInput`]: ../enum.ErrorKind.html#variant.InvalidInput
+    pub fn decode(&mut self) -> Result<Option<Packet>, Error> {
+        let mechanism = match &self.greeting {
+            Some(greeting) => greeting.mechanism.clone(),
+            None => {
+                // The signature can be validated before the whole greeting
+                // is received.
+                if !self.buf.is_empty() && self.buf[0] != 0xFF {
+                    return Err(invalid("invalid greeting signature"));
+                }
+                if self.buf.len() < GREETING_SIZE {
+                    return Ok(None);
+                }
+                let greeting = Greeting::parse(&self.buf[..GREETING_SIZE])?;
+                self.consume(GREETING_SIZE);
+                self.greeting = Some(greeting.clone());
+                return Ok(Some(Packet::Greeting(greeting)));
+            }
+        };
+
+        if self.buf.len() < 2 {
+            return Ok(None);
+        }
+        let flags = self.buf[0];
+        if flags & !(MORE_FLAG | LONG_FLAG | COMMAND_FLAG) != 0 {
+            return Err(invalid("invalid frame flags"));
+        }
+
+        let (header, size) = if flags & LONG_FLAG != 0 {
+            if self.buf.len() < 9 {
+                return Ok(None);
+            }
+            (9, BigEndian::read_u64(&self.buf[1..9]))
+        } else {
+            (2, u64::from(self.buf[1]))
+        };
+
+        if ((self.buf.len() - header) as u64) < size {
+            return Ok(None);
+        }
+        let end = header + size as usize;
+        let body = &self.buf[header..end];
+
+        let packet = if flags & COMMAND_FLAG != 0 {
+            if flags & MORE_FLAG != 0 {
+                return Err(invalid("command cannot have more frames"));
+            }
+            Packet::Command(Command::parse(body, &mechanism)?)
+        } else if mechanism == MechanismName::Curve
+            && body.starts_with(CURVE_MESSAGE)
+        {
+            Packet::Command(Command::parse(body, &mechanism)?)
+        } else {
+            Packet::Frame(Frame {
+                more: flags & MORE_FLAG != 0,
+                data: body.to_vec(),
+            })
+        };
+
+        self.consume(end);
+        Ok(Some(packet))
+    }
+
+    fn consume(&mut self, size: usize) {
+        self.buf.drain(..size);
+        self.offset += size as u64;
+    }
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take(&mut self, size: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() < size {
+            return Err(invalid("truncated command"));
+        }
+        let (taken, rest) = self.bytes.split_at(size);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let rest = self.bytes;
+        self.bytes = &[];
+        rest
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        self.take(2).map(BigEndian::read_u16)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        self.take(4).map(BigEndian::read_u32)
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        self.take(8).map(BigEndian::read_u64)
+    }
+}
+
+// Displays bytes as a quoted string if they are printable, otherwise
+// as hexadecimal.
+struct Bytes<'a>(&'a [u8]);
+
+impl<'a> fmt::Display for Bytes<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let shown = &self.0[..self.0.len().min(DISPLAY_SIZE)];
+        match str::from_utf8(shown) {
+            Ok(s) if !s.chars().any(char::is_control) => {
+                write!(f, "{:?}", s)?;
+            }
+            _ => write!(f, "{}", Hex(shown))?,
+        }
+        if shown.len() < self.0.len() {
+            write!(f, "...")?;
+        }
+        Ok(())
+    }
+}
+
+struct Hex<'a>(&'a [u8]);
+
+impl<'a> fmt::Display for Hex<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "0x")?;
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{prelude::*, *};
+
+    use std::{
+        io::{ErrorKind as IoErrorKind, Read, Write},
+        net::TcpListener,
+    };
+
+    // The client side of a `CURVE` connection to a `Server` that received
+    // a "hello" message, captured from libzmq 4.3.4.
+    const CURVE_CLIENT: &str = concat!(
+        "ff00000000000000017f03014355525645000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "04c80548454c4c4f010000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "000000000000000000000000000000000000c0e89d91467ce81d2a1e6bd3693d",
+        "e7b62397385db0d5a0dafd635a60a627df2c00000000000000018a9b18c4079e",
+        "3af42127c41ca6a462da1bfec5e8bde63afd82473f9c900c9f64e477a25f1e56",
+        "df20e7020669f2aedd905df0eb54ad8bb7cf30c641076ea9fe240005db650f63",
+        "c5b66f96ea05b605c84806000000000000011708494e49544941544547e978f5",
+        "a1030243c170429f6329ed88d266c6c7f53c53921c98f08f484257d2dd6c9672",
+        "854448c72663cb2fc94872bd18d71439f5f7ce40eca438b13473b88aca2eda40",
+        "9d0d8f13f77f5bc73523cdce90dc319bd7b57dcb14956e82b88350cc00000000",
+        "0000000281b9e1e4d0cb81b731b1729bd99e675f6c96032489b377e6c1157dd4",
+        "7b99edd2c2db80af095bcfeb2ce8307659788ad0ba6941618c5ac8478e7f5024",
+        "dfe869601dc39056f35b536e7a034c56af5a115a741cfd4cf949a6be06330008",
+        "4cb080663b2cab702bc33401fad808d18ad3494152f05d8b17614bc32b9005d8",
+        "2e8c662b6561f97a33f87bf7890d43829a03b7a924519237e4fab63419cf64af",
+        "33d5593451c37dbc6ab20026074d45535341474500000000000000036d71a0e4",
+        "7af0548853ecff669609c25d899da561ca68",
+    );
+
+    fn greeting(mechanism: &str, as_server: bool) -> Vec<u8> {
+        let mut bytes = vec![0; GREETING_SIZE];
+        bytes[0] = 0xFF;
+        bytes[9] = 0x7F;
+        bytes[10] = 3;
+        bytes[11] = 1;
+        bytes[12..12 + mechanism.len()].copy_from_slice(mechanism.as_bytes());
+        bytes[32] = as_server as u8;
+        bytes
+    }
+
+    fn command(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut body = vec![name.len() as u8];
+        body.extend_from_slice(name.as_bytes());
+        body.extend_from_slice(data);
+
+        let mut bytes = if body.len() > 255 {
+            let mut header = vec![COMMAND_FLAG | LONG_FLAG, 0, 0, 0, 0];
+            header.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            header
+        } else {
+            vec![COMMAND_FLAG, body.len() as u8]
+        };
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    fn decode_all(bytes: &[u8]) -> Vec<Packet> {
+        let mut decoder = Decoder::new();
+        decoder.feed(bytes);
+        let mut packets = vec![];
+        while let Some(packet) = decoder.decode().unwrap() {
+            packets.push(packet);
+        }
+        assert_eq!(decoder.pending(), 0);
+        packets
+    }
+
+    #[test]
+    fn test_null_handshake() {
+        let mut bytes = greeting("NULL", false);
+        bytes
+            .extend(command("READY", b"\x0bSocket-Type\x00\x00\x00\x06CLIENT"));
+        // A two part message, the second frame being long.
+        bytes.extend(b"\x01\x05hello");
+        bytes.extend(b"\x02\x00\x00\x00\x00\x00\x00\x01\x00");
+        bytes.extend(vec![0xAB; 256]);
+
+        let packets = decode_all(&bytes);
+        assert_eq!(packets.len(), 4);
+
+        match &packets[0] {
+            Packet::Greeting(greeting) => {
+                assert_eq!(greeting.version(), (3, 1));
+                assert_eq!(greeting.mechanism(), &MechanismName::Null);
+                assert!(!greeting.as_server());
+            }
+            _ => panic!("expected greeting"),
+        }
+        match &packets[1] {
+            Packet::Command(Command::Ready(metadata)) => {
+                assert_eq!(metadata.get("socket-type"), Some(&b"CLIENT"[..]));
+            }
+            _ => panic!("expected READY"),
+        }
+        assert_eq!(packets[1].to_string(), "READY Socket-Type=\"CLIENT\"");
+        assert_eq!(packets[2].to_string(), "FRAME more=true size=5 \"hello\"");
+        match &packets[3] {
+            Packet::Frame(frame) => {
+                assert!(!frame.more());
+                assert_eq!(frame.data().len(), 256);
+            }
+            _ => panic!("expected frame"),
+        }
+    }
+
+    #[test]
+    fn test_plain_and_curve_commands() {
+        let mut bytes = greeting("PLAIN", false);
+        bytes.extend(command("HELLO", b"\x05admin\x03pwd"));
+        bytes.extend(command("ERROR", b"\x0bAccess deny"));
+
+        let packets = decode_all(&bytes);
+        assert_eq!(
+            packets[1],
+            Packet::Command(Command::PlainHello {
+                username: b"admin".to_vec(),
+                password: b"pwd".to_vec(),
+            })
+        );
+        assert_eq!(packets[2].to_string(), "ERROR reason=\"Access deny\"");
+
+        let mut hello = vec![1, 0];
+        hello.extend(vec![0; 72]);
+        hello.extend(vec![7; 32]);
+        hello.extend(&42u64.to_be_bytes());
+        hello.extend(vec![0; 80]);
+
+        let mut bytes = greeting("CURVE", false);
+        bytes.extend(command("HELLO", &hello));
+
+        let packets = decode_all(&bytes);
+        assert_eq!(
+            packets[1],
+            Packet::Command(Command::CurveHello {
+                version: (1, 0),
+                client_key: vec![7; 32],
+                nonce: 42,
+            })
+        );
+    }
+
+    #[test]
+    fn test_curve_client_traffic() {
+        let bytes = (0..CURVE_CLIENT.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&CURVE_CLIENT[i..i + 2], 16).unwrap())
+            .collect::<Vec<_>>();
+
+        let packets = decode_all(&bytes);
+        assert_eq!(packets.len(), 4);
+
+        match &packets[1] {
+            Packet::Command(Command::CurveHello { version, nonce, .. }) => {
+                assert_eq!(*version, (1, 0));
+                assert_eq!(*nonce, 1);
+            }
+            _ => panic!("expected HELLO"),
+        }
+        match &packets[2] {
+            Packet::Command(Command::CurveInitiate { nonce, .. }) => {
+                assert_eq!(*nonce, 2);
+            }
+            _ => panic!("expected INITIATE"),
+        }
+        // The flags, the "hello" payload and the 16 bytes MAC.
+        assert_eq!(
+            packets[3],
+            Packet::Command(Command::CurveMessage {
+                nonce: 3,
+                box_size: 22,
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_stream() {
+        let mut decoder = Decoder::new();
+        decoder.feed(b"GET / HTTP/1.1");
+        let err = decoder.decode().unwrap_err();
+        assert_eq!(
+            err.kind(),
+            ErrorKind::InvalidInput("invalid greeting signature")
+        );
+
+        let mut decoder = Decoder::new();
+        decoder.feed(&greeting("NULL", false));
+        decoder.feed(&command("HELLO", b"\x05admin"));
+        decoder.decode().unwrap();
+        // A truncated PLAIN hello in a NULL stream is an unknown command.
+        match decoder.decode().unwrap() {
+            Some(Packet::Command(Command::Unknown { name, .. })) => {
+                assert_eq!(name, "HELLO")
+            }
+            _ => panic!("expected unknown command"),
+        }
+
+        let mut decoder = Decoder::new();
+        decoder.feed(&greeting("PLAIN", false));
+        decoder.feed(&command("HELLO", b"\x05admin"));
+        decoder.decode().unwrap();
+        let err = decoder.decode().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput("truncated command"));
+    }
+
+    #[test]
+    fn test_decode_client_traffic() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr: TcpAddr = listener
+            .local_addr()
+            .unwrap()
+            .to_string()
+            .try_into()
+            .unwrap();
+
+        let client = ClientBuilder::new().connect(addr).build().unwrap();
+        client.try_send("some msg").unwrap();
+
+        let (mut stream, _) = listener.accept().unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        // Complete the handshake so that the client sends its message.
+        stream.write_all(&greeting("NULL", true)).unwrap();
+        stream
+            .write_all(&command(
+                "READY",
+                b"\x0bSocket-Type\x00\x00\x00\x06SERVER",
+            ))
+            .unwrap();
+
+        let mut decoder = Decoder::new();
+        let mut packets = vec![];
+        let mut buf = [0; 512];
+        while packets.len() < 3 {
+            match stream.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => decoder.feed(&buf[..n]),
+                Err(err) if err.kind() == IoErrorKind::WouldBlock => break,
+                Err(err) => panic!("{}", err),
+            }
+            while let Some(packet) = decoder.decode().unwrap() {
+                packets.push(packet);
+            }
+        }
+
+        match &packets[1] {
+            Packet::Command(Command::Ready(metadata)) => {
+                assert_eq!(metadata.get("Socket-Type"), Some(&b"CLIENT"[..]));
+            }
+            _ => panic!("expected READY"),
+        }
+        assert_eq!(
+            packets[2],
+            Packet::Frame(Frame {
+                more: false,
+                data: b"some msg".to_vec(),
+            })
+        );
+    }
+}
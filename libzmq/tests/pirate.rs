@@ -0,0 +1,118 @@
+use libzmq::{addr::Endpoint, pirate::*, prelude::*, *};
+
+use std::{
+    env,
+    io::{BufRead, BufReader, Write},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+const INTERVAL: Duration = Duration::from_millis(100);
+// The backend endpoint of the broker, set for the worker process.
+const BROKER_VAR: &str = "LIBZMQ_PIRATE_BROKER";
+// Printed by the worker process once it holds a request.
+const RECEIVED: &str = "pirate worker received";
+
+fn heartbeat() -> Heartbeat {
+    Heartbeat::new(INTERVAL).add_timeout(3 * INTERVAL)
+}
+
+// Runs as the worker process of `test_worker_process_killed`. It receives a
+// request and never replies to it.
+#[test]
+#[ignore]
+fn pirate_worker_process() {
+    let addr: TcpAddr = match env::var(BROKER_VAR) {
+        Ok(addr) => addr.parse().unwrap(),
+        Err(_) => return,
+    };
+
+    let mut worker = PirateWorkerBuilder::new()
+        .broker(addr)
+        .heartbeat(heartbeat())
+        .reconnect(INTERVAL)
+        .build()
+        .unwrap();
+    let request = worker.recv().unwrap();
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    writeln!(stdout, "{} {:?}", RECEIVED, request.body()).unwrap();
+    stdout.flush().unwrap();
+
+    loop {
+        thread::sleep(Duration::from_secs(60));
+    }
+}
+
+#[test]
+fn test_worker_process_killed() {
+    let ctx = Ctx::new();
+    let addr: TcpAddr = "127.0.0.1:*".try_into().unwrap();
+    let broker = PirateBrokerBuilder::new()
+        .frontend(addr.clone())
+        .backend(addr)
+        .heartbeat(heartbeat())
+        .with_ctx(ctx.handle())
+        .unwrap();
+
+    let backend = match broker.backend_endpoint() {
+        Endpoint::Tcp(addr) => addr.to_string(),
+        _ => unreachable!(),
+    };
+    let mut child = Command::new(env::current_exe().unwrap())
+        .arg("pirate_worker_process")
+        .args(["--exact", "--ignored", "--nocapture"])
+        .env(BROKER_VAR, backend)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while broker.ready_workers() != 1 {
+        assert!(Instant::now() < deadline, "timed out");
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let mut client = PirateClientBuilder::new()
+        .broker(broker.frontend_endpoint().clone())
+        .timeout(5 * INTERVAL)
+        .retries(5)
+        .backoff(INTERVAL)
+        .with_ctx(ctx.handle())
+        .unwrap();
+    let handle = thread::spawn(move || client.request("survive"));
+
+    // Kill the worker process while it holds the request. The marker
+    // follows the name of the test on the line printed by libtest.
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    let received = stdout
+        .lines()
+        .map(Result::unwrap)
+        .find(|line| line.contains(RECEIVED))
+        .expect("worker process exited");
+    assert!(received.ends_with(&format!("{:?}", b"survive")));
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    // The retried request is handled by a healthy worker.
+    let mut worker = PirateWorkerBuilder::new()
+        .broker(broker.backend_endpoint().clone())
+        .heartbeat(heartbeat())
+        .reconnect(INTERVAL)
+        .with_ctx(ctx.handle())
+        .unwrap();
+    let echo = thread::spawn(move || -> Result<(), Error> {
+        loop {
+            let request = worker.recv()?;
+            let body = request.body().to_vec();
+            worker.reply(&request, body)?;
+        }
+    });
+    assert_eq!(handle.join().unwrap().unwrap(), b"survive");
+
+    ctx.shutdown();
+    let err = echo.join().unwrap().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidCtx);
+}